@@ -0,0 +1,89 @@
+//! Reads of a project storage with 10k projects, each done by a fresh
+//! repository that has to decode the file (`uncached`) and by one that
+//! already holds it in its cache (`cached`).
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use serde::Serialize;
+use tauri_todo_app::ports::{ProjectFilter, ProjectRepository as _, ProjectSort};
+use tauri_todo_app::repositories::ProjectRepository;
+use time::OffsetDateTime;
+
+const PROJECTS: u64 = 10_000;
+
+/// Project as `ProjectRepository` stores it, to write a big storage in one go.
+#[derive(Serialize)]
+struct StoredProject {
+    id: u64,
+    name: String,
+    #[serde(with = "time::serde::iso8601")]
+    created_at: OffsetDateTime,
+    #[serde(with = "time::serde::iso8601")]
+    updated_at: OffsetDateTime,
+    is_active: bool,
+    #[serde(with = "time::serde::iso8601::option")]
+    archived_at: Option<OffsetDateTime>,
+}
+
+#[derive(Serialize)]
+struct StoredProjects {
+    projects: Vec<StoredProject>,
+    last_id: u64,
+    version: i32,
+}
+
+fn write_projects(path: &std::path::Path) {
+    let now = OffsetDateTime::now_utc();
+    let data = StoredProjects {
+        projects: (1..=PROJECTS)
+            .map(|id| StoredProject {
+                id,
+                name: format!("Project {}", id),
+                created_at: now,
+                updated_at: now,
+                is_active: id % 10 != 0,
+                archived_at: (id % 10 == 0).then_some(now),
+            })
+            .collect(),
+        last_id: PROJECTS,
+        version: 1,
+    };
+
+    let bytes = bson::to_vec(&data).expect("Failed to encode projects");
+    std::fs::write(path, bytes).expect("Failed to write projects");
+}
+
+fn project_repository(c: &mut Criterion) {
+    let dir = std::env::temp_dir().join(format!("bench_Projects_{}", rand::random::<u32>()));
+    std::fs::create_dir_all(&dir).expect("Failed to create directory");
+    let path = dir.join("Projects.bson");
+    write_projects(&path);
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("Failed to start runtime");
+    let cached = ProjectRepository::new(&path);
+
+    let mut group = c.benchmark_group("project_repository_10k");
+    group.bench_function(BenchmarkId::new("get", "uncached"), |b| {
+        b.iter(|| runtime.block_on(ProjectRepository::new(&path).get(PROJECTS / 2)))
+    });
+    group.bench_function(BenchmarkId::new("get", "cached"), |b| {
+        b.iter(|| runtime.block_on(cached.get(PROJECTS / 2)))
+    });
+    group.bench_function(BenchmarkId::new("list", "uncached"), |b| {
+        b.iter(|| {
+            runtime.block_on(
+                ProjectRepository::new(&path).list(ProjectFilter::Active, ProjectSort::default()),
+            )
+        })
+    });
+    group.bench_function(BenchmarkId::new("list", "cached"), |b| {
+        b.iter(|| runtime.block_on(cached.list(ProjectFilter::Active, ProjectSort::default())))
+    });
+    group.finish();
+
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+criterion_group!(benches, project_repository);
+criterion_main!(benches);
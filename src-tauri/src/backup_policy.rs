@@ -0,0 +1,132 @@
+//! When automatic backups are taken and which backups are kept.
+//!
+//! Retention works like a rotation: the newest backup of each of the last
+//! `keep_daily` days that have one is kept, and so is the newest backup of
+//! each of the last `keep_weekly` ISO weeks. Days and weeks are in UTC.
+
+use std::collections::HashSet;
+
+use time::{Duration, OffsetDateTime};
+
+use crate::models::Backup;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BackupPolicy {
+    /// Longest time a write may stay without a backup.
+    pub interval: Duration,
+    /// Writes that trigger a backup before `interval` is over.
+    pub writes: u64,
+    pub keep_daily: usize,
+    pub keep_weekly: usize,
+}
+
+impl Default for BackupPolicy {
+    fn default() -> Self {
+        BackupPolicy {
+            interval: Duration::hours(6),
+            writes: 100,
+            keep_daily: 7,
+            keep_weekly: 4,
+        }
+    }
+}
+
+impl BackupPolicy {
+    /// Whether to back up now, given when the last backup was taken in this
+    /// run of the app and how many writes happened since. The first check
+    /// of a run always backs up.
+    pub fn is_due(
+        &self,
+        last: Option<OffsetDateTime>,
+        writes_since: u64,
+        now: OffsetDateTime,
+    ) -> bool {
+        let Some(last) = last else {
+            return true;
+        };
+
+        writes_since >= self.writes || (writes_since > 0 && now - last >= self.interval)
+    }
+
+    /// Ids of the backups the rotation drops; `backups` go from the newest.
+    pub fn expired(&self, backups: &[Backup]) -> Vec<u64> {
+        let mut days = HashSet::new();
+        let mut weeks = HashSet::new();
+        let mut kept = HashSet::new();
+
+        for backup in backups {
+            let date = backup.created_at.date();
+
+            if days.len() < self.keep_daily && days.insert(date) {
+                kept.insert(backup.id);
+            }
+
+            let (year, week, _) = date.to_iso_week_date();
+            if weeks.len() < self.keep_weekly && weeks.insert((year, week)) {
+                kept.insert(backup.id);
+            }
+        }
+
+        backups
+            .iter()
+            .map(|b| b.id)
+            .filter(|id| !kept.contains(id))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    fn backup(created_at: OffsetDateTime) -> Backup {
+        Backup {
+            id: (created_at.unix_timestamp() / 3600) as u64,
+            created_at,
+            size: 1,
+        }
+    }
+
+    #[test]
+    fn first_check_is_due() {
+        let policy = BackupPolicy::default();
+        let now = datetime!(2024-03-01 12:00 UTC);
+
+        assert!(policy.is_due(None, 0, now));
+    }
+
+    #[test]
+    fn due_after_interval_with_writes() {
+        let policy = BackupPolicy::default();
+        let last = datetime!(2024-03-01 12:00 UTC);
+
+        assert!(!policy.is_due(Some(last), 0, last + Duration::hours(7)));
+        assert!(!policy.is_due(Some(last), 5, last + Duration::hours(5)));
+        assert!(policy.is_due(Some(last), 5, last + Duration::hours(6)));
+        assert!(policy.is_due(Some(last), 100, last + Duration::minutes(1)));
+    }
+
+    #[test]
+    fn expired_keeps_newest_per_day_and_week() {
+        let policy = BackupPolicy {
+            keep_daily: 2,
+            keep_weekly: 2,
+            ..Default::default()
+        };
+        // 2024-03-04 is a Monday.
+        let backups = [
+            backup(datetime!(2024-03-06 18:00 UTC)),
+            backup(datetime!(2024-03-06 09:00 UTC)),
+            backup(datetime!(2024-03-05 09:00 UTC)),
+            backup(datetime!(2024-03-04 09:00 UTC)),
+            backup(datetime!(2024-03-01 18:00 UTC)),
+            backup(datetime!(2024-03-01 09:00 UTC)),
+            backup(datetime!(2024-02-20 09:00 UTC)),
+        ];
+
+        let expired = policy.expired(&backups);
+
+        assert_eq!(expired, [1, 3, 5, 6].map(|i| backups[i].id).to_vec(),);
+    }
+}
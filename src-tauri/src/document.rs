@@ -0,0 +1,406 @@
+//! Editable JSON form of a project: its groups with their todos, nested
+//! in display order and without positions or timestamps.
+//!
+//! Entries keep the `id` of what they were exported from; entries without
+//! one are created, and stored groups or todos missing from the document
+//! are deleted.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationError, ValidationErrors};
+
+use crate::models::{Group, ProjectContent, Todo};
+use crate::ports::{self, GroupRef};
+use crate::position;
+use crate::result::Result;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectDocument {
+    pub id: u64,
+    #[validate(custom = "ports::validate_project_name")]
+    pub name: String,
+    #[serde(default)]
+    #[validate]
+    pub groups: Vec<GroupDocument>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct GroupDocument {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    #[validate(length(min = 1, message = "Must not be empty"))]
+    pub name: String,
+    #[serde(default = "default_is_opened")]
+    pub is_opened: bool,
+    #[serde(default)]
+    #[validate]
+    pub todos: Vec<TodoDocument>,
+}
+
+fn default_is_opened() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct TodoDocument {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    #[validate(length(min = 1, message = "Must not be empty"))]
+    pub text: String,
+    #[serde(default)]
+    pub is_done: bool,
+}
+
+/// How many entities of one kind an applied document changed. An entity
+/// can be both updated and moved.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct EntityChanges {
+    pub created: usize,
+    pub updated: usize,
+    pub moved: usize,
+    pub deleted: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct ChangeSummary {
+    pub project: EntityChanges,
+    pub groups: EntityChanges,
+    pub todos: EntityChanges,
+}
+
+pub fn export(content: &ProjectContent) -> ProjectDocument {
+    ProjectDocument {
+        id: content.project.id,
+        name: content.project.name.clone(),
+        groups: content
+            .groups
+            .iter()
+            .map(|group| GroupDocument {
+                id: Some(group.id),
+                name: group.name.clone(),
+                is_opened: group.is_opened,
+                todos: content
+                    .todos
+                    .iter()
+                    .filter(|t| t.group_id == group.id)
+                    .map(|todo| TodoDocument {
+                        id: Some(todo.id),
+                        text: todo.text.clone(),
+                        is_done: todo.is_done,
+                    })
+                    .collect(),
+            })
+            .collect(),
+    }
+}
+
+/// Parses the document; malformed JSON is reported as a validation error
+/// of the `document` field.
+pub fn parse(json: &str) -> Result<ProjectDocument> {
+    serde_json::from_str(json).map_err(|e| {
+        let mut errors = ValidationErrors::new();
+        errors.add("document", invalid("json", e.to_string()));
+        errors.into()
+    })
+}
+
+/// Changes that turn the stored `content` into `document`, together with
+/// their summary.
+///
+/// Fails with validation errors when the document is invalid or lists a
+/// group or todo that is not part of the project, or lists it twice.
+pub fn plan(
+    content: &ProjectContent,
+    document: &ProjectDocument,
+) -> Result<(ports::ProjectChanges, ChangeSummary)> {
+    let stored_groups: HashMap<u64, &Group> = content.groups.iter().map(|g| (g.id, g)).collect();
+    let stored_todos: HashMap<u64, &Todo> = content.todos.iter().map(|t| (t.id, t)).collect();
+
+    let mut errors = document.validate().err().unwrap_or_default();
+    if document.id != content.project.id {
+        errors.add(
+            "id",
+            invalid(
+                "project_id",
+                format!(
+                    "Must be {}, the id of the edited project",
+                    content.project.id
+                ),
+            ),
+        );
+    }
+    check_ids(
+        &mut errors,
+        "group_ids",
+        "Group",
+        document.groups.iter().filter_map(|g| g.id),
+        &stored_groups,
+    );
+    check_ids(
+        &mut errors,
+        "todo_ids",
+        "Todo",
+        document
+            .groups
+            .iter()
+            .flat_map(|g| &g.todos)
+            .filter_map(|t| t.id),
+        &stored_todos,
+    );
+    if !errors.is_empty() {
+        return Err(errors.into());
+    }
+
+    let mut changes = ports::ProjectChanges::default();
+    let mut summary = ChangeSummary::default();
+
+    if document.name != content.project.name {
+        changes.name = Some(document.name.clone());
+        summary.project.updated += 1;
+    }
+
+    let stored_positions = document
+        .groups
+        .iter()
+        .map(|g| g.id.map(|id| stored_groups[&id].position))
+        .collect::<Vec<_>>();
+    let (positions, moved) = arrange(&stored_positions);
+
+    let mut targets = Vec::with_capacity(document.groups.len());
+    for ((group, position), is_moved) in document.groups.iter().zip(positions).zip(moved) {
+        let Some(stored) = group.id.map(|id| stored_groups[&id]) else {
+            targets.push(GroupRef::Created(changes.create_groups.len()));
+            changes.create_groups.push(ports::NewGroupData {
+                name: group.name.clone(),
+                position,
+                is_opened: group.is_opened,
+            });
+            summary.groups.created += 1;
+            continue;
+        };
+
+        targets.push(GroupRef::Stored(stored.id));
+
+        let is_updated = group.name != stored.name || group.is_opened != stored.is_opened;
+        if is_updated {
+            summary.groups.updated += 1;
+        }
+        if is_moved {
+            summary.groups.moved += 1;
+        }
+        if is_updated || position != stored.position {
+            changes.update_groups.push(ports::GroupChangeData {
+                id: stored.id,
+                name: group.name.clone(),
+                position,
+                is_opened: group.is_opened,
+            });
+        }
+    }
+
+    for (group, target) in document.groups.iter().zip(targets) {
+        let stored_positions = group
+            .todos
+            .iter()
+            .map(|t| {
+                t.id.map(|id| stored_todos[&id])
+                    .filter(|stored| GroupRef::Stored(stored.group_id) == target)
+                    .map(|stored| stored.position)
+            })
+            .collect::<Vec<_>>();
+        let (positions, moved) = arrange(&stored_positions);
+
+        for ((todo, position), is_moved) in group.todos.iter().zip(positions).zip(moved) {
+            let Some(stored) = todo.id.map(|id| stored_todos[&id]) else {
+                changes.create_todos.push(ports::NewTodoData {
+                    text: todo.text.clone(),
+                    is_done: todo.is_done,
+                    group: target,
+                    position,
+                });
+                summary.todos.created += 1;
+                continue;
+            };
+
+            let is_updated = todo.text != stored.text || todo.is_done != stored.is_done;
+            let is_moved = is_moved || GroupRef::Stored(stored.group_id) != target;
+            if is_updated {
+                summary.todos.updated += 1;
+            }
+            if is_moved {
+                summary.todos.moved += 1;
+            }
+            if is_updated || is_moved || position != stored.position {
+                changes.update_todos.push(ports::TodoChangeData {
+                    id: stored.id,
+                    text: todo.text.clone(),
+                    is_done: todo.is_done,
+                    group: target,
+                    position,
+                });
+            }
+        }
+    }
+
+    let listed_groups: HashSet<u64> = document.groups.iter().filter_map(|g| g.id).collect();
+    changes.delete_groups = content
+        .groups
+        .iter()
+        .map(|g| g.id)
+        .filter(|id| !listed_groups.contains(id))
+        .collect();
+    summary.groups.deleted = changes.delete_groups.len();
+
+    let listed_todos: HashSet<u64> = document
+        .groups
+        .iter()
+        .flat_map(|g| &g.todos)
+        .filter_map(|t| t.id)
+        .collect();
+    changes.delete_todos = content
+        .todos
+        .iter()
+        .map(|t| t.id)
+        .filter(|id| !listed_todos.contains(id))
+        .collect();
+    summary.todos.deleted = changes.delete_todos.len();
+
+    Ok((changes, summary))
+}
+
+fn invalid(code: &'static str, message: String) -> ValidationError {
+    let mut error = ValidationError::new(code);
+    error.message = Some(message.into());
+
+    error
+}
+
+fn check_ids<T>(
+    errors: &mut ValidationErrors,
+    field: &'static str,
+    entity: &str,
+    ids: impl Iterator<Item = u64>,
+    stored: &HashMap<u64, T>,
+) {
+    let mut seen = HashSet::new();
+
+    for id in ids {
+        if !stored.contains_key(&id) {
+            let message = format!("{} {} is not part of the project", entity, id);
+            errors.add(field, invalid("unknown_id", message));
+        } else if !seen.insert(id) {
+            let message = format!("{} {} is listed more than once", entity, id);
+            errors.add(field, invalid("duplicate_id", message));
+        }
+    }
+}
+
+/// Positions for siblings listed in their new order, where `stored` holds
+/// the position of each sibling that already was in the same parent.
+///
+/// The longest run of siblings still in their stored order keeps its
+/// positions and the others are slotted in between; these others are
+/// reported as moved unless they are new to the parent.
+fn arrange(stored: &[Option<f64>]) -> (Vec<f64>, Vec<bool>) {
+    let kept = longest_increasing(stored);
+    let moved = stored
+        .iter()
+        .zip(&kept)
+        .map(|(position, is_kept)| position.is_some() && !is_kept)
+        .collect();
+
+    let mut positions = Vec::with_capacity(stored.len());
+    let mut previous = None;
+    for (i, is_kept) in kept.iter().enumerate() {
+        let position = if *is_kept {
+            stored[i]
+        } else {
+            let next = (i + 1..stored.len())
+                .find(|&j| kept[j])
+                .and_then(|j| stored[j]);
+            position::between(previous, next)
+        };
+
+        let Some(position) = position else {
+            return (position::spread(stored.len()).collect(), moved);
+        };
+
+        positions.push(position);
+        previous = Some(position);
+    }
+
+    (positions, moved)
+}
+
+/// Marks the longest strictly increasing subsequence of the known positions.
+fn longest_increasing(stored: &[Option<f64>]) -> Vec<bool> {
+    // `tails[k]` ends the increasing run of length `k + 1` with the smallest last position.
+    let mut tails: Vec<(usize, f64)> = Vec::new();
+    let mut previous = vec![None; stored.len()];
+
+    for (i, position) in stored.iter().enumerate() {
+        let Some(position) = *position else {
+            continue;
+        };
+
+        let k = tails.partition_point(|&(_, tail)| tail < position);
+        previous[i] = k.checked_sub(1).map(|k| tails[k].0);
+        if k == tails.len() {
+            tails.push((i, position));
+        } else {
+            tails[k] = (i, position);
+        }
+    }
+
+    let mut kept = vec![false; stored.len()];
+    let mut next = tails.last().map(|&(i, _)| i);
+    while let Some(i) = next {
+        kept[i] = true;
+        next = previous[i];
+    }
+
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arrange_keeps_positions_in_order() {
+        assert_eq!(
+            arrange(&[Some(1.0), Some(2.0), Some(3.0)]),
+            (vec![1.0, 2.0, 3.0], vec![false, false, false])
+        );
+    }
+
+    #[test]
+    fn arrange_moves_only_out_of_order() {
+        assert_eq!(
+            arrange(&[Some(1.0), Some(3.0), Some(2.0), Some(4.0)]),
+            (vec![1.0, 1.5, 2.0, 4.0], vec![false, true, false, false])
+        );
+    }
+
+    #[test]
+    fn arrange_slots_in_new_siblings() {
+        assert_eq!(
+            arrange(&[None, Some(1.0), None, Some(2.0), None]),
+            (vec![0.0, 1.0, 1.5, 2.0, 3.0], vec![false; 5])
+        );
+    }
+
+    #[test]
+    fn arrange_spreads_when_gap_is_exhausted() {
+        let after = 1.0 + 1e-10;
+
+        assert_eq!(
+            arrange(&[Some(1.0), None, Some(after)]),
+            (vec![1.0, 2.0, 3.0], vec![false; 3])
+        );
+    }
+}
@@ -0,0 +1,214 @@
+use std::sync::{Arc, PoisonError, RwLock};
+
+use crate::document::ChangeSummary;
+use crate::history::HistoryAction;
+use crate::models::{Backup, DeletedProject, Group, Project, Todo, TrashItem};
+
+/// Name of the Tauri event that carries domain events to the windows.
+pub const DOMAIN_EVENT: &str = "domain-event";
+
+/// Change made through the interactors, published after it was stored.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(tag = "type")]
+pub enum DomainEvent {
+    ProjectCreated {
+        project: Project,
+    },
+    ProjectUpdated {
+        project: Project,
+    },
+    ProjectArchived {
+        project: Project,
+    },
+    ProjectUnarchived {
+        project: Project,
+    },
+    /// `updated_at` of the projects was recomputed.
+    ProjectsRepaired {
+        projects: Vec<Project>,
+    },
+    /// An edited JSON document of the project was applied.
+    ProjectContentApplied {
+        project_id: u64,
+        summary: ChangeSummary,
+    },
+    ProjectDeleted {
+        deleted: DeletedProject,
+    },
+    GroupCreated {
+        group: Group,
+    },
+    GroupOpenedChanged {
+        group: Group,
+    },
+    GroupMoved {
+        group: Group,
+    },
+    /// The group was moved to the trash together with its todos.
+    GroupDeleted {
+        group: Group,
+    },
+    TodoCreated {
+        todo: Todo,
+    },
+    TodoUpdated {
+        todo: Todo,
+    },
+    TodoCompleted {
+        todo: Todo,
+    },
+    TodoReopened {
+        todo: Todo,
+    },
+    TodoMoved {
+        todo: Todo,
+    },
+    TodoDeleted {
+        todo: Todo,
+    },
+    /// Entities of the item are back where they were deleted from.
+    RestoredFromTrash {
+        item: TrashItem,
+    },
+    /// All data was replaced by the backup; anything shown may be stale.
+    BackupRestored {
+        backup: Backup,
+    },
+    /// A change was taken back; contents of the projects changed.
+    Undone {
+        action: HistoryAction,
+        project_ids: Vec<u64>,
+    },
+    /// A change that was taken back was made again.
+    Redone {
+        action: HistoryAction,
+        project_ids: Vec<u64>,
+    },
+}
+
+/// Handle of a subscription, for `EventBus::unsubscribe`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubscriptionId(u64);
+
+type Subscriber = Arc<dyn Fn(&DomainEvent) + Send + Sync>;
+
+/// Delivers published events to every subscriber in the order they
+/// subscribed. Subscribers run on the publishing task, so they should hand
+/// anything slow off to a thread of their own.
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: RwLock<(u64, Vec<(SubscriptionId, Subscriber)>)>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subscribe<F>(&self, subscriber: F) -> SubscriptionId
+    where
+        F: Fn(&DomainEvent) + Send + Sync + 'static,
+    {
+        let mut subscribers = self
+            .subscribers
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let (last_id, list) = &mut *subscribers;
+
+        *last_id += 1;
+        let id = SubscriptionId(*last_id);
+        list.push((id, Arc::new(subscriber)));
+
+        id
+    }
+
+    /// Returns whether the subscription was still active.
+    pub fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        let mut subscribers = self
+            .subscribers
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let list = &mut subscribers.1;
+
+        let len = list.len();
+        list.retain(|(subscription_id, _)| *subscription_id != id);
+
+        list.len() != len
+    }
+
+    pub fn publish(&self, event: DomainEvent) {
+        // Called without the lock, so subscribers may (un)subscribe.
+        let subscribers = self
+            .subscribers
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .1
+            .iter()
+            .map(|(_, subscriber)| subscriber.clone())
+            .collect::<Vec<_>>();
+
+        for subscriber in subscribers {
+            subscriber(&event);
+        }
+    }
+}
+
+impl std::fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let subscribers = self
+            .subscribers
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        f.debug_struct("EventBus")
+            .field("subscribers", &subscribers.1.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    fn event(project_id: u64) -> DomainEvent {
+        DomainEvent::ProjectContentApplied {
+            project_id,
+            summary: ChangeSummary::default(),
+        }
+    }
+
+    #[test]
+    fn publish_reaches_subscribers_until_they_unsubscribe() {
+        let bus = EventBus::new();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+
+        let first = {
+            let seen = seen.clone();
+            bus.subscribe(move |event| seen.lock().unwrap().push((1, event.clone())))
+        };
+        {
+            let seen = seen.clone();
+            bus.subscribe(move |event| seen.lock().unwrap().push((2, event.clone())));
+        }
+
+        bus.publish(event(1));
+        assert!(bus.unsubscribe(first));
+        assert!(!bus.unsubscribe(first));
+        bus.publish(event(2));
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [(1, event(1)), (2, event(1)), (2, event(2))]
+        );
+    }
+
+    #[test]
+    fn events_serialize_with_their_type() {
+        let json = serde_json::to_value(event(3)).expect("serialize");
+
+        assert_eq!(json["type"], "ProjectContentApplied");
+        assert_eq!(json["project_id"], 3);
+    }
+}
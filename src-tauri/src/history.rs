@@ -0,0 +1,472 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::models::{ChangedIds, EntityKind, Group, Project, Snapshot, StorageChange, Todo};
+
+/// Most entries kept for undo; the oldest are dropped first.
+pub const LIMIT: usize = 100;
+
+/// Change an entry takes back, for labelling the undo and redo buttons.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryAction {
+    CreateProject,
+    UpdateProject,
+    ArchiveProject,
+    UnarchiveProject,
+    ApplyProjectJson,
+    DeleteProject,
+    CreateGroup,
+    DeleteGroup,
+    SetGroupOpened,
+    MoveGroup,
+    CreateTodo,
+    UpdateTodo,
+    CompleteTodo,
+    ReopenTodo,
+    CompleteGroup,
+    MoveTodo,
+    DeleteTodo,
+    RestoreFromTrash,
+}
+
+/// Entity of a `Snapshot`.
+pub trait Entity: Clone + Serialize {
+    /// Name used in errors, as in `NotFound::entity`.
+    const NAME: &'static str;
+    const KIND: EntityKind;
+
+    fn id(&self) -> u64;
+
+    /// Equal apart from `updated_at`, which also moves when children change
+    /// and is stamped anew whenever history is replayed.
+    fn same(&self, other: &Self) -> bool;
+
+    fn list(snapshot: &Snapshot) -> &Vec<Self>;
+
+    fn list_mut(snapshot: &mut Snapshot) -> &mut Vec<Self>;
+}
+
+impl Entity for Project {
+    const NAME: &'static str = "project";
+    const KIND: EntityKind = EntityKind::Project;
+
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn same(&self, other: &Self) -> bool {
+        Project {
+            updated_at: other.updated_at,
+            ..self.clone()
+        } == *other
+    }
+
+    fn list(snapshot: &Snapshot) -> &Vec<Self> {
+        &snapshot.projects
+    }
+
+    fn list_mut(snapshot: &mut Snapshot) -> &mut Vec<Self> {
+        &mut snapshot.projects
+    }
+}
+
+impl Entity for Group {
+    const NAME: &'static str = "group";
+    const KIND: EntityKind = EntityKind::Group;
+
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn same(&self, other: &Self) -> bool {
+        self == other
+    }
+
+    fn list(snapshot: &Snapshot) -> &Vec<Self> {
+        &snapshot.groups
+    }
+
+    fn list_mut(snapshot: &mut Snapshot) -> &mut Vec<Self> {
+        &mut snapshot.groups
+    }
+}
+
+impl Entity for Todo {
+    const NAME: &'static str = "todo";
+    const KIND: EntityKind = EntityKind::Todo;
+
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn same(&self, other: &Self) -> bool {
+        Todo {
+            updated_at: other.updated_at,
+            ..self.clone()
+        } == *other
+    }
+
+    fn list(snapshot: &Snapshot) -> &Vec<Self> {
+        &snapshot.todos
+    }
+
+    fn list_mut(snapshot: &mut Snapshot) -> &mut Vec<Self> {
+        &mut snapshot.todos
+    }
+}
+
+/// Ids of the entities of kind `T` in either snapshot, in order of appearance.
+pub fn ids<T: Entity>(before: &Snapshot, after: &Snapshot) -> Vec<u64> {
+    let mut ids = Vec::new();
+    for id in T::list(before).iter().chain(T::list(after)).map(T::id) {
+        if !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+
+    ids
+}
+
+/// The entity with `id` in `snapshot`, or `None` when it did not exist.
+pub fn find<T: Entity>(snapshot: &Snapshot, id: u64) -> Option<&T> {
+    T::list(snapshot).iter().find(|entity| entity.id() == id)
+}
+
+/// Ids of the entities of kind `T` in `from` that are missing in `to`,
+/// i.e. deleted when going from one to the other.
+pub fn deleted<T: Entity>(from: &Snapshot, to: &Snapshot) -> Vec<u64> {
+    T::list(from)
+        .iter()
+        .map(T::id)
+        .filter(|id| find::<T>(to, *id).is_none())
+        .collect()
+}
+
+/// First entity of kind `T` in either snapshot whose `current` state is
+/// not the one `expected` describes.
+pub fn changed_since<T: Entity>(
+    expected: &Snapshot,
+    target: &Snapshot,
+    current: &Snapshot,
+) -> Option<u64> {
+    ids::<T>(expected, target).into_iter().find(|id| {
+        match (find::<T>(current, *id), find::<T>(expected, *id)) {
+            (None, None) => false,
+            (Some(current), Some(expected)) => !current.same(expected),
+            _ => true,
+        }
+    })
+}
+
+/// Entities of kind `T` that differ between the snapshots, as they were
+/// before and after; `None` on the side where the entity did not exist.
+pub fn changes<'a, T: Entity>(
+    before: &'a Snapshot,
+    after: &'a Snapshot,
+) -> Vec<(Option<&'a T>, Option<&'a T>)> {
+    ids::<T>(before, after)
+        .into_iter()
+        .map(|id| (find::<T>(before, id), find::<T>(after, id)))
+        .filter(|change| !matches!(change, (Some(before), Some(after)) if before.same(after)))
+        .collect()
+}
+
+/// Drops the entities of kind `T` that are the same in both snapshots.
+fn drop_unchanged<T: Entity>(before: &mut Snapshot, after: &mut Snapshot) {
+    let unchanged = T::list(before)
+        .iter()
+        .filter(|entity| find::<T>(after, entity.id()).is_some_and(|other| entity.same(other)))
+        .map(T::id)
+        .collect::<Vec<_>>();
+
+    T::list_mut(before).retain(|entity| !unchanged.contains(&entity.id()));
+    T::list_mut(after).retain(|entity| !unchanged.contains(&entity.id()));
+}
+
+/// Entity with its groups and todos that replaying an entry moves into
+/// the trash or takes out of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrashMove {
+    pub entity: EntityKind,
+    pub id: u64,
+    pub into_trash: bool,
+}
+
+/// Undoable change: the entities it touched as they were before and after
+/// it. An entity missing on one side was created or deleted by the change.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub action: HistoryAction,
+    #[serde(with = "time::serde::iso8601")]
+    pub at: OffsetDateTime,
+    pub before: Snapshot,
+    pub after: Snapshot,
+}
+
+impl HistoryEntry {
+    /// Keeps only the entities that differ between `before` and `after`, so
+    /// callers can pass everything the change might have touched. `None`
+    /// when nothing changed.
+    pub fn new(
+        action: HistoryAction,
+        at: OffsetDateTime,
+        mut before: Snapshot,
+        mut after: Snapshot,
+    ) -> Option<Self> {
+        drop_unchanged::<Project>(&mut before, &mut after);
+        drop_unchanged::<Group>(&mut before, &mut after);
+        drop_unchanged::<Todo>(&mut before, &mut after);
+
+        if before.is_empty() && after.is_empty() {
+            return None;
+        }
+
+        Some(HistoryEntry {
+            action,
+            at,
+            before,
+            after,
+        })
+    }
+
+    /// How undoing or redoing the entry moves entities across the trash;
+    /// `None` unless it deleted or restored them. The entity is the topmost
+    /// one of the deleted or restored side, as trash items hold one project,
+    /// group or todo with what belongs to it.
+    pub fn trash_move(&self, undo: bool) -> Option<TrashMove> {
+        let (snapshot, into_trash) = match self.action {
+            HistoryAction::DeleteProject
+            | HistoryAction::DeleteGroup
+            | HistoryAction::DeleteTodo => (&self.before, !undo),
+            HistoryAction::RestoreFromTrash => (&self.after, undo),
+            _ => return None,
+        };
+        let (entity, id) = if let Some(project) = snapshot.projects.first() {
+            (EntityKind::Project, project.id)
+        } else if let Some(group) = snapshot.groups.first() {
+            (EntityKind::Group, group.id)
+        } else {
+            (EntityKind::Todo, snapshot.todos.first()?.id)
+        };
+
+        Some(TrashMove {
+            entity,
+            id,
+            into_trash,
+        })
+    }
+
+    fn touches<T: Entity>(&self, changed: &ChangedIds) -> bool {
+        ids::<T>(&self.before, &self.after).iter().any(|id| {
+            changed.created.contains(id)
+                || changed.updated.contains(id)
+                || changed.deleted.contains(id)
+        })
+    }
+}
+
+/// What `undo` and `redo` would take back or redo next.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+pub struct HistoryState {
+    pub undo: Option<HistoryAction>,
+    pub redo: Option<HistoryAction>,
+}
+
+/// Undo and redo stacks, oldest entry first.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct History {
+    pub undo: Vec<HistoryEntry>,
+    pub redo: Vec<HistoryEntry>,
+}
+
+impl History {
+    /// Adds a change made now; whatever was undone before can no longer be
+    /// redone on top of it.
+    pub fn record(&mut self, entry: HistoryEntry) {
+        self.redo.clear();
+        self.push_undo(entry);
+    }
+
+    pub fn push_undo(&mut self, entry: HistoryEntry) {
+        self.undo.push(entry);
+
+        let excess = self.undo.len().saturating_sub(LIMIT);
+        self.undo.drain(..excess);
+    }
+
+    /// Drops the entries touching entities another process changed; they
+    /// describe states that no longer exist. Returns whether any was dropped.
+    pub fn invalidate(&mut self, change: &StorageChange) -> bool {
+        let touches = |entry: &HistoryEntry| match change {
+            StorageChange::Projects { ids } => entry.touches::<Project>(ids),
+            StorageChange::Groups { ids, .. } => entry.touches::<Group>(ids),
+            StorageChange::Todos { ids, .. } => entry.touches::<Todo>(ids),
+        };
+
+        let len = self.undo.len() + self.redo.len();
+        self.undo.retain(|entry| !touches(entry));
+        self.redo.retain(|entry| !touches(entry));
+
+        self.undo.len() + self.redo.len() != len
+    }
+
+    pub fn state(&self) -> HistoryState {
+        HistoryState {
+            undo: self.undo.last().map(|entry| entry.action),
+            redo: self.redo.last().map(|entry| entry.action),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    const AT: OffsetDateTime = datetime!(2024-03-01 12:00 UTC);
+
+    fn todo(id: u64, text: &str) -> Todo {
+        Todo {
+            id,
+            text: text.into(),
+            position: id as f64,
+            created_at: AT,
+            updated_at: AT,
+            is_done: false,
+            done_at: None,
+            group_id: 1,
+        }
+    }
+
+    fn todos(todos: &[Todo]) -> Snapshot {
+        Snapshot {
+            todos: todos.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    fn entry(id: u64) -> HistoryEntry {
+        HistoryEntry::new(
+            HistoryAction::CreateTodo,
+            AT,
+            Snapshot::default(),
+            todos(&[todo(id, "new")]),
+        )
+        .expect("entry")
+    }
+
+    #[test]
+    fn new_entry_keeps_only_changed_entities() {
+        let moved = Todo {
+            updated_at: AT + time::Duration::hours(1),
+            ..todo(2, "b")
+        };
+
+        let entry = HistoryEntry::new(
+            HistoryAction::UpdateTodo,
+            AT,
+            todos(&[todo(1, "a"), todo(2, "b"), todo(3, "c")]),
+            todos(&[todo(1, "a"), moved, todo(3, "renamed"), todo(4, "d")]),
+        )
+        .expect("entry");
+
+        assert_eq!(entry.before, todos(&[todo(3, "c")]));
+        assert_eq!(entry.after, todos(&[todo(3, "renamed"), todo(4, "d")]));
+        assert_eq!(
+            HistoryEntry::new(
+                HistoryAction::UpdateTodo,
+                AT,
+                todos(&[todo(1, "a")]),
+                todos(&[todo(1, "a")])
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn trash_move_of_deletes_and_restores() {
+        let delete = HistoryEntry::new(
+            HistoryAction::DeleteTodo,
+            AT,
+            todos(&[todo(2, "b")]),
+            Snapshot::default(),
+        )
+        .expect("entry");
+        let restore = HistoryEntry {
+            action: HistoryAction::RestoreFromTrash,
+            before: delete.after.clone(),
+            after: delete.before.clone(),
+            ..delete.clone()
+        };
+        let todo_move = |into_trash| {
+            Some(TrashMove {
+                entity: EntityKind::Todo,
+                id: 2,
+                into_trash,
+            })
+        };
+
+        assert_eq!(delete.trash_move(true), todo_move(false));
+        assert_eq!(delete.trash_move(false), todo_move(true));
+        assert_eq!(restore.trash_move(true), todo_move(true));
+        assert_eq!(restore.trash_move(false), todo_move(false));
+        assert_eq!(entry(1).trash_move(true), None);
+    }
+
+    #[test]
+    fn record_is_bounded_and_forgets_redo() {
+        let mut history = History {
+            redo: vec![entry(0)],
+            ..Default::default()
+        };
+
+        for id in 1..=LIMIT as u64 + 5 {
+            history.record(entry(id));
+        }
+
+        assert_eq!(history.undo.len(), LIMIT);
+        assert_eq!(history.undo[0], entry(6));
+        assert_eq!(history.redo, []);
+        assert_eq!(
+            history.state(),
+            HistoryState {
+                undo: Some(HistoryAction::CreateTodo),
+                redo: None,
+            }
+        );
+    }
+
+    #[test]
+    fn invalidate_drops_entries_of_changed_entities() {
+        let mut history = History {
+            undo: vec![entry(1), entry(2)],
+            redo: vec![entry(3)],
+        };
+
+        let changed = history.invalidate(&StorageChange::Todos {
+            ids: ChangedIds {
+                updated: vec![2, 3],
+                ..Default::default()
+            },
+            group_ids: vec![1],
+        });
+        let unchanged = history.invalidate(&StorageChange::Groups {
+            ids: ChangedIds {
+                updated: vec![1],
+                ..Default::default()
+            },
+            project_ids: vec![1],
+        });
+
+        assert!(changed);
+        assert!(!unchanged);
+        assert_eq!(
+            history,
+            History {
+                undo: vec![entry(1)],
+                redo: vec![],
+            }
+        );
+    }
+}
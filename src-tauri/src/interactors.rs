@@ -1,14 +1,32 @@
+use anyhow::Context;
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::sync::Arc;
+use tauri::async_runtime::Mutex;
+use time::OffsetDateTime;
 use validator::Validate;
 
-use crate::models::{Group, Project};
+use crate::backup_policy::BackupPolicy;
+use crate::document::{self, ChangeSummary};
+use crate::events::{DomainEvent, EventBus};
+use crate::history::{self, Entity, History, HistoryAction, HistoryEntry, HistoryState, TrashMove};
+use crate::models::{
+    ActivityPage, Backup, DeletedProject, EntityKind, Group, Project, ProjectContent,
+    RecoveryReport, Settings, Snapshot, StorageChange, Todo, TrashItem,
+};
 use crate::ports;
-use crate::result::Result;
+use crate::position;
+use crate::result::{Conflict, ErrorContext, ErrorKind, NotFound, Result};
 use crate::utils::{IsSend, IsSync};
 
 pub struct ProjectInteractor {
     project_repository: Arc<dyn ports::ProjectRepository + Send + Sync>,
+    group_repository: Arc<dyn ports::GroupRepository + Send + Sync>,
+    todo_repository: Arc<dyn ports::TodoRepository + Send + Sync>,
+    settings_repository: Arc<dyn ports::SettingsRepository + Send + Sync>,
+    content_repository: Arc<dyn ports::ProjectContentRepository + Send + Sync>,
+    events: Arc<EventBus>,
+    history: Arc<HistoryInteractor>,
 }
 
 impl IsSync for ProjectInteractor {}
@@ -21,24 +39,273 @@ impl Debug for ProjectInteractor {
 }
 
 impl ProjectInteractor {
-    pub fn new(project_repository: Arc<dyn ports::ProjectRepository + Send + Sync>) -> Self {
-        ProjectInteractor { project_repository }
+    pub fn new(
+        project_repository: Arc<dyn ports::ProjectRepository + Send + Sync>,
+        group_repository: Arc<dyn ports::GroupRepository + Send + Sync>,
+        todo_repository: Arc<dyn ports::TodoRepository + Send + Sync>,
+        settings_repository: Arc<dyn ports::SettingsRepository + Send + Sync>,
+        content_repository: Arc<dyn ports::ProjectContentRepository + Send + Sync>,
+        events: Arc<EventBus>,
+        history: Arc<HistoryInteractor>,
+    ) -> Self {
+        ProjectInteractor {
+            project_repository,
+            group_repository,
+            todo_repository,
+            settings_repository,
+            content_repository,
+            events,
+            history,
+        }
     }
 
+    /// Creates the project with the groups listed in `Settings::default_groups`.
     pub async fn create(&self, name: &str) -> Result<Project> {
         let data = ports::CreateProjectData { name };
         data.validate()?;
 
-        self.project_repository.create(data).await
+        let settings = self.settings_repository.get().await?;
+        let ProjectContent {
+            project, groups, ..
+        } = self
+            .content_repository
+            .create(data, &settings.default_groups)
+            .await?;
+
+        self.events.publish(DomainEvent::ProjectCreated {
+            project: project.clone(),
+        });
+        for group in &groups {
+            self.events.publish(DomainEvent::GroupCreated {
+                group: group.clone(),
+            });
+        }
+
+        let after = Snapshot {
+            projects: vec![project.clone()],
+            groups,
+            ..Default::default()
+        };
+        let recorded = self
+            .history
+            .record(HistoryAction::CreateProject, Snapshot::default(), after)
+            .await;
+        log_unrecorded(recorded);
+
+        Ok(project)
+    }
+
+    pub async fn list(
+        &self,
+        filter: ports::ProjectFilter,
+        sort: ports::ProjectSort,
+    ) -> Result<Vec<Project>> {
+        self.project_repository.list(filter, sort).await
+    }
+
+    /// Raises `updated_at` of every project that is behind its `created_at`
+    /// or the latest `updated_at` of its todos, see `Project::updated_at`.
+    ///
+    /// Fixes projects left behind by earlier versions, which wrote a todo
+    /// and touched its project separately. A later `updated_at` is kept, as
+    /// group changes leave nothing else to tell it by. Returns the projects
+    /// that changed.
+    pub async fn repair_updated_at(&self) -> Result<Vec<Project>> {
+        let projects = self
+            .project_repository
+            .list(ports::ProjectFilter::All, ports::ProjectSort::default())
+            .await?;
+        let project_ids = self
+            .group_repository
+            .list()
+            .await?
+            .into_iter()
+            .map(|g| (g.id, g.project_id))
+            .collect::<HashMap<_, _>>();
+
+        let mut latest = HashMap::new();
+        for todo in self.todo_repository.list().await? {
+            if let Some(&project_id) = project_ids.get(&todo.group_id) {
+                let at = latest.entry(project_id).or_insert(todo.updated_at);
+                *at = todo.updated_at.max(*at);
+            }
+        }
+
+        let mut repaired = Vec::new();
+        for mut project in projects {
+            let updated_at = latest
+                .get(&project.id)
+                .map_or(project.created_at, |&at| at.max(project.created_at));
+
+            if updated_at > project.updated_at {
+                project.updated_at = updated_at;
+                repaired.push(project);
+            }
+        }
+
+        let updates = repaired
+            .iter()
+            .map(|p| (p.id, p.updated_at))
+            .collect::<Vec<_>>();
+        if !updates.is_empty() {
+            self.project_repository.set_updated_at(&updates).await?;
+            self.events.publish(DomainEvent::ProjectsRepaired {
+                projects: repaired.clone(),
+            });
+        }
+
+        Ok(repaired)
+    }
+
+    pub async fn update(
+        &self,
+        id: u64,
+        name: Option<&str>,
+        is_active: Option<bool>,
+    ) -> Result<Project> {
+        let data = ports::UpdateProjectData { name, is_active };
+        data.validate()?;
+
+        let before = self.get(id).await?;
+        let project = self
+            .project_repository
+            .update(id, data)
+            .await?
+            .ok_or(NotFound {
+                entity: "project",
+                id,
+            })?;
+
+        self.events.publish(DomainEvent::ProjectUpdated {
+            project: project.clone(),
+        });
+        self.record(HistoryAction::UpdateProject, before, &project)
+            .await;
+
+        Ok(project)
+    }
+
+    pub async fn archive(&self, id: u64) -> Result<Project> {
+        let before = self.get(id).await?;
+        let project = self.project_repository.archive(id).await?.ok_or(NotFound {
+            entity: "project",
+            id,
+        })?;
+
+        self.events.publish(DomainEvent::ProjectArchived {
+            project: project.clone(),
+        });
+        self.record(HistoryAction::ArchiveProject, before, &project)
+            .await;
+
+        Ok(project)
+    }
+
+    pub async fn unarchive(&self, id: u64) -> Result<Project> {
+        let before = self.get(id).await?;
+        let project = self
+            .project_repository
+            .unarchive(id)
+            .await?
+            .ok_or(NotFound {
+                entity: "project",
+                id,
+            })?;
+
+        self.events.publish(DomainEvent::ProjectUnarchived {
+            project: project.clone(),
+        });
+        self.record(HistoryAction::UnarchiveProject, before, &project)
+            .await;
+
+        Ok(project)
+    }
+
+    async fn get(&self, id: u64) -> Result<Project> {
+        self.project_repository.get(id).await?.ok_or_else(|| {
+            NotFound {
+                entity: "project",
+                id,
+            }
+            .into()
+        })
+    }
+
+    async fn record(&self, action: HistoryAction, before: Project, after: &Project) {
+        let snapshot = |project| Snapshot {
+            projects: vec![project],
+            ..Default::default()
+        };
+
+        let recorded = self
+            .history
+            .record(action, snapshot(before), snapshot(after.clone()))
+            .await;
+        log_unrecorded(recorded);
     }
 
-    pub async fn list(&self) -> Result<Vec<Project>> {
-        self.project_repository.list().await
+    /// The project with its groups and todos as an editable JSON document.
+    pub async fn export_json(&self, id: u64) -> Result<String> {
+        let content = self.content(id).await?;
+
+        let json = serde_json::to_string_pretty(&document::export(&content))
+            .context("Failed to serialize project")?;
+
+        Ok(json)
+    }
+
+    /// Validates an edited document of the project and applies everything
+    /// that differs from the stored state at once. Fails with `Conflict`
+    /// when the project changed while the changes were planned.
+    pub async fn apply_json(&self, id: u64, json: &str) -> Result<ChangeSummary> {
+        let document = document::parse(json)?;
+        let content = self.content(id).await?;
+
+        let (changes, summary) = document::plan(&content, &document)?;
+        if !changes.is_empty() {
+            let applied = self
+                .content_repository
+                .apply(id, Some(content.clone()), changes)
+                .await?
+                .ok_or(NotFound {
+                    entity: "project",
+                    id,
+                })?;
+
+            self.events.publish(DomainEvent::ProjectContentApplied {
+                project_id: id,
+                summary,
+            });
+            let recorded = self
+                .history
+                .record(
+                    HistoryAction::ApplyProjectJson,
+                    content.into(),
+                    applied.into(),
+                )
+                .await;
+            log_unrecorded(recorded);
+        }
+
+        Ok(summary)
+    }
+
+    async fn content(&self, id: u64) -> Result<ProjectContent> {
+        self.content_repository.get(id).await?.ok_or_else(|| {
+            NotFound {
+                entity: "project",
+                id,
+            }
+            .into()
+        })
     }
 }
 
 pub struct GroupInteractor {
     group_repository: Arc<dyn ports::GroupRepository + Send + Sync>,
+    content_repository: Arc<dyn ports::ProjectContentRepository + Send + Sync>,
+    events: Arc<EventBus>,
+    history: Arc<HistoryInteractor>,
 }
 
 impl IsSync for GroupInteractor {}
@@ -51,13 +318,3605 @@ impl Debug for GroupInteractor {
 }
 
 impl GroupInteractor {
-    pub fn new(group_repository: Arc<dyn ports::GroupRepository + Send + Sync>) -> Self {
-        GroupInteractor { group_repository }
+    pub fn new(
+        group_repository: Arc<dyn ports::GroupRepository + Send + Sync>,
+        content_repository: Arc<dyn ports::ProjectContentRepository + Send + Sync>,
+        events: Arc<EventBus>,
+        history: Arc<HistoryInteractor>,
+    ) -> Self {
+        GroupInteractor {
+            group_repository,
+            content_repository,
+            events,
+            history,
+        }
     }
 
+    /// Adds the group at the end of the project.
     pub async fn create(&self, name: &str, project_id: u64) -> Result<Group> {
-        self.group_repository
-            .create(ports::CreateGroupData { name, project_id })
+        let content = self.content(project_id).await?;
+        let last = content.groups.iter().map(|g| g.position).reduce(f64::max);
+        let changes = ports::ProjectChanges {
+            create_groups: vec![ports::NewGroupData {
+                name: name.to_string(),
+                position: position::between(last, None).unwrap_or_default(),
+                is_opened: true,
+            }],
+            ..Default::default()
+        };
+
+        // Nothing else changed in between, so the largest id is the new one.
+        let after = self.apply(content, changes).await?;
+        let group = after
+            .groups
+            .into_iter()
+            .max_by_key(|g| g.id)
+            .context("Created group is missing")?;
+
+        self.events.publish(DomainEvent::GroupCreated {
+            group: group.clone(),
+        });
+        self.record(HistoryAction::CreateGroup, vec![], vec![group.clone()])
+            .await;
+
+        Ok(group)
+    }
+
+    pub async fn find_by_project(&self, project_id: u64) -> Result<Vec<Group>> {
+        self.group_repository.find_by_project(project_id).await
+    }
+
+    /// Expands or collapses the group; the flag is kept with the group.
+    pub async fn set_opened(&self, id: u64, is_opened: bool) -> Result<Group> {
+        let (content, before) = self.content_of(id).await?;
+        let changes = ports::ProjectChanges {
+            update_groups: vec![ports::GroupChangeData {
+                id,
+                name: before.name.clone(),
+                position: before.position,
+                is_opened,
+            }],
+            ..Default::default()
+        };
+
+        let after = self.apply(content, changes).await?;
+        let group = find_group(after.groups, id)?;
+
+        self.events.publish(DomainEvent::GroupOpenedChanged {
+            group: group.clone(),
+        });
+        self.record(
+            HistoryAction::SetGroupOpened,
+            vec![before],
+            vec![group.clone()],
+        )
+        .await;
+
+        Ok(group)
+    }
+
+    /// Moves the group right after `after_id` or right before `before_id`
+    /// within its project; with neither the group goes to the end.
+    ///
+    /// Only the moved group is written, unless its new neighbours are too
+    /// close to split, in which case the whole project is renumbered once.
+    pub async fn move_group(
+        &self,
+        id: u64,
+        before_id: Option<u64>,
+        after_id: Option<u64>,
+    ) -> Result<Group> {
+        let (content, group) = self.content_of(id).await?;
+
+        let before_groups = content.groups.clone();
+        let mut siblings = content.groups.clone();
+        siblings.retain(|g| g.id != id);
+
+        let index = insertion_index(siblings.iter().map(|g| g.id), "group", before_id, after_id)?;
+
+        let before = index.checked_sub(1).map(|i| siblings[i].position);
+        let after = siblings.get(index).map(|g| g.position);
+
+        let update_groups = if let Some(position) = position::between(before, after) {
+            vec![group_change(&group, position)]
+        } else {
+            siblings.insert(index, group);
+            siblings
+                .iter()
+                .zip(position::spread(siblings.len()))
+                .map(|(g, position)| group_change(g, position))
+                .collect()
+        };
+        let changes = ports::ProjectChanges {
+            update_groups,
+            ..Default::default()
+        };
+
+        let after_groups = self.apply(content, changes).await?.groups;
+        let moved = find_group(after_groups.clone(), id)?;
+
+        self.events.publish(DomainEvent::GroupMoved {
+            group: moved.clone(),
+        });
+        self.record(HistoryAction::MoveGroup, before_groups, after_groups)
+            .await;
+
+        Ok(moved)
+    }
+
+    async fn content(&self, project_id: u64) -> Result<ProjectContent> {
+        self.content_repository
+            .get(project_id)
+            .await?
+            .ok_or_else(|| {
+                NotFound {
+                    entity: "project",
+                    id: project_id,
+                }
+                .into()
+            })
+    }
+
+    /// Content of the group's project, with the group as read along with it.
+    async fn content_of(&self, id: u64) -> Result<(ProjectContent, Group)> {
+        let group = self.group_repository.get(id).await?.ok_or(NotFound {
+            entity: "group",
+            id,
+        })?;
+        let content = self.content(group.project_id).await?;
+        let group = find_group(content.groups.clone(), id)?;
+
+        Ok((content, group))
+    }
+
+    /// Applies `changes` planned against `content`. Groups have no
+    /// `updated_at` of their own, so the project's is raised in the same
+    /// write, see `ports::ProjectContentRepository::apply`.
+    async fn apply(
+        &self,
+        content: ProjectContent,
+        changes: ports::ProjectChanges,
+    ) -> Result<ProjectContent> {
+        let id = content.project.id;
+
+        self.content_repository
+            .apply(id, Some(content), changes)
+            .await?
+            .ok_or_else(|| {
+                NotFound {
+                    entity: "project",
+                    id,
+                }
+                .into()
+            })
+    }
+
+    async fn record(&self, action: HistoryAction, before: Vec<Group>, after: Vec<Group>) {
+        let snapshot = |groups| Snapshot {
+            groups,
+            ..Default::default()
+        };
+
+        let recorded = self
+            .history
+            .record(action, snapshot(before), snapshot(after))
+            .await;
+        log_unrecorded(recorded);
+    }
+}
+
+fn find_group(groups: Vec<Group>, id: u64) -> Result<Group> {
+    groups.into_iter().find(|g| g.id == id).ok_or_else(|| {
+        NotFound {
+            entity: "group",
+            id,
+        }
+        .into()
+    })
+}
+
+/// Change keeping everything of `group` but its position.
+fn group_change(group: &Group, position: f64) -> ports::GroupChangeData {
+    ports::GroupChangeData {
+        id: group.id,
+        name: group.name.clone(),
+        position,
+        is_opened: group.is_opened,
+    }
+}
+
+/// `position` unless a sibling has it already; then the position right
+/// after that sibling, or after the last sibling when the gap is too small.
+fn free_position(position: f64, siblings: impl Iterator<Item = f64>) -> f64 {
+    let siblings = siblings.collect::<Vec<_>>();
+    if !siblings.contains(&position) {
+        return position;
+    }
+
+    let next = siblings.iter().copied().find(|p| *p > position);
+    position::between(Some(position), next)
+        .or_else(|| position::between(siblings.last().copied(), None))
+        .unwrap_or_default()
+}
+
+/// Index among `sibling_ids` where an item goes when placed right after
+/// `after_id` or right before `before_id`; with neither it goes last.
+/// Giving both is rejected, as they may not be neighbours.
+fn insertion_index(
+    sibling_ids: impl Iterator<Item = u64> + Clone,
+    entity: &'static str,
+    before_id: Option<u64>,
+    after_id: Option<u64>,
+) -> Result<usize> {
+    if before_id.is_some() && after_id.is_some() {
+        let mut errors = validator::ValidationErrors::new();
+        let mut error = validator::ValidationError::new("one_anchor");
+        error.message = Some("Must not be given together with after_id".into());
+        errors.add("before_id", error);
+
+        return Err(errors.into());
+    }
+
+    let find = |id: u64| {
+        sibling_ids
+            .clone()
+            .position(|sibling_id| sibling_id == id)
+            .ok_or(NotFound { entity, id })
+    };
+
+    Ok(match (after_id, before_id) {
+        (Some(after_id), _) => find(after_id)? + 1,
+        (None, Some(before_id)) => find(before_id)?,
+        (None, None) => sibling_ids.count(),
+    })
+}
+
+fn find_todo(todos: Vec<Todo>, id: u64) -> Result<Todo> {
+    todos
+        .into_iter()
+        .find(|t| t.id == id)
+        .ok_or_else(|| NotFound { entity: "todo", id }.into())
+}
+
+/// Todos of the group among `todos`, in their order.
+fn group_todos(todos: &[Todo], group_id: u64) -> Vec<Todo> {
+    todos
+        .iter()
+        .filter(|t| t.group_id == group_id)
+        .cloned()
+        .collect()
+}
+
+/// Change keeping everything of `todo` as it is.
+fn todo_change(todo: &Todo) -> ports::TodoChangeData {
+    ports::TodoChangeData {
+        id: todo.id,
+        text: todo.text.clone(),
+        is_done: todo.is_done,
+        group: ports::GroupRef::Stored(todo.group_id),
+        position: todo.position,
+    }
+}
+
+pub struct TodoInteractor {
+    todo_repository: Arc<dyn ports::TodoRepository + Send + Sync>,
+    group_repository: Arc<dyn ports::GroupRepository + Send + Sync>,
+    content_repository: Arc<dyn ports::ProjectContentRepository + Send + Sync>,
+    events: Arc<EventBus>,
+    history: Arc<HistoryInteractor>,
+}
+
+impl IsSync for TodoInteractor {}
+impl IsSend for TodoInteractor {}
+
+impl Debug for TodoInteractor {
+    fn fmt(&self, _f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        panic!("TodoInteractor.fmt not implemented")
+    }
+}
+
+impl TodoInteractor {
+    pub fn new(
+        todo_repository: Arc<dyn ports::TodoRepository + Send + Sync>,
+        group_repository: Arc<dyn ports::GroupRepository + Send + Sync>,
+        content_repository: Arc<dyn ports::ProjectContentRepository + Send + Sync>,
+        events: Arc<EventBus>,
+        history: Arc<HistoryInteractor>,
+    ) -> Self {
+        TodoInteractor {
+            todo_repository,
+            group_repository,
+            content_repository,
+            events,
+            history,
+        }
+    }
+
+    /// Adds the todo at the end of the group.
+    pub async fn create(&self, text: &str, group_id: u64) -> Result<Todo> {
+        let data = ports::CreateTodoData { text, group_id };
+        data.validate()?;
+
+        let group = self.get_group(group_id).await?;
+        let content = self.content(group.project_id).await?;
+        let last = group_todos(&content.todos, group_id)
+            .iter()
+            .map(|t| t.position)
+            .reduce(f64::max);
+        let changes = ports::ProjectChanges {
+            create_todos: vec![ports::NewTodoData {
+                text: text.to_string(),
+                is_done: false,
+                group: ports::GroupRef::Stored(group_id),
+                position: position::between(last, None).unwrap_or_default(),
+            }],
+            ..Default::default()
+        };
+
+        // Nothing else changed in between, so the largest id is the new one.
+        let after = self.apply(content, changes).await?;
+        let todo = after
+            .todos
+            .into_iter()
+            .max_by_key(|t| t.id)
+            .context("Created todo is missing")?;
+
+        self.events
+            .publish(DomainEvent::TodoCreated { todo: todo.clone() });
+        self.record(HistoryAction::CreateTodo, vec![], vec![todo.clone()])
+            .await;
+
+        Ok(todo)
+    }
+
+    pub async fn find_by_group(&self, group_id: u64) -> Result<Vec<Todo>> {
+        self.todo_repository.find_by_group(group_id).await
+    }
+
+    pub async fn update(&self, id: u64, text: Option<&str>) -> Result<Todo> {
+        let data = ports::UpdateTodoData { text };
+        data.validate()?;
+
+        let (content, before) = self.content_of(id).await?;
+        let changes = ports::ProjectChanges {
+            update_todos: vec![ports::TodoChangeData {
+                text: text.map_or_else(|| before.text.clone(), ToString::to_string),
+                ..todo_change(&before)
+            }],
+            ..Default::default()
+        };
+        let todo = find_todo(self.apply(content, changes).await?.todos, id)?;
+
+        self.events
+            .publish(DomainEvent::TodoUpdated { todo: todo.clone() });
+        self.record(HistoryAction::UpdateTodo, vec![before], vec![todo.clone()])
+            .await;
+
+        Ok(todo)
+    }
+
+    pub async fn complete(&self, id: u64) -> Result<Todo> {
+        self.set_done(id, true).await
+    }
+
+    pub async fn reopen(&self, id: u64) -> Result<Todo> {
+        self.set_done(id, false).await
+    }
+
+    /// Completes every todo of the group at once.
+    pub async fn complete_group(&self, group_id: u64) -> Result<Vec<Todo>> {
+        let group = self.get_group(group_id).await?;
+        let content = self.content(group.project_id).await?;
+        let before = group_todos(&content.todos, group_id);
+        let open_ids = before
+            .iter()
+            .filter(|t| !t.is_done)
+            .map(|t| t.id)
+            .collect::<Vec<_>>();
+
+        let update_todos = before
+            .iter()
+            .filter(|t| !t.is_done)
+            .map(|t| ports::TodoChangeData {
+                is_done: true,
+                ..todo_change(t)
+            })
+            .collect::<Vec<_>>();
+        let todos = if update_todos.is_empty() {
+            before.clone()
+        } else {
+            let changes = ports::ProjectChanges {
+                update_todos,
+                ..Default::default()
+            };
+            group_todos(&self.apply(content, changes).await?.todos, group_id)
+        };
+
+        for todo in todos.iter().filter(|t| open_ids.contains(&t.id)) {
+            self.events
+                .publish(DomainEvent::TodoCompleted { todo: todo.clone() });
+        }
+        self.record(HistoryAction::CompleteGroup, before, todos.clone())
+            .await;
+
+        Ok(todos)
+    }
+
+    /// Sets `is_done` and bumps the project's `updated_at`.
+    async fn set_done(&self, id: u64, is_done: bool) -> Result<Todo> {
+        let (content, before) = self.content_of(id).await?;
+        let changes = ports::ProjectChanges {
+            update_todos: vec![ports::TodoChangeData {
+                is_done,
+                ..todo_change(&before)
+            }],
+            ..Default::default()
+        };
+        let todo = find_todo(self.apply(content, changes).await?.todos, id)?;
+
+        let (event, action) = if is_done {
+            (
+                DomainEvent::TodoCompleted { todo: todo.clone() },
+                HistoryAction::CompleteTodo,
+            )
+        } else {
+            (
+                DomainEvent::TodoReopened { todo: todo.clone() },
+                HistoryAction::ReopenTodo,
+            )
+        };
+        self.events.publish(event);
+        self.record(action, vec![before], vec![todo.clone()]).await;
+
+        Ok(todo)
+    }
+
+    /// Moves the todo into `group_id`, right after `after_id` or right
+    /// before `before_id`; with neither it goes to the end of the group.
+    ///
+    /// The target group has to belong to the same project. Only the moved
+    /// todo is written, unless its new neighbours are too close to split,
+    /// in which case the group is renumbered in the same write. Bumps the
+    /// project's `updated_at`.
+    pub async fn move_todo(
+        &self,
+        id: u64,
+        group_id: u64,
+        before_id: Option<u64>,
+        after_id: Option<u64>,
+    ) -> Result<Todo> {
+        let (content, todo) = self.content_of(id).await?;
+        let target = self.get_group(group_id).await?;
+
+        if target.project_id != content.project.id {
+            let mut errors = validator::ValidationErrors::new();
+            let mut error = validator::ValidationError::new("same_project");
+            error.message = Some("Must be a group of the same project".into());
+            errors.add("group_id", error);
+
+            return Err(errors.into());
+        }
+
+        let mut siblings = group_todos(&content.todos, group_id);
+        siblings.retain(|t| t.id != id);
+        let mut before_todos = siblings.clone();
+        before_todos.push(todo.clone());
+
+        let index = insertion_index(siblings.iter().map(|t| t.id), "todo", before_id, after_id)?;
+
+        let before = index.checked_sub(1).map(|i| siblings[i].position);
+        let after = siblings.get(index).map(|t| t.position);
+
+        let moved = |todo: &Todo, position| ports::TodoChangeData {
+            group: ports::GroupRef::Stored(group_id),
+            position,
+            ..todo_change(todo)
+        };
+        let update_todos = if let Some(position) = position::between(before, after) {
+            vec![moved(&todo, position)]
+        } else {
+            siblings.insert(index, todo);
+            siblings
+                .iter()
+                .zip(position::spread(siblings.len()))
+                .map(|(t, position)| moved(t, position))
+                .collect()
+        };
+        let changes = ports::ProjectChanges {
+            update_todos,
+            ..Default::default()
+        };
+
+        let after_todos = group_todos(&self.apply(content, changes).await?.todos, group_id);
+        let todo = find_todo(after_todos.clone(), id)?;
+
+        self.events
+            .publish(DomainEvent::TodoMoved { todo: todo.clone() });
+        self.record(HistoryAction::MoveTodo, before_todos, after_todos)
+            .await;
+
+        Ok(todo)
+    }
+
+    async fn get(&self, id: u64) -> Result<Todo> {
+        self.todo_repository
+            .get(id)
+            .await?
+            .ok_or_else(|| NotFound { entity: "todo", id }.into())
+    }
+
+    async fn content(&self, project_id: u64) -> Result<ProjectContent> {
+        self.content_repository
+            .get(project_id)
+            .await?
+            .ok_or_else(|| {
+                NotFound {
+                    entity: "project",
+                    id: project_id,
+                }
+                .into()
+            })
+    }
+
+    /// Content of the todo's project, with the todo as read along with it.
+    async fn content_of(&self, id: u64) -> Result<(ProjectContent, Todo)> {
+        let todo = self.get(id).await?;
+        let group = self.get_group(todo.group_id).await?;
+        let content = self.content(group.project_id).await?;
+        let todo = find_todo(content.todos.clone(), id)?;
+
+        Ok((content, todo))
+    }
+
+    /// Applies `changes` planned against `content`; the project's
+    /// `updated_at` is raised in the same write, see
+    /// `ports::ProjectContentRepository::apply`.
+    async fn apply(
+        &self,
+        content: ProjectContent,
+        changes: ports::ProjectChanges,
+    ) -> Result<ProjectContent> {
+        let id = content.project.id;
+
+        self.content_repository
+            .apply(id, Some(content), changes)
+            .await?
+            .ok_or_else(|| {
+                NotFound {
+                    entity: "project",
+                    id,
+                }
+                .into()
+            })
+    }
+
+    async fn get_group(&self, id: u64) -> Result<Group> {
+        self.group_repository.get(id).await?.ok_or_else(|| {
+            NotFound {
+                entity: "group",
+                id,
+            }
+            .into()
+        })
+    }
+
+    async fn record(&self, action: HistoryAction, before: Vec<Todo>, after: Vec<Todo>) {
+        let snapshot = |todos| Snapshot {
+            todos,
+            ..Default::default()
+        };
+
+        let recorded = self
+            .history
+            .record(action, snapshot(before), snapshot(after))
+            .await;
+        log_unrecorded(recorded);
+    }
+}
+
+pub struct TrashInteractor {
+    trash_repository: Arc<dyn ports::TrashRepository + Send + Sync>,
+    group_repository: Arc<dyn ports::GroupRepository + Send + Sync>,
+    todo_repository: Arc<dyn ports::TodoRepository + Send + Sync>,
+    content_repository: Arc<dyn ports::ProjectContentRepository + Send + Sync>,
+    events: Arc<EventBus>,
+    history: Arc<HistoryInteractor>,
+}
+
+impl IsSync for TrashInteractor {}
+impl IsSend for TrashInteractor {}
+
+impl Debug for TrashInteractor {
+    fn fmt(&self, _f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        panic!("TrashInteractor.fmt not implemented")
+    }
+}
+
+impl TrashInteractor {
+    pub fn new(
+        trash_repository: Arc<dyn ports::TrashRepository + Send + Sync>,
+        group_repository: Arc<dyn ports::GroupRepository + Send + Sync>,
+        todo_repository: Arc<dyn ports::TodoRepository + Send + Sync>,
+        content_repository: Arc<dyn ports::ProjectContentRepository + Send + Sync>,
+        events: Arc<EventBus>,
+        history: Arc<HistoryInteractor>,
+    ) -> Self {
+        TrashInteractor {
+            trash_repository,
+            group_repository,
+            todo_repository,
+            content_repository,
+            events,
+            history,
+        }
+    }
+
+    /// Moves the project with its groups and todos to the trash.
+    pub async fn delete_project(&self, id: u64) -> Result<DeletedProject> {
+        let item = self.trash(EntityKind::Project, Project::NAME, id).await?;
+        let Snapshot {
+            projects,
+            groups,
+            todos,
+        } = item.entities.clone();
+        let deleted = DeletedProject {
+            project: projects
+                .into_iter()
+                .next()
+                .context("Trashed project is missing")?,
+            groups,
+            todos,
+        };
+
+        self.events.publish(DomainEvent::ProjectDeleted {
+            deleted: deleted.clone(),
+        });
+        let recorded = self
+            .history
+            .record(
+                HistoryAction::DeleteProject,
+                item.entities,
+                Snapshot::default(),
+            )
+            .await;
+        log_unrecorded(recorded);
+
+        Ok(deleted)
+    }
+
+    /// Moves the group with its todos to the trash.
+    pub async fn delete_group(&self, id: u64) -> Result<Group> {
+        let item = self.trash(EntityKind::Group, Group::NAME, id).await?;
+        let group = item
+            .entities
+            .groups
+            .first()
+            .cloned()
+            .context("Trashed group is missing")?;
+
+        self.events.publish(DomainEvent::GroupDeleted {
+            group: group.clone(),
+        });
+        let recorded = self
+            .history
+            .record(
+                HistoryAction::DeleteGroup,
+                item.entities,
+                Snapshot::default(),
+            )
+            .await;
+        log_unrecorded(recorded);
+
+        Ok(group)
+    }
+
+    pub async fn delete_todo(&self, id: u64) -> Result<Todo> {
+        let item = self.trash(EntityKind::Todo, Todo::NAME, id).await?;
+        let todo = item
+            .entities
+            .todos
+            .first()
+            .cloned()
+            .context("Trashed todo is missing")?;
+
+        self.events
+            .publish(DomainEvent::TodoDeleted { todo: todo.clone() });
+        let recorded = self
+            .history
+            .record(
+                HistoryAction::DeleteTodo,
+                item.entities,
+                Snapshot::default(),
+            )
+            .await;
+        log_unrecorded(recorded);
+
+        Ok(todo)
+    }
+
+    /// Items ordered from the most recently deleted.
+    pub async fn list(&self) -> Result<Vec<TrashItem>> {
+        self.trash_repository.list().await
+    }
+
+    /// Puts the entities of the item back with their ids and positions.
+    /// A restored group or todo whose position was taken by a sibling
+    /// meanwhile goes right after that sibling.
+    ///
+    /// Fails with a conflict while the project or group they belong to is
+    /// deleted, or while the entity itself exists again; the item stays then.
+    pub async fn restore(&self, id: u64) -> Result<TrashItem> {
+        let item = self.trash_repository.get(id).await?.ok_or(NotFound {
+            entity: "trash item",
+            id,
+        })?;
+
+        let item = self.replace_on_collision(item).await?;
+
+        // Whether the entities can go back is checked in the same write that
+        // takes the item out of the trash. A restored project keeps its own
+        // `updated_at`; the projects that restored groups and todos go back
+        // into are raised in it too.
+        let item = self
+            .content_repository
+            .restore(item, OffsetDateTime::now_utc())
+            .await?
+            .ok_or(NotFound {
+                entity: "trash item",
+                id,
+            })?;
+
+        self.events
+            .publish(DomainEvent::RestoredFromTrash { item: item.clone() });
+        let recorded = self
+            .history
+            .record(
+                HistoryAction::RestoreFromTrash,
+                Snapshot::default(),
+                item.entities.clone(),
+            )
+            .await;
+        log_unrecorded(recorded);
+
+        Ok(item)
+    }
+
+    /// Deletes for good the items deleted more than `retention_days` ago;
+    /// with 0 they are kept. Returns the deleted items.
+    pub async fn purge(&self, now: OffsetDateTime, retention_days: u32) -> Result<Vec<TrashItem>> {
+        if retention_days == 0 {
+            return Ok(Vec::new());
+        }
+        // Nothing can be older than a cutoff before the earliest date.
+        let Some(before) = now.checked_sub(time::Duration::days(retention_days.into())) else {
+            return Ok(Vec::new());
+        };
+
+        self.trash_repository.purge(before).await
+    }
+
+    /// Reads, trashes and deletes the entity in one write, so nothing added
+    /// to it meanwhile is left behind without its parent.
+    async fn trash(&self, entity: EntityKind, name: &'static str, id: u64) -> Result<TrashItem> {
+        self.content_repository
+            .trash(entity, id, OffsetDateTime::now_utc())
+            .await?
+            .ok_or_else(|| NotFound { entity: name, id }.into())
+    }
+
+    /// Moves the trashed group or todo off the position of a sibling that
+    /// took it since the item was deleted.
+    async fn replace_on_collision(&self, mut item: TrashItem) -> Result<TrashItem> {
+        match item.entity {
+            EntityKind::Project => {}
+            EntityKind::Group => {
+                if let Some(group) = item
+                    .entities
+                    .groups
+                    .iter_mut()
+                    .find(|g| g.id == item.entity_id)
+                {
+                    let siblings = self
+                        .group_repository
+                        .find_by_project(group.project_id)
+                        .await?;
+                    group.position =
+                        free_position(group.position, siblings.iter().map(|g| g.position));
+                }
+            }
+            EntityKind::Todo => {
+                if let Some(todo) = item
+                    .entities
+                    .todos
+                    .iter_mut()
+                    .find(|t| t.id == item.entity_id)
+                {
+                    let siblings = self.todo_repository.find_by_group(todo.group_id).await?;
+                    todo.position =
+                        free_position(todo.position, siblings.iter().map(|t| t.position));
+                }
+            }
+        }
+
+        Ok(item)
+    }
+}
+
+pub struct SettingsInteractor {
+    settings_repository: Arc<dyn ports::SettingsRepository + Send + Sync>,
+}
+
+impl IsSync for SettingsInteractor {}
+impl IsSend for SettingsInteractor {}
+
+impl Debug for SettingsInteractor {
+    fn fmt(&self, _f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        panic!("SettingsInteractor.fmt not implemented")
+    }
+}
+
+impl SettingsInteractor {
+    pub fn new(settings_repository: Arc<dyn ports::SettingsRepository + Send + Sync>) -> Self {
+        SettingsInteractor {
+            settings_repository,
+        }
+    }
+
+    pub async fn get(&self) -> Result<Settings> {
+        self.settings_repository.get().await
+    }
+
+    pub async fn update(
+        &self,
+        default_groups: Option<Vec<String>>,
+        activity_retention_days: Option<u32>,
+        trash_retention_days: Option<u32>,
+    ) -> Result<Settings> {
+        let data = ports::UpdateSettingsData {
+            default_groups,
+            activity_retention_days,
+            trash_retention_days,
+        };
+        data.validate()?;
+
+        self.settings_repository.update(data).await
+    }
+}
+
+/// Logs a failure to record a change that is already stored; the change
+/// is not failed for it.
+fn log_unrecorded(recorded: Result<()>) {
+    if let Err(error) = recorded {
+        log::error!("Failed to record change: {:?}", error);
+    }
+}
+
+pub struct HistoryInteractor {
+    history_repository: Arc<dyn ports::HistoryRepository + Send + Sync>,
+    project_repository: Arc<dyn ports::ProjectRepository + Send + Sync>,
+    group_repository: Arc<dyn ports::GroupRepository + Send + Sync>,
+    todo_repository: Arc<dyn ports::TodoRepository + Send + Sync>,
+    trash_repository: Arc<dyn ports::TrashRepository + Send + Sync>,
+    content_repository: Arc<dyn ports::ProjectContentRepository + Send + Sync>,
+    events: Arc<EventBus>,
+    activity: Arc<ActivityInteractor>,
+    /// Held while the stacks are read, changed and saved.
+    lock: Mutex<()>,
+}
+
+impl IsSync for HistoryInteractor {}
+impl IsSend for HistoryInteractor {}
+
+impl Debug for HistoryInteractor {
+    fn fmt(&self, _f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        panic!("HistoryInteractor.fmt not implemented")
+    }
+}
+
+impl HistoryInteractor {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        history_repository: Arc<dyn ports::HistoryRepository + Send + Sync>,
+        project_repository: Arc<dyn ports::ProjectRepository + Send + Sync>,
+        group_repository: Arc<dyn ports::GroupRepository + Send + Sync>,
+        todo_repository: Arc<dyn ports::TodoRepository + Send + Sync>,
+        trash_repository: Arc<dyn ports::TrashRepository + Send + Sync>,
+        content_repository: Arc<dyn ports::ProjectContentRepository + Send + Sync>,
+        events: Arc<EventBus>,
+        activity: Arc<ActivityInteractor>,
+    ) -> Self {
+        HistoryInteractor {
+            history_repository,
+            project_repository,
+            group_repository,
+            todo_repository,
+            trash_repository,
+            content_repository,
+            events,
+            activity,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Logs a change just made as activity and adds an entry for it,
+    /// unless it changed nothing.
+    ///
+    /// A corrupt history is started over, as it holds no data of its own;
+    /// any other failure to read it is returned.
+    pub async fn record(
+        &self,
+        action: HistoryAction,
+        before: Snapshot,
+        after: Snapshot,
+    ) -> Result<()> {
+        self.log_activity(action, false, &before, &after).await;
+
+        let Some(entry) = HistoryEntry::new(action, OffsetDateTime::now_utc(), before, after)
+        else {
+            return Ok(());
+        };
+
+        let _lock = self.lock.lock().await;
+        let mut history = match self.history_repository.get().await {
+            Ok(history) => history,
+            Err(error) if error.kind() == ErrorKind::StorageCorrupt => {
+                log::warn!("Starting over corrupt history: {:?}", error);
+                History::default()
+            }
+            Err(error) => return Err(error),
+        };
+        history.record(entry);
+
+        self.history_repository
+            .save(history)
             .await
+            .context("Failed to record history")?;
+
+        Ok(())
+    }
+
+    pub async fn state(&self) -> Result<HistoryState> {
+        Ok(self.history_repository.get().await?.state())
+    }
+
+    /// Takes back the last change.
+    ///
+    /// Fails with a conflict when an entity of the change was changed
+    /// since; the entry is dropped then, as it can never apply again.
+    pub async fn undo(&self) -> Result<HistoryState> {
+        self.replay(true).await
+    }
+
+    /// Makes the last change taken back again, failing like `undo`.
+    pub async fn redo(&self) -> Result<HistoryState> {
+        self.replay(false).await
+    }
+
+    /// Drops the entries touching entities another process changed.
+    pub async fn invalidate(&self, change: &StorageChange) -> Result<()> {
+        let _lock = self.lock.lock().await;
+        let mut history = self.history_repository.get().await?;
+
+        if history.invalidate(change) {
+            self.history_repository.save(history).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Forgets every entry, e.g. after a backup replaced all data.
+    pub async fn clear(&self) -> Result<()> {
+        let _lock = self.lock.lock().await;
+
+        self.history_repository.save(Default::default()).await
+    }
+
+    /// Logs the change as activity on its own: history and the activity
+    /// log are stored apart, and neither failing keeps the other from
+    /// being written.
+    async fn log_activity(
+        &self,
+        action: HistoryAction,
+        undone: bool,
+        before: &Snapshot,
+        after: &Snapshot,
+    ) {
+        if let Err(error) = self.activity.record(action, undone, before, after).await {
+            log::warn!("Failed to log activity: {:?}", error);
+        }
+    }
+
+    async fn replay(&self, undo: bool) -> Result<HistoryState> {
+        let _lock = self.lock.lock().await;
+        let mut history = self.history_repository.get().await?;
+
+        let entry = if undo {
+            history.undo.pop()
+        } else {
+            history.redo.pop()
+        };
+        let Some(entry) = entry else {
+            return Ok(history.state());
+        };
+        let (expected, target) = if undo {
+            (&entry.after, &entry.before)
+        } else {
+            (&entry.before, &entry.after)
+        };
+
+        if let Err(error) = self.check(expected, target).await {
+            if error.kind() == ErrorKind::Conflict {
+                self.history_repository.save(history).await?;
+            }
+            return Err(error);
+        }
+        let project_ids = match self.write(expected, target, entry.trash_move(undo)).await {
+            Ok(project_ids) => project_ids,
+            Err(error) => {
+                if error.kind() == ErrorKind::Conflict {
+                    self.history_repository.save(history).await?;
+                }
+                return Err(error);
+            }
+        };
+        self.log_activity(entry.action, undo, expected, target)
+            .await;
+
+        let action = entry.action;
+        if undo {
+            history.redo.push(entry);
+        } else {
+            history.push_undo(entry);
+        }
+        let state = history.state();
+        self.history_repository.save(history).await?;
+
+        self.events.publish(if undo {
+            DomainEvent::Undone {
+                action,
+                project_ids,
+            }
+        } else {
+            DomainEvent::Redone {
+                action,
+                project_ids,
+            }
+        });
+
+        Ok(state)
+    }
+
+    /// Fails unless the entities are stored as `expected` describes, and
+    /// the groups and projects `target` deletes gained no children since.
+    async fn check(&self, expected: &Snapshot, target: &Snapshot) -> Result<()> {
+        let mut current = Snapshot::default();
+        for id in history::ids::<Project>(expected, target) {
+            current
+                .projects
+                .extend(self.project_repository.get(id).await?);
+        }
+        for id in history::ids::<Group>(expected, target) {
+            current.groups.extend(self.group_repository.get(id).await?);
+        }
+        for id in history::ids::<Todo>(expected, target) {
+            current.todos.extend(self.todo_repository.get(id).await?);
+        }
+
+        let changed = [
+            history::changed_since::<Project>(expected, target, &current).map(|id| ("project", id)),
+            history::changed_since::<Group>(expected, target, &current).map(|id| ("group", id)),
+            history::changed_since::<Todo>(expected, target, &current).map(|id| ("todo", id)),
+        ];
+        if let Some((entity, id)) = changed.into_iter().flatten().next() {
+            return Err(Conflict {
+                entity,
+                id,
+                reason: "was changed since",
+            }
+            .into());
+        }
+
+        let has_children = |entity: &'static str, id: u64| Conflict {
+            entity,
+            id,
+            reason: "has children that were added since",
+        };
+        for id in history::deleted::<Project>(expected, target) {
+            for group in self.group_repository.find_by_project(id).await? {
+                if history::find::<Group>(expected, group.id).is_none() {
+                    return Err(has_children("project", id).into());
+                }
+            }
+        }
+        for id in history::deleted::<Group>(expected, target) {
+            for todo in self.todo_repository.find_by_group(id).await? {
+                if history::find::<Todo>(expected, todo.id).is_none() {
+                    return Err(has_children("group", id).into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes `target` over `expected`, stamping `updated_at` of the written
+    /// entities and their projects with now. Deleted entities go into the
+    /// trash and restored ones come out of it along the way, as `trash_move`
+    /// says. Returns the projects involved.
+    async fn write(
+        &self,
+        expected: &Snapshot,
+        target: &Snapshot,
+        trash_move: Option<TrashMove>,
+    ) -> Result<Vec<u64>> {
+        let now = OffsetDateTime::now_utc();
+
+        let mut project_ids = history::ids::<Project>(expected, target);
+        let groups = expected.groups.iter().chain(&target.groups);
+        project_ids.extend(groups.map(|g| g.project_id));
+        for todo in expected.todos.iter().chain(&target.todos) {
+            let group = match history::find::<Group>(target, todo.group_id) {
+                Some(group) => Some(group.clone()),
+                None => self.group_repository.get(todo.group_id).await?,
+            };
+            project_ids.extend(group.map(|g| g.project_id));
+        }
+        project_ids.sort_unstable();
+        project_ids.dedup();
+
+        let mut entities = target.clone();
+        for project in &mut entities.projects {
+            project.updated_at = now;
+        }
+        for todo in &mut entities.todos {
+            todo.updated_at = now;
+        }
+        let deleted = ports::EntityIds {
+            projects: history::deleted::<Project>(expected, target),
+            groups: history::deleted::<Group>(expected, target),
+            todos: history::deleted::<Todo>(expected, target),
+        };
+        // Each of these raises `updated_at` of the projects around the
+        // groups and todos in the same write.
+        match trash_move {
+            None => self.content_repository.put(entities, deleted, now).await?,
+            Some(TrashMove {
+                entity,
+                id,
+                into_trash: true,
+            }) => {
+                // `check` found the entities stored as `expected` holds them,
+                // so the item gets exactly those.
+                self.content_repository
+                    .trash(entity, id, now)
+                    .await?
+                    .ok_or_else(|| out_of_trash(entity, id, "was deleted since"))?;
+            }
+            Some(TrashMove {
+                entity,
+                id,
+                into_trash: false,
+            }) => {
+                let item = self
+                    .trash_repository
+                    .list()
+                    .await?
+                    .into_iter()
+                    .find(|item| item.entity == entity && item.entity_id == id)
+                    .ok_or_else(|| out_of_trash(entity, id, "is no longer in the trash"))?;
+                self.content_repository
+                    .restore(TrashItem { entities, ..item }, now)
+                    .await?
+                    .ok_or_else(|| out_of_trash(entity, id, "is no longer in the trash"))?;
+            }
+        }
+
+        Ok(project_ids)
+    }
+}
+
+/// Conflict of replaying a delete or restore whose entity moved across the
+/// trash behind the history's back.
+fn out_of_trash(entity: EntityKind, id: u64, reason: &'static str) -> crate::result::Error {
+    let entity = match entity {
+        EntityKind::Project => Project::NAME,
+        EntityKind::Group => Group::NAME,
+        EntityKind::Todo => Todo::NAME,
+    };
+
+    Conflict { entity, id, reason }.into()
+}
+
+/// Activities per page when `ActivityInteractor::list` is not given a limit.
+pub const ACTIVITY_PAGE_SIZE: usize = 50;
+/// Most activities per page.
+pub const ACTIVITY_PAGE_LIMIT: usize = 500;
+
+pub struct ActivityInteractor {
+    activity_repository: Arc<dyn ports::ActivityRepository + Send + Sync>,
+    group_repository: Arc<dyn ports::GroupRepository + Send + Sync>,
+    settings_repository: Arc<dyn ports::SettingsRepository + Send + Sync>,
+}
+
+impl IsSync for ActivityInteractor {}
+impl IsSend for ActivityInteractor {}
+
+impl Debug for ActivityInteractor {
+    fn fmt(&self, _f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        panic!("ActivityInteractor.fmt not implemented")
+    }
+}
+
+impl ActivityInteractor {
+    pub fn new(
+        activity_repository: Arc<dyn ports::ActivityRepository + Send + Sync>,
+        group_repository: Arc<dyn ports::GroupRepository + Send + Sync>,
+        settings_repository: Arc<dyn ports::SettingsRepository + Send + Sync>,
+    ) -> Self {
+        ActivityInteractor {
+            activity_repository,
+            group_repository,
+            settings_repository,
+        }
+    }
+
+    /// Logs one activity for every entity that differs between `before`
+    /// and `after`; `undone` marks a change taken back by undo.
+    pub async fn record(
+        &self,
+        action: HistoryAction,
+        undone: bool,
+        before: &Snapshot,
+        after: &Snapshot,
+    ) -> Result<()> {
+        let context = ActivityContext {
+            at: OffsetDateTime::now_utc(),
+            action,
+            undone,
+        };
+
+        let mut activities = Vec::new();
+        for (before, after) in history::changes::<Project>(before, after) {
+            if let Some(project) = before.or(after) {
+                activities.push(context.data(project.id, before, after)?);
+            }
+        }
+        for (before, after) in history::changes::<Group>(before, after) {
+            if let Some(group) = before.or(after) {
+                activities.push(context.data(group.project_id, before, after)?);
+            }
+        }
+        for (todo_before, todo_after) in history::changes::<Todo>(before, after) {
+            let Some(todo) = todo_before.or(todo_after) else {
+                continue;
+            };
+
+            let project_id = match history::find::<Group>(after, todo.group_id)
+                .or_else(|| history::find::<Group>(before, todo.group_id))
+            {
+                Some(group) => group.project_id,
+                None => {
+                    self.group_repository
+                        .get(todo.group_id)
+                        .await?
+                        .ok_or(NotFound {
+                            entity: "group",
+                            id: todo.group_id,
+                        })?
+                        .project_id
+                }
+            };
+            activities.push(context.data(project_id, todo_before, todo_after)?);
+        }
+
+        if !activities.is_empty() {
+            self.activity_repository
+                .append(activities)
+                .await
+                .context("Failed to record activity")?;
+        }
+
+        Ok(())
+    }
+
+    /// Page of the project's activities, newest first, starting below
+    /// `before_id` when it is given.
+    pub async fn list(
+        &self,
+        project_id: u64,
+        before_id: Option<u64>,
+        limit: Option<usize>,
+    ) -> Result<ActivityPage> {
+        let limit = limit
+            .unwrap_or(ACTIVITY_PAGE_SIZE)
+            .clamp(1, ACTIVITY_PAGE_LIMIT);
+
+        // One more than asked for tells whether there is another page.
+        let mut activities = self
+            .activity_repository
+            .list(project_id, before_id, limit + 1)
+            .await?;
+
+        let next_before_id = if activities.len() > limit {
+            activities.truncate(limit);
+            activities.last().map(|activity| activity.id)
+        } else {
+            None
+        };
+
+        Ok(ActivityPage {
+            activities,
+            next_before_id,
+        })
+    }
+
+    /// Deletes activities older than `Settings::activity_retention_days`.
+    /// Returns how many were deleted.
+    pub async fn prune(&self, now: OffsetDateTime) -> Result<usize> {
+        let days = self
+            .settings_repository
+            .get()
+            .await?
+            .activity_retention_days;
+        if days == 0 {
+            return Ok(0);
+        }
+        // Nothing can be older than a cutoff before the earliest date.
+        let Some(before) = now.checked_sub(time::Duration::days(days.into())) else {
+            return Ok(0);
+        };
+
+        self.activity_repository.prune(before).await
+    }
+}
+
+/// What the activities logged for one change have in common.
+struct ActivityContext {
+    at: OffsetDateTime,
+    action: HistoryAction,
+    undone: bool,
+}
+
+impl ActivityContext {
+    fn data<T: Entity>(
+        &self,
+        project_id: u64,
+        before: Option<&T>,
+        after: Option<&T>,
+    ) -> Result<ports::CreateActivityData> {
+        let value = |entity: Option<&T>| {
+            entity
+                .map(serde_json::to_value)
+                .transpose()
+                .context("Failed to serialize activity")
+        };
+
+        Ok(ports::CreateActivityData {
+            at: self.at,
+            project_id,
+            entity: T::KIND,
+            entity_id: before.or(after).map_or(0, T::id),
+            action: self.action,
+            undone: self.undone,
+            before: value(before)?,
+            after: value(after)?,
+        })
+    }
+}
+
+pub struct BackupInteractor {
+    backup_repository: Arc<dyn ports::BackupRepository + Send + Sync>,
+    policy: BackupPolicy,
+    recovery_interactor: Arc<RecoveryInteractor>,
+    events: Arc<EventBus>,
+    history: Arc<HistoryInteractor>,
+    /// Time and write count of the last backup taken in this run.
+    last: Mutex<Option<(OffsetDateTime, u64)>>,
+}
+
+impl IsSync for BackupInteractor {}
+impl IsSend for BackupInteractor {}
+
+impl Debug for BackupInteractor {
+    fn fmt(&self, _f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        panic!("BackupInteractor.fmt not implemented")
+    }
+}
+
+impl BackupInteractor {
+    pub fn new(
+        backup_repository: Arc<dyn ports::BackupRepository + Send + Sync>,
+        policy: BackupPolicy,
+        recovery_interactor: Arc<RecoveryInteractor>,
+        events: Arc<EventBus>,
+        history: Arc<HistoryInteractor>,
+    ) -> Self {
+        BackupInteractor {
+            backup_repository,
+            policy,
+            recovery_interactor,
+            events,
+            history,
+            last: Mutex::new(None),
+        }
+    }
+
+    pub async fn create(&self) -> Result<Backup> {
+        let backup = self.backup(OffsetDateTime::now_utc()).await?;
+        self.prune().await?;
+
+        Ok(backup)
+    }
+
+    pub async fn list(&self) -> Result<Vec<Backup>> {
+        self.backup_repository.list().await
+    }
+
+    /// Restores the backup after backing up the current state, so the
+    /// restore itself can be undone. Expired backups are left for the next
+    /// `tick`, so the restored one is never dropped in favour of that
+    /// safety backup before it is used.
+    pub async fn restore(&self, id: u64) -> Result<Backup> {
+        let not_found = || NotFound {
+            entity: "backup",
+            id,
+        };
+
+        let backups = self.backup_repository.list().await?;
+        if !backups.iter().any(|b| b.id == id) {
+            return Err(not_found().into());
+        }
+
+        self.backup(OffsetDateTime::now_utc())
+            .await
+            .context("Failed to backup before restore")?;
+
+        let backup = self
+            .backup_repository
+            .restore(id)
+            .await?
+            .ok_or_else(not_found)?;
+
+        // The history describes the data the backup replaced.
+        if let Err(error) = self.history.clear().await {
+            log::error!("Failed to clear history: {:?}", error);
+        }
+        self.events.publish(DomainEvent::BackupRestored {
+            backup: backup.clone(),
+        });
+
+        Ok(backup)
+    }
+
+    /// Takes a backup when the policy says it is due; called on startup and
+    /// then periodically. Nothing is backed up while recovered files wait
+    /// for the user, so the salvaged data doesn't push out the backups
+    /// taken before the damage.
+    pub async fn tick(&self, now: OffsetDateTime) -> Result<Option<Backup>> {
+        if self.recovery_interactor.is_pending().await {
+            return Ok(None);
+        }
+
+        let writes = self.backup_repository.write_count().await?;
+        let last = *self.last.lock().await;
+
+        let is_due = self.policy.is_due(
+            last.map(|(at, _)| at),
+            writes.saturating_sub(last.map_or(0, |(_, writes)| writes)),
+            now,
+        );
+        if !is_due {
+            return Ok(None);
+        }
+
+        let backup = self.backup(now).await?;
+        self.prune().await?;
+
+        Ok(Some(backup))
+    }
+
+    async fn backup(&self, now: OffsetDateTime) -> Result<Backup> {
+        let backup = self.backup_repository.create(now).await?;
+        // Counted afterwards, so anything written until the backup was taken
+        // is in it.
+        let writes = self.backup_repository.write_count().await?;
+        *self.last.lock().await = Some((now, writes));
+
+        Ok(backup)
+    }
+
+    /// Drops the backups the policy no longer keeps, unless recovered files
+    /// wait for the user to pick one of them.
+    async fn prune(&self) -> Result<()> {
+        if self.recovery_interactor.is_pending().await {
+            return Ok(());
+        }
+
+        let backups = self.backup_repository.list().await?;
+        for id in self.policy.expired(&backups) {
+            self.backup_repository
+                .delete(id)
+                .await
+                .context(format!("Failed to delete expired backup {}", id))?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct RecoveryInteractor {
+    recovery_repository: Arc<dyn ports::RecoveryRepository + Send + Sync>,
+    history: Arc<HistoryInteractor>,
+    report: Mutex<RecoveryReport>,
+}
+
+impl IsSync for RecoveryInteractor {}
+impl IsSend for RecoveryInteractor {}
+
+impl Debug for RecoveryInteractor {
+    fn fmt(&self, _f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        panic!("RecoveryInteractor.fmt not implemented")
+    }
+}
+
+impl RecoveryInteractor {
+    pub fn new(
+        recovery_repository: Arc<dyn ports::RecoveryRepository + Send + Sync>,
+        history: Arc<HistoryInteractor>,
+    ) -> Self {
+        RecoveryInteractor {
+            recovery_repository,
+            history,
+            report: Mutex::new(RecoveryReport::default()),
+        }
+    }
+
+    /// Checks the storage before anything else reads it; damaged files are
+    /// kept in the report until it is dismissed.
+    pub async fn check(&self) -> Result<RecoveryReport> {
+        let files = self
+            .recovery_repository
+            .recover(OffsetDateTime::now_utc())
+            .await?;
+
+        // The history may describe data that could not be salvaged.
+        if !files.is_empty() {
+            if let Err(error) = self.history.clear().await {
+                log::error!("Failed to clear history: {:?}", error);
+            }
+        }
+
+        let mut report = self.report.lock().await;
+        report.files.extend(files);
+
+        Ok(report.clone())
+    }
+
+    pub async fn report(&self) -> RecoveryReport {
+        self.report.lock().await.clone()
+    }
+
+    /// Whether damaged files were recovered and the report is not dismissed.
+    pub async fn is_pending(&self) -> bool {
+        !self.report.lock().await.files.is_empty()
+    }
+
+    /// Clears the report once the user chose to restore a backup or to go
+    /// on with the salvaged data.
+    pub async fn dismiss(&self) -> RecoveryReport {
+        std::mem::take(&mut *self.report.lock().await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports::{
+        ActivityRepository as _, GroupRepository as _, HistoryRepository as _,
+        ProjectRepository as _, SettingsRepository as _,
+    };
+    use crate::repositories::fake::{
+        FakeActivityRepository, FakeBackupRepository, FakeGroupRepository, FakeHistoryRepository,
+        FakeProjectContentRepository, FakeProjectRepository, FakeRecoveryRepository,
+        FakeSettingsRepository, FakeTodoRepository, FakeTrashRepository,
+    };
+
+    fn project_interactor() -> ProjectInteractor {
+        project_and_trash_interactors().0
+    }
+
+    /// Project and trash interactors over the same repositories.
+    fn project_and_trash_interactors() -> (ProjectInteractor, TrashInteractor) {
+        let project = Arc::new(FakeProjectRepository::new());
+        let group = Arc::new(FakeGroupRepository::new());
+        let todo = Arc::new(FakeTodoRepository::new());
+        let trash = Arc::new(FakeTrashRepository::new());
+        let events = Arc::new(EventBus::new());
+        let history = history_interactor(
+            project.clone(),
+            group.clone(),
+            todo.clone(),
+            trash.clone(),
+            &events,
+        );
+
+        let interactor = ProjectInteractor::new(
+            project.clone(),
+            group.clone(),
+            todo.clone(),
+            Arc::new(FakeSettingsRepository::new()),
+            Arc::new(FakeProjectContentRepository::new(
+                project,
+                group,
+                todo,
+                trash.clone(),
+            )),
+            events,
+            history,
+        );
+        let trash = TrashInteractor::new(
+            trash,
+            interactor.group_repository.clone(),
+            interactor.todo_repository.clone(),
+            interactor.content_repository.clone(),
+            interactor.events.clone(),
+            interactor.history.clone(),
+        );
+
+        (interactor, trash)
+    }
+
+    /// History, with its activity log, over the repositories.
+    fn history_interactor(
+        project: Arc<FakeProjectRepository>,
+        group: Arc<FakeGroupRepository>,
+        todo: Arc<FakeTodoRepository>,
+        trash: Arc<FakeTrashRepository>,
+        events: &Arc<EventBus>,
+    ) -> Arc<HistoryInteractor> {
+        let activity = Arc::new(ActivityInteractor::new(
+            Arc::new(FakeActivityRepository::new()),
+            group.clone(),
+            Arc::new(FakeSettingsRepository::new()),
+        ));
+        Arc::new(HistoryInteractor::new(
+            Arc::new(FakeHistoryRepository::new()),
+            project.clone(),
+            group.clone(),
+            todo.clone(),
+            trash.clone(),
+            Arc::new(FakeProjectContentRepository::new(
+                project, group, todo, trash,
+            )),
+            events.clone(),
+            activity,
+        ))
+    }
+
+    /// Collects the events published on `events` from now on.
+    fn record(events: &EventBus) -> Arc<std::sync::Mutex<Vec<DomainEvent>>> {
+        let recorded = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = recorded.clone();
+        events.subscribe(move |event| sink.lock().unwrap().push(event.clone()));
+
+        recorded
+    }
+
+    async fn create_group(interactor: &ProjectInteractor, name: &str, project_id: u64) -> Group {
+        interactor
+            .group_repository
+            .create(ports::CreateGroupData { name, project_id })
+            .await
+            .expect("Failed to create group")
+    }
+
+    async fn create_todo(interactor: &ProjectInteractor, text: &str, group_id: u64) -> Todo {
+        interactor
+            .todo_repository
+            .create(ports::CreateTodoData { text, group_id })
+            .await
+            .expect("Failed to create todo")
+    }
+
+    /// Stores `todos` as they are, behind the interactors' backs.
+    async fn put_todos(content_repository: &dyn ports::ProjectContentRepository, todos: Vec<Todo>) {
+        content_repository
+            .put(
+                Snapshot {
+                    todos,
+                    ..Default::default()
+                },
+                Default::default(),
+                OffsetDateTime::now_utc(),
+            )
+            .await
+            .expect("Failed to put todos")
+    }
+
+    #[tokio::test]
+    async fn project_names_need_three_characters() {
+        let interactor = project_interactor();
+        let project = interactor.create("Project").await.expect("create");
+        let message = |error: crate::result::Error| {
+            serde_json::to_value(&error).expect("serialize")["validation"]["name"][0]["message"]
+                .clone()
+        };
+        let expected = "Must be at least 3 characters long";
+
+        let error = interactor
+            .create("ab")
+            .await
+            .expect_err("create should fail");
+        assert_eq!(message(error), expected);
+        let error = interactor
+            .update(project.id, Some("ab"), None)
+            .await
+            .expect_err("update should fail");
+        assert_eq!(message(error), expected);
+        let renamed = serde_json::json!({ "id": project.id, "name": "ab" });
+        let error = interactor
+            .apply_json(project.id, &renamed.to_string())
+            .await
+            .expect_err("apply should fail");
+        assert_eq!(message(error), expected);
+
+        interactor
+            .update(project.id, Some("abc"), None)
+            .await
+            .expect("update");
+    }
+
+    #[tokio::test]
+    async fn create_project_adds_default_groups() {
+        let interactor = project_interactor();
+        let settings = SettingsInteractor::new(interactor.settings_repository.clone());
+        let empty = interactor.create("Empty").await.expect("create");
+
+        settings
+            .update(
+                Some(vec!["Backlog".into(), "Today".into(), "Done".into()]),
+                None,
+                None,
+            )
+            .await
+            .expect("update settings");
+        let project = interactor.create("Project").await.expect("create");
+
+        let group_names =
+            |groups: Vec<Group>| groups.into_iter().map(|g| g.name).collect::<Vec<_>>();
+        let groups = interactor
+            .group_repository
+            .find_by_project(project.id)
+            .await
+            .expect("find groups");
+        assert_eq!(group_names(groups), ["Backlog", "Today", "Done"]);
+        let groups = interactor
+            .group_repository
+            .find_by_project(empty.id)
+            .await
+            .expect("find groups");
+        assert_eq!(group_names(groups), Vec::<String>::new());
+    }
+
+    #[tokio::test]
+    async fn create_project_publishes_project_and_groups() {
+        let interactor = project_interactor();
+        interactor
+            .settings_repository
+            .update(ports::UpdateSettingsData {
+                default_groups: Some(vec!["Today".into()]),
+                ..Default::default()
+            })
+            .await
+            .expect("update settings");
+        let events = record(&interactor.events);
+
+        let project = interactor.create("Project").await.expect("create");
+
+        let groups = interactor
+            .group_repository
+            .find_by_project(project.id)
+            .await
+            .expect("find groups");
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                DomainEvent::ProjectCreated { project },
+                DomainEvent::GroupCreated {
+                    group: groups[0].clone()
+                }
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn update_settings_rejects_empty_group_name() {
+        let settings = SettingsInteractor::new(Arc::new(FakeSettingsRepository::new()));
+
+        let error = settings
+            .update(Some(vec!["Today".into(), " ".into()]), None, None)
+            .await
+            .expect_err("update should fail");
+
+        assert_eq!(
+            serde_json::to_value(&error).expect("serialize")["validation"]["default_groups"][0]
+                ["message"],
+            "Group names must not be empty"
+        );
+        assert_eq!(settings.get().await.expect("get"), Settings::default());
+    }
+
+    #[tokio::test]
+    async fn update_settings_rejects_activity_retention_over_a_century() {
+        let settings = SettingsInteractor::new(Arc::new(FakeSettingsRepository::new()));
+
+        let error = settings
+            .update(None, Some(36501), None)
+            .await
+            .expect_err("update should fail");
+
+        assert_eq!(error.kind(), ErrorKind::Validation);
+        assert_eq!(settings.get().await.expect("get"), Settings::default());
+    }
+
+    #[tokio::test]
+    async fn update_settings_rejects_trash_retention_over_a_century() {
+        let settings = SettingsInteractor::new(Arc::new(FakeSettingsRepository::new()));
+
+        let error = settings
+            .update(None, None, Some(36501))
+            .await
+            .expect_err("update should fail");
+
+        assert_eq!(error.kind(), ErrorKind::Validation);
+        assert_eq!(settings.get().await.expect("get"), Settings::default());
+    }
+
+    #[tokio::test]
+    async fn todo_changes_touch_project() {
+        let TodoFixture {
+            interactor,
+            project_repository,
+            project,
+            groups,
+            ..
+        } = todo_fixture().await;
+        let get_project = || async {
+            project_repository
+                .get(project.id)
+                .await
+                .expect("get project")
+                .expect("project")
+        };
+
+        let created = interactor
+            .create("new", groups[0].id)
+            .await
+            .expect("create");
+        assert_eq!(get_project().await.updated_at, created.updated_at);
+
+        let updated = interactor
+            .update(created.id, Some("renamed"))
+            .await
+            .expect("update");
+        assert_eq!(get_project().await.updated_at, updated.updated_at);
+
+        let completed = interactor
+            .complete_group(groups[1].id)
+            .await
+            .expect("complete");
+        assert_eq!(
+            get_project().await.updated_at,
+            completed.iter().map(|t| t.updated_at).max().expect("todos")
+        );
+    }
+
+    #[tokio::test]
+    async fn repair_updated_at_raises_to_todos() {
+        let TodoFixture {
+            interactor: todo_interactor,
+            project_repository,
+            project,
+            todos,
+            ..
+        } = todo_fixture().await;
+        let interactor = ProjectInteractor::new(
+            project_repository.clone(),
+            todo_interactor.group_repository.clone(),
+            todo_interactor.todo_repository.clone(),
+            Arc::new(FakeSettingsRepository::new()),
+            Arc::new(FakeProjectContentRepository::new(
+                Arc::new(FakeProjectRepository::new()),
+                Arc::new(FakeGroupRepository::new()),
+                Arc::new(FakeTodoRepository::new()),
+                Arc::new(FakeTrashRepository::new()),
+            )),
+            todo_interactor.events.clone(),
+            todo_interactor.history.clone(),
+        );
+        let empty = interactor
+            .project_repository
+            .create(ports::CreateProjectData { name: "Empty" })
+            .await
+            .expect("create");
+        let latest = todos.iter().map(|t| t.updated_at).max().expect("todos");
+
+        interactor
+            .project_repository
+            .set_updated_at(&[
+                (project.id, latest - time::Duration::days(1)),
+                (empty.id, empty.created_at + time::Duration::days(1)),
+            ])
+            .await
+            .expect("set updated_at");
+
+        let repaired = interactor.repair_updated_at().await.expect("repair");
+
+        assert_eq!(
+            repaired
+                .iter()
+                .map(|p| (p.id, p.updated_at))
+                .collect::<Vec<_>>(),
+            [(project.id, latest)]
+        );
+        assert_eq!(
+            interactor.repair_updated_at().await.expect("repair"),
+            Vec::new()
+        );
+    }
+
+    #[tokio::test]
+    async fn apply_exported_json_changes_nothing() {
+        let interactor = project_interactor();
+        let project = interactor.create("Project").await.expect("create");
+        let group = create_group(&interactor, "First", project.id).await;
+        create_todo(&interactor, "a", group.id).await;
+
+        let json = interactor.export_json(project.id).await.expect("export");
+        let summary = interactor
+            .apply_json(project.id, &json)
+            .await
+            .expect("apply");
+
+        assert_eq!(summary, ChangeSummary::default());
+        assert_eq!(
+            interactor.export_json(project.id).await.expect("export"),
+            json
+        );
+    }
+
+    #[tokio::test]
+    async fn apply_json_changes_project() {
+        let interactor = project_interactor();
+        let project = interactor.create("Project").await.expect("create");
+        let first = create_group(&interactor, "First", project.id).await;
+        let second = create_group(&interactor, "Second", project.id).await;
+        let a = create_todo(&interactor, "a", first.id).await;
+        let b = create_todo(&interactor, "b", first.id).await;
+        let c = create_todo(&interactor, "c", second.id).await;
+
+        let json = serde_json::json!({
+            "id": project.id,
+            "name": "Renamed",
+            "groups": [
+                {"name": "New", "todos": [{"text": "d"}]},
+                {"id": first.id, "name": "First", "is_opened": false, "todos": [
+                    {"id": c.id, "text": "c", "is_done": true},
+                    {"id": b.id, "text": "b"},
+                ]},
+            ],
+        });
+        let summary = interactor
+            .apply_json(project.id, &json.to_string())
+            .await
+            .expect("apply");
+
+        assert_eq!(
+            serde_json::to_value(summary).expect("serialize"),
+            serde_json::json!({
+                "project": {"created": 0, "updated": 1, "moved": 0, "deleted": 0},
+                "groups": {"created": 1, "updated": 1, "moved": 0, "deleted": 1},
+                "todos": {"created": 1, "updated": 1, "moved": 1, "deleted": 1},
+            })
+        );
+
+        let document: serde_json::Value =
+            serde_json::from_str(&interactor.export_json(project.id).await.expect("export"))
+                .expect("parse");
+        assert_eq!(document["name"], "Renamed");
+        assert_eq!(document["groups"][0]["name"], "New");
+        assert_eq!(document["groups"][0]["todos"][0]["text"], "d");
+        assert_eq!(
+            document["groups"][1],
+            serde_json::json!({"id": first.id, "name": "First", "is_opened": false, "todos": [
+                {"id": c.id, "text": "c", "is_done": true},
+                {"id": b.id, "text": "b", "is_done": false},
+            ]})
+        );
+        assert_eq!(
+            interactor.todo_repository.get(a.id).await.expect("get"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn apply_invalid_json_changes_nothing() {
+        let interactor = project_interactor();
+        let project = interactor.create("Project").await.expect("create");
+        let other = interactor.create("Other").await.expect("create");
+        let group = create_group(&interactor, "First", project.id).await;
+        let foreign = create_group(&interactor, "Foreign", other.id).await;
+        let json = interactor.export_json(project.id).await.expect("export");
+
+        let invalid = serde_json::json!({
+            "id": project.id,
+            "name": "Renamed",
+            "groups": [
+                {"id": group.id, "name": "", "todos": []},
+                {"id": foreign.id, "name": "Foreign"},
+            ],
+        });
+        let error = interactor
+            .apply_json(project.id, &invalid.to_string())
+            .await
+            .expect_err("apply should fail");
+
+        let error = serde_json::to_value(&error).expect("serialize");
+        assert_eq!(
+            error["validation"]["groups"]["0"]["name"][0]["message"],
+            "Must not be empty"
+        );
+        assert_eq!(
+            error["validation"]["group_ids"][0]["message"],
+            format!("Group {} is not part of the project", foreign.id)
+        );
+
+        let error = interactor
+            .apply_json(project.id, "{")
+            .await
+            .expect_err("apply should fail");
+        assert_eq!(
+            serde_json::to_value(&error).expect("serialize")["validation"]["document"][0]["code"],
+            "json"
+        );
+        assert_eq!(
+            interactor.export_json(project.id).await.expect("export"),
+            json
+        );
+    }
+
+    #[tokio::test]
+    async fn delete_project_cascades() {
+        let (interactor, trash) = project_and_trash_interactors();
+        let project = interactor.create("Project").await.expect("create");
+        let other = interactor.create("Other").await.expect("create");
+
+        let first = create_group(&interactor, "First", project.id).await;
+        let second = create_group(&interactor, "Second", project.id).await;
+        let kept = create_group(&interactor, "Kept", other.id).await;
+        for (text, group_id) in [("a", first.id), ("b", first.id), ("c", second.id)] {
+            create_todo(&interactor, text, group_id).await;
+        }
+        let kept_todo = create_todo(&interactor, "d", kept.id).await;
+
+        let deleted = trash.delete_project(project.id).await.expect("delete");
+
+        assert_eq!(deleted.project, project);
+        assert_eq!(deleted.groups.len(), 2);
+        assert_eq!(deleted.todos.len(), 3);
+        assert_eq!(
+            interactor
+                .list(ports::ProjectFilter::All, ports::ProjectSort::default())
+                .await
+                .expect("list"),
+            vec![other.clone()]
+        );
+        assert_eq!(
+            interactor
+                .group_repository
+                .find_by_project(other.id)
+                .await
+                .expect("find groups"),
+            vec![kept.clone()]
+        );
+        assert_eq!(
+            interactor
+                .todo_repository
+                .find_by_group(kept.id)
+                .await
+                .expect("find todos"),
+            vec![kept_todo]
+        );
+
+        let items = trash.list().await.expect("list trash");
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].entity, EntityKind::Project);
+        assert_eq!(items[0].entities, deleted.into());
+    }
+
+    #[tokio::test]
+    async fn undo_delete_project_restores_ids() {
+        let (interactor, trash) = project_and_trash_interactors();
+        let project = interactor.create("Project").await.expect("create");
+        let group = create_group(&interactor, "Group", project.id).await;
+        let todo = create_todo(&interactor, "Todo", group.id).await;
+
+        trash.delete_project(project.id).await.expect("delete");
+        interactor.history.undo().await.expect("undo");
+
+        assert_eq!(trash.list().await.expect("list trash"), []);
+        let content = interactor.content(project.id).await.expect("content");
+        assert_eq!(content.project.name, project.name);
+        assert_eq!(content.groups, [group]);
+        assert_eq!(content.todos.len(), 1);
+        assert_eq!(content.todos[0].id, todo.id);
+        assert_eq!(content.todos[0].text, todo.text);
+
+        interactor.history.redo().await.expect("redo");
+        let error = interactor
+            .content(project.id)
+            .await
+            .expect_err("project should be deleted again");
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+        let items = trash.list().await.expect("list trash");
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].entity, EntityKind::Project);
+        assert_eq!(items[0].entity_id, project.id);
+        assert_eq!(items[0].entities.todos.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn restore_todo_keeps_id_and_position() {
+        let (interactor, trash) = project_and_trash_interactors();
+        let project = interactor.create("Project").await.expect("create");
+        let group = create_group(&interactor, "Group", project.id).await;
+        for text in ["a", "b", "c"] {
+            create_todo(&interactor, text, group.id).await;
+        }
+        let todos = interactor
+            .todo_repository
+            .find_by_group(group.id)
+            .await
+            .expect("find todos");
+
+        let deleted = trash.delete_todo(todos[1].id).await.expect("delete");
+        assert_eq!(deleted, todos[1]);
+        assert_eq!(
+            interactor
+                .todo_repository
+                .find_by_group(group.id)
+                .await
+                .expect("find todos"),
+            [todos[0].clone(), todos[2].clone()]
+        );
+
+        let item = trash.list().await.expect("list trash").remove(0);
+        trash.restore(item.id).await.expect("restore");
+
+        assert_eq!(
+            interactor
+                .todo_repository
+                .find_by_group(group.id)
+                .await
+                .expect("find todos"),
+            todos
+        );
+        assert_eq!(trash.list().await.expect("list trash"), []);
+    }
+
+    #[tokio::test]
+    async fn restore_todo_moves_off_taken_position() {
+        let (interactor, trash) = project_and_trash_interactors();
+        let project = interactor.create("Project").await.expect("create");
+        let group = create_group(&interactor, "Group", project.id).await;
+        for text in ["a", "b", "c"] {
+            create_todo(&interactor, text, group.id).await;
+        }
+        let todos = interactor
+            .todo_repository
+            .find_by_group(group.id)
+            .await
+            .expect("find todos");
+
+        trash.delete_todo(todos[1].id).await.expect("delete");
+        // Siblings were renumbered while "b" was in the trash.
+        put_todos(
+            &*interactor.content_repository,
+            vec![Todo {
+                position: todos[1].position,
+                ..todos[2].clone()
+            }],
+        )
+        .await;
+        let item = trash.list().await.expect("list trash").remove(0);
+
+        let restored = trash.restore(item.id).await.expect("restore");
+
+        let after = interactor
+            .todo_repository
+            .find_by_group(group.id)
+            .await
+            .expect("find todos");
+        assert_eq!(
+            after.iter().map(|t| t.id).collect::<Vec<_>>(),
+            [todos[0].id, todos[2].id, todos[1].id]
+        );
+        assert!(after[2].position > after[1].position);
+        assert_eq!(restored.entities.todos, [after[2].clone()]);
+
+        // The restore is recorded and can be taken back.
+        interactor.history.undo().await.expect("undo");
+        assert_eq!(
+            interactor
+                .todo_repository
+                .find_by_group(group.id)
+                .await
+                .expect("find todos"),
+            after[..2]
+        );
+    }
+
+    #[tokio::test]
+    async fn restore_group_waits_for_its_project() {
+        let (interactor, trash) = project_and_trash_interactors();
+        let project = interactor.create("Project").await.expect("create");
+        let group = create_group(&interactor, "Group", project.id).await;
+        let todo = create_todo(&interactor, "Todo", group.id).await;
+
+        trash.delete_group(group.id).await.expect("delete group");
+        trash
+            .delete_project(project.id)
+            .await
+            .expect("delete project");
+        let items = trash.list().await.expect("list trash");
+        assert_eq!(
+            items.iter().map(|i| i.entity).collect::<Vec<_>>(),
+            [EntityKind::Project, EntityKind::Group]
+        );
+
+        let error = trash.restore(items[1].id).await.expect_err("conflict");
+        assert_eq!(error.kind(), ErrorKind::Conflict);
+
+        trash.restore(items[0].id).await.expect("restore project");
+        trash.restore(items[1].id).await.expect("restore group");
+
+        // The group was taken out of the project and put back since.
+        let content = interactor.content(project.id).await.expect("content");
+        assert!(content.project.updated_at > project.updated_at);
+        assert_eq!(content.project.name, project.name);
+        assert_eq!(content.groups, [group]);
+        assert_eq!(content.todos, [todo]);
+    }
+
+    #[tokio::test]
+    async fn undo_and_redo_restore_from_trash() {
+        let (interactor, trash) = project_and_trash_interactors();
+        let project = interactor.create("Project").await.expect("create");
+        let group = create_group(&interactor, "Group", project.id).await;
+        let todo = create_todo(&interactor, "Todo", group.id).await;
+        trash.delete_group(group.id).await.expect("delete");
+        let item = trash.list().await.expect("list trash").remove(0);
+        trash.restore(item.id).await.expect("restore");
+
+        interactor.history.undo().await.expect("undo");
+
+        let items = trash.list().await.expect("list trash");
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].entity, EntityKind::Group);
+        assert_eq!(items[0].entity_id, group.id);
+        assert_eq!(items[0].entities.groups.len(), 1);
+        assert_eq!(items[0].entities.todos.len(), 1);
+        assert_eq!(items[0].entities.todos[0].id, todo.id);
+        let content = interactor.content(project.id).await.expect("content");
+        assert_eq!(content.groups, []);
+        assert_eq!(content.todos, []);
+
+        interactor.history.redo().await.expect("redo");
+
+        assert_eq!(trash.list().await.expect("list trash"), []);
+        let content = interactor.content(project.id).await.expect("content");
+        assert_eq!(content.groups, [group]);
+        assert_eq!(content.todos.len(), 1);
+        assert_eq!(content.todos[0].text, todo.text);
+    }
+
+    #[tokio::test]
+    async fn undo_delete_conflicts_when_purged() {
+        let (interactor, trash) = project_and_trash_interactors();
+        let project = interactor.create("Project").await.expect("create");
+        let group = create_group(&interactor, "Group", project.id).await;
+        let todo = create_todo(&interactor, "Todo", group.id).await;
+        trash.delete_todo(todo.id).await.expect("delete");
+        let now = OffsetDateTime::now_utc();
+        trash
+            .purge(now + time::Duration::days(2), 1)
+            .await
+            .expect("purge");
+
+        let error = interactor.history.undo().await.expect_err("conflict");
+
+        assert_eq!(error.kind(), ErrorKind::Conflict);
+        assert_eq!(error.entity(), Some(("todo", todo.id)));
+        assert_eq!(
+            interactor
+                .todo_repository
+                .find_by_group(group.id)
+                .await
+                .expect("find todos"),
+            []
+        );
+    }
+
+    #[tokio::test]
+    async fn restore_of_existing_entity_keeps_item() {
+        let (interactor, trash) = project_and_trash_interactors();
+        let project = interactor.create("Project").await.expect("create");
+        let group = create_group(&interactor, "Group", project.id).await;
+        let todo = create_todo(&interactor, "Todo", group.id).await;
+        trash.delete_todo(todo.id).await.expect("delete");
+        // Put back behind the trash's back, e.g. by another process.
+        put_todos(&*interactor.content_repository, vec![todo]).await;
+        let item = trash.list().await.expect("list trash").remove(0);
+
+        let error = trash.restore(item.id).await.expect_err("conflict");
+
+        assert_eq!(error.kind(), ErrorKind::Conflict);
+        assert_eq!(trash.list().await.expect("list trash"), [item]);
+    }
+
+    #[tokio::test]
+    async fn purge_trash_keeps_retention_days() {
+        let (interactor, trash) = project_and_trash_interactors();
+        let project = interactor.create("Project").await.expect("create");
+        trash.delete_project(project.id).await.expect("delete");
+        let now = OffsetDateTime::now_utc();
+
+        assert_eq!(
+            trash
+                .purge(now + time::Duration::days(90), 0)
+                .await
+                .expect("purge"),
+            []
+        );
+        assert_eq!(trash.purge(now, 1).await.expect("purge"), []);
+        assert_eq!(trash.purge(now, u32::MAX).await.expect("purge"), []);
+
+        let purged = trash
+            .purge(now + time::Duration::days(2), 1)
+            .await
+            .expect("purge");
+        assert_eq!(purged.len(), 1);
+        assert_eq!(purged[0].entity_id, project.id);
+        assert_eq!(trash.list().await.expect("list trash"), []);
+    }
+
+    async fn group_interactor_with(names: &[&str]) -> (GroupInteractor, Vec<Group>) {
+        let group_repository = Arc::new(FakeGroupRepository::new());
+        let project_repository = Arc::new(FakeProjectRepository::new());
+        let events = Arc::new(EventBus::new());
+        let history = history_interactor(
+            project_repository.clone(),
+            group_repository.clone(),
+            Arc::new(FakeTodoRepository::new()),
+            Arc::new(FakeTrashRepository::new()),
+            &events,
+        );
+        let content_repository = Arc::new(FakeProjectContentRepository::new(
+            project_repository.clone(),
+            group_repository.clone(),
+            Arc::new(FakeTodoRepository::new()),
+            Arc::new(FakeTrashRepository::new()),
+        ));
+        let interactor =
+            GroupInteractor::new(group_repository, content_repository, events, history);
+        project_repository
+            .create(ports::CreateProjectData { name: "Project" })
+            .await
+            .expect("create project");
+
+        let mut groups = Vec::new();
+        for name in names {
+            groups.push(interactor.create(name, 1).await.expect("create"));
+        }
+
+        (interactor, groups)
+    }
+
+    async fn group_names(interactor: &GroupInteractor) -> Vec<String> {
+        interactor
+            .find_by_project(1)
+            .await
+            .expect("find groups")
+            .into_iter()
+            .map(|g| g.name)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn group_changes_touch_project() {
+        let (interactor, groups) = group_interactor_with(&["a", "b"]).await;
+        let updated_at = || async {
+            let content = interactor.content(1).await.expect("content");
+            content.project.updated_at
+        };
+
+        let created = updated_at().await;
+        interactor
+            .set_opened(groups[0].id, false)
+            .await
+            .expect("set opened");
+        let opened = updated_at().await;
+        interactor
+            .move_group(groups[1].id, Some(groups[0].id), None)
+            .await
+            .expect("move");
+        let moved = updated_at().await;
+
+        assert!(created < opened && opened < moved);
+    }
+
+    #[tokio::test]
+    async fn move_group_between_neighbours() {
+        let (interactor, groups) = group_interactor_with(&["a", "b", "c", "d"]).await;
+
+        interactor
+            .move_group(groups[3].id, None, Some(groups[0].id))
+            .await
+            .expect("move after");
+        assert_eq!(group_names(&interactor).await, ["a", "d", "b", "c"]);
+
+        interactor
+            .move_group(groups[0].id, Some(groups[2].id), None)
+            .await
+            .expect("move before");
+        assert_eq!(group_names(&interactor).await, ["d", "b", "a", "c"]);
+
+        interactor
+            .move_group(groups[3].id, None, None)
+            .await
+            .expect("move to end");
+        assert_eq!(group_names(&interactor).await, ["b", "a", "c", "d"]);
+
+        interactor
+            .move_group(groups[2].id, Some(groups[1].id), None)
+            .await
+            .expect("move to start");
+        assert_eq!(group_names(&interactor).await, ["c", "b", "a", "d"]);
+    }
+
+    #[tokio::test]
+    async fn move_group_rebalances_when_gap_is_exhausted() {
+        let (interactor, groups) = group_interactor_with(&["a", "b", "c"]).await;
+
+        // Keep dropping the last group right after "a", halving the gap
+        // between "a" and whatever follows it on every move.
+        for i in 0..100 {
+            let moved = &groups[if i % 2 == 0 { 2 } else { 1 }];
+            interactor
+                .move_group(moved.id, None, Some(groups[0].id))
+                .await
+                .expect("move");
+        }
+
+        assert_eq!(group_names(&interactor).await, ["a", "b", "c"]);
+
+        let positions = interactor
+            .find_by_project(1)
+            .await
+            .expect("find groups")
+            .into_iter()
+            .map(|g| g.position)
+            .collect::<Vec<_>>();
+        assert!(
+            positions.windows(2).all(|w| w[1] - w[0] >= 1e-9),
+            "positions are too close: {:?}",
+            positions
+        );
+    }
+
+    #[tokio::test]
+    async fn set_opened_is_restored_with_groups() {
+        let (interactor, groups) = group_interactor_with(&["a", "b", "c"]).await;
+
+        interactor
+            .set_opened(groups[1].id, false)
+            .await
+            .expect("close");
+
+        let opened = interactor
+            .find_by_project(1)
+            .await
+            .expect("find groups")
+            .into_iter()
+            .map(|g| g.is_opened)
+            .collect::<Vec<_>>();
+        assert_eq!(opened, [true, false, true]);
+
+        let error = interactor
+            .set_opened(100, true)
+            .await
+            .expect_err("set_opened should fail");
+        assert_eq!(
+            (error.kind(), error.entity()),
+            (ErrorKind::NotFound, Some(("group", 100)))
+        );
+    }
+
+    #[tokio::test]
+    async fn move_group_unknown_anchor() {
+        let (interactor, groups) = group_interactor_with(&["a", "b"]).await;
+
+        let error = interactor
+            .move_group(groups[0].id, None, Some(groups[0].id))
+            .await
+            .expect_err("move should fail");
+
+        assert_eq!(
+            (error.kind(), error.entity()),
+            (ErrorKind::NotFound, Some(("group", groups[0].id)))
+        );
+        assert_eq!(group_names(&interactor).await, ["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn move_group_rejects_both_anchors() {
+        let (interactor, groups) = group_interactor_with(&["a", "b", "c"]).await;
+
+        let error = interactor
+            .move_group(groups[2].id, Some(groups[1].id), Some(groups[0].id))
+            .await
+            .expect_err("move should fail");
+
+        assert_eq!(error.kind(), ErrorKind::Validation);
+        assert_eq!(
+            serde_json::to_value(&error).expect("serialize")["validation"]["before_id"][0]["code"],
+            "one_anchor"
+        );
+        assert_eq!(group_names(&interactor).await, ["a", "b", "c"]);
+    }
+
+    struct TodoFixture {
+        interactor: TodoInteractor,
+        project_repository: Arc<FakeProjectRepository>,
+        project: Project,
+        groups: Vec<Group>,
+        todos: Vec<Todo>,
+    }
+
+    /// Project with groups "left" and "right", two todos in each, and a
+    /// group "elsewhere" in another project.
+    async fn todo_fixture() -> TodoFixture {
+        let project_repository = Arc::new(FakeProjectRepository::new());
+        let group_repository = Arc::new(FakeGroupRepository::new());
+        let todo_repository = Arc::new(FakeTodoRepository::new());
+        let events = Arc::new(EventBus::new());
+        let history = history_interactor(
+            project_repository.clone(),
+            group_repository.clone(),
+            todo_repository.clone(),
+            Arc::new(FakeTrashRepository::new()),
+            &events,
+        );
+        let content_repository = Arc::new(FakeProjectContentRepository::new(
+            project_repository.clone(),
+            group_repository.clone(),
+            todo_repository.clone(),
+            Arc::new(FakeTrashRepository::new()),
+        ));
+        let interactor = TodoInteractor::new(
+            todo_repository,
+            group_repository.clone(),
+            content_repository,
+            events,
+            history,
+        );
+
+        let mut projects = Vec::new();
+        for name in ["Project", "Other"] {
+            projects.push(
+                project_repository
+                    .create(ports::CreateProjectData { name })
+                    .await
+                    .expect("create project"),
+            );
+        }
+
+        let mut groups = Vec::new();
+        for (name, project_id) in [
+            ("left", projects[0].id),
+            ("right", projects[0].id),
+            ("elsewhere", projects[1].id),
+        ] {
+            groups.push(
+                group_repository
+                    .create(ports::CreateGroupData { name, project_id })
+                    .await
+                    .expect("create group"),
+            );
+        }
+
+        let mut todos = Vec::new();
+        for (text, group_id) in [
+            ("l1", groups[0].id),
+            ("l2", groups[0].id),
+            ("r1", groups[1].id),
+            ("r2", groups[1].id),
+        ] {
+            todos.push(interactor.create(text, group_id).await.expect("create"));
+        }
+
+        TodoFixture {
+            interactor,
+            project_repository,
+            project: projects.swap_remove(0),
+            groups,
+            todos,
+        }
+    }
+
+    async fn todo_texts(interactor: &TodoInteractor, group_id: u64) -> Vec<String> {
+        interactor
+            .find_by_group(group_id)
+            .await
+            .expect("find todos")
+            .into_iter()
+            .map(|t| t.text)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn move_todo_between_groups() {
+        let TodoFixture {
+            interactor,
+            project_repository,
+            project,
+            groups,
+            todos,
+        } = todo_fixture().await;
+
+        let moved = interactor
+            .move_todo(todos[0].id, groups[1].id, None, Some(todos[2].id))
+            .await
+            .expect("move");
+
+        assert_eq!(moved.group_id, groups[1].id);
+        assert_eq!(todo_texts(&interactor, groups[0].id).await, ["l2"]);
+        assert_eq!(
+            todo_texts(&interactor, groups[1].id).await,
+            ["r1", "l1", "r2"]
+        );
+
+        let touched = project_repository
+            .get(project.id)
+            .await
+            .expect("get project")
+            .expect("project");
+        assert!(touched.updated_at > project.updated_at);
+    }
+
+    #[tokio::test]
+    async fn move_todo_within_group() {
+        let TodoFixture {
+            interactor,
+            groups,
+            todos,
+            ..
+        } = todo_fixture().await;
+
+        interactor
+            .move_todo(todos[3].id, groups[1].id, Some(todos[2].id), None)
+            .await
+            .expect("move");
+        assert_eq!(todo_texts(&interactor, groups[1].id).await, ["r2", "r1"]);
+
+        interactor
+            .move_todo(todos[3].id, groups[1].id, None, None)
+            .await
+            .expect("move to end");
+        assert_eq!(todo_texts(&interactor, groups[1].id).await, ["r1", "r2"]);
+    }
+
+    #[tokio::test]
+    async fn move_todo_rebalances_when_gap_is_exhausted() {
+        let TodoFixture {
+            interactor,
+            groups,
+            todos,
+            ..
+        } = todo_fixture().await;
+
+        for i in 0..100 {
+            let moved = &todos[if i % 2 == 0 { 3 } else { 0 }];
+            interactor
+                .move_todo(moved.id, groups[1].id, None, Some(todos[2].id))
+                .await
+                .expect("move");
+        }
+
+        assert_eq!(
+            todo_texts(&interactor, groups[1].id).await,
+            ["r1", "l1", "r2"]
+        );
+    }
+
+    /// Content storage that can not be written, e.g. as the disk is full.
+    struct UnwritableContentRepository {
+        inner: Arc<dyn ports::ProjectContentRepository + Send + Sync>,
+    }
+
+    #[async_trait::async_trait]
+    impl ports::ProjectContentRepository for UnwritableContentRepository {
+        async fn create(
+            &self,
+            project: ports::CreateProjectData<'_>,
+            group_names: &[String],
+        ) -> Result<ProjectContent> {
+            self.inner.create(project, group_names).await
+        }
+
+        async fn get(&self, project_id: u64) -> Result<Option<ProjectContent>> {
+            self.inner.get(project_id).await
+        }
+
+        async fn apply(
+            &self,
+            _project_id: u64,
+            _expected: Option<ProjectContent>,
+            _changes: ports::ProjectChanges,
+        ) -> Result<Option<ProjectContent>> {
+            Err(anyhow::anyhow!("No space left on device").into())
+        }
+
+        async fn put(
+            &self,
+            _entities: Snapshot,
+            _deleted: ports::EntityIds,
+            _at: OffsetDateTime,
+        ) -> Result<()> {
+            Err(anyhow::anyhow!("No space left on device").into())
+        }
+
+        async fn trash(
+            &self,
+            _entity: EntityKind,
+            _id: u64,
+            _at: OffsetDateTime,
+        ) -> Result<Option<TrashItem>> {
+            Err(anyhow::anyhow!("No space left on device").into())
+        }
+
+        async fn restore(
+            &self,
+            _item: TrashItem,
+            _at: OffsetDateTime,
+        ) -> Result<Option<TrashItem>> {
+            Err(anyhow::anyhow!("No space left on device").into())
+        }
+    }
+
+    #[tokio::test]
+    async fn move_todo_rebalance_fails_as_a_whole() {
+        let TodoFixture {
+            interactor,
+            groups,
+            todos,
+            ..
+        } = todo_fixture().await;
+        // Too close to split, so moving in between renumbers the group.
+        put_todos(
+            &*interactor.content_repository,
+            vec![
+                Todo {
+                    position: 1.0,
+                    ..todos[2].clone()
+                },
+                Todo {
+                    position: 1.0 + 1e-12,
+                    ..todos[3].clone()
+                },
+            ],
+        )
+        .await;
+        let stored = || async {
+            let mut stored = Vec::new();
+            for group in &groups {
+                stored.push(
+                    interactor
+                        .find_by_group(group.id)
+                        .await
+                        .expect("find todos"),
+                );
+            }
+            stored
+        };
+        let before = stored().await;
+        let state = interactor.history.state().await.expect("state");
+
+        let unwritable = TodoInteractor::new(
+            interactor.todo_repository.clone(),
+            interactor.group_repository.clone(),
+            Arc::new(UnwritableContentRepository {
+                inner: interactor.content_repository.clone(),
+            }),
+            interactor.events.clone(),
+            interactor.history.clone(),
+        );
+        unwritable
+            .move_todo(todos[0].id, groups[1].id, Some(todos[3].id), None)
+            .await
+            .expect_err("move should fail");
+
+        assert_eq!(stored().await, before);
+        assert_eq!(interactor.history.state().await.expect("state"), state);
+
+        interactor
+            .move_todo(todos[0].id, groups[1].id, Some(todos[3].id), None)
+            .await
+            .expect("move");
+        assert_eq!(
+            todo_texts(&interactor, groups[1].id).await,
+            ["r1", "l1", "r2"]
+        );
+    }
+
+    #[tokio::test]
+    async fn complete_and_reopen_todo() {
+        let TodoFixture {
+            interactor,
+            project_repository,
+            project,
+            todos,
+            ..
+        } = todo_fixture().await;
+
+        let completed = interactor.complete(todos[0].id).await.expect("complete");
+        assert!(completed.is_done);
+        assert!(completed.done_at.is_some());
+
+        let touched = project_repository
+            .get(project.id)
+            .await
+            .expect("get project")
+            .expect("project");
+        assert!(touched.updated_at > project.updated_at);
+
+        let reopened = interactor.reopen(todos[0].id).await.expect("reopen");
+        assert!(!reopened.is_done);
+        assert_eq!(reopened.done_at, None);
+
+        let error = interactor
+            .complete(100)
+            .await
+            .expect_err("complete should fail");
+        assert_eq!(
+            (error.kind(), error.entity()),
+            (ErrorKind::NotFound, Some(("todo", 100)))
+        );
+    }
+
+    #[tokio::test]
+    async fn complete_group_completes_only_its_todos() {
+        let TodoFixture {
+            interactor,
+            groups,
+            todos,
+            ..
+        } = todo_fixture().await;
+        let done_before = interactor.complete(todos[2].id).await.expect("complete");
+
+        let completed = interactor
+            .complete_group(groups[1].id)
+            .await
+            .expect("complete group");
+
+        assert!(completed.iter().all(|t| t.is_done));
+        assert_eq!(completed[0].done_at, done_before.done_at);
+        assert!(interactor
+            .find_by_group(groups[0].id)
+            .await
+            .expect("find todos")
+            .iter()
+            .all(|t| !t.is_done));
+    }
+
+    #[tokio::test]
+    async fn todo_changes_publish_events() {
+        let TodoFixture {
+            interactor,
+            groups,
+            todos,
+            ..
+        } = todo_fixture().await;
+        let events = record(&interactor.events);
+
+        interactor.complete(todos[2].id).await.expect("complete");
+        interactor
+            .complete_group(groups[1].id)
+            .await
+            .expect("complete group");
+        let moved = interactor
+            .move_todo(todos[0].id, groups[1].id, None, None)
+            .await
+            .expect("move");
+
+        let events = events
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|event| match event {
+                DomainEvent::TodoCompleted { todo } => ("completed", todo.id),
+                DomainEvent::TodoMoved { todo } => ("moved", todo.id),
+                event => panic!("unexpected event {:?}", event),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                ("completed", todos[2].id),
+                ("completed", todos[3].id),
+                ("moved", moved.id)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn undo_and_redo_todo_changes() {
+        let TodoFixture {
+            interactor,
+            groups,
+            todos,
+            ..
+        } = todo_fixture().await;
+        let history = interactor.history.clone();
+        let events = record(&interactor.events);
+
+        interactor
+            .update(todos[0].id, Some("renamed"))
+            .await
+            .expect("update");
+        interactor
+            .move_todo(todos[1].id, groups[1].id, None, Some(todos[2].id))
+            .await
+            .expect("move");
+        assert_eq!(
+            todo_texts(&interactor, groups[1].id).await,
+            ["r1", "l2", "r2"]
+        );
+
+        let state = history.undo().await.expect("undo move");
+        assert_eq!(
+            todo_texts(&interactor, groups[0].id).await,
+            ["renamed", "l2"]
+        );
+        assert_eq!(todo_texts(&interactor, groups[1].id).await, ["r1", "r2"]);
+        assert_eq!(
+            state,
+            HistoryState {
+                undo: Some(HistoryAction::UpdateTodo),
+                redo: Some(HistoryAction::MoveTodo),
+            }
+        );
+
+        history.undo().await.expect("undo update");
+        assert_eq!(todo_texts(&interactor, groups[0].id).await, ["l1", "l2"]);
+
+        history.redo().await.expect("redo update");
+        let state = history.redo().await.expect("redo move");
+        assert_eq!(todo_texts(&interactor, groups[0].id).await, ["renamed"]);
+        assert_eq!(
+            todo_texts(&interactor, groups[1].id).await,
+            ["r1", "l2", "r2"]
+        );
+        assert_eq!(state.redo, None);
+
+        let events = events.lock().unwrap();
+        assert_eq!(
+            events[2..],
+            [
+                DomainEvent::Undone {
+                    action: HistoryAction::MoveTodo,
+                    project_ids: vec![groups[0].project_id],
+                },
+                DomainEvent::Undone {
+                    action: HistoryAction::UpdateTodo,
+                    project_ids: vec![groups[0].project_id],
+                },
+                DomainEvent::Redone {
+                    action: HistoryAction::UpdateTodo,
+                    project_ids: vec![groups[0].project_id],
+                },
+                DomainEvent::Redone {
+                    action: HistoryAction::MoveTodo,
+                    project_ids: vec![groups[0].project_id],
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn todo_changes_are_logged_as_activity() {
+        let TodoFixture {
+            interactor,
+            project,
+            groups,
+            todos,
+            ..
+        } = todo_fixture().await;
+        let activity = interactor.history.activity.clone();
+
+        interactor
+            .update(todos[0].id, Some("renamed"))
+            .await
+            .expect("update");
+        interactor
+            .complete_group(groups[1].id)
+            .await
+            .expect("complete group");
+        interactor.history.undo().await.expect("undo");
+
+        let page = activity
+            .list(project.id, None, Some(3))
+            .await
+            .expect("list");
+        let summary = page
+            .activities
+            .iter()
+            .map(|a| (a.action, a.undone, a.entity_id))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                (HistoryAction::CompleteGroup, true, todos[3].id),
+                (HistoryAction::CompleteGroup, true, todos[2].id),
+                (HistoryAction::CompleteGroup, false, todos[3].id),
+            ]
+        );
+        assert_eq!(page.activities[0].before.as_ref().unwrap()["is_done"], true);
+        assert_eq!(page.activities[0].after.as_ref().unwrap()["is_done"], false);
+
+        let page = activity
+            .list(project.id, page.next_before_id, Some(3))
+            .await
+            .expect("list");
+        let renamed = &page.activities[1];
+        assert_eq!(renamed.action, HistoryAction::UpdateTodo);
+        assert_eq!(renamed.entity, crate::models::EntityKind::Todo);
+        assert_eq!(renamed.before.as_ref().unwrap()["text"], "l1");
+        assert_eq!(renamed.after.as_ref().unwrap()["text"], "renamed");
+
+        // The rest are todos the fixture created.
+        let page = activity
+            .list(project.id, page.next_before_id, None)
+            .await
+            .expect("list");
+        assert_eq!(page.activities.len(), 3);
+        assert!(page.activities.iter().all(|a| a.before.is_none()));
+        assert_eq!(page.next_before_id, None);
+    }
+
+    #[tokio::test]
+    async fn prune_activity_keeps_retention_days() {
+        let settings_repository = Arc::new(FakeSettingsRepository::new());
+        let activity = ActivityInteractor::new(
+            Arc::new(FakeActivityRepository::new()),
+            Arc::new(FakeGroupRepository::new()),
+            settings_repository.clone(),
+        );
+        let now = OffsetDateTime::now_utc();
+        let project = Project {
+            id: 1,
+            name: "Project".into(),
+            created_at: now,
+            updated_at: now,
+            is_active: true,
+            archived_at: None,
+        };
+        activity
+            .record(
+                HistoryAction::CreateProject,
+                false,
+                &Snapshot::default(),
+                &Snapshot {
+                    projects: vec![project],
+                    ..Default::default()
+                },
+            )
+            .await
+            .expect("record");
+
+        assert_eq!(
+            activity
+                .prune(now + time::Duration::days(30))
+                .await
+                .expect("prune"),
+            0
+        );
+
+        settings_repository
+            .update(ports::UpdateSettingsData {
+                activity_retention_days: Some(30),
+                ..Default::default()
+            })
+            .await
+            .expect("update settings");
+        assert_eq!(
+            activity
+                .prune(now + time::Duration::days(29))
+                .await
+                .expect("prune"),
+            0
+        );
+        assert_eq!(
+            activity
+                .prune(now + time::Duration::days(31))
+                .await
+                .expect("prune"),
+            1
+        );
+        assert_eq!(
+            activity.list(1, None, None).await.expect("list").activities,
+            []
+        );
+    }
+
+    #[tokio::test]
+    async fn prune_activity_with_endless_retention() {
+        let settings_repository = Arc::new(FakeSettingsRepository::new());
+        let activity = ActivityInteractor::new(
+            Arc::new(FakeActivityRepository::new()),
+            Arc::new(FakeGroupRepository::new()),
+            settings_repository.clone(),
+        );
+        // Stored before retention was validated.
+        settings_repository
+            .update(ports::UpdateSettingsData {
+                activity_retention_days: Some(u32::MAX),
+                ..Default::default()
+            })
+            .await
+            .expect("update settings");
+
+        assert_eq!(
+            activity
+                .prune(OffsetDateTime::now_utc())
+                .await
+                .expect("prune"),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn undo_conflicts_when_changed_since() {
+        let TodoFixture {
+            interactor, todos, ..
+        } = todo_fixture().await;
+        let history = interactor.history.clone();
+
+        interactor
+            .update(todos[0].id, Some("renamed"))
+            .await
+            .expect("update");
+        let renamed = interactor.get(todos[0].id).await.expect("get");
+        put_todos(
+            &*interactor.content_repository,
+            vec![Todo {
+                text: "elsewhere".into(),
+                ..renamed
+            }],
+        )
+        .await;
+
+        let error = history.undo().await.expect_err("undo should fail");
+
+        assert_eq!(error.kind(), ErrorKind::Conflict);
+        assert_eq!(error.entity(), Some(("todo", todos[0].id)));
+        assert_eq!(
+            history.state().await.expect("state").undo,
+            Some(HistoryAction::CreateTodo)
+        );
+        let todo = interactor.get(todos[0].id).await.expect("get");
+        assert_eq!(todo.text, "elsewhere");
+    }
+
+    #[tokio::test]
+    async fn move_todo_to_other_project() {
+        let TodoFixture {
+            interactor,
+            groups,
+            todos,
+            ..
+        } = todo_fixture().await;
+
+        let error = interactor
+            .move_todo(todos[0].id, groups[2].id, None, None)
+            .await
+            .expect_err("move should fail");
+
+        assert_eq!(
+            serde_json::to_value(&error).expect("serialize")["validation"]["group_id"][0]["code"],
+            "same_project"
+        );
+        assert_eq!(todo_texts(&interactor, groups[0].id).await, ["l1", "l2"]);
+        assert_eq!(
+            todo_texts(&interactor, groups[2].id).await,
+            Vec::<String>::new()
+        );
+    }
+
+    #[tokio::test]
+    async fn delete_unknown_project() {
+        let (_, trash) = project_and_trash_interactors();
+
+        let error = trash
+            .delete_project(1)
+            .await
+            .expect_err("delete should fail");
+
+        assert_eq!(
+            (error.kind(), error.entity()),
+            (ErrorKind::NotFound, Some(("project", 1)))
+        );
+    }
+
+    /// History storage that can not be read; saving works.
+    struct UnreadableHistoryRepository {
+        corrupt: bool,
+        saved: FakeHistoryRepository,
+    }
+
+    #[async_trait::async_trait]
+    impl ports::HistoryRepository for UnreadableHistoryRepository {
+        async fn get(&self) -> Result<History> {
+            let error = anyhow::anyhow!("Unexpected end of document");
+            if self.corrupt {
+                return Err(error
+                    .context(crate::result::StorageCorrupt {
+                        path: "History.bson".into(),
+                    })
+                    .into());
+            }
+
+            Err(error.into())
+        }
+
+        async fn save(&self, history: History) -> Result<()> {
+            self.saved.save(history).await
+        }
+    }
+
+    async fn record_with_unreadable_history(
+        corrupt: bool,
+    ) -> (Result<()>, History, Vec<crate::models::Activity>) {
+        let project = Arc::new(FakeProjectRepository::new());
+        let group = Arc::new(FakeGroupRepository::new());
+        let todo = Arc::new(FakeTodoRepository::new());
+        let repository = Arc::new(UnreadableHistoryRepository {
+            corrupt,
+            saved: FakeHistoryRepository::new(),
+        });
+        let activity = Arc::new(FakeActivityRepository::new());
+        let history = HistoryInteractor::new(
+            repository.clone(),
+            project.clone(),
+            group.clone(),
+            todo.clone(),
+            Arc::new(FakeTrashRepository::new()),
+            Arc::new(FakeProjectContentRepository::new(
+                project.clone(),
+                group.clone(),
+                todo,
+                Arc::new(FakeTrashRepository::new()),
+            )),
+            Arc::new(EventBus::new()),
+            Arc::new(ActivityInteractor::new(
+                activity.clone(),
+                group,
+                Arc::new(FakeSettingsRepository::new()),
+            )),
+        );
+        let created = project
+            .create(ports::CreateProjectData { name: "Project" })
+            .await
+            .expect("Failed to create project");
+
+        let recorded = history
+            .record(
+                HistoryAction::CreateProject,
+                Snapshot::default(),
+                Snapshot {
+                    projects: vec![created.clone()],
+                    ..Default::default()
+                },
+            )
+            .await;
+
+        (
+            recorded,
+            repository.saved.get().await.expect("get"),
+            activity.list(created.id, None, 10).await.expect("list"),
+        )
+    }
+
+    #[tokio::test]
+    async fn record_starts_over_corrupt_history() {
+        let (recorded, saved, _) = record_with_unreadable_history(true).await;
+
+        recorded.expect("record");
+        assert_eq!(saved.undo.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn record_fails_when_history_is_unreadable() {
+        let (recorded, saved, activities) = record_with_unreadable_history(false).await;
+
+        assert_eq!(
+            recorded.expect_err("record should fail").kind(),
+            ErrorKind::Unknown
+        );
+        assert_eq!(saved, History::default());
+        // The activity log is written all the same.
+        assert_eq!(
+            activities.iter().map(|a| a.action).collect::<Vec<_>>(),
+            [HistoryAction::CreateProject]
+        );
+    }
+
+    /// Activity storage that can be read but not written.
+    struct UnwritableActivityRepository;
+
+    #[async_trait::async_trait]
+    impl ports::ActivityRepository for UnwritableActivityRepository {
+        async fn append(&self, _activities: Vec<ports::CreateActivityData>) -> Result<()> {
+            Err(anyhow::anyhow!("No space left on device").into())
+        }
+
+        async fn list(
+            &self,
+            _project_id: u64,
+            _before_id: Option<u64>,
+            _limit: usize,
+        ) -> Result<Vec<crate::models::Activity>> {
+            Ok(Vec::new())
+        }
+
+        async fn prune(&self, _before: OffsetDateTime) -> Result<usize> {
+            Ok(0)
+        }
+    }
+
+    #[tokio::test]
+    async fn record_keeps_history_when_activity_fails() {
+        let project = Arc::new(FakeProjectRepository::new());
+        let group = Arc::new(FakeGroupRepository::new());
+        let todo = Arc::new(FakeTodoRepository::new());
+        let history = HistoryInteractor::new(
+            Arc::new(FakeHistoryRepository::new()),
+            project.clone(),
+            group.clone(),
+            todo.clone(),
+            Arc::new(FakeTrashRepository::new()),
+            Arc::new(FakeProjectContentRepository::new(
+                project.clone(),
+                group.clone(),
+                todo,
+                Arc::new(FakeTrashRepository::new()),
+            )),
+            Arc::new(EventBus::new()),
+            Arc::new(ActivityInteractor::new(
+                Arc::new(UnwritableActivityRepository),
+                group,
+                Arc::new(FakeSettingsRepository::new()),
+            )),
+        );
+        let created = project
+            .create(ports::CreateProjectData { name: "Project" })
+            .await
+            .expect("Failed to create project");
+
+        history
+            .record(
+                HistoryAction::CreateProject,
+                Snapshot::default(),
+                Snapshot {
+                    projects: vec![created.clone()],
+                    ..Default::default()
+                },
+            )
+            .await
+            .expect("record");
+
+        assert_eq!(
+            history.state().await.expect("state").undo,
+            Some(HistoryAction::CreateProject)
+        );
+        history.undo().await.expect("undo");
+        assert_eq!(project.get(created.id).await.expect("get project"), None);
+    }
+
+    fn backup_interactor(
+        repository: Arc<FakeBackupRepository>,
+        recovery_interactor: &Arc<RecoveryInteractor>,
+    ) -> BackupInteractor {
+        BackupInteractor::new(
+            repository,
+            BackupPolicy::default(),
+            recovery_interactor.clone(),
+            Arc::new(EventBus::new()),
+            empty_history(),
+        )
+    }
+
+    fn recovery_interactor() -> Arc<RecoveryInteractor> {
+        Arc::new(RecoveryInteractor::new(
+            Arc::new(FakeRecoveryRepository::new()),
+            empty_history(),
+        ))
+    }
+
+    fn empty_history() -> Arc<HistoryInteractor> {
+        history_interactor(
+            Arc::new(FakeProjectRepository::new()),
+            Arc::new(FakeGroupRepository::new()),
+            Arc::new(FakeTodoRepository::new()),
+            Arc::new(FakeTrashRepository::new()),
+            &Arc::new(EventBus::new()),
+        )
+    }
+
+    /// History interactor with the creation of a project to undo.
+    async fn history_with_entry() -> Arc<HistoryInteractor> {
+        let history = empty_history();
+        let project = history
+            .project_repository
+            .create(ports::CreateProjectData { name: "Project" })
+            .await
+            .expect("Failed to create project");
+        history
+            .record(
+                HistoryAction::CreateProject,
+                Snapshot::default(),
+                Snapshot {
+                    projects: vec![project],
+                    ..Default::default()
+                },
+            )
+            .await
+            .expect("record");
+
+        history
+    }
+
+    #[tokio::test]
+    async fn tick_backs_up_on_start_and_when_due() {
+        let repository = Arc::new(FakeBackupRepository::new());
+        let interactor = backup_interactor(repository.clone(), &recovery_interactor());
+        let start = time::macros::datetime!(2024-03-01 12:00 UTC);
+
+        assert!(interactor.tick(start).await.expect("tick").is_some());
+        assert!(interactor
+            .tick(start + time::Duration::hours(7))
+            .await
+            .expect("tick")
+            .is_none());
+
+        repository.add_writes(1).await;
+        let backup = interactor
+            .tick(start + time::Duration::hours(8))
+            .await
+            .expect("tick");
+        assert!(backup.is_some());
+
+        repository.add_writes(100).await;
+        let backup = interactor
+            .tick(start + time::Duration::hours(9))
+            .await
+            .expect("tick");
+        assert!(backup.is_some());
+        assert_eq!(interactor.list().await.expect("list").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn tick_waits_for_recovery_report() {
+        let recovery_repository = Arc::new(FakeRecoveryRepository::new());
+        let recovery = Arc::new(RecoveryInteractor::new(
+            recovery_repository.clone(),
+            empty_history(),
+        ));
+        recovery_repository
+            .damage(crate::models::RecoveredFile {
+                path: "Projects.bson".into(),
+                quarantine_path: Some("Projects.bson.corrupt-1".into()),
+                problem: "Projects.bson is corrupt".into(),
+                salvaged: 3,
+                dropped: 1,
+            })
+            .await;
+        recovery.check().await.expect("check");
+        let repository = Arc::new(FakeBackupRepository::new());
+        let interactor = backup_interactor(repository, &recovery);
+        let start = time::macros::datetime!(2024-03-01 12:00 UTC);
+
+        assert!(interactor.tick(start).await.expect("tick").is_none());
+        assert_eq!(interactor.list().await.expect("list"), []);
+
+        recovery.dismiss().await;
+        assert!(interactor
+            .tick(start + time::Duration::minutes(1))
+            .await
+            .expect("tick")
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn restore_backs_up_current_state_first() {
+        let repository = Arc::new(FakeBackupRepository::new());
+        let events = Arc::new(EventBus::new());
+        let history = history_with_entry().await;
+        let interactor = BackupInteractor::new(
+            repository.clone(),
+            BackupPolicy::default(),
+            recovery_interactor(),
+            events.clone(),
+            history.clone(),
+        );
+        let backup = interactor.create().await.expect("create");
+        let recorded = record(&events);
+
+        let restored = interactor.restore(backup.id).await.expect("restore");
+
+        assert_eq!(restored, backup);
+        assert_eq!(repository.restored().await, [backup.id]);
+        assert_eq!(interactor.list().await.expect("list").len(), 2);
+        assert_eq!(
+            *recorded.lock().unwrap(),
+            [DomainEvent::BackupRestored { backup }]
+        );
+        assert_eq!(
+            history.state().await.expect("state"),
+            HistoryState::default()
+        );
+    }
+
+    #[tokio::test]
+    async fn restore_unknown_backup() {
+        let repository = Arc::new(FakeBackupRepository::new());
+        let interactor = backup_interactor(repository.clone(), &recovery_interactor());
+
+        let error = interactor
+            .restore(7)
+            .await
+            .expect_err("restore should fail");
+
+        assert_eq!(
+            (error.kind(), error.entity()),
+            (ErrorKind::NotFound, Some(("backup", 7)))
+        );
+        assert_eq!(interactor.list().await.expect("list"), []);
+    }
+
+    #[tokio::test]
+    async fn check_keeps_report_until_dismissed() {
+        let repository = Arc::new(FakeRecoveryRepository::new());
+        let history = history_with_entry().await;
+        let interactor = RecoveryInteractor::new(repository.clone(), history.clone());
+        let file = crate::models::RecoveredFile {
+            path: "Projects.bson".into(),
+            quarantine_path: Some("Projects.bson.corrupt-1".into()),
+            problem: "Projects.bson is corrupt".into(),
+            salvaged: 3,
+            dropped: 1,
+        };
+        repository.damage(file.clone()).await;
+
+        let report = interactor.check().await.expect("check");
+
+        assert_eq!(report.files, std::slice::from_ref(&file));
+        assert_eq!(
+            history.state().await.expect("state"),
+            HistoryState::default()
+        );
+        assert_eq!(interactor.report().await, report);
+        assert_eq!(interactor.dismiss().await.files, [file]);
+        assert_eq!(interactor.report().await, RecoveryReport::default());
     }
 }
@@ -0,0 +1,17 @@
+//! Storage and use cases of the app, shared by the Tauri binary and the
+//! benchmarks.
+
+// #[macro_use(defer)]
+extern crate scopeguard;
+
+pub mod backup_policy;
+pub mod document;
+pub mod events;
+pub mod history;
+pub mod interactors;
+pub mod models;
+pub mod ports;
+pub mod position;
+pub mod repositories;
+pub mod result;
+pub mod utils;
@@ -1,29 +1,41 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-// #[macro_use(defer)]
-extern crate scopeguard;
-
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use anyhow::Context;
-use interactors::{GroupInteractor, ProjectInteractor};
-use models::{Group, Project};
+use tauri::async_runtime::JoinHandle;
 use tauri::Manager;
+use tauri_todo_app::document::ChangeSummary;
+use tauri_todo_app::events::{self, EventBus};
+use tauri_todo_app::history::HistoryState;
+use tauri_todo_app::interactors::{
+    ActivityInteractor, BackupInteractor, GroupInteractor, HistoryInteractor, ProjectInteractor,
+    RecoveryInteractor, SettingsInteractor, TodoInteractor, TrashInteractor,
+};
+use tauri_todo_app::models::{
+    ActivityPage, Backup, DeletedProject, Group, Project, RecoveryReport, Settings, StorageChange,
+    Todo, TrashItem,
+};
+use tauri_todo_app::{backup_policy, ports, repositories};
+use tokio::time::MissedTickBehavior;
 
-mod interactors;
-mod models;
-mod ports;
-mod repositories;
-mod result;
-mod utils;
-
-use result::Result;
+use tauri_todo_app::result::Result;
 
 #[derive(Debug)]
 pub struct AppState {
     project_interactor: ProjectInteractor,
     group_interactor: GroupInteractor,
+    todo_interactor: TodoInteractor,
+    trash_interactor: TrashInteractor,
+    settings_interactor: SettingsInteractor,
+    backup_interactor: BackupInteractor,
+    recovery_interactor: Arc<RecoveryInteractor>,
+    history_interactor: Arc<HistoryInteractor>,
+    activity_interactor: Arc<ActivityInteractor>,
 }
 
 #[tauri::command]
@@ -32,8 +44,62 @@ async fn create_project(name: &str, state: tauri::State<'_, AppState>) -> Result
 }
 
 #[tauri::command]
-async fn get_all_projects(state: tauri::State<'_, AppState>) -> Result<Vec<Project>> {
-    state.project_interactor.list().await
+async fn get_all_projects(
+    filter: Option<ports::ProjectFilter>,
+    sort: Option<ports::ProjectSort>,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<Project>> {
+    state
+        .project_interactor
+        .list(filter.unwrap_or_default(), sort.unwrap_or_default())
+        .await
+}
+
+#[tauri::command]
+async fn repair_projects(state: tauri::State<'_, AppState>) -> Result<Vec<Project>> {
+    state.project_interactor.repair_updated_at().await
+}
+
+#[tauri::command]
+async fn update_project(
+    id: u64,
+    name: Option<&str>,
+    is_active: Option<bool>,
+    state: tauri::State<'_, AppState>,
+) -> Result<Project> {
+    state.project_interactor.update(id, name, is_active).await
+}
+
+#[tauri::command]
+async fn archive_project(id: u64, state: tauri::State<'_, AppState>) -> Result<Project> {
+    state.project_interactor.archive(id).await
+}
+
+#[tauri::command]
+async fn unarchive_project(id: u64, state: tauri::State<'_, AppState>) -> Result<Project> {
+    state.project_interactor.unarchive(id).await
+}
+
+#[tauri::command]
+async fn delete_project(id: u64, state: tauri::State<'_, AppState>) -> Result<DeletedProject> {
+    state.trash_interactor.delete_project(id).await
+}
+
+#[tauri::command]
+async fn export_project_json(project_id: u64, state: tauri::State<'_, AppState>) -> Result<String> {
+    state.project_interactor.export_json(project_id).await
+}
+
+#[tauri::command]
+async fn apply_project_json(
+    project_id: u64,
+    document: &str,
+    state: tauri::State<'_, AppState>,
+) -> Result<ChangeSummary> {
+    state
+        .project_interactor
+        .apply_json(project_id, document)
+        .await
 }
 
 #[tauri::command]
@@ -45,7 +111,352 @@ async fn create_group(
     state.group_interactor.create(name, project_id).await
 }
 
+#[tauri::command]
+async fn get_groups(project_id: u64, state: tauri::State<'_, AppState>) -> Result<Vec<Group>> {
+    state.group_interactor.find_by_project(project_id).await
+}
+
+#[tauri::command]
+async fn move_group(
+    id: u64,
+    before_id: Option<u64>,
+    after_id: Option<u64>,
+    state: tauri::State<'_, AppState>,
+) -> Result<Group> {
+    state
+        .group_interactor
+        .move_group(id, before_id, after_id)
+        .await
+}
+
+#[tauri::command]
+async fn toggle_group(
+    id: u64,
+    is_opened: bool,
+    state: tauri::State<'_, AppState>,
+) -> Result<Group> {
+    state.group_interactor.set_opened(id, is_opened).await
+}
+
+#[tauri::command]
+async fn delete_group(id: u64, state: tauri::State<'_, AppState>) -> Result<Group> {
+    state.trash_interactor.delete_group(id).await
+}
+
+#[tauri::command]
+async fn create_todo(text: &str, group_id: u64, state: tauri::State<'_, AppState>) -> Result<Todo> {
+    state.todo_interactor.create(text, group_id).await
+}
+
+#[tauri::command]
+async fn get_todos(group_id: u64, state: tauri::State<'_, AppState>) -> Result<Vec<Todo>> {
+    state.todo_interactor.find_by_group(group_id).await
+}
+
+#[tauri::command]
+async fn update_todo(
+    id: u64,
+    text: Option<&str>,
+    state: tauri::State<'_, AppState>,
+) -> Result<Todo> {
+    state.todo_interactor.update(id, text).await
+}
+
+#[tauri::command]
+async fn complete_todo(id: u64, state: tauri::State<'_, AppState>) -> Result<Todo> {
+    state.todo_interactor.complete(id).await
+}
+
+#[tauri::command]
+async fn reopen_todo(id: u64, state: tauri::State<'_, AppState>) -> Result<Todo> {
+    state.todo_interactor.reopen(id).await
+}
+
+#[tauri::command]
+async fn complete_group(group_id: u64, state: tauri::State<'_, AppState>) -> Result<Vec<Todo>> {
+    state.todo_interactor.complete_group(group_id).await
+}
+
+#[tauri::command]
+async fn move_todo(
+    id: u64,
+    group_id: u64,
+    before_id: Option<u64>,
+    after_id: Option<u64>,
+    state: tauri::State<'_, AppState>,
+) -> Result<Todo> {
+    state
+        .todo_interactor
+        .move_todo(id, group_id, before_id, after_id)
+        .await
+}
+
+#[tauri::command]
+async fn delete_todo(id: u64, state: tauri::State<'_, AppState>) -> Result<Todo> {
+    state.trash_interactor.delete_todo(id).await
+}
+
+#[tauri::command]
+async fn list_trash(state: tauri::State<'_, AppState>) -> Result<Vec<TrashItem>> {
+    state.trash_interactor.list().await
+}
+
+#[tauri::command]
+async fn restore_from_trash(id: u64, state: tauri::State<'_, AppState>) -> Result<TrashItem> {
+    state.trash_interactor.restore(id).await
+}
+
+#[tauri::command]
+async fn get_settings(state: tauri::State<'_, AppState>) -> Result<Settings> {
+    state.settings_interactor.get().await
+}
+
+#[tauri::command]
+async fn update_settings(
+    default_groups: Option<Vec<String>>,
+    activity_retention_days: Option<u32>,
+    trash_retention_days: Option<u32>,
+    state: tauri::State<'_, AppState>,
+) -> Result<Settings> {
+    state
+        .settings_interactor
+        .update(
+            default_groups,
+            activity_retention_days,
+            trash_retention_days,
+        )
+        .await
+}
+
+#[tauri::command]
+async fn get_activity(
+    project_id: u64,
+    before_id: Option<u64>,
+    limit: Option<usize>,
+    state: tauri::State<'_, AppState>,
+) -> Result<ActivityPage> {
+    state
+        .activity_interactor
+        .list(project_id, before_id, limit)
+        .await
+}
+
+#[tauri::command]
+async fn create_backup(state: tauri::State<'_, AppState>) -> Result<Backup> {
+    state.backup_interactor.create().await
+}
+
+#[tauri::command]
+async fn list_backups(state: tauri::State<'_, AppState>) -> Result<Vec<Backup>> {
+    state.backup_interactor.list().await
+}
+
+#[tauri::command]
+async fn restore_backup(id: u64, state: tauri::State<'_, AppState>) -> Result<Backup> {
+    state.backup_interactor.restore(id).await
+}
+
+#[tauri::command]
+async fn undo(state: tauri::State<'_, AppState>) -> Result<HistoryState> {
+    state.history_interactor.undo().await
+}
+
+#[tauri::command]
+async fn redo(state: tauri::State<'_, AppState>) -> Result<HistoryState> {
+    state.history_interactor.redo().await
+}
+
+#[tauri::command]
+async fn get_history_state(state: tauri::State<'_, AppState>) -> Result<HistoryState> {
+    state.history_interactor.state().await
+}
+
+#[tauri::command]
+async fn get_recovery_report(state: tauri::State<'_, AppState>) -> Result<RecoveryReport> {
+    Ok(state.recovery_interactor.report().await)
+}
+
+#[tauri::command]
+async fn dismiss_recovery_report(state: tauri::State<'_, AppState>) -> Result<RecoveryReport> {
+    Ok(state.recovery_interactor.dismiss().await)
+}
+
+struct Repositories {
+    project: Arc<dyn ports::ProjectRepository + Send + Sync>,
+    group: Arc<dyn ports::GroupRepository + Send + Sync>,
+    todo: Arc<dyn ports::TodoRepository + Send + Sync>,
+    settings: Arc<dyn ports::SettingsRepository + Send + Sync>,
+    content: Arc<dyn ports::ProjectContentRepository + Send + Sync>,
+    backup: Arc<dyn ports::BackupRepository + Send + Sync>,
+    recovery: Arc<dyn ports::RecoveryRepository + Send + Sync>,
+    history: Arc<dyn ports::HistoryRepository + Send + Sync>,
+    activity: Arc<dyn ports::ActivityRepository + Send + Sync>,
+    trash: Arc<dyn ports::TrashRepository + Send + Sync>,
+    /// Storage to watch for changes of other processes.
+    watched: WatchedStorage,
+}
+
+enum WatchedStorage {
+    Files(repositories::watcher::WatchedFiles),
+    Sqlite(repositories::sqlite::SqliteDatabase),
+}
+
+/// Picks the storage backend from `TODO_APP_STORAGE` (`bson` by default or `sqlite`).
+fn open_repositories(app_data_dir: &Path) -> Result<Repositories> {
+    let storage = std::env::var("TODO_APP_STORAGE").unwrap_or_else(|_| "bson".into());
+    let backups_dir = app_data_dir.join("backups");
+
+    match storage.as_str() {
+        "sqlite" => {
+            let db_path = app_data_dir.join("Todo.sqlite3");
+            let db = repositories::sqlite::SqliteDatabase::open(&db_path)?;
+
+            Ok(Repositories {
+                project: Arc::new(repositories::sqlite::SqliteProjectRepository::new(
+                    db.clone(),
+                )),
+                group: Arc::new(repositories::sqlite::SqliteGroupRepository::new(db.clone())),
+                todo: Arc::new(repositories::sqlite::SqliteTodoRepository::new(db.clone())),
+                settings: Arc::new(repositories::sqlite::SqliteSettingsRepository::new(
+                    db.clone(),
+                )),
+                content: Arc::new(repositories::sqlite::SqliteProjectContentRepository::new(
+                    db.clone(),
+                )),
+                backup: Arc::new(repositories::sqlite::SqliteBackupRepository::new(
+                    db.clone(),
+                    &backups_dir,
+                )),
+                recovery: Arc::new(repositories::sqlite::SqliteRecoveryRepository::new(
+                    db.clone(),
+                    &db_path,
+                )),
+                history: Arc::new(repositories::sqlite::SqliteHistoryRepository::new(
+                    db.clone(),
+                )),
+                activity: Arc::new(repositories::sqlite::SqliteActivityRepository::new(
+                    db.clone(),
+                )),
+                trash: Arc::new(repositories::sqlite::SqliteTrashRepository::new(db.clone())),
+                watched: WatchedStorage::Sqlite(db),
+            })
+        }
+        "bson" => {
+            let projects_path = app_data_dir.join("Projects.bson");
+            let groups_path = app_data_dir.join("Groups.bson");
+            let todos_path = app_data_dir.join("Todos.bson");
+            let settings_path = app_data_dir.join("Settings.bson");
+            let history_path = app_data_dir.join("History.bson");
+            let activity_path = app_data_dir.join("Activity.bson");
+            let trash_path = app_data_dir.join("Trash.bson");
+
+            Ok(Repositories {
+                project: Arc::new(repositories::ProjectRepository::new(&projects_path)),
+                group: Arc::new(repositories::GroupRepository::new(&groups_path)),
+                todo: Arc::new(repositories::TodoRepository::new(&todos_path)),
+                settings: Arc::new(repositories::SettingsRepository::new(&settings_path)),
+                content: Arc::new(repositories::ProjectContentRepository::new(
+                    &projects_path,
+                    &groups_path,
+                    &todos_path,
+                    &trash_path,
+                )),
+                recovery: Arc::new(repositories::RecoveryRepository::new(
+                    &projects_path,
+                    &groups_path,
+                    &todos_path,
+                    &settings_path,
+                    &history_path,
+                    &activity_path,
+                    &trash_path,
+                )),
+                backup: Arc::new(repositories::BackupRepository::new(
+                    &[
+                        projects_path.clone(),
+                        groups_path.clone(),
+                        todos_path.clone(),
+                        settings_path,
+                        history_path.clone(),
+                        activity_path.clone(),
+                        trash_path.clone(),
+                    ],
+                    &backups_dir,
+                )),
+                history: Arc::new(repositories::HistoryRepository::new(&history_path)),
+                activity: Arc::new(repositories::ActivityRepository::new(&activity_path)),
+                trash: Arc::new(repositories::TrashRepository::new(&trash_path)),
+                watched: WatchedStorage::Files(repositories::watcher::WatchedFiles {
+                    projects: projects_path,
+                    groups: groups_path,
+                    todos: todos_path,
+                }),
+            })
+        }
+        _ => Err(anyhow::anyhow!("Unknown TODO_APP_STORAGE {:?}", storage).into()),
+    }
+}
+
+/// Forwards every domain event to all windows.
+fn bridge_events(events: &EventBus, app: tauri::AppHandle) {
+    events.subscribe(move |event| {
+        if let Err(error) = app.emit_all(events::DOMAIN_EVENT, event) {
+            log::error!("Failed to emit domain event: {:?}", error);
+        }
+    });
+}
+
+/// How often the backup policy is checked.
+const BACKUP_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Backs up on startup and then whenever the backup policy asks for it.
+fn spawn_backups(app: tauri::AppHandle) -> JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(BACKUP_CHECK_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+
+            let state = app.state::<AppState>();
+            let now = time::OffsetDateTime::now_utc();
+            if let Err(error) = state.backup_interactor.tick(now).await {
+                log::error!("Failed to backup: {:?}", error);
+            }
+        }
+    })
+}
+
+/// How often trash items past `Settings::trash_retention_days` are purged.
+const TRASH_PURGE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Purges the trash on startup and then every `TRASH_PURGE_INTERVAL`.
+fn spawn_trash_purge(app: tauri::AppHandle) -> JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(TRASH_PURGE_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+
+            let state = app.state::<AppState>();
+            let now = time::OffsetDateTime::now_utc();
+            let purge = async {
+                let days = state.settings_interactor.get().await?.trash_retention_days;
+                state.trash_interactor.purge(now, days).await
+            };
+            if let Err(error) = purge.await {
+                log::error!("Failed to purge trash: {:?}", error);
+            }
+        }
+    })
+}
+
+/// Periodic tasks of the app, stopped when it exits.
+struct BackgroundTasks(Vec<JoinHandle<()>>);
+
 fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
     tauri::Builder::default()
         .setup(|app| {
             let app_data_dir = app
@@ -58,24 +469,171 @@ fn main() {
                 app_data_dir.display()
             ))?;
 
-            let project_repository = Arc::new(repositories::ProjectRepository::new(
-                &app_data_dir.join("Projects.bson"),
-            ));
+            let repositories = open_repositories(&app_data_dir)
+                .map_err(anyhow::Error::from)
+                .context("Failed to open repositories")?;
+            let watched = repositories.watched;
 
-            let group_repository = Arc::new(repositories::GroupRepository::new());
+            let events = Arc::new(EventBus::new());
+            bridge_events(&events, app.handle());
+
+            let activity_interactor = Arc::new(ActivityInteractor::new(
+                repositories.activity,
+                repositories.group.clone(),
+                repositories.settings.clone(),
+            ));
+            let history_interactor = Arc::new(HistoryInteractor::new(
+                repositories.history,
+                repositories.project.clone(),
+                repositories.group.clone(),
+                repositories.todo.clone(),
+                repositories.trash.clone(),
+                repositories.content.clone(),
+                events.clone(),
+                activity_interactor.clone(),
+            ));
+            let recovery_interactor = Arc::new(RecoveryInteractor::new(
+                repositories.recovery,
+                history_interactor.clone(),
+            ));
 
             app.manage(AppState {
-                project_interactor: ProjectInteractor::new(project_repository),
-                group_interactor: GroupInteractor::new(group_repository),
+                project_interactor: ProjectInteractor::new(
+                    repositories.project.clone(),
+                    repositories.group.clone(),
+                    repositories.todo.clone(),
+                    repositories.settings.clone(),
+                    repositories.content.clone(),
+                    events.clone(),
+                    history_interactor.clone(),
+                ),
+                group_interactor: GroupInteractor::new(
+                    repositories.group.clone(),
+                    repositories.content.clone(),
+                    events.clone(),
+                    history_interactor.clone(),
+                ),
+                todo_interactor: TodoInteractor::new(
+                    repositories.todo.clone(),
+                    repositories.group.clone(),
+                    repositories.content.clone(),
+                    events.clone(),
+                    history_interactor.clone(),
+                ),
+                trash_interactor: TrashInteractor::new(
+                    repositories.trash,
+                    repositories.group,
+                    repositories.todo,
+                    repositories.content,
+                    events.clone(),
+                    history_interactor.clone(),
+                ),
+                settings_interactor: SettingsInteractor::new(repositories.settings),
+                backup_interactor: BackupInteractor::new(
+                    repositories.backup,
+                    backup_policy::BackupPolicy::default(),
+                    recovery_interactor.clone(),
+                    events.clone(),
+                    history_interactor.clone(),
+                ),
+                recovery_interactor,
+                history_interactor,
+                activity_interactor,
             });
+            // Other subsystems subscribe through `app.state::<Arc<EventBus>>()`.
+            app.manage(events);
+
+            // Damaged files are replaced before the first backup or command
+            // reads them; the report waits for the UI to ask for it.
+            let state = app.state::<AppState>();
+            if let Err(error) = tauri::async_runtime::block_on(state.recovery_interactor.check()) {
+                log::error!("Failed to check storage: {:?}", error);
+            }
+
+            let now = time::OffsetDateTime::now_utc();
+            if let Err(error) = tauri::async_runtime::block_on(state.activity_interactor.prune(now))
+            {
+                log::error!("Failed to prune activity: {:?}", error);
+            }
+
+            app.manage(BackgroundTasks(vec![
+                spawn_backups(app.handle()),
+                spawn_trash_purge(app.handle()),
+            ]));
+
+            let handle = app.handle();
+            let on_change = move |change: StorageChange| {
+                let state = handle.state::<AppState>();
+                let invalidate = state.history_interactor.invalidate(&change);
+                if let Err(error) = tauri::async_runtime::block_on(invalidate) {
+                    log::error!("Failed to invalidate history: {:?}", error);
+                }
+
+                if let Err(error) = handle.emit_all(change.event(), change) {
+                    log::error!("Failed to emit storage change: {:?}", error);
+                }
+            };
+            match watched {
+                WatchedStorage::Files(files) => {
+                    let watcher = repositories::watcher::StorageWatcher::start(files, on_change)
+                        .map_err(anyhow::Error::from)
+                        .context("Failed to watch storage")?;
+                    app.manage(watcher);
+                }
+                WatchedStorage::Sqlite(db) => {
+                    let watcher = repositories::sqlite::SqliteStorageWatcher::start(db, on_change)
+                        .map_err(anyhow::Error::from)
+                        .context("Failed to watch database")?;
+                    app.manage(watcher);
+                }
+            }
 
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             create_project,
             get_all_projects,
-            create_group
+            update_project,
+            archive_project,
+            unarchive_project,
+            delete_project,
+            repair_projects,
+            export_project_json,
+            apply_project_json,
+            create_group,
+            get_groups,
+            move_group,
+            toggle_group,
+            delete_group,
+            create_todo,
+            get_todos,
+            update_todo,
+            complete_todo,
+            reopen_todo,
+            complete_group,
+            move_todo,
+            delete_todo,
+            list_trash,
+            restore_from_trash,
+            get_settings,
+            update_settings,
+            create_backup,
+            list_backups,
+            restore_backup,
+            get_recovery_report,
+            dismiss_recovery_report,
+            undo,
+            redo,
+            get_history_state,
+            get_activity
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                for task in &app.state::<BackgroundTasks>().0 {
+                    task.abort();
+                }
+            }
+        });
 }
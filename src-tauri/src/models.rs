@@ -1,11 +1,15 @@
 use time::OffsetDateTime;
 
-#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+use crate::history::HistoryAction;
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct Project {
     pub id: u64,
     pub name: String,
     #[serde(with = "time::serde::iso8601")]
     pub created_at: OffsetDateTime,
+    /// Raised to the time of every change to the project, its groups or its
+    /// todos; never behind `created_at` or the `updated_at` of its todos.
     #[serde(with = "time::serde::iso8601")]
     pub updated_at: OffsetDateTime,
     pub is_active: bool,
@@ -13,15 +17,232 @@ pub struct Project {
     pub archived_at: Option<OffsetDateTime>,
 }
 
-#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct Group {
     pub id: u64,
     pub name: String,
-    pub position: u64,
+    pub position: f64,
     pub is_opened: bool,
     pub project_id: u64,
 }
 
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Todo {
+    pub id: u64,
+    pub text: String,
+    pub position: f64,
+    #[serde(with = "time::serde::iso8601")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::iso8601")]
+    pub updated_at: OffsetDateTime,
+    pub is_done: bool,
+    #[serde(with = "time::serde::iso8601::option")]
+    pub done_at: Option<OffsetDateTime>,
+    pub group_id: u64,
+}
+
+/// App-level preferences shared by all projects.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Settings {
+    /// Names of the groups every new project starts with, in order.
+    pub default_groups: Vec<String>,
+    /// Days activity is kept before it is pruned at startup; 0 keeps it
+    /// forever.
+    pub activity_retention_days: u32,
+    /// Days deleted entities stay in the trash before they are purged; 0
+    /// keeps them until they are restored.
+    pub trash_retention_days: u32,
+}
+
+impl Settings {
+    /// Activity is pruned after a year unless the user chose otherwise.
+    pub const DEFAULT_ACTIVITY_RETENTION_DAYS: u32 = 365;
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            default_groups: Vec::new(),
+            activity_retention_days: Self::DEFAULT_ACTIVITY_RETENTION_DAYS,
+            trash_retention_days: 0,
+        }
+    }
+}
+
+/// Snapshot of all storage files, named by the millisecond it was taken.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Backup {
+    pub id: u64,
+    #[serde(with = "time::serde::iso8601")]
+    pub created_at: OffsetDateTime,
+    /// Total size of the snapshot in bytes.
+    pub size: u64,
+}
+
+/// Storage file found damaged by the startup check.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct RecoveredFile {
+    pub path: std::path::PathBuf,
+    /// Where the damaged file was moved, or `None` when it was left in place.
+    pub quarantine_path: Option<std::path::PathBuf>,
+    /// Why the file could not be read.
+    pub problem: String,
+    /// Records carried over into the replacement file.
+    pub salvaged: usize,
+    /// Records found in the damaged file that could not be decoded; records
+    /// lost in a cut-off part of the file are not counted.
+    pub dropped: usize,
+}
+
+/// Outcome of the startup check, kept until the user has seen it.
+#[derive(Debug, Clone, PartialEq, Default, serde::Serialize)]
+pub struct RecoveryReport {
+    pub files: Vec<RecoveredFile>,
+}
+
+/// Entities of one kind that another process created, updated or deleted.
+#[derive(Debug, Clone, PartialEq, Default, serde::Serialize)]
+pub struct ChangedIds {
+    pub created: Vec<u64>,
+    pub updated: Vec<u64>,
+    pub deleted: Vec<u64>,
+}
+
+/// Change of a storage file noticed by the file watcher, sent to the
+/// windows as the event named by `event`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(untagged)]
+pub enum StorageChange {
+    Projects {
+        #[serde(flatten)]
+        ids: ChangedIds,
+    },
+    Groups {
+        #[serde(flatten)]
+        ids: ChangedIds,
+        /// Projects whose groups changed, before or after the change.
+        project_ids: Vec<u64>,
+    },
+    Todos {
+        #[serde(flatten)]
+        ids: ChangedIds,
+        /// Groups whose todos changed, before or after the change.
+        group_ids: Vec<u64>,
+    },
+}
+
+impl StorageChange {
+    pub fn event(&self) -> &'static str {
+        match self {
+            StorageChange::Projects { .. } => "projects-changed",
+            StorageChange::Groups { .. } => "groups-changed",
+            StorageChange::Todos { .. } => "todos-changed",
+        }
+    }
+}
+
+/// Project with its groups ordered by `position`, and its todos ordered
+/// by group and then by `position`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ProjectContent {
+    pub project: Project,
+    pub groups: Vec<Group>,
+    pub todos: Vec<Todo>,
+}
+
+/// Projects, groups and todos as they were stored at one moment; only
+/// the entities of interest are included.
+#[derive(Debug, Clone, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+pub struct Snapshot {
+    pub projects: Vec<Project>,
+    pub groups: Vec<Group>,
+    pub todos: Vec<Todo>,
+}
+
+impl Snapshot {
+    pub fn is_empty(&self) -> bool {
+        self.projects.is_empty() && self.groups.is_empty() && self.todos.is_empty()
+    }
+}
+
+impl From<ProjectContent> for Snapshot {
+    fn from(content: ProjectContent) -> Self {
+        Snapshot {
+            projects: vec![content.project],
+            groups: content.groups,
+            todos: content.todos,
+        }
+    }
+}
+
+impl From<DeletedProject> for Snapshot {
+    fn from(deleted: DeletedProject) -> Self {
+        Snapshot {
+            projects: vec![deleted.project],
+            groups: deleted.groups,
+            todos: deleted.todos,
+        }
+    }
+}
+
+/// Kind of entity an `Activity` is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityKind {
+    Project,
+    Group,
+    Todo,
+}
+
+/// Change of one entity, as kept in the activity log of its project.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Activity {
+    pub id: u64,
+    #[serde(with = "time::serde::iso8601")]
+    pub at: OffsetDateTime,
+    pub project_id: u64,
+    pub entity: EntityKind,
+    pub entity_id: u64,
+    pub action: HistoryAction,
+    /// The change `action` made was taken back; `before` and `after`
+    /// describe the undo.
+    pub undone: bool,
+    /// The entity before the change, `None` when the change created it.
+    pub before: Option<serde_json::Value>,
+    /// The entity after the change, `None` when the change deleted it.
+    pub after: Option<serde_json::Value>,
+}
+
+/// Page of the activity log of a project, newest first.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ActivityPage {
+    pub activities: Vec<Activity>,
+    /// `before_id` that fetches the next page, `None` on the last page.
+    pub next_before_id: Option<u64>,
+}
+
+/// Deleted project, group or todo, kept with everything deleted along with
+/// it until it is restored or purged.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TrashItem {
+    pub id: u64,
+    #[serde(with = "time::serde::iso8601")]
+    pub deleted_at: OffsetDateTime,
+    pub entity: EntityKind,
+    pub entity_id: u64,
+    /// The entity and its children as they were stored, ids and positions
+    /// included.
+    pub entities: Snapshot,
+}
+
+/// Project removed by `delete_project` together with its contents.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct DeletedProject {
+    pub project: Project,
+    pub groups: Vec<Group>,
+    pub todos: Vec<Todo>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -48,12 +269,29 @@ mod tests {
         let group = Group {
             id: 123,
             name: "First group".into(),
-            position: 0,
+            position: 1.5,
             is_opened: true,
             project_id: 12,
         };
 
         let j = serde_json::to_string(&group).expect("Group serialization");
-        assert_eq!(j, "{\"id\":123,\"name\":\"First group\",\"position\":0,\"is_opened\":true,\"project_id\":12}");
+        assert_eq!(j, "{\"id\":123,\"name\":\"First group\",\"position\":1.5,\"is_opened\":true,\"project_id\":12}");
+    }
+
+    #[test]
+    fn serialize_todo() {
+        let todo = Todo {
+            id: 123,
+            text: "First todo".into(),
+            position: 2.5,
+            created_at: datetime!(2019-01-02 12:34:56.123 UTC),
+            updated_at: datetime!(2020-01-02 12:34:56.123 UTC),
+            is_done: false,
+            done_at: None,
+            group_id: 12,
+        };
+
+        let j = serde_json::to_string(&todo).expect("Todo serialization");
+        assert_eq!(j, "{\"id\":123,\"text\":\"First todo\",\"position\":2.5,\"created_at\":\"+002019-01-02T12:34:56.123000000Z\",\"updated_at\":\"+002020-01-02T12:34:56.123000000Z\",\"is_done\":false,\"done_at\":null,\"group_id\":12}");
     }
 }
@@ -1,18 +1,107 @@
-use crate::models::{Group, Project};
-use crate::result::Result;
+use std::collections::HashSet;
+
+use crate::history::{History, HistoryAction};
+use crate::models::{
+    Activity, Backup, EntityKind, Group, Project, ProjectContent, RecoveredFile, Settings,
+    Snapshot, Todo, TrashItem,
+};
+use crate::result::{Conflict, Result};
+use anyhow::anyhow;
 use async_trait::async_trait;
+use time::OffsetDateTime;
+
+/// Fewest characters a project name may have.
+pub const PROJECT_NAME_MIN_LENGTH: usize = 3;
+
+/// Checks a project name wherever one is given, so all of them fail alike.
+pub fn validate_project_name(name: &str) -> std::result::Result<(), validator::ValidationError> {
+    if name.chars().count() >= PROJECT_NAME_MIN_LENGTH {
+        return Ok(());
+    }
+
+    let mut error = validator::ValidationError::new("length");
+    error.add_param("min".into(), &PROJECT_NAME_MIN_LENGTH);
+    error.message = Some(
+        format!(
+            "Must be at least {} characters long",
+            PROJECT_NAME_MIN_LENGTH
+        )
+        .into(),
+    );
+
+    Err(error)
+}
 
 #[derive(validator::Validate)]
 pub struct CreateProjectData<'a> {
-    #[validate(length(min = 3, message = "Must be at least 3 character long"))]
+    #[validate(custom = "validate_project_name")]
     pub name: &'a str,
 }
 
+#[derive(Default, validator::Validate)]
+pub struct UpdateProjectData<'a> {
+    #[validate(custom = "validate_project_name")]
+    pub name: Option<&'a str>,
+    /// Deactivating archives the project as `ProjectRepository::archive`
+    /// does; activating unarchives it.
+    pub is_active: Option<bool>,
+}
+
+/// Which projects `ProjectRepository::list` returns, by `archived_at`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectFilter {
+    #[default]
+    Active,
+    Archived,
+    All,
+}
+
+impl ProjectFilter {
+    pub fn matches(&self, project: &Project) -> bool {
+        match self {
+            ProjectFilter::Active => project.archived_at.is_none(),
+            ProjectFilter::Archived => project.archived_at.is_some(),
+            ProjectFilter::All => true,
+        }
+    }
+}
+
+/// Order of `ProjectRepository::list`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectSort {
+    /// Newest projects first.
+    #[default]
+    Created,
+    /// Latest `updated_at` first, i.e. projects whose todos changed last.
+    RecentlyTouched,
+}
+
+impl ProjectSort {
+    pub fn sort(&self, projects: &mut [Project]) {
+        match self {
+            ProjectSort::Created => projects.sort_by_key(|p| std::cmp::Reverse(p.id)),
+            ProjectSort::RecentlyTouched => {
+                projects.sort_by_key(|p| std::cmp::Reverse((p.updated_at, p.id)))
+            }
+        }
+    }
+}
+
 #[async_trait]
 pub trait ProjectRepository: Sync + Send {
     async fn create(&self, project: CreateProjectData<'_>) -> Result<Project>;
     async fn get(&self, id: u64) -> Result<Option<Project>>;
-    async fn list(&self) -> Result<Vec<Project>>;
+    async fn list(&self, filter: ProjectFilter, sort: ProjectSort) -> Result<Vec<Project>>;
+    async fn update(&self, id: u64, project: UpdateProjectData<'_>) -> Result<Option<Project>>;
+    /// Sets `archived_at` and clears `is_active`; archiving twice keeps the first `archived_at`.
+    async fn archive(&self, id: u64) -> Result<Option<Project>>;
+    /// Clears `archived_at` and sets `is_active`.
+    async fn unarchive(&self, id: u64) -> Result<Option<Project>>;
+    /// Overwrites `updated_at` of the listed projects in a single write;
+    /// unknown ids are skipped.
+    async fn set_updated_at(&self, updates: &[(u64, OffsetDateTime)]) -> Result<()>;
 }
 
 pub struct CreateGroupData<'a> {
@@ -23,7 +112,353 @@ pub struct CreateGroupData<'a> {
 #[async_trait]
 pub trait GroupRepository: Sync + Send {
     async fn create(&self, group: CreateGroupData<'_>) -> Result<Group>;
-    // async fn find_by_project(&self, project_id: u64) -> Result<Vec<Group>>;
+    async fn get(&self, id: u64) -> Result<Option<Group>>;
+    /// Groups of the project ordered by `position`.
+    async fn find_by_project(&self, project_id: u64) -> Result<Vec<Group>>;
+    /// Groups of all projects ordered by id.
+    async fn list(&self) -> Result<Vec<Group>>;
+}
+
+#[derive(validator::Validate)]
+pub struct CreateTodoData<'a> {
+    #[validate(length(min = 1, message = "Must not be empty"))]
+    pub text: &'a str,
+    pub group_id: u64,
+}
+
+#[derive(Default, validator::Validate)]
+pub struct UpdateTodoData<'a> {
+    #[validate(length(min = 1, message = "Must not be empty"))]
+    pub text: Option<&'a str>,
+}
+
+#[async_trait]
+pub trait TodoRepository: Sync + Send {
+    async fn create(&self, todo: CreateTodoData<'_>) -> Result<Todo>;
+    async fn get(&self, id: u64) -> Result<Option<Todo>>;
+    /// Todos of the group ordered by `position`.
+    async fn find_by_group(&self, group_id: u64) -> Result<Vec<Todo>>;
+    /// Todos of all groups ordered by id.
+    async fn list(&self) -> Result<Vec<Todo>>;
+}
+
+/// Group of a todo in `ProjectChanges`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupRef {
+    Stored(u64),
+    /// Index into `ProjectChanges::create_groups`.
+    Created(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewGroupData {
+    pub name: String,
+    pub position: f64,
+    pub is_opened: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupChangeData {
+    pub id: u64,
+    pub name: String,
+    pub position: f64,
+    pub is_opened: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewTodoData {
+    pub text: String,
+    pub is_done: bool,
+    pub group: GroupRef,
+    pub position: f64,
+}
+
+/// Replaces every editable field of the todo and sets its `updated_at`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TodoChangeData {
+    pub id: u64,
+    pub text: String,
+    pub is_done: bool,
+    pub group: GroupRef,
+    pub position: f64,
+}
+
+/// Creates, updates and deletes within one project, applied together.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProjectChanges {
+    pub name: Option<String>,
+    pub create_groups: Vec<NewGroupData>,
+    pub update_groups: Vec<GroupChangeData>,
+    /// Todos still in these groups are deleted with them.
+    pub delete_groups: Vec<u64>,
+    pub create_todos: Vec<NewTodoData>,
+    pub update_todos: Vec<TodoChangeData>,
+    pub delete_todos: Vec<u64>,
+}
+
+impl ProjectChanges {
+    pub fn is_empty(&self) -> bool {
+        *self == ProjectChanges::default()
+    }
+
+    /// Fails unless every stored id is one of the project's `group_ids` or
+    /// `todo_ids`, and todos only go to groups that outlive the changes.
+    pub fn check_ids(&self, group_ids: &HashSet<u64>, todo_ids: &HashSet<u64>) -> Result<()> {
+        let updated_groups = self.update_groups.iter().map(|g| g.id);
+        for id in updated_groups.chain(self.delete_groups.iter().copied()) {
+            if !group_ids.contains(&id) {
+                return Err(Conflict {
+                    entity: "group",
+                    id,
+                    reason: "is not part of the project",
+                }
+                .into());
+            }
+        }
+
+        let updated_todos = self.update_todos.iter().map(|t| t.id);
+        for id in updated_todos.chain(self.delete_todos.iter().copied()) {
+            if !todo_ids.contains(&id) {
+                return Err(Conflict {
+                    entity: "todo",
+                    id,
+                    reason: "is not part of the project",
+                }
+                .into());
+            }
+        }
+
+        let targets = self.create_todos.iter().map(|t| t.group);
+        for group in targets.chain(self.update_todos.iter().map(|t| t.group)) {
+            match group {
+                GroupRef::Stored(id) if !group_ids.contains(&id) => {
+                    return Err(Conflict {
+                        entity: "group",
+                        id,
+                        reason: "is not part of the project",
+                    }
+                    .into());
+                }
+                GroupRef::Stored(id) if self.delete_groups.contains(&id) => {
+                    return Err(Conflict {
+                        entity: "group",
+                        id,
+                        reason: "is deleted by the same changes",
+                    }
+                    .into());
+                }
+                GroupRef::Created(index) if index >= self.create_groups.len() => {
+                    return Err(anyhow!("No created group at index {}", index).into());
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Fails with a conflict unless the entities of `item` can go back: its
+/// entity must not exist again, and the project or group each of its groups
+/// and todos belongs to must come with it or be stored, as `exists` tells.
+pub fn check_restorable(
+    item: &TrashItem,
+    mut exists: impl FnMut(EntityKind, u64) -> Result<bool>,
+) -> Result<()> {
+    let entity = match item.entity {
+        EntityKind::Project => "project",
+        EntityKind::Group => "group",
+        EntityKind::Todo => "todo",
+    };
+    if exists(item.entity, item.entity_id)? {
+        return Err(Conflict {
+            entity,
+            id: item.entity_id,
+            reason: "already exists",
+        }
+        .into());
+    }
+
+    let entities = &item.entities;
+    for group in &entities.groups {
+        if !entities.projects.iter().any(|p| p.id == group.project_id)
+            && !exists(EntityKind::Project, group.project_id)?
+        {
+            return Err(Conflict {
+                entity: "group",
+                id: group.id,
+                reason: "belongs to a deleted project",
+            }
+            .into());
+        }
+    }
+    for todo in &entities.todos {
+        if !entities.groups.iter().any(|g| g.id == todo.group_id)
+            && !exists(EntityKind::Group, todo.group_id)?
+        {
+            return Err(Conflict {
+                entity: "todo",
+                id: todo.id,
+                reason: "belongs to a deleted group",
+            }
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Entities `ProjectContentRepository::put` deletes, by kind.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EntityIds {
+    pub projects: Vec<u64>,
+    pub groups: Vec<u64>,
+    pub todos: Vec<u64>,
+}
+
+#[async_trait]
+pub trait ProjectContentRepository: Sync + Send {
+    /// Creates the project with a group for each of `group_names`, in that
+    /// order; all at once. Returns the project with its groups.
+    async fn create(
+        &self,
+        project: CreateProjectData<'_>,
+        group_names: &[String],
+    ) -> Result<ProjectContent>;
+    /// The project with its groups and todos, all read at once.
+    async fn get(&self, project_id: u64) -> Result<Option<ProjectContent>>;
+    /// Applies all `changes` to the project or, when any of them fails,
+    /// none; the project's `updated_at` is raised to now. Returns the
+    /// project with its groups and todos afterwards.
+    ///
+    /// `expected` is the content the changes were planned against, as `get`
+    /// returned it; when the stored content differs, nothing is applied and
+    /// it fails with `Conflict`.
+    async fn apply(
+        &self,
+        project_id: u64,
+        expected: Option<ProjectContent>,
+        changes: ProjectChanges,
+    ) -> Result<Option<ProjectContent>>;
+    /// Stores the entities exactly as given, inserting the missing ones
+    /// with their ids, then deletes the `deleted` ones together with their
+    /// groups and todos, and raises `updated_at` to `at` of the projects
+    /// whose groups or todos were stored or deleted, other than the stored
+    /// projects; all at once. Meant for writing back earlier states, so
+    /// nothing is checked beyond what the storage enforces.
+    async fn put(&self, entities: Snapshot, deleted: EntityIds, at: OffsetDateTime) -> Result<()>;
+    /// Moves the entity with its groups and todos into a new trash item
+    /// deleted `at`, and raises `updated_at` of the project a trashed group
+    /// or todo belonged to; all at once. Returns `None` when there is no
+    /// such entity.
+    async fn trash(
+        &self,
+        entity: EntityKind,
+        id: u64,
+        at: OffsetDateTime,
+    ) -> Result<Option<TrashItem>>;
+    /// Stores the entities of the trash item as `put` does, raising
+    /// `updated_at` of their projects to `at` likewise, and removes the
+    /// item; all at once. Returns `None`, storing nothing, when there is no
+    /// such item, and fails with a conflict, keeping the item, when
+    /// `check_restorable` does against the stored entities.
+    async fn restore(&self, item: TrashItem, at: OffsetDateTime) -> Result<Option<TrashItem>>;
+}
+
+fn validate_group_names(names: &[String]) -> std::result::Result<(), validator::ValidationError> {
+    if names.iter().all(|name| !name.trim().is_empty()) {
+        return Ok(());
+    }
+
+    let mut error = validator::ValidationError::new("length");
+    error.message = Some("Group names must not be empty".into());
+
+    Err(error)
+}
+
+#[derive(Default, validator::Validate)]
+pub struct UpdateSettingsData {
+    #[validate(custom = "validate_group_names")]
+    pub default_groups: Option<Vec<String>>,
+    #[validate(range(min = 0, max = 36500, message = "Must be at most 36500 days"))]
+    pub activity_retention_days: Option<u32>,
+    #[validate(range(min = 0, max = 36500, message = "Must be at most 36500 days"))]
+    pub trash_retention_days: Option<u32>,
+}
+
+#[async_trait]
+pub trait SettingsRepository: Sync + Send {
+    /// Stored settings, or the defaults when nothing was saved yet.
+    async fn get(&self) -> Result<Settings>;
+    async fn update(&self, settings: UpdateSettingsData) -> Result<Settings>;
+}
+
+#[async_trait]
+pub trait RecoveryRepository: Sync + Send {
+    /// Checks that every storage file can be read; damaged files are moved
+    /// aside and replaced by whatever could be salvaged from them.
+    async fn recover(&self, at: OffsetDateTime) -> Result<Vec<RecoveredFile>>;
+}
+
+#[async_trait]
+pub trait HistoryRepository: Sync + Send {
+    /// Stored undo and redo stacks, or empty ones when nothing was saved yet.
+    async fn get(&self) -> Result<History>;
+    /// Replaces the stored stacks.
+    async fn save(&self, history: History) -> Result<()>;
+}
+
+/// `Activity` to append; the repository assigns its id.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateActivityData {
+    pub at: OffsetDateTime,
+    pub project_id: u64,
+    pub entity: EntityKind,
+    pub entity_id: u64,
+    pub action: HistoryAction,
+    pub undone: bool,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
+#[async_trait]
+pub trait ActivityRepository: Sync + Send {
+    /// Appends the activities in order; ids keep growing and are never reused.
+    async fn append(&self, activities: Vec<CreateActivityData>) -> Result<()>;
+    /// Up to `limit` activities of the project, newest first, only those
+    /// with ids below `before_id` when it is given.
+    async fn list(
+        &self,
+        project_id: u64,
+        before_id: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<Activity>>;
+    /// Deletes the activities older than `before`; returns how many.
+    async fn prune(&self, before: OffsetDateTime) -> Result<usize>;
+}
+
+#[async_trait]
+pub trait TrashRepository: Sync + Send {
+    async fn get(&self, id: u64) -> Result<Option<TrashItem>>;
+    /// Items ordered from the most recently deleted.
+    async fn list(&self) -> Result<Vec<TrashItem>>;
+    async fn remove(&self, id: u64) -> Result<Option<TrashItem>>;
+    /// Removes the items deleted before `before`; returns them.
+    async fn purge(&self, before: OffsetDateTime) -> Result<Vec<TrashItem>>;
+}
+
+#[async_trait]
+pub trait BackupRepository: Sync + Send {
+    /// Copies every storage file at once, consistent across files.
+    async fn create(&self, at: OffsetDateTime) -> Result<Backup>;
+    /// Backups ordered from the newest.
+    async fn list(&self) -> Result<Vec<Backup>>;
+    /// Replaces all storage files with the backup while no one else can
+    /// read or write them; nothing changes when the backup is unreadable.
+    async fn restore(&self, id: u64) -> Result<Option<Backup>>;
+    async fn delete(&self, id: u64) -> Result<Option<Backup>>;
+    /// Grows with every write to the storage.
+    async fn write_count(&self) -> Result<u64>;
 }
 
 #[cfg(test)]
@@ -40,6 +475,17 @@ pub mod repository_tests {
             $crate::project_repository_test!($init, project_repo_get_one);
             $crate::project_repository_test!($init, project_repo_get_from_empty);
             $crate::project_repository_test!($init, project_repo_list_returns_all);
+            $crate::project_repository_test!($init, project_repo_update_name);
+            $crate::project_repository_test!($init, project_repo_update_is_active);
+            $crate::project_repository_test!($init, project_repo_update_is_active_archives);
+            $crate::project_repository_test!($init, project_repo_update_unknown);
+            $crate::project_repository_test!($init, project_repo_archive_one);
+            $crate::project_repository_test!($init, project_repo_archive_twice);
+            $crate::project_repository_test!($init, project_repo_unarchive_one);
+            $crate::project_repository_test!($init, project_repo_archive_unknown);
+            $crate::project_repository_test!($init, project_repo_list_filters_archived);
+            $crate::project_repository_test!($init, project_repo_set_updated_at);
+            $crate::project_repository_test!($init, project_repo_list_recently_touched);
         };
         ($init:expr, $name:ident) => {
             #[tokio::test]
@@ -112,7 +558,7 @@ pub mod repository_tests {
 
     #[allow(dead_code)]
     pub async fn project_repo_list_returns_all<R: ProjectRepository>(repo: Arc<R>) {
-        let names = vec!["First", "Second", "Third"];
+        let names = ["First", "Second", "Third"];
 
         for name in names.iter() {
             repo.create(CreateProjectData { name })
@@ -120,7 +566,10 @@ pub mod repository_tests {
                 .expect("Failed create project");
         }
 
-        let projects = repo.list().await.expect("Failed list projects");
+        let projects = repo
+            .list(ProjectFilter::All, ProjectSort::default())
+            .await
+            .expect("Failed list projects");
         let project_names = projects.into_iter().map(|p| p.name).collect::<Vec<_>>();
 
         for name in names.iter() {
@@ -133,10 +582,301 @@ pub mod repository_tests {
         }
     }
 
+    #[allow(dead_code)]
+    pub async fn project_repo_update_name<R: ProjectRepository>(repo: Arc<R>) {
+        let project = repo
+            .create(CreateProjectData { name: "Old name" })
+            .await
+            .expect("Failed create project");
+
+        let updated = repo
+            .update(
+                project.id,
+                UpdateProjectData {
+                    name: Some("New name"),
+                    ..Default::default()
+                },
+            )
+            .await
+            .expect("Failed update project")
+            .expect("Project not found");
+
+        assert_eq!(updated.name, "New name");
+        assert_eq!(updated.is_active, project.is_active);
+        assert_eq!(updated.created_at, project.created_at);
+        assert!(updated.updated_at >= project.updated_at);
+        assert_eq!(
+            repo.get(project.id).await.expect("Failed to get project"),
+            Some(updated)
+        );
+    }
+
+    #[allow(dead_code)]
+    pub async fn project_repo_update_is_active<R: ProjectRepository>(repo: Arc<R>) {
+        let project = repo
+            .create(CreateProjectData { name: "Project" })
+            .await
+            .expect("Failed create project");
+
+        let updated = repo
+            .update(
+                project.id,
+                UpdateProjectData {
+                    is_active: Some(false),
+                    ..Default::default()
+                },
+            )
+            .await
+            .expect("Failed update project")
+            .expect("Project not found");
+
+        assert_eq!(updated.name, project.name);
+        assert!(!updated.is_active);
+    }
+
+    #[allow(dead_code)]
+    pub async fn project_repo_update_is_active_archives<R: ProjectRepository>(repo: Arc<R>) {
+        let project = repo
+            .create(CreateProjectData { name: "Project" })
+            .await
+            .expect("Failed create project");
+        let set_active = |is_active| {
+            let repo = repo.clone();
+            async move {
+                repo.update(
+                    project.id,
+                    UpdateProjectData {
+                        is_active: Some(is_active),
+                        ..Default::default()
+                    },
+                )
+                .await
+                .expect("Failed update project")
+                .expect("Project not found")
+            }
+        };
+
+        let deactivated = set_active(false).await;
+        assert!(deactivated.archived_at.is_some());
+        assert_eq!(
+            repo.list(ProjectFilter::Archived, ProjectSort::default())
+                .await
+                .expect("Failed to list projects"),
+            vec![deactivated.clone()]
+        );
+        assert_eq!(set_active(false).await.archived_at, deactivated.archived_at);
+
+        let activated = set_active(true).await;
+        assert_eq!(activated.archived_at, None);
+        assert_eq!(
+            repo.get(project.id).await.expect("Failed to get project"),
+            Some(activated)
+        );
+    }
+
+    #[allow(dead_code)]
+    pub async fn project_repo_update_unknown<R: ProjectRepository>(repo: Arc<R>) {
+        let updated = repo
+            .update(
+                1,
+                UpdateProjectData {
+                    name: Some("New name"),
+                    ..Default::default()
+                },
+            )
+            .await
+            .expect("Failed update project");
+
+        assert_eq!(updated, None);
+    }
+
+    #[allow(dead_code)]
+    pub async fn project_repo_archive_one<R: ProjectRepository>(repo: Arc<R>) {
+        let project = repo
+            .create(CreateProjectData { name: "Project" })
+            .await
+            .expect("Failed create project");
+
+        let archived = repo
+            .archive(project.id)
+            .await
+            .expect("Failed archive project")
+            .expect("Project not found");
+
+        assert!(!archived.is_active);
+        assert!(archived.archived_at.is_some());
+        assert_eq!(
+            repo.get(project.id).await.expect("Failed to get project"),
+            Some(archived)
+        );
+    }
+
+    #[allow(dead_code)]
+    pub async fn project_repo_archive_twice<R: ProjectRepository>(repo: Arc<R>) {
+        let project = repo
+            .create(CreateProjectData { name: "Project" })
+            .await
+            .expect("Failed create project");
+
+        let first = repo
+            .archive(project.id)
+            .await
+            .expect("Failed archive project")
+            .expect("Project not found");
+        let second = repo
+            .archive(project.id)
+            .await
+            .expect("Failed archive project")
+            .expect("Project not found");
+
+        assert_eq!(first.archived_at, second.archived_at);
+    }
+
+    #[allow(dead_code)]
+    pub async fn project_repo_unarchive_one<R: ProjectRepository>(repo: Arc<R>) {
+        let project = repo
+            .create(CreateProjectData { name: "Project" })
+            .await
+            .expect("Failed create project");
+
+        repo.archive(project.id)
+            .await
+            .expect("Failed archive project");
+        let unarchived = repo
+            .unarchive(project.id)
+            .await
+            .expect("Failed unarchive project")
+            .expect("Project not found");
+
+        assert!(unarchived.is_active);
+        assert_eq!(unarchived.archived_at, None);
+    }
+
+    #[allow(dead_code)]
+    pub async fn project_repo_archive_unknown<R: ProjectRepository>(repo: Arc<R>) {
+        assert_eq!(repo.archive(1).await.expect("Failed archive project"), None);
+        assert_eq!(
+            repo.unarchive(1).await.expect("Failed unarchive project"),
+            None
+        );
+    }
+
+    #[allow(dead_code)]
+    pub async fn project_repo_set_updated_at<R: ProjectRepository>(repo: Arc<R>) {
+        let first = repo
+            .create(CreateProjectData { name: "First" })
+            .await
+            .expect("Failed create project");
+        let second = repo
+            .create(CreateProjectData { name: "Second" })
+            .await
+            .expect("Failed create project");
+        let earlier = first.updated_at - time::Duration::days(1);
+
+        repo.set_updated_at(&[(first.id, earlier), (1000, earlier)])
+            .await
+            .expect("Failed set updated_at");
+
+        let get = |id| repo.get(id);
+        assert_eq!(
+            get(first.id)
+                .await
+                .expect("Failed to get project")
+                .map(|p| p.updated_at),
+            Some(earlier)
+        );
+        assert_eq!(
+            get(second.id).await.expect("Failed to get project"),
+            Some(second)
+        );
+    }
+
+    #[allow(dead_code)]
+    pub async fn project_repo_list_recently_touched<R: ProjectRepository>(repo: Arc<R>) {
+        let mut projects = Vec::new();
+        for name in ["First", "Second", "Third"] {
+            projects.push(
+                repo.create(CreateProjectData { name })
+                    .await
+                    .expect("Failed create project"),
+            );
+        }
+        let later = projects[2].updated_at + time::Duration::hours(1);
+        repo.set_updated_at(&[(projects[0].id, later)])
+            .await
+            .expect("Failed to set updated_at");
+
+        let names =
+            |projects: Vec<Project>| projects.into_iter().map(|p| p.name).collect::<Vec<_>>();
+
+        assert_eq!(
+            names(
+                repo.list(ProjectFilter::All, ProjectSort::RecentlyTouched)
+                    .await
+                    .expect("Failed list")
+            ),
+            vec!["First", "Third", "Second"]
+        );
+        assert_eq!(
+            names(
+                repo.list(ProjectFilter::All, ProjectSort::Created)
+                    .await
+                    .expect("Failed list")
+            ),
+            vec!["Third", "Second", "First"]
+        );
+    }
+
+    #[allow(dead_code)]
+    pub async fn project_repo_list_filters_archived<R: ProjectRepository>(repo: Arc<R>) {
+        let active = repo
+            .create(CreateProjectData { name: "Active" })
+            .await
+            .expect("Failed create project");
+        let archived = repo
+            .create(CreateProjectData { name: "Archived" })
+            .await
+            .expect("Failed create project");
+        repo.archive(archived.id)
+            .await
+            .expect("Failed archive project");
+
+        let ids = |projects: Vec<Project>| projects.into_iter().map(|p| p.id).collect::<Vec<_>>();
+
+        assert_eq!(
+            ids(repo
+                .list(ProjectFilter::Active, ProjectSort::default())
+                .await
+                .expect("Failed list")),
+            vec![active.id]
+        );
+        assert_eq!(
+            ids(repo
+                .list(ProjectFilter::Archived, ProjectSort::default())
+                .await
+                .expect("Failed list")),
+            vec![archived.id]
+        );
+
+        let mut all = ids(repo
+            .list(ProjectFilter::All, ProjectSort::default())
+            .await
+            .expect("Failed list"));
+        all.sort();
+        assert_eq!(all, vec![active.id, archived.id]);
+    }
+
     #[macro_export]
     macro_rules! group_repository_test {
         ($init:expr) => {
             $crate::group_repository_test!($init, group_repo_create_one);
+            $crate::group_repository_test!($init, group_repo_create_returns_unique_ids);
+            $crate::group_repository_test!($init, group_repo_create_increments_position);
+            $crate::group_repository_test!($init, group_repo_find_by_project_returns_own);
+            $crate::group_repository_test!($init, group_repo_find_by_project_from_empty);
+            $crate::group_repository_test!($init, group_repo_list_all);
+            $crate::group_repository_test!($init, group_repo_get_one);
+            $crate::group_repository_test!($init, group_repo_get_from_empty);
         };
         ($init:expr, $name:ident) => {
             #[tokio::test]
@@ -160,4 +900,1587 @@ pub mod repository_tests {
         assert_eq!(result.name, name);
         assert_eq!(result.project_id, project_id);
     }
+
+    #[allow(dead_code)]
+    pub async fn group_repo_create_returns_unique_ids<R: GroupRepository + 'static>(repo: Arc<R>) {
+        let names = vec!["First group", "Second group", "Another group"];
+        let names_n = names.len();
+        let mut unique_ids = HashSet::<u64>::new();
+
+        let tasks = names
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let repo = repo.clone();
+                let project_id = (i % 2) as u64 + 1;
+                tokio::spawn(async move { repo.create(CreateGroupData { name, project_id }).await })
+            })
+            .collect::<Vec<_>>();
+
+        for task in tasks {
+            let r = task
+                .await
+                .expect("Failed to finish task")
+                .expect("Failed to create group");
+            unique_ids.insert(r.id);
+        }
+
+        assert_eq!(names_n, unique_ids.len());
+    }
+
+    #[allow(dead_code)]
+    pub async fn group_repo_create_increments_position<R: GroupRepository>(repo: Arc<R>) {
+        let mut positions = Vec::new();
+
+        for (name, project_id) in [("A", 1), ("B", 2), ("C", 1), ("D", 1)] {
+            let group = repo
+                .create(CreateGroupData { name, project_id })
+                .await
+                .expect("Failed to create group");
+
+            if project_id == 1 {
+                positions.push(group.position);
+            }
+        }
+
+        assert!(
+            positions.windows(2).all(|w| w[0] < w[1]),
+            "positions = {:?} are not increasing",
+            positions
+        );
+    }
+
+    #[allow(dead_code)]
+    pub async fn group_repo_find_by_project_returns_own<R: GroupRepository>(repo: Arc<R>) {
+        for (name, project_id) in [("First", 1), ("Other", 2), ("Second", 1), ("Third", 1)] {
+            repo.create(CreateGroupData { name, project_id })
+                .await
+                .expect("Failed to create group");
+        }
+
+        let groups = repo
+            .find_by_project(1)
+            .await
+            .expect("Failed to find groups");
+
+        assert!(groups.iter().all(|g| g.project_id == 1));
+        assert_eq!(
+            groups.into_iter().map(|g| g.name).collect::<Vec<_>>(),
+            vec!["First", "Second", "Third"]
+        );
+    }
+
+    #[allow(dead_code)]
+    pub async fn group_repo_find_by_project_from_empty<R: GroupRepository>(repo: Arc<R>) {
+        let groups = repo
+            .find_by_project(1)
+            .await
+            .expect("Failed to find groups");
+
+        assert_eq!(groups, vec![]);
+    }
+
+    #[allow(dead_code)]
+    pub async fn group_repo_list_all<R: GroupRepository>(repo: Arc<R>) {
+        let mut created = Vec::new();
+        for (name, project_id) in [("First", 2), ("Other", 1), ("Second", 2)] {
+            let group = repo
+                .create(CreateGroupData { name, project_id })
+                .await
+                .expect("Failed to create group");
+            created.push(group);
+        }
+
+        let groups = repo.list().await.expect("Failed to list groups");
+
+        assert_eq!(groups, created);
+    }
+
+    #[allow(dead_code)]
+    pub async fn group_repo_get_one<R: GroupRepository>(repo: Arc<R>) {
+        let group = repo
+            .create(CreateGroupData {
+                name: "Group in repository",
+                project_id: 1,
+            })
+            .await
+            .expect("Failed to create group");
+
+        let group_from_repo = repo.get(group.id).await.expect("Failed to get group");
+
+        assert_eq!(group_from_repo, Some(group));
+    }
+
+    #[allow(dead_code)]
+    pub async fn group_repo_get_from_empty<R: GroupRepository>(repo: Arc<R>) {
+        let group_from_repo = repo.get(1).await.expect("Failed to get group");
+
+        assert_eq!(group_from_repo, None);
+    }
+
+    #[macro_export]
+    macro_rules! todo_repository_test {
+        ($init:expr) => {
+            $crate::todo_repository_test!($init, todo_repo_create_one);
+            $crate::todo_repository_test!($init, todo_repo_create_returns_unique_ids);
+            $crate::todo_repository_test!($init, todo_repo_get_one);
+            $crate::todo_repository_test!($init, todo_repo_get_from_empty);
+            $crate::todo_repository_test!($init, todo_repo_find_by_group_returns_own);
+            $crate::todo_repository_test!($init, todo_repo_list_all);
+        };
+        ($init:expr, $name:ident) => {
+            #[tokio::test]
+            async fn $name() {
+                let repo = std::sync::Arc::new($init);
+                $crate::ports::repository_tests::$name(repo).await;
+            }
+        };
+    }
+
+    #[allow(dead_code)]
+    pub async fn todo_repo_create_one<R: TodoRepository>(repo: Arc<R>) {
+        let text = "First todo";
+        let group_id = 12;
+
+        let result = repo
+            .create(CreateTodoData { text, group_id })
+            .await
+            .expect("Failed to create object");
+
+        assert_eq!(result.text, text);
+        assert_eq!(result.group_id, group_id);
+        assert!(!result.is_done);
+        assert_eq!(result.done_at, None);
+    }
+
+    #[allow(dead_code)]
+    pub async fn todo_repo_create_returns_unique_ids<R: TodoRepository + 'static>(repo: Arc<R>) {
+        let texts = vec!["First todo", "Second todo", "Another todo"];
+        let texts_n = texts.len();
+        let mut unique_ids = HashSet::<u64>::new();
+
+        let tasks = texts
+            .into_iter()
+            .map(|text| {
+                let repo = repo.clone();
+                tokio::spawn(async move { repo.create(CreateTodoData { text, group_id: 1 }).await })
+            })
+            .collect::<Vec<_>>();
+
+        for task in tasks {
+            let r = task
+                .await
+                .expect("Failed to finish task")
+                .expect("Failed to create todo");
+            unique_ids.insert(r.id);
+        }
+
+        assert_eq!(texts_n, unique_ids.len());
+    }
+
+    #[allow(dead_code)]
+    pub async fn todo_repo_get_one<R: TodoRepository>(repo: Arc<R>) {
+        let todo = repo
+            .create(CreateTodoData {
+                text: "Todo in repository",
+                group_id: 1,
+            })
+            .await
+            .expect("Failed create todo");
+
+        let todo_from_repo = repo.get(todo.id).await.expect("Failed to get todo");
+
+        assert_eq!(todo_from_repo, Some(todo));
+    }
+
+    #[allow(dead_code)]
+    pub async fn todo_repo_get_from_empty<R: TodoRepository>(repo: Arc<R>) {
+        let todo_from_repo = repo.get(1).await.expect("Failed to get object");
+
+        assert_eq!(todo_from_repo, None);
+    }
+
+    #[allow(dead_code)]
+    pub async fn todo_repo_find_by_group_returns_own<R: TodoRepository>(repo: Arc<R>) {
+        for (text, group_id) in [("First", 1), ("Other", 2), ("Second", 1), ("Third", 1)] {
+            repo.create(CreateTodoData { text, group_id })
+                .await
+                .expect("Failed to create todo");
+        }
+
+        let todos = repo.find_by_group(1).await.expect("Failed to find todos");
+
+        assert!(todos.iter().all(|t| t.group_id == 1));
+        assert_eq!(
+            todos.into_iter().map(|t| t.text).collect::<Vec<_>>(),
+            vec!["First", "Second", "Third"]
+        );
+    }
+
+    #[allow(dead_code)]
+    pub async fn todo_repo_list_all<R: TodoRepository>(repo: Arc<R>) {
+        let mut created = Vec::new();
+        for (text, group_id) in [("First", 2), ("Other", 1), ("Second", 2)] {
+            let todo = repo
+                .create(CreateTodoData { text, group_id })
+                .await
+                .expect("Failed to create todo");
+            created.push(todo);
+        }
+
+        let todos = repo.list().await.expect("Failed to list todos");
+
+        assert_eq!(todos, created);
+    }
+
+    #[macro_export]
+    macro_rules! settings_repository_test {
+        ($init:expr) => {
+            $crate::settings_repository_test!($init, settings_repo_get_defaults);
+            $crate::settings_repository_test!($init, settings_repo_update_default_groups);
+            $crate::settings_repository_test!($init, settings_repo_update_nothing);
+            $crate::settings_repository_test!($init, settings_repo_update_activity_retention);
+            $crate::settings_repository_test!($init, settings_repo_update_trash_retention);
+        };
+        ($init:expr, $name:ident) => {
+            #[tokio::test]
+            async fn $name() {
+                let repo = std::sync::Arc::new($init);
+                $crate::ports::repository_tests::$name(repo).await;
+            }
+        };
+    }
+
+    #[allow(dead_code)]
+    pub async fn settings_repo_get_defaults<R: SettingsRepository>(repo: Arc<R>) {
+        let settings = repo.get().await.expect("Failed to get settings");
+
+        assert_eq!(settings, Settings::default());
+    }
+
+    #[allow(dead_code)]
+    pub async fn settings_repo_update_default_groups<R: SettingsRepository>(repo: Arc<R>) {
+        let default_groups = vec!["Backlog".to_string(), "Today".into(), "Done".into()];
+
+        let updated = repo
+            .update(UpdateSettingsData {
+                default_groups: Some(default_groups.clone()),
+                ..Default::default()
+            })
+            .await
+            .expect("Failed to update settings");
+
+        assert_eq!(updated.default_groups, default_groups);
+        assert_eq!(repo.get().await.expect("Failed to get settings"), updated);
+    }
+
+    #[allow(dead_code)]
+    pub async fn settings_repo_update_nothing<R: SettingsRepository>(repo: Arc<R>) {
+        repo.update(UpdateSettingsData {
+            default_groups: Some(vec!["Today".into()]),
+            activity_retention_days: Some(30),
+            trash_retention_days: Some(7),
+        })
+        .await
+        .expect("Failed to update settings");
+
+        let updated = repo
+            .update(UpdateSettingsData::default())
+            .await
+            .expect("Failed to update settings");
+
+        assert_eq!(updated.default_groups, vec!["Today"]);
+        assert_eq!(updated.activity_retention_days, 30);
+        assert_eq!(updated.trash_retention_days, 7);
+    }
+
+    #[allow(dead_code)]
+    pub async fn settings_repo_update_activity_retention<R: SettingsRepository>(repo: Arc<R>) {
+        let updated = repo
+            .update(UpdateSettingsData {
+                activity_retention_days: Some(90),
+                ..Default::default()
+            })
+            .await
+            .expect("Failed to update settings");
+
+        assert_eq!(updated.activity_retention_days, 90);
+        assert_eq!(updated.default_groups, Vec::<String>::new());
+        assert_eq!(repo.get().await.expect("Failed to get settings"), updated);
+    }
+
+    #[allow(dead_code)]
+    pub async fn settings_repo_update_trash_retention<R: SettingsRepository>(repo: Arc<R>) {
+        let updated = repo
+            .update(UpdateSettingsData {
+                trash_retention_days: Some(30),
+                ..Default::default()
+            })
+            .await
+            .expect("Failed to update settings");
+
+        assert_eq!(updated.trash_retention_days, 30);
+        assert_eq!(
+            updated.activity_retention_days,
+            Settings::DEFAULT_ACTIVITY_RETENTION_DAYS
+        );
+        assert_eq!(repo.get().await.expect("Failed to get settings"), updated);
+    }
+
+    /// Repositories over one storage for the `ProjectContentRepository`
+    /// suite, which sets up and checks contents through the others.
+    pub struct ContentRepositories {
+        pub project: Arc<dyn ProjectRepository>,
+        pub group: Arc<dyn GroupRepository>,
+        pub todo: Arc<dyn TodoRepository>,
+        pub trash: Arc<dyn TrashRepository>,
+        pub content: Arc<dyn ProjectContentRepository>,
+        /// Runs once the test is done, e.g. to remove storage files.
+        pub cleanup: Option<Box<dyn FnOnce() + Send + Sync>>,
+    }
+
+    impl Drop for ContentRepositories {
+        fn drop(&mut self) {
+            if let Some(cleanup) = self.cleanup.take() {
+                cleanup();
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    pub async fn content_repo_trash_then_create(repos: Arc<ContentRepositories>) {
+        let content = create_content(&repos).await;
+        let at = time::macros::datetime!(2100-01-01 12:00 UTC);
+
+        repos
+            .content
+            .trash(EntityKind::Project, content.project.id, at)
+            .await
+            .expect("Failed to trash")
+            .expect("Project not found");
+        assert_eq!(
+            repos
+                .project
+                .get(content.project.id)
+                .await
+                .expect("Failed to get project"),
+            None
+        );
+        assert_eq!(
+            repos
+                .project
+                .list(ProjectFilter::All, ProjectSort::default())
+                .await
+                .expect("Failed to list projects"),
+            vec![]
+        );
+
+        // Ids of trashed entities stay taken, so a restore never clashes.
+        let recreated = create_content(&repos).await;
+
+        assert!(recreated.project.id > content.project.id);
+        assert!(recreated.groups[0].id > content.groups[0].id);
+        assert!(recreated.todos[0].id > content.todos[1].id);
+    }
+
+    #[macro_export]
+    macro_rules! project_content_repository_test {
+        ($init:expr) => {
+            $crate::project_content_repository_test!($init, content_repo_create);
+            $crate::project_content_repository_test!($init, content_repo_apply_changes);
+            $crate::project_content_repository_test!($init, content_repo_apply_unknown_project);
+            $crate::project_content_repository_test!($init, content_repo_apply_foreign_ids);
+            $crate::project_content_repository_test!($init, content_repo_apply_stale);
+            $crate::project_content_repository_test!($init, content_repo_put_keeps_ids);
+            $crate::project_content_repository_test!($init, content_repo_put_deletes_children);
+            $crate::project_content_repository_test!($init, content_repo_trash);
+            $crate::project_content_repository_test!($init, content_repo_restore);
+            $crate::project_content_repository_test!($init, content_repo_restore_conflicts);
+            $crate::project_content_repository_test!($init, content_repo_trash_then_create);
+            $crate::project_content_repository_test!($init, trash_repo_list_empty);
+            $crate::project_content_repository_test!($init, trash_repo_get);
+            $crate::project_content_repository_test!($init, trash_repo_list_newest_first);
+            $crate::project_content_repository_test!($init, trash_repo_remove);
+            $crate::project_content_repository_test!($init, trash_repo_purge);
+        };
+        ($init:expr, $name:ident) => {
+            #[tokio::test]
+            async fn $name() {
+                let repos = std::sync::Arc::new($init);
+                $crate::ports::repository_tests::$name(repos).await;
+            }
+        };
+    }
+
+    #[allow(dead_code)]
+    pub async fn content_repo_create(repos: Arc<ContentRepositories>) {
+        let other = repos
+            .project
+            .create(CreateProjectData { name: "Other" })
+            .await
+            .expect("Failed to create project");
+
+        let content = repos
+            .content
+            .create(
+                CreateProjectData { name: "Project" },
+                &["Todo".into(), "Done".into()],
+            )
+            .await
+            .expect("Failed to create project");
+
+        assert_eq!(content.project.name, "Project");
+        assert!(content.project.id > other.id);
+        assert_eq!(
+            content
+                .groups
+                .iter()
+                .map(|g| (g.name.as_str(), g.position, g.is_opened, g.project_id))
+                .collect::<Vec<_>>(),
+            [
+                ("Todo", 1.0, true, content.project.id),
+                ("Done", 2.0, true, content.project.id)
+            ]
+        );
+        assert_eq!(content.todos, []);
+        assert_eq!(
+            repos
+                .content
+                .get(content.project.id)
+                .await
+                .expect("Failed to get content"),
+            Some(content.clone())
+        );
+        assert_eq!(
+            repos
+                .group
+                .find_by_project(content.project.id)
+                .await
+                .expect("Failed to find groups"),
+            content.groups
+        );
+    }
+
+    #[allow(dead_code)]
+    pub async fn content_repo_apply_changes(repos: Arc<ContentRepositories>) {
+        let project = repos
+            .project
+            .create(CreateProjectData { name: "Project" })
+            .await
+            .expect("Failed to create project");
+        let mut groups = Vec::new();
+        for name in ["First", "Second"] {
+            let group = repos
+                .group
+                .create(CreateGroupData {
+                    name,
+                    project_id: project.id,
+                })
+                .await
+                .expect("Failed to create group");
+            groups.push(group);
+        }
+        let mut todos = Vec::new();
+        for (text, group) in [("a", &groups[0]), ("b", &groups[0]), ("c", &groups[1])] {
+            let todo = repos
+                .todo
+                .create(CreateTodoData {
+                    text,
+                    group_id: group.id,
+                })
+                .await
+                .expect("Failed to create todo");
+            todos.push(todo);
+        }
+
+        let content = repos
+            .content
+            .apply(
+                project.id,
+                None,
+                ProjectChanges {
+                    name: Some("Renamed".into()),
+                    create_groups: vec![NewGroupData {
+                        name: "New".into(),
+                        position: 0.5,
+                        is_opened: false,
+                    }],
+                    update_groups: vec![GroupChangeData {
+                        id: groups[0].id,
+                        name: "First renamed".into(),
+                        position: 2.0,
+                        is_opened: false,
+                    }],
+                    delete_groups: vec![groups[1].id],
+                    create_todos: vec![NewTodoData {
+                        text: "new".into(),
+                        is_done: true,
+                        group: GroupRef::Created(0),
+                        position: 1.0,
+                    }],
+                    update_todos: vec![TodoChangeData {
+                        id: todos[2].id,
+                        text: "c moved".into(),
+                        is_done: false,
+                        group: GroupRef::Stored(groups[0].id),
+                        position: 0.5,
+                    }],
+                    delete_todos: vec![todos[1].id],
+                },
+            )
+            .await
+            .expect("Failed to apply changes")
+            .expect("Project not found");
+
+        assert_eq!(content.project.name, "Renamed");
+        assert!(content.project.updated_at >= todos[2].updated_at);
+        assert_eq!(
+            content
+                .groups
+                .iter()
+                .map(|g| (g.name.as_str(), g.is_opened))
+                .collect::<Vec<_>>(),
+            [("New", false), ("First renamed", false)]
+        );
+        assert_eq!(
+            content
+                .todos
+                .iter()
+                .map(|t| (t.text.as_str(), t.group_id, t.is_done))
+                .collect::<Vec<_>>(),
+            [
+                ("new", content.groups[0].id, true),
+                ("c moved", groups[0].id, false),
+                ("a", groups[0].id, false)
+            ]
+        );
+        assert!(content.todos[0].done_at.is_some());
+        assert!(content.todos[1].updated_at >= todos[2].updated_at);
+
+        assert_eq!(
+            repos
+                .project
+                .get(project.id)
+                .await
+                .expect("Failed to get project"),
+            Some(content.project.clone())
+        );
+        assert_eq!(
+            repos
+                .group
+                .find_by_project(project.id)
+                .await
+                .expect("Failed to find groups"),
+            content.groups
+        );
+        assert_eq!(
+            repos
+                .todo
+                .find_by_group(groups[0].id)
+                .await
+                .expect("Failed to find todos"),
+            content.todos[1..]
+        );
+        assert_eq!(
+            repos
+                .todo
+                .get(todos[1].id)
+                .await
+                .expect("Failed to get todo"),
+            None
+        );
+    }
+
+    #[allow(dead_code)]
+    pub async fn content_repo_apply_unknown_project(repos: Arc<ContentRepositories>) {
+        let content = repos
+            .content
+            .apply(
+                42,
+                None,
+                ProjectChanges {
+                    name: Some("Renamed".into()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .expect("Failed to apply changes");
+
+        assert_eq!(content, None);
+    }
+
+    #[allow(dead_code)]
+    pub async fn content_repo_apply_foreign_ids(repos: Arc<ContentRepositories>) {
+        let project = repos
+            .project
+            .create(CreateProjectData { name: "Project" })
+            .await
+            .expect("Failed to create project");
+        let other = repos
+            .project
+            .create(CreateProjectData { name: "Other" })
+            .await
+            .expect("Failed to create project");
+        let foreign = repos
+            .group
+            .create(CreateGroupData {
+                name: "Foreign",
+                project_id: other.id,
+            })
+            .await
+            .expect("Failed to create group");
+
+        repos
+            .content
+            .apply(
+                project.id,
+                None,
+                ProjectChanges {
+                    name: Some("Renamed".into()),
+                    create_groups: vec![NewGroupData {
+                        name: "New".into(),
+                        position: 1.0,
+                        is_opened: true,
+                    }],
+                    delete_groups: vec![foreign.id],
+                    ..Default::default()
+                },
+            )
+            .await
+            .expect_err("apply should fail");
+
+        assert_eq!(
+            repos
+                .project
+                .get(project.id)
+                .await
+                .expect("Failed to get project"),
+            Some(project.clone())
+        );
+        assert_eq!(
+            repos
+                .group
+                .find_by_project(project.id)
+                .await
+                .expect("Failed to find groups"),
+            vec![]
+        );
+        assert_eq!(
+            repos
+                .group
+                .find_by_project(other.id)
+                .await
+                .expect("Failed to find groups"),
+            vec![foreign]
+        );
+    }
+
+    #[allow(dead_code)]
+    pub async fn content_repo_apply_stale(repos: Arc<ContentRepositories>) {
+        let content = create_content(&repos).await;
+        assert_eq!(
+            repos
+                .content
+                .get(content.project.id)
+                .await
+                .expect("Failed to get content"),
+            Some(content.clone())
+        );
+
+        let todo = Todo {
+            text: "changed".into(),
+            ..content.todos[0].clone()
+        };
+        repos
+            .content
+            .put(
+                Snapshot {
+                    todos: vec![todo.clone()],
+                    ..Default::default()
+                },
+                EntityIds::default(),
+                OffsetDateTime::now_utc(),
+            )
+            .await
+            .expect("Failed to put");
+
+        let error = repos
+            .content
+            .apply(
+                content.project.id,
+                Some(content.clone()),
+                ProjectChanges {
+                    name: Some("Renamed".into()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .expect_err("apply should fail");
+        assert_eq!(error.kind(), crate::result::ErrorKind::Conflict);
+
+        let stored = repos
+            .content
+            .get(content.project.id)
+            .await
+            .expect("Failed to get content")
+            .expect("Project not found");
+        assert_eq!(stored.project.name, "Project");
+        assert_eq!(stored.todos[0], todo);
+    }
+
+    /// Project with one group holding todos "a" and "b".
+    async fn create_content(repos: &ContentRepositories) -> ProjectContent {
+        let project = repos
+            .project
+            .create(CreateProjectData { name: "Project" })
+            .await
+            .expect("Failed to create project");
+        let group = repos
+            .group
+            .create(CreateGroupData {
+                name: "Group",
+                project_id: project.id,
+            })
+            .await
+            .expect("Failed to create group");
+        let mut todos = Vec::new();
+        for text in ["a", "b"] {
+            let todo = repos
+                .todo
+                .create(CreateTodoData {
+                    text,
+                    group_id: group.id,
+                })
+                .await
+                .expect("Failed to create todo");
+            todos.push(todo);
+        }
+
+        ProjectContent {
+            project,
+            groups: vec![group],
+            todos,
+        }
+    }
+
+    /// Trashes a fresh todo of `content`'s group deleted `at`.
+    async fn trash_todo(
+        repos: &ContentRepositories,
+        content: &ProjectContent,
+        at: OffsetDateTime,
+    ) -> TrashItem {
+        let todo = repos
+            .todo
+            .create(CreateTodoData {
+                text: "Todo",
+                group_id: content.groups[0].id,
+            })
+            .await
+            .expect("Failed to create todo");
+
+        repos
+            .content
+            .trash(EntityKind::Todo, todo.id, at)
+            .await
+            .expect("Failed to trash")
+            .expect("Todo not found")
+    }
+
+    async fn trash_ids(repo: &dyn TrashRepository) -> Vec<u64> {
+        repo.list()
+            .await
+            .expect("Failed to list trash")
+            .iter()
+            .map(|item| item.id)
+            .collect()
+    }
+
+    #[allow(dead_code)]
+    pub async fn trash_repo_list_empty(repos: Arc<ContentRepositories>) {
+        assert_eq!(trash_ids(&*repos.trash).await, Vec::<u64>::new());
+    }
+
+    #[allow(dead_code)]
+    pub async fn trash_repo_get(repos: Arc<ContentRepositories>) {
+        let content = create_content(&repos).await;
+        let deleted_at = time::macros::datetime!(2100-03-01 12:00:00.123 UTC);
+
+        let item = trash_todo(&repos, &content, deleted_at).await;
+
+        assert_eq!(item.deleted_at, deleted_at);
+        assert_eq!(
+            repos.trash.get(item.id).await.expect("Failed to get item"),
+            Some(item.clone())
+        );
+        assert_eq!(
+            repos
+                .trash
+                .get(item.id + 1)
+                .await
+                .expect("Failed to get item"),
+            None
+        );
+    }
+
+    #[allow(dead_code)]
+    pub async fn trash_repo_list_newest_first(repos: Arc<ContentRepositories>) {
+        let content = create_content(&repos).await;
+        let at = time::macros::datetime!(2100-03-01 12:00 UTC);
+        let first = trash_todo(&repos, &content, at).await;
+        let second = trash_todo(&repos, &content, at).await;
+
+        assert_eq!(trash_ids(&*repos.trash).await, [second.id, first.id]);
+    }
+
+    #[allow(dead_code)]
+    pub async fn trash_repo_remove(repos: Arc<ContentRepositories>) {
+        let content = create_content(&repos).await;
+        let at = time::macros::datetime!(2100-03-01 12:00 UTC);
+        let first = trash_todo(&repos, &content, at).await;
+        let second = trash_todo(&repos, &content, at).await;
+
+        let removed = repos
+            .trash
+            .remove(first.id)
+            .await
+            .expect("Failed to remove item");
+        let third = trash_todo(&repos, &content, at).await;
+
+        assert_eq!(removed, Some(first.clone()));
+        assert_eq!(
+            repos
+                .trash
+                .remove(first.id)
+                .await
+                .expect("Failed to remove item"),
+            None
+        );
+        assert!(third.id > second.id);
+        assert_eq!(trash_ids(&*repos.trash).await, [third.id, second.id]);
+    }
+
+    #[allow(dead_code)]
+    pub async fn trash_repo_purge(repos: Arc<ContentRepositories>) {
+        let content = create_content(&repos).await;
+        let at = time::macros::datetime!(2100-03-01 12:00 UTC);
+        let hours = |hours| at + time::Duration::hours(hours);
+        let old = trash_todo(&repos, &content, hours(-2)).await;
+        let kept = trash_todo(&repos, &content, hours(-1)).await;
+
+        let purged = repos.trash.purge(hours(-1)).await.expect("Failed to purge");
+
+        assert_eq!(purged, vec![old]);
+        assert_eq!(trash_ids(&*repos.trash).await, [kept.id]);
+        assert_eq!(
+            repos.trash.purge(hours(-1)).await.expect("Failed to purge"),
+            vec![]
+        );
+    }
+
+    #[allow(dead_code)]
+    pub async fn content_repo_put_keeps_ids(repos: Arc<ContentRepositories>) {
+        let content = create_content(&repos).await;
+        let mut moved = content.todos[1].clone();
+        moved.text = "b moved".into();
+        moved.position = 0.5;
+        let at = time::macros::datetime!(2100-01-01 12:00 UTC);
+
+        repos
+            .content
+            .put(
+                Snapshot {
+                    todos: vec![moved.clone()],
+                    ..Default::default()
+                },
+                EntityIds {
+                    todos: vec![content.todos[0].id],
+                    ..Default::default()
+                },
+                at,
+            )
+            .await
+            .expect("Failed to put");
+        assert_eq!(
+            repos
+                .todo
+                .find_by_group(content.groups[0].id)
+                .await
+                .expect("Failed to find todos"),
+            vec![moved.clone()]
+        );
+        let project = repos
+            .project
+            .get(content.project.id)
+            .await
+            .expect("Failed to get project")
+            .expect("Project not found");
+        assert_eq!(project.updated_at, at);
+
+        repos
+            .content
+            .put(
+                Snapshot {
+                    todos: vec![content.todos[0].clone()],
+                    ..Default::default()
+                },
+                EntityIds::default(),
+                at,
+            )
+            .await
+            .expect("Failed to put");
+        assert_eq!(
+            repos
+                .todo
+                .find_by_group(content.groups[0].id)
+                .await
+                .expect("Failed to find todos"),
+            vec![moved, content.todos[0].clone()]
+        );
+
+        let created = repos
+            .todo
+            .create(CreateTodoData {
+                text: "c",
+                group_id: content.groups[0].id,
+            })
+            .await
+            .expect("Failed to create todo");
+        assert!(created.id > content.todos[1].id);
+    }
+
+    #[allow(dead_code)]
+    pub async fn content_repo_put_deletes_children(repos: Arc<ContentRepositories>) {
+        let content = create_content(&repos).await;
+        let at = time::macros::datetime!(2100-01-01 12:00 UTC);
+
+        repos
+            .content
+            .put(
+                Snapshot::default(),
+                EntityIds {
+                    projects: vec![content.project.id],
+                    ..Default::default()
+                },
+                at,
+            )
+            .await
+            .expect("Failed to put");
+        assert_eq!(
+            repos
+                .project
+                .get(content.project.id)
+                .await
+                .expect("Failed to get project"),
+            None
+        );
+        assert_eq!(
+            repos
+                .todo
+                .get(content.todos[0].id)
+                .await
+                .expect("Failed to get todo"),
+            None
+        );
+
+        repos
+            .content
+            .put(
+                Snapshot {
+                    projects: vec![content.project.clone()],
+                    groups: content.groups.clone(),
+                    todos: content.todos.clone(),
+                },
+                EntityIds::default(),
+                at,
+            )
+            .await
+            .expect("Failed to put");
+        assert_eq!(
+            repos
+                .project
+                .get(content.project.id)
+                .await
+                .expect("Failed to get project"),
+            Some(content.project)
+        );
+        assert_eq!(
+            repos
+                .group
+                .find_by_project(content.groups[0].project_id)
+                .await
+                .expect("Failed to find groups"),
+            content.groups
+        );
+        assert_eq!(
+            repos
+                .todo
+                .find_by_group(content.groups[0].id)
+                .await
+                .expect("Failed to find todos"),
+            content.todos
+        );
+    }
+
+    #[allow(dead_code)]
+    pub async fn content_repo_trash(repos: Arc<ContentRepositories>) {
+        let content = create_content(&repos).await;
+        let group = content.groups[0].clone();
+        let at = time::macros::datetime!(2100-01-01 12:00 UTC);
+
+        let item = repos
+            .content
+            .trash(EntityKind::Group, group.id, at)
+            .await
+            .expect("Failed to trash")
+            .expect("Group not found");
+        assert_eq!(item.deleted_at, at);
+        assert_eq!(item.entity, EntityKind::Group);
+        assert_eq!(item.entity_id, group.id);
+        assert_eq!(
+            item.entities,
+            Snapshot {
+                projects: vec![],
+                groups: vec![group.clone()],
+                todos: content.todos.clone(),
+            }
+        );
+        assert_eq!(
+            repos
+                .group
+                .get(group.id)
+                .await
+                .expect("Failed to get group"),
+            None
+        );
+        assert_eq!(
+            repos
+                .todo
+                .find_by_group(group.id)
+                .await
+                .expect("Failed to find todos"),
+            vec![]
+        );
+        let project = repos
+            .project
+            .get(content.project.id)
+            .await
+            .expect("Failed to get project")
+            .expect("Project not found");
+        assert_eq!(project.updated_at, at);
+        assert_eq!(
+            repos.trash.list().await.expect("Failed to list trash"),
+            vec![item]
+        );
+
+        assert_eq!(
+            repos
+                .content
+                .trash(EntityKind::Group, group.id, at)
+                .await
+                .expect("Failed to trash"),
+            None
+        );
+    }
+
+    #[allow(dead_code)]
+    pub async fn content_repo_restore(repos: Arc<ContentRepositories>) {
+        let content = create_content(&repos).await;
+        let at = time::macros::datetime!(2100-01-01 12:00 UTC);
+        let later = time::macros::datetime!(2100-01-02 12:00 UTC);
+
+        let item = repos
+            .content
+            .trash(EntityKind::Todo, content.todos[0].id, at)
+            .await
+            .expect("Failed to trash")
+            .expect("Todo not found");
+        let restored = repos
+            .content
+            .restore(item.clone(), later)
+            .await
+            .expect("Failed to restore");
+        assert_eq!(restored, Some(item.clone()));
+        assert_eq!(
+            repos
+                .todo
+                .find_by_group(content.groups[0].id)
+                .await
+                .expect("Failed to find todos"),
+            content.todos
+        );
+        let project = repos
+            .project
+            .get(content.project.id)
+            .await
+            .expect("Failed to get project")
+            .expect("Project not found");
+        assert_eq!(project.updated_at, later);
+        assert_eq!(
+            repos.trash.list().await.expect("Failed to list trash"),
+            vec![]
+        );
+
+        assert_eq!(
+            repos
+                .content
+                .restore(item, later)
+                .await
+                .expect("Failed to restore"),
+            None
+        );
+    }
+
+    #[allow(dead_code)]
+    pub async fn content_repo_restore_conflicts(repos: Arc<ContentRepositories>) {
+        let content = create_content(&repos).await;
+        let at = time::macros::datetime!(2100-01-01 12:00 UTC);
+        let trash = |entity, id| {
+            let repos = repos.clone();
+            async move {
+                repos
+                    .content
+                    .trash(entity, id, at)
+                    .await
+                    .expect("Failed to trash")
+                    .expect("Entity not found")
+            }
+        };
+        let restore_error = |item: TrashItem| {
+            let repos = repos.clone();
+            async move {
+                repos
+                    .content
+                    .restore(item, at)
+                    .await
+                    .expect_err("restore should fail")
+            }
+        };
+
+        let existing = trash(EntityKind::Todo, content.todos[0].id).await;
+        repos
+            .content
+            .put(
+                Snapshot {
+                    todos: vec![content.todos[0].clone()],
+                    ..Default::default()
+                },
+                EntityIds::default(),
+                at,
+            )
+            .await
+            .expect("Failed to put");
+        let error = restore_error(existing.clone()).await;
+        assert_eq!(error.kind(), crate::result::ErrorKind::Conflict);
+        assert_eq!(error.entity(), Some(("todo", content.todos[0].id)));
+
+        let orphan = trash(EntityKind::Todo, content.todos[1].id).await;
+        trash(EntityKind::Group, content.groups[0].id).await;
+        let error = restore_error(orphan.clone()).await;
+        assert_eq!(error.kind(), crate::result::ErrorKind::Conflict);
+        assert_eq!(error.entity(), Some(("todo", content.todos[1].id)));
+
+        let items = repos.trash.list().await.expect("Failed to list trash");
+        assert!(items.contains(&existing));
+        assert!(items.contains(&orphan));
+        assert_eq!(
+            repos
+                .todo
+                .get(content.todos[1].id)
+                .await
+                .expect("Failed to get todo"),
+            None
+        );
+    }
+
+    #[macro_export]
+    macro_rules! activity_repository_test {
+        ($init:expr) => {
+            $crate::activity_repository_test!($init, activity_repo_list_empty);
+            $crate::activity_repository_test!($init, activity_repo_append_and_list);
+            $crate::activity_repository_test!($init, activity_repo_list_pages);
+            $crate::activity_repository_test!($init, activity_repo_prune);
+        };
+        ($init:expr, $name:ident) => {
+            #[tokio::test]
+            async fn $name() {
+                let repo = std::sync::Arc::new($init);
+                $crate::ports::repository_tests::$name(repo).await;
+            }
+        };
+    }
+
+    fn create_activity(
+        at: OffsetDateTime,
+        project_id: u64,
+        name: Option<&str>,
+    ) -> CreateActivityData {
+        CreateActivityData {
+            at,
+            project_id,
+            entity: EntityKind::Todo,
+            entity_id: 7,
+            action: HistoryAction::UpdateTodo,
+            undone: false,
+            before: Some(serde_json::json!({ "id": 7, "text": "Todo", "position": 1.5 })),
+            after: name.map(|name| serde_json::json!({ "id": 7, "text": name, "position": 1.5 })),
+        }
+    }
+
+    async fn list_ids<R: ActivityRepository>(
+        repo: &R,
+        project_id: u64,
+        before_id: Option<u64>,
+        limit: usize,
+    ) -> Vec<u64> {
+        repo.list(project_id, before_id, limit)
+            .await
+            .expect("Failed to list activities")
+            .iter()
+            .map(|activity| activity.id)
+            .collect()
+    }
+
+    #[allow(dead_code)]
+    pub async fn activity_repo_list_empty<R: ActivityRepository>(repo: Arc<R>) {
+        assert_eq!(list_ids(&*repo, 1, None, 10).await, Vec::<u64>::new());
+    }
+
+    #[allow(dead_code)]
+    pub async fn activity_repo_append_and_list<R: ActivityRepository>(repo: Arc<R>) {
+        let at = time::macros::datetime!(2024-03-01 12:00:00.123 UTC);
+        let data = create_activity(at, 1, Some("Renamed"));
+
+        repo.append(vec![data.clone(), create_activity(at, 2, None)])
+            .await
+            .expect("Failed to append activities");
+        repo.append(vec![create_activity(at, 1, None)])
+            .await
+            .expect("Failed to append activities");
+
+        let activities = repo.list(1, None, 10).await.expect("Failed to list");
+
+        assert_eq!(activities.iter().map(|a| a.id).collect::<Vec<_>>(), [3, 1]);
+        assert_eq!(
+            activities[1],
+            Activity {
+                id: 1,
+                at,
+                project_id: 1,
+                entity: data.entity,
+                entity_id: data.entity_id,
+                action: data.action,
+                undone: false,
+                before: data.before,
+                after: data.after,
+            }
+        );
+        assert_eq!(activities[0].after, None);
+    }
+
+    #[allow(dead_code)]
+    pub async fn activity_repo_list_pages<R: ActivityRepository>(repo: Arc<R>) {
+        let at = OffsetDateTime::now_utc();
+        repo.append((0..5).map(|_| create_activity(at, 1, None)).collect())
+            .await
+            .expect("Failed to append activities");
+
+        assert_eq!(list_ids(&*repo, 1, None, 2).await, [5, 4]);
+        assert_eq!(list_ids(&*repo, 1, Some(4), 2).await, [3, 2]);
+        assert_eq!(list_ids(&*repo, 1, Some(2), 2).await, [1]);
+    }
+
+    #[allow(dead_code)]
+    pub async fn activity_repo_prune<R: ActivityRepository>(repo: Arc<R>) {
+        let at = time::macros::datetime!(2024-03-01 12:00 UTC);
+        let hours = |hours| at + time::Duration::hours(hours);
+        repo.append(vec![
+            create_activity(hours(-2), 1, None),
+            create_activity(hours(-1), 2, None),
+            create_activity(at, 1, None),
+        ])
+        .await
+        .expect("Failed to append activities");
+
+        let pruned = repo.prune(hours(-1)).await.expect("Failed to prune");
+        repo.append(vec![create_activity(hours(1), 1, None)])
+            .await
+            .expect("Failed to append activities");
+
+        assert_eq!(pruned, 1);
+        assert_eq!(list_ids(&*repo, 1, None, 10).await, [4, 3]);
+        assert_eq!(list_ids(&*repo, 2, None, 10).await, [2]);
+    }
+
+    #[macro_export]
+    macro_rules! history_repository_test {
+        ($init:expr) => {
+            $crate::history_repository_test!($init, history_repo_get_empty);
+            $crate::history_repository_test!($init, history_repo_save_and_get);
+        };
+        ($init:expr, $name:ident) => {
+            #[tokio::test]
+            async fn $name() {
+                let repo = std::sync::Arc::new($init);
+                $crate::ports::repository_tests::$name(repo).await;
+            }
+        };
+    }
+
+    #[allow(dead_code)]
+    pub async fn history_repo_get_empty<R: HistoryRepository>(repo: Arc<R>) {
+        let history = repo.get().await.expect("Failed to get history");
+
+        assert_eq!(history, History::default());
+    }
+
+    #[allow(dead_code)]
+    pub async fn history_repo_save_and_get<R: HistoryRepository>(repo: Arc<R>) {
+        use crate::history::{HistoryAction, HistoryEntry};
+
+        let at = OffsetDateTime::now_utc();
+        let project = Project {
+            id: 1,
+            name: "Project".into(),
+            created_at: at,
+            updated_at: at,
+            is_active: true,
+            archived_at: None,
+        };
+        let renamed = Project {
+            name: "Renamed".into(),
+            ..project.clone()
+        };
+        let entry = |action, before: &Project, after: &Project| {
+            HistoryEntry::new(
+                action,
+                at,
+                Snapshot {
+                    projects: vec![before.clone()],
+                    ..Default::default()
+                },
+                Snapshot {
+                    projects: vec![after.clone()],
+                    ..Default::default()
+                },
+            )
+            .expect("entry")
+        };
+        let history = History {
+            undo: vec![entry(HistoryAction::UpdateProject, &project, &renamed)],
+            redo: vec![entry(HistoryAction::UpdateProject, &renamed, &project)],
+        };
+
+        repo.save(history.clone())
+            .await
+            .expect("Failed to save history");
+        assert_eq!(repo.get().await.expect("Failed to get history"), history);
+
+        repo.save(History::default())
+            .await
+            .expect("Failed to save history");
+        assert_eq!(
+            repo.get().await.expect("Failed to get history"),
+            History::default()
+        );
+    }
+
+    /// Repositories over one storage for the `BackupRepository` suite,
+    /// which changes the storage through the project repository.
+    pub struct BackupRepositories {
+        pub project: Arc<dyn ProjectRepository>,
+        pub backup: Arc<dyn BackupRepository>,
+        /// Runs once the test is done, e.g. to remove storage files.
+        pub cleanup: Option<Box<dyn FnOnce() + Send + Sync>>,
+    }
+
+    impl Drop for BackupRepositories {
+        fn drop(&mut self) {
+            if let Some(cleanup) = self.cleanup.take() {
+                cleanup();
+            }
+        }
+    }
+
+    #[macro_export]
+    macro_rules! backup_repository_test {
+        ($init:expr) => {
+            $crate::backup_repository_test!($init, backup_repo_create_and_list);
+            $crate::backup_repository_test!($init, backup_repo_restore);
+            $crate::backup_repository_test!($init, backup_repo_restore_unknown);
+            $crate::backup_repository_test!($init, backup_repo_delete);
+            $crate::backup_repository_test!($init, backup_repo_count_writes);
+        };
+        ($init:expr, $name:ident) => {
+            #[tokio::test]
+            async fn $name() {
+                let repos = std::sync::Arc::new($init);
+                $crate::ports::repository_tests::$name(repos).await;
+            }
+        };
+    }
+
+    #[allow(dead_code)]
+    pub async fn backup_repo_create_and_list(repos: Arc<BackupRepositories>) {
+        repos
+            .project
+            .create(CreateProjectData { name: "Project" })
+            .await
+            .expect("Failed to create project");
+
+        let at = time::macros::datetime!(2024-03-01 12:00:00.250 UTC);
+        let first = repos.backup.create(at).await.expect("Failed to backup");
+        let second = repos.backup.create(at).await.expect("Failed to backup");
+
+        assert_eq!(first.created_at, at);
+        assert!(first.size > 0);
+        assert_eq!(second.id, first.id + 1);
+        assert_eq!(
+            repos.backup.list().await.expect("Failed to list backups"),
+            vec![second, first]
+        );
+    }
+
+    #[allow(dead_code)]
+    pub async fn backup_repo_restore(repos: Arc<BackupRepositories>) {
+        let kept = repos
+            .project
+            .create(CreateProjectData { name: "Kept" })
+            .await
+            .expect("Failed to create project");
+        let backup = repos
+            .backup
+            .create(OffsetDateTime::now_utc())
+            .await
+            .expect("Failed to backup");
+
+        repos
+            .project
+            .update(
+                kept.id,
+                UpdateProjectData {
+                    name: Some("Renamed"),
+                    ..Default::default()
+                },
+            )
+            .await
+            .expect("Failed to update project");
+        repos
+            .project
+            .create(CreateProjectData { name: "Dropped" })
+            .await
+            .expect("Failed to create project");
+
+        let restored = repos
+            .backup
+            .restore(backup.id)
+            .await
+            .expect("Failed to restore backup");
+
+        assert_eq!(restored, Some(backup));
+        assert_eq!(
+            repos
+                .project
+                .list(ProjectFilter::All, ProjectSort::default())
+                .await
+                .expect("Failed to list projects"),
+            vec![kept.clone()]
+        );
+
+        let created = repos
+            .project
+            .create(CreateProjectData { name: "After" })
+            .await
+            .expect("Failed to create project");
+        assert!(created.id > kept.id);
+    }
+
+    #[allow(dead_code)]
+    pub async fn backup_repo_restore_unknown(repos: Arc<BackupRepositories>) {
+        let project = repos
+            .project
+            .create(CreateProjectData { name: "Project" })
+            .await
+            .expect("Failed to create project");
+
+        let restored = repos
+            .backup
+            .restore(42)
+            .await
+            .expect("Failed to restore backup");
+
+        assert_eq!(restored, None);
+        assert_eq!(
+            repos
+                .project
+                .get(project.id)
+                .await
+                .expect("Failed to get project"),
+            Some(project)
+        );
+    }
+
+    #[allow(dead_code)]
+    pub async fn backup_repo_delete(repos: Arc<BackupRepositories>) {
+        let backup = repos
+            .backup
+            .create(OffsetDateTime::now_utc())
+            .await
+            .expect("Failed to backup");
+
+        let deleted = repos
+            .backup
+            .delete(backup.id)
+            .await
+            .expect("Failed to delete backup");
+
+        assert_eq!(deleted, Some(backup.clone()));
+        assert_eq!(
+            repos.backup.list().await.expect("Failed to list backups"),
+            vec![]
+        );
+        assert_eq!(
+            repos
+                .backup
+                .delete(backup.id)
+                .await
+                .expect("Failed to delete backup"),
+            None
+        );
+    }
+
+    #[allow(dead_code)]
+    pub async fn backup_repo_count_writes(repos: Arc<BackupRepositories>) {
+        let before = repos
+            .backup
+            .write_count()
+            .await
+            .expect("Failed to count writes");
+
+        repos
+            .project
+            .create(CreateProjectData { name: "Project" })
+            .await
+            .expect("Failed to create project");
+
+        let after = repos
+            .backup
+            .write_count()
+            .await
+            .expect("Failed to count writes");
+        assert!(after > before);
+
+        repos
+            .backup
+            .create(OffsetDateTime::now_utc())
+            .await
+            .expect("Failed to create backup");
+        assert_eq!(
+            repos
+                .backup
+                .write_count()
+                .await
+                .expect("Failed to count writes"),
+            after
+        );
+    }
 }
@@ -0,0 +1,74 @@
+//! Fractional positions for ordered siblings.
+//!
+//! Moving an item only rewrites its own position: it gets the midpoint of
+//! its new neighbours. Once neighbours get too close to split, `between`
+//! gives up and the caller renumbers all siblings with `spread`.
+
+/// Smallest gap between neighbours that is still split in two.
+const MIN_GAP: f64 = 1e-9;
+
+/// Position strictly between `before` and `after`, where `None` means
+/// the start or the end of the list.
+///
+/// Returns `None` when the neighbours are too close and siblings have to
+/// be renumbered.
+pub fn between(before: Option<f64>, after: Option<f64>) -> Option<f64> {
+    match (before, after) {
+        (None, None) => Some(1.0),
+        (Some(before), None) => Some(before.floor() + 1.0),
+        (None, Some(after)) => Some(after.ceil() - 1.0),
+        (Some(before), Some(after)) => {
+            let middle = before + (after - before) / 2.0;
+
+            if after - before < MIN_GAP || middle <= before || middle >= after {
+                None
+            } else {
+                Some(middle)
+            }
+        }
+    }
+}
+
+/// Evenly spaced positions for `n` siblings.
+pub fn spread(n: usize) -> impl Iterator<Item = f64> {
+    (1..=n).map(|i| i as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn between_ends() {
+        assert_eq!(between(None, None), Some(1.0));
+        assert_eq!(between(Some(3.0), None), Some(4.0));
+        assert_eq!(between(Some(3.5), None), Some(4.0));
+        assert_eq!(between(None, Some(1.0)), Some(0.0));
+        assert_eq!(between(None, Some(0.5)), Some(0.0));
+    }
+
+    #[test]
+    fn between_neighbours() {
+        assert_eq!(between(Some(1.0), Some(2.0)), Some(1.5));
+        assert_eq!(between(Some(-1.0), Some(0.0)), Some(-0.5));
+    }
+
+    #[test]
+    fn between_gives_up_on_small_gap() {
+        let mut after = 2.0;
+        let mut splits = 0;
+
+        while let Some(position) = between(Some(1.0), Some(after)) {
+            assert!(1.0 < position && position < after);
+            after = position;
+            splits += 1;
+        }
+
+        assert!(splits > 20, "only {} splits", splits);
+    }
+
+    #[test]
+    fn spread_is_increasing() {
+        assert_eq!(spread(3).collect::<Vec<_>>(), vec![1.0, 2.0, 3.0]);
+    }
+}
@@ -1,6 +1,26 @@
+pub mod activity;
+pub mod backup;
+pub mod content;
 pub mod fake;
+mod file_storage;
 pub mod group;
+pub mod history;
 pub mod project;
+pub mod recovery;
+mod salvage;
+pub mod settings;
+pub mod sqlite;
+pub mod todo;
+pub mod trash;
+pub mod watcher;
 
+pub use activity::ActivityRepository;
+pub use backup::BackupRepository;
+pub use content::ProjectContentRepository;
 pub use group::GroupRepository;
+pub use history::HistoryRepository;
 pub use project::ProjectRepository;
+pub use recovery::RecoveryRepository;
+pub use settings::SettingsRepository;
+pub use todo::TodoRepository;
+pub use trash::TrashRepository;
@@ -0,0 +1,209 @@
+use std::path::Path;
+
+use super::file_storage::{FileStorage, Migration, Salvaged, StorageData};
+use super::salvage;
+use crate::history::HistoryAction;
+use crate::models;
+use crate::ports;
+use crate::result::{ErrorContext, Result};
+use crate::utils::{IsSend, IsSync};
+use async_trait::async_trait;
+use blocking::unblock;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) struct Activity {
+    id: u64,
+    #[serde(with = "time::serde::iso8601")]
+    at: OffsetDateTime,
+    project_id: u64,
+    entity: models::EntityKind,
+    entity_id: u64,
+    action: HistoryAction,
+    undone: bool,
+    before: Option<serde_json::Value>,
+    after: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub(super) struct ActivityFileStorageData {
+    /// Oldest first.
+    activities: Vec<Activity>,
+    /// Largest id ever handed out, so ids of pruned activities are never reused.
+    last_id: u64,
+}
+
+impl StorageData for ActivityFileStorageData {
+    const MIGRATIONS: &'static [Migration] = &[];
+
+    fn salvage(document: &bson::Document) -> Salvaged<Self> {
+        let (activities, dropped) = salvage::items::<Activity>(document, "activities");
+        let last_id = salvage::last_id(document, activities.iter().map(|item| item.id));
+
+        Salvaged {
+            kept: activities.len(),
+            data: ActivityFileStorageData {
+                activities,
+                last_id,
+            },
+            dropped,
+        }
+    }
+}
+
+pub(super) type ActivityFileStorage = FileStorage<ActivityFileStorageData>;
+
+impl From<Activity> for models::Activity {
+    fn from(activity: Activity) -> Self {
+        models::Activity {
+            id: activity.id,
+            at: activity.at,
+            project_id: activity.project_id,
+            entity: activity.entity,
+            entity_id: activity.entity_id,
+            action: activity.action,
+            undone: activity.undone,
+            before: activity.before,
+            after: activity.after,
+        }
+    }
+}
+
+pub struct ActivityRepository {
+    file_path: std::path::PathBuf,
+}
+
+impl IsSync for ActivityRepository {}
+impl IsSend for ActivityRepository {}
+
+impl ActivityRepository {
+    pub fn new(file_path: &Path) -> Self {
+        ActivityRepository {
+            file_path: std::path::PathBuf::from(file_path),
+        }
+    }
+}
+
+#[async_trait]
+impl ports::ActivityRepository for ActivityRepository {
+    async fn append(&self, activities: Vec<ports::CreateActivityData>) -> Result<()> {
+        let file_path = self.file_path.clone();
+
+        unblock(move || {
+            let mut storage = ActivityFileStorage::open_exclusive(&file_path)
+                .context("Failed to open_exclusive storage")?;
+
+            for data in activities {
+                storage.data.last_id += 1;
+
+                storage.data.activities.push(Activity {
+                    id: storage.data.last_id,
+                    at: data.at,
+                    project_id: data.project_id,
+                    entity: data.entity,
+                    entity_id: data.entity_id,
+                    action: data.action,
+                    undone: data.undone,
+                    before: data.before,
+                    after: data.after,
+                });
+            }
+
+            storage.save().context("Failed to save storage")?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn list(
+        &self,
+        project_id: u64,
+        before_id: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<models::Activity>> {
+        let file_path = self.file_path.clone();
+
+        let data = unblock(move || {
+            ActivityFileStorage::read_data(&file_path).context("Failed to read storage")
+        })
+        .await?;
+
+        Ok(data
+            .activities
+            .into_iter()
+            .rev()
+            .filter(|a| a.project_id == project_id && before_id.is_none_or(|id| a.id < id))
+            .take(limit)
+            .map(Into::into)
+            .collect())
+    }
+
+    async fn prune(&self, before: OffsetDateTime) -> Result<usize> {
+        let file_path = self.file_path.clone();
+
+        unblock(move || {
+            let mut storage = ActivityFileStorage::open_exclusive(&file_path)
+                .context("Failed to open_exclusive storage")?;
+
+            let len = storage.data.activities.len();
+            storage.data.activities.retain(|a| a.at >= before);
+            let pruned = len - storage.data.activities.len();
+
+            if pruned > 0 {
+                storage.save().context("Failed to save storage")?;
+            }
+
+            Ok(pruned)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activity_repository_test;
+    use crate::repositories::file_storage;
+
+    struct ActivityRepositoryTest {
+        repo: ActivityRepository,
+        path: std::path::PathBuf,
+    }
+
+    impl Drop for ActivityRepositoryTest {
+        fn drop(&mut self) {
+            file_storage::remove_files(&self.path);
+        }
+    }
+
+    #[async_trait]
+    impl ports::ActivityRepository for ActivityRepositoryTest {
+        async fn append(&self, activities: Vec<ports::CreateActivityData>) -> Result<()> {
+            self.repo.append(activities).await
+        }
+
+        async fn list(
+            &self,
+            project_id: u64,
+            before_id: Option<u64>,
+            limit: usize,
+        ) -> Result<Vec<models::Activity>> {
+            self.repo.list(project_id, before_id, limit).await
+        }
+
+        async fn prune(&self, before: OffsetDateTime) -> Result<usize> {
+            self.repo.prune(before).await
+        }
+    }
+
+    activity_repository_test! {{
+        let name = format!("test_Activity_{}.bson", rand::random::<u32>());
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tmp").join(name);
+        ActivityRepositoryTest {
+            repo: ActivityRepository::new(&path),
+            path,
+        }
+    }}
+}
@@ -0,0 +1,258 @@
+use std::path::{Path, PathBuf};
+
+use super::file_storage::{self, Staged, StorageLock};
+use crate::models;
+use crate::ports;
+use crate::result::Result;
+use crate::utils::{IsSend, IsSync};
+use anyhow::Context;
+use async_trait::async_trait;
+use blocking::unblock;
+use time::OffsetDateTime;
+
+/// Picks the directory for a backup taken `at`, named by its millisecond;
+/// the id is bumped past backups taken within the same millisecond.
+pub(super) fn new_backup_dir(backups_dir: &Path, at: OffsetDateTime) -> Result<(u64, PathBuf)> {
+    std::fs::create_dir_all(backups_dir).context(format!(
+        "Failed to create backups directory {}",
+        backups_dir.display()
+    ))?;
+
+    let mut id = u64::try_from(at.unix_timestamp_nanos() / 1_000_000)
+        .context("Backup time is before 1970")?;
+    while backups_dir.join(id.to_string()).exists() {
+        id += 1;
+    }
+
+    Ok((id, backups_dir.join(id.to_string())))
+}
+
+/// Lets `write` fill `<dir>.tmp` and then renames it to `dir`, so a listed
+/// backup is always complete.
+pub(super) fn publish<F>(dir: &Path, write: F) -> Result<()>
+where
+    F: FnOnce(&Path) -> Result<()>,
+{
+    let tmp_dir = dir.with_extension("tmp");
+
+    if tmp_dir.exists() {
+        std::fs::remove_dir_all(&tmp_dir)
+            .context(format!("Failed to remove {}", tmp_dir.display()))?;
+    }
+    std::fs::create_dir_all(&tmp_dir).context(format!("Failed to create {}", tmp_dir.display()))?;
+
+    write(&tmp_dir)?;
+
+    std::fs::rename(&tmp_dir, dir).context(format!(
+        "Failed to rename {} to {}",
+        tmp_dir.display(),
+        dir.display()
+    ))?;
+
+    Ok(())
+}
+
+pub(super) fn read_backup(backups_dir: &Path, id: u64) -> Result<Option<models::Backup>> {
+    let dir = backups_dir.join(id.to_string());
+    if !dir.is_dir() {
+        return Ok(None);
+    }
+
+    let mut size = 0;
+    for entry in std::fs::read_dir(&dir).context(format!("Failed to read {}", dir.display()))? {
+        let metadata = entry
+            .and_then(|entry| entry.metadata())
+            .context(format!("Failed to read {}", dir.display()))?;
+        size += metadata.len();
+    }
+
+    let created_at = OffsetDateTime::from_unix_timestamp_nanos(i128::from(id) * 1_000_000)
+        .context(format!("Invalid backup id {}", id))?;
+
+    Ok(Some(models::Backup {
+        id,
+        created_at,
+        size,
+    }))
+}
+
+/// Backups in `backups_dir` from the newest; unfinished ones are skipped.
+pub(super) fn list_backups(backups_dir: &Path) -> Result<Vec<models::Backup>> {
+    if !backups_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut ids: Vec<u64> = Vec::new();
+    let entries = std::fs::read_dir(backups_dir)
+        .context(format!("Failed to read {}", backups_dir.display()))?;
+    for entry in entries {
+        let entry = entry.context(format!("Failed to read {}", backups_dir.display()))?;
+
+        if let Some(id) = entry.file_name().to_str().and_then(|n| n.parse().ok()) {
+            ids.push(id);
+        }
+    }
+    ids.sort_unstable_by(|a, b| b.cmp(a));
+
+    let mut backups = Vec::with_capacity(ids.len());
+    for id in ids {
+        backups.extend(read_backup(backups_dir, id)?);
+    }
+
+    Ok(backups)
+}
+
+pub(super) fn delete_backup(backups_dir: &Path, id: u64) -> Result<Option<models::Backup>> {
+    let Some(backup) = read_backup(backups_dir, id)? else {
+        return Ok(None);
+    };
+
+    let dir = backups_dir.join(id.to_string());
+    std::fs::remove_dir_all(&dir).context(format!("Failed to remove {}", dir.display()))?;
+
+    Ok(Some(backup))
+}
+
+/// Backups of the file storages: each one is a directory in `backups_dir`
+/// with a copy of every storage file that existed at the time.
+pub struct BackupRepository {
+    storage_paths: Vec<PathBuf>,
+    backups_dir: PathBuf,
+}
+
+impl IsSync for BackupRepository {}
+impl IsSend for BackupRepository {}
+
+impl BackupRepository {
+    pub fn new(storage_paths: &[PathBuf], backups_dir: &Path) -> Self {
+        BackupRepository {
+            storage_paths: storage_paths.to_vec(),
+            backups_dir: backups_dir.to_path_buf(),
+        }
+    }
+}
+
+fn file_name(path: &Path) -> Result<&std::ffi::OsStr> {
+    let name = path
+        .file_name()
+        .context(format!("Storage path {} has no file name", path.display()))?;
+
+    Ok(name)
+}
+
+#[async_trait]
+impl ports::BackupRepository for BackupRepository {
+    async fn create(&self, at: OffsetDateTime) -> Result<models::Backup> {
+        let storage_paths = self.storage_paths.clone();
+        let backups_dir = self.backups_dir.clone();
+
+        unblock(move || {
+            let _locks = storage_paths
+                .iter()
+                .map(|path| StorageLock::shared(path))
+                .collect::<Result<Vec<_>>>()?;
+
+            let (id, dir) = new_backup_dir(&backups_dir, at)?;
+            publish(&dir, |tmp_dir| {
+                for path in storage_paths.iter().filter(|path| path.exists()) {
+                    Staged::copy(path, &tmp_dir.join(file_name(path)?))?.commit()?;
+                }
+
+                Ok(())
+            })?;
+
+            let backup = read_backup(&backups_dir, id)?
+                .context("Backup disappeared after it was written")?;
+
+            Ok(backup)
+        })
+        .await
+    }
+
+    async fn list(&self) -> Result<Vec<models::Backup>> {
+        let backups_dir = self.backups_dir.clone();
+
+        unblock(move || list_backups(&backups_dir)).await
+    }
+
+    async fn restore(&self, id: u64) -> Result<Option<models::Backup>> {
+        let storage_paths = self.storage_paths.clone();
+        let backups_dir = self.backups_dir.clone();
+
+        unblock(move || {
+            let _locks = storage_paths
+                .iter()
+                .map(|path| StorageLock::exclusive(path))
+                .collect::<Result<Vec<_>>>()?;
+
+            let Some(backup) = read_backup(&backups_dir, id)? else {
+                return Ok(None);
+            };
+
+            let dir = backups_dir.join(id.to_string());
+            let mut staged = Vec::new();
+            for path in &storage_paths {
+                let source = dir.join(file_name(path)?);
+
+                if source.exists() {
+                    file_storage::check_document(&source)?;
+                    staged.push(Staged::copy(&source, path)?);
+                } else if path.exists() {
+                    // The storage did not exist at backup time, so it was empty.
+                    staged.push(Staged::empty(path)?);
+                }
+            }
+
+            Staged::commit_all(staged)?;
+
+            Ok(Some(backup))
+        })
+        .await
+    }
+
+    async fn delete(&self, id: u64) -> Result<Option<models::Backup>> {
+        let backups_dir = self.backups_dir.clone();
+
+        unblock(move || delete_backup(&backups_dir, id)).await
+    }
+
+    async fn write_count(&self) -> Result<u64> {
+        Ok(self
+            .storage_paths
+            .iter()
+            .map(|path| file_storage::save_count(path))
+            .sum())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::backup_repository_test;
+    use crate::ports::repository_tests::BackupRepositories;
+    use crate::repositories::ProjectRepository;
+
+    fn repositories() -> BackupRepositories {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tmp")
+            .join(format!("test_Backup_{}", rand::random::<u32>()));
+        let projects_path = dir.join("Projects.bson");
+        let groups_path = dir.join("Groups.bson");
+        std::fs::create_dir_all(&dir).expect("Failed to create directory");
+
+        BackupRepositories {
+            project: Arc::new(ProjectRepository::new(&projects_path)),
+            backup: Arc::new(BackupRepository::new(
+                &[projects_path, groups_path],
+                &dir.join("backups"),
+            )),
+            cleanup: Some(Box::new(move || {
+                let _ = std::fs::remove_dir_all(dir);
+            })),
+        }
+    }
+
+    backup_repository_test! {repositories()}
+}
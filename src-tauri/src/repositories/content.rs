@@ -0,0 +1,714 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use super::file_storage::Staged;
+use super::group::GroupFileStorage;
+use super::project::ProjectFileStorage;
+use super::todo::TodoFileStorage;
+use super::trash::TrashFileStorage;
+use crate::models;
+use crate::ports;
+use crate::position;
+use crate::result::{Conflict, ErrorContext, Result};
+use crate::utils::{IsSend, IsSync};
+use anyhow::Context;
+use async_trait::async_trait;
+use blocking::unblock;
+use time::OffsetDateTime;
+
+/// Projects, groups, todos and trash items of a storage loaded into memory,
+/// so the file and fake backends share one way of applying
+/// `ports::ProjectChanges`.
+pub(super) struct Tables {
+    pub projects: Vec<models::Project>,
+    pub groups: Vec<models::Group>,
+    pub todos: Vec<models::Todo>,
+    /// Oldest first.
+    pub trash: Vec<models::TrashItem>,
+    pub last_project_id: u64,
+    pub last_group_id: u64,
+    pub last_todo_id: u64,
+    pub last_trash_id: u64,
+}
+
+/// Replaces the item with the same id as `item`, or adds it.
+fn upsert<T>(items: &mut Vec<T>, item: T, id: impl Fn(&T) -> u64) {
+    match items.iter_mut().find(|other| id(other) == id(&item)) {
+        Some(other) => *other = item,
+        None => items.push(item),
+    }
+}
+
+impl Tables {
+    /// Adds a project with a group for each of `group_names`, as
+    /// `ports::ProjectContentRepository::create` does.
+    pub fn create(
+        &mut self,
+        name: String,
+        group_names: Vec<String>,
+        now: OffsetDateTime,
+    ) -> models::ProjectContent {
+        self.last_project_id += 1;
+        let project = models::Project {
+            id: self.last_project_id,
+            name,
+            created_at: now,
+            updated_at: now,
+            is_active: true,
+            archived_at: None,
+        };
+        self.projects.push(project.clone());
+
+        let positions = position::spread(group_names.len());
+        let mut groups = Vec::with_capacity(group_names.len());
+        for (name, position) in group_names.into_iter().zip(positions) {
+            self.last_group_id += 1;
+            groups.push(models::Group {
+                id: self.last_group_id,
+                name,
+                position,
+                is_opened: true,
+                project_id: project.id,
+            });
+        }
+        self.groups.extend(groups.iter().cloned());
+
+        models::ProjectContent {
+            project,
+            groups,
+            todos: Vec::new(),
+        }
+    }
+
+    /// Applies `changes` to the project; the tables are left untouched when
+    /// the changes refer to groups or todos of other projects.
+    pub fn apply(
+        &mut self,
+        project_id: u64,
+        expected: Option<models::ProjectContent>,
+        changes: ports::ProjectChanges,
+        now: OffsetDateTime,
+    ) -> Result<Option<models::ProjectContent>> {
+        let Some(content) = self.content(project_id) else {
+            return Ok(None);
+        };
+        if expected.is_some_and(|expected| expected != content) {
+            return Err(stale(project_id).into());
+        }
+        let Some(project) = self.projects.iter_mut().find(|p| p.id == project_id) else {
+            return Ok(None);
+        };
+
+        let group_ids: HashSet<u64> = self
+            .groups
+            .iter()
+            .filter(|g| g.project_id == project_id)
+            .map(|g| g.id)
+            .collect();
+        let todo_ids: HashSet<u64> = self
+            .todos
+            .iter()
+            .filter(|t| group_ids.contains(&t.group_id))
+            .map(|t| t.id)
+            .collect();
+        changes.check_ids(&group_ids, &todo_ids)?;
+
+        if let Some(name) = changes.name {
+            project.name = name;
+        }
+        project.updated_at = project.updated_at.max(now);
+
+        let mut created_ids = Vec::with_capacity(changes.create_groups.len());
+        for data in changes.create_groups {
+            self.last_group_id += 1;
+            created_ids.push(self.last_group_id);
+
+            self.groups.push(models::Group {
+                id: self.last_group_id,
+                name: data.name,
+                position: data.position,
+                is_opened: data.is_opened,
+                project_id,
+            });
+        }
+
+        for data in changes.update_groups {
+            if let Some(group) = self.groups.iter_mut().find(|g| g.id == data.id) {
+                group.name = data.name;
+                group.position = data.position;
+                group.is_opened = data.is_opened;
+            }
+        }
+
+        let group_id = |group| match group {
+            ports::GroupRef::Stored(id) => id,
+            ports::GroupRef::Created(index) => created_ids[index],
+        };
+
+        for data in changes.create_todos {
+            self.last_todo_id += 1;
+
+            self.todos.push(models::Todo {
+                id: self.last_todo_id,
+                text: data.text,
+                position: data.position,
+                created_at: now,
+                updated_at: now,
+                is_done: data.is_done,
+                done_at: data.is_done.then_some(now),
+                group_id: group_id(data.group),
+            });
+        }
+
+        for data in changes.update_todos {
+            if let Some(todo) = self.todos.iter_mut().find(|t| t.id == data.id) {
+                todo.text = data.text;
+                todo.position = data.position;
+                todo.is_done = data.is_done;
+                todo.done_at = if data.is_done {
+                    todo.done_at.or(Some(now))
+                } else {
+                    None
+                };
+                todo.updated_at = now;
+                todo.group_id = group_id(data.group);
+            }
+        }
+
+        self.todos.retain(|t| {
+            !changes.delete_todos.contains(&t.id) && !changes.delete_groups.contains(&t.group_id)
+        });
+        self.groups
+            .retain(|g| !changes.delete_groups.contains(&g.id));
+
+        Ok(self.content(project_id))
+    }
+
+    /// Stores `entities`, deletes the `deleted` ones with their children
+    /// and raises `updated_at` of the projects that changed, as
+    /// `ports::ProjectContentRepository::put` does.
+    pub fn put(
+        &mut self,
+        entities: models::Snapshot,
+        deleted: &ports::EntityIds,
+        at: OffsetDateTime,
+    ) {
+        let stored_project_ids = entities.projects.iter().map(|p| p.id).collect::<Vec<_>>();
+        let stored_todo_ids = entities.todos.iter().map(|t| t.id).collect::<Vec<_>>();
+        let mut project_ids = entities
+            .groups
+            .iter()
+            .map(|g| g.project_id)
+            .collect::<HashSet<_>>();
+
+        for project in entities.projects {
+            self.last_project_id = self.last_project_id.max(project.id);
+            upsert(&mut self.projects, project, |p| p.id);
+        }
+        for group in entities.groups {
+            self.last_group_id = self.last_group_id.max(group.id);
+            upsert(&mut self.groups, group, |g| g.id);
+        }
+        for todo in entities.todos {
+            self.last_todo_id = self.last_todo_id.max(todo.id);
+            upsert(&mut self.todos, todo, |t| t.id);
+        }
+
+        // Looked up before the deletes, which take the groups along.
+        let todo_group_ids = self
+            .todos
+            .iter()
+            .filter(|t| stored_todo_ids.contains(&t.id) || deleted.todos.contains(&t.id))
+            .map(|t| t.group_id)
+            .collect::<Vec<_>>();
+        project_ids.extend(
+            self.groups
+                .iter()
+                .filter(|g| deleted.groups.contains(&g.id) || todo_group_ids.contains(&g.id))
+                .map(|g| g.project_id),
+        );
+
+        let deleted_group_ids = self
+            .groups
+            .iter()
+            .filter(|g| deleted.groups.contains(&g.id) || deleted.projects.contains(&g.project_id))
+            .map(|g| g.id)
+            .collect::<HashSet<_>>();
+        self.todos
+            .retain(|t| !deleted.todos.contains(&t.id) && !deleted_group_ids.contains(&t.group_id));
+        self.groups.retain(|g| !deleted_group_ids.contains(&g.id));
+        self.projects.retain(|p| !deleted.projects.contains(&p.id));
+
+        for project in &mut self.projects {
+            if project_ids.contains(&project.id) && !stored_project_ids.contains(&project.id) {
+                project.updated_at = project.updated_at.max(at);
+            }
+        }
+    }
+
+    /// Stores the entities of the item and removes it, as
+    /// `ports::ProjectContentRepository::restore` does.
+    pub fn restore(
+        &mut self,
+        item: models::TrashItem,
+        at: OffsetDateTime,
+    ) -> Result<Option<models::TrashItem>> {
+        let Some(index) = self.trash.iter().position(|other| other.id == item.id) else {
+            return Ok(None);
+        };
+        ports::check_restorable(&item, |entity, id| {
+            Ok(match entity {
+                models::EntityKind::Project => self.projects.iter().any(|p| p.id == id),
+                models::EntityKind::Group => self.groups.iter().any(|g| g.id == id),
+                models::EntityKind::Todo => self.todos.iter().any(|t| t.id == id),
+            })
+        })?;
+        self.trash.remove(index);
+        self.put(item.entities.clone(), &ports::EntityIds::default(), at);
+
+        Ok(Some(item))
+    }
+
+    /// Moves the entity with its children into a new trash item, as
+    /// `ports::ProjectContentRepository::trash` does.
+    pub fn trash(
+        &mut self,
+        entity: models::EntityKind,
+        id: u64,
+        at: OffsetDateTime,
+    ) -> Option<models::TrashItem> {
+        let (entities, deleted) = match entity {
+            models::EntityKind::Project => {
+                let content = self.content(id)?;
+                let entities = models::Snapshot {
+                    projects: vec![content.project],
+                    groups: content.groups,
+                    todos: content.todos,
+                };
+                let deleted = ports::EntityIds {
+                    projects: vec![id],
+                    ..Default::default()
+                };
+
+                (entities, deleted)
+            }
+            models::EntityKind::Group => {
+                let group = self.groups.iter().find(|g| g.id == id)?.clone();
+                let mut todos = self
+                    .todos
+                    .iter()
+                    .filter(|t| t.group_id == id)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                todos.sort_by(|a, b| a.position.total_cmp(&b.position).then(a.id.cmp(&b.id)));
+                let entities = models::Snapshot {
+                    groups: vec![group],
+                    todos,
+                    ..Default::default()
+                };
+                let deleted = ports::EntityIds {
+                    groups: vec![id],
+                    ..Default::default()
+                };
+
+                (entities, deleted)
+            }
+            models::EntityKind::Todo => {
+                let todo = self.todos.iter().find(|t| t.id == id)?.clone();
+                let entities = models::Snapshot {
+                    todos: vec![todo],
+                    ..Default::default()
+                };
+                let deleted = ports::EntityIds {
+                    todos: vec![id],
+                    ..Default::default()
+                };
+
+                (entities, deleted)
+            }
+        };
+
+        // Raises `updated_at` of the project a group or todo leaves.
+        self.put(models::Snapshot::default(), &deleted, at);
+
+        self.last_trash_id += 1;
+        let item = models::TrashItem {
+            id: self.last_trash_id,
+            deleted_at: at,
+            entity,
+            entity_id: id,
+            entities,
+        };
+        self.trash.push(item.clone());
+
+        Some(item)
+    }
+
+    pub fn content(&self, project_id: u64) -> Option<models::ProjectContent> {
+        let project = self.projects.iter().find(|p| p.id == project_id)?;
+
+        let mut groups = self
+            .groups
+            .iter()
+            .filter(|g| g.project_id == project_id)
+            .cloned()
+            .collect::<Vec<_>>();
+        groups.sort_by(|a, b| a.position.total_cmp(&b.position).then(a.id.cmp(&b.id)));
+
+        let mut todos = Vec::new();
+        for group in &groups {
+            let start = todos.len();
+            todos.extend(
+                self.todos
+                    .iter()
+                    .filter(|t| t.group_id == group.id)
+                    .cloned(),
+            );
+            todos[start..].sort_by(|a, b| a.position.total_cmp(&b.position).then(a.id.cmp(&b.id)));
+        }
+
+        Some(models::ProjectContent {
+            project: project.clone(),
+            groups,
+            todos,
+        })
+    }
+}
+
+/// Fails `ProjectContentRepository::apply` when the stored content is not
+/// the one the changes were planned against.
+pub(super) fn stale(project_id: u64) -> Conflict {
+    Conflict {
+        entity: "project",
+        id: project_id,
+        reason: "changed since the changes were planned",
+    }
+}
+
+/// Applies project changes across the project, group, todo and trash files.
+///
+/// All four files stay locked while the changes are applied and are then
+/// committed together by `Staged::commit_all`: other writers never see half
+/// of the changes, and neither does anyone after a crash.
+pub struct ProjectContentRepository {
+    projects_path: std::path::PathBuf,
+    groups_path: std::path::PathBuf,
+    todos_path: std::path::PathBuf,
+    trash_path: std::path::PathBuf,
+}
+
+impl IsSync for ProjectContentRepository {}
+impl IsSend for ProjectContentRepository {}
+
+impl ProjectContentRepository {
+    pub fn new(
+        projects_path: &Path,
+        groups_path: &Path,
+        todos_path: &Path,
+        trash_path: &Path,
+    ) -> Self {
+        ProjectContentRepository {
+            projects_path: projects_path.to_path_buf(),
+            groups_path: groups_path.to_path_buf(),
+            todos_path: todos_path.to_path_buf(),
+            trash_path: trash_path.to_path_buf(),
+        }
+    }
+
+    /// Runs `f` on the tables of all four files and saves them unless it
+    /// returns `None`; only the files whose table changed are written.
+    async fn write<T, F>(&self, f: F) -> Result<Option<T>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Tables) -> Result<Option<T>> + Send + 'static,
+    {
+        let projects_path = self.projects_path.clone();
+        let groups_path = self.groups_path.clone();
+        let todos_path = self.todos_path.clone();
+        let trash_path = self.trash_path.clone();
+
+        unblock(move || {
+            let mut projects = ProjectFileStorage::open_exclusive(&projects_path)
+                .context("Failed to open_exclusive project storage")?;
+            let mut groups = GroupFileStorage::open_exclusive(&groups_path)
+                .context("Failed to open_exclusive group storage")?;
+            let mut todos = TodoFileStorage::open_exclusive(&todos_path)
+                .context("Failed to open_exclusive todo storage")?;
+            let mut trash = TrashFileStorage::open_exclusive(&trash_path)
+                .context("Failed to open_exclusive trash storage")?;
+
+            let mut tables = Tables {
+                projects: projects
+                    .data
+                    .projects
+                    .iter()
+                    .cloned()
+                    .map(Into::into)
+                    .collect(),
+                groups: groups.data.groups.iter().cloned().map(Into::into).collect(),
+                todos: todos.data.todos.iter().cloned().map(Into::into).collect(),
+                trash: trash.data.items.iter().cloned().map(Into::into).collect(),
+                last_project_id: projects.data.last_id,
+                last_group_id: groups.data.last_id,
+                last_todo_id: todos.data.last_id,
+                last_trash_id: trash.data.last_id,
+            };
+            let projects_before = tables.projects.clone();
+            let groups_before = tables.groups.clone();
+            let todos_before = tables.todos.clone();
+            let trash_before = tables.trash.clone();
+
+            let Some(result) = f(&mut tables)? else {
+                return Ok(None);
+            };
+
+            // The last ids still in the storages are the ones read.
+            let mut staged = Vec::new();
+            if tables.todos != todos_before || tables.last_todo_id != todos.data.last_id {
+                todos.data.todos = tables.todos.into_iter().map(Into::into).collect();
+                todos.data.last_id = tables.last_todo_id;
+                staged.push(todos.stage().context("Failed to save todo storage")?);
+            }
+            if tables.groups != groups_before || tables.last_group_id != groups.data.last_id {
+                groups.data.groups = tables.groups.into_iter().map(Into::into).collect();
+                groups.data.last_id = tables.last_group_id;
+                staged.push(groups.stage().context("Failed to save group storage")?);
+            }
+            if tables.projects != projects_before || tables.last_project_id != projects.data.last_id
+            {
+                projects.data.projects = tables.projects.into_iter().map(Into::into).collect();
+                projects.data.last_id = tables.last_project_id;
+                staged.push(projects.stage().context("Failed to save project storage")?);
+            }
+            if tables.trash != trash_before || tables.last_trash_id != trash.data.last_id {
+                trash.data.items = tables.trash.into_iter().map(Into::into).collect();
+                trash.data.last_id = tables.last_trash_id;
+                staged.push(trash.stage().context("Failed to save trash storage")?);
+            }
+            Staged::commit_all(staged).context("Failed to save storage")?;
+
+            Ok(Some(result))
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl ports::ProjectContentRepository for ProjectContentRepository {
+    async fn create(
+        &self,
+        project: ports::CreateProjectData<'_>,
+        group_names: &[String],
+    ) -> Result<models::ProjectContent> {
+        let now = OffsetDateTime::now_utc();
+        let name = project.name.to_string();
+        let group_names = group_names.to_vec();
+
+        let content = self
+            .write(move |tables| Ok(Some(tables.create(name, group_names, now))))
+            .await?;
+
+        Ok(content.context("Failed to create project")?)
+    }
+
+    async fn get(&self, project_id: u64) -> Result<Option<models::ProjectContent>> {
+        let projects_path = self.projects_path.clone();
+        let groups_path = self.groups_path.clone();
+        let todos_path = self.todos_path.clone();
+
+        unblock(move || {
+            // All three stay locked until the content is put together, so it
+            // is read as of one commit.
+            let (projects, _projects_lock) = ProjectFileStorage::read_locked(&projects_path)
+                .context("Failed to read project storage")?;
+            let (groups, _groups_lock) = GroupFileStorage::read_locked(&groups_path)
+                .context("Failed to read group storage")?;
+            let (todos, _todos_lock) =
+                TodoFileStorage::read_locked(&todos_path).context("Failed to read todo storage")?;
+
+            // The trash file is not read, the content does not need it.
+            let tables = Tables {
+                projects: projects.projects.into_iter().map(Into::into).collect(),
+                groups: groups.groups.into_iter().map(Into::into).collect(),
+                todos: todos.todos.into_iter().map(Into::into).collect(),
+                trash: Vec::new(),
+                last_project_id: projects.last_id,
+                last_group_id: groups.last_id,
+                last_todo_id: todos.last_id,
+                last_trash_id: 0,
+            };
+
+            Ok(tables.content(project_id))
+        })
+        .await
+    }
+
+    async fn apply(
+        &self,
+        project_id: u64,
+        expected: Option<models::ProjectContent>,
+        changes: ports::ProjectChanges,
+    ) -> Result<Option<models::ProjectContent>> {
+        let now = OffsetDateTime::now_utc();
+
+        self.write(move |tables| tables.apply(project_id, expected, changes, now))
+            .await
+    }
+
+    async fn trash(
+        &self,
+        entity: models::EntityKind,
+        id: u64,
+        at: OffsetDateTime,
+    ) -> Result<Option<models::TrashItem>> {
+        self.write(move |tables| Ok(tables.trash(entity, id, at)))
+            .await
+    }
+
+    async fn put(
+        &self,
+        entities: models::Snapshot,
+        deleted: ports::EntityIds,
+        at: OffsetDateTime,
+    ) -> Result<()> {
+        self.write(move |tables| {
+            tables.put(entities, &deleted, at);
+
+            Ok(Some(()))
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    async fn restore(
+        &self,
+        item: models::TrashItem,
+        at: OffsetDateTime,
+    ) -> Result<Option<models::TrashItem>> {
+        self.write(move |tables| tables.restore(item, at)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::ports::repository_tests::ContentRepositories;
+    use crate::project_content_repository_test;
+    use crate::repositories::{
+        file_storage, GroupRepository, ProjectRepository, TodoRepository, TrashRepository,
+    };
+
+    fn paths() -> [std::path::PathBuf; 4] {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tmp");
+        let suffix = rand::random::<u32>();
+
+        ["Projects", "Groups", "Todos", "Trash"]
+            .map(|name| dir.join(format!("test_Content_{}_{}.bson", name, suffix)))
+    }
+
+    fn repositories() -> ContentRepositories {
+        repositories_at(paths())
+    }
+
+    fn repositories_at(paths: [std::path::PathBuf; 4]) -> ContentRepositories {
+        ContentRepositories {
+            project: Arc::new(ProjectRepository::new(&paths[0])),
+            group: Arc::new(GroupRepository::new(&paths[1])),
+            todo: Arc::new(TodoRepository::new(&paths[2])),
+            trash: Arc::new(TrashRepository::new(&paths[3])),
+            content: Arc::new(ProjectContentRepository::new(
+                &paths[0], &paths[1], &paths[2], &paths[3],
+            )),
+            cleanup: Some(Box::new(move || {
+                for path in &paths {
+                    file_storage::remove_files(path);
+                }
+            })),
+        }
+    }
+
+    project_content_repository_test! {repositories()}
+
+    #[tokio::test]
+    async fn apply_writes_only_changed_files() {
+        let paths = paths();
+        let repos = repositories_at(paths.clone());
+        let content = repos
+            .content
+            .create(
+                ports::CreateProjectData { name: "Project" },
+                &["Group".into()],
+            )
+            .await
+            .expect("Failed to create project");
+        let saves = || paths.clone().map(|path| file_storage::save_count(&path));
+        let before = saves();
+
+        repos
+            .content
+            .apply(
+                content.project.id,
+                None,
+                ports::ProjectChanges {
+                    create_todos: vec![ports::NewTodoData {
+                        text: "Todo".into(),
+                        is_done: false,
+                        group: ports::GroupRef::Stored(content.groups[0].id),
+                        position: 1.0,
+                    }],
+                    ..Default::default()
+                },
+            )
+            .await
+            .expect("Failed to apply changes");
+
+        let after = saves();
+        // Projects for `updated_at`, and todos.
+        assert_eq!(after[0], before[0] + 1);
+        assert_eq!(after[1], before[1]);
+        assert_eq!(after[2], before[2] + 1);
+        assert_eq!(after[3], before[3]);
+    }
+
+    #[tokio::test]
+    async fn get_reads_unversioned_files() {
+        let paths = paths();
+        let now = OffsetDateTime::now_utc();
+        let project = models::Project {
+            id: 1,
+            name: "Project".into(),
+            created_at: now,
+            updated_at: now,
+            is_active: true,
+            archived_at: None,
+        };
+        let projects = bson::doc! { "projects": [bson::to_bson(&project).unwrap()] };
+        let groups = bson::doc! {
+            "groups": [{
+                "id": 2_i64,
+                "name": "Group",
+                "position": 1_i32,
+                "is_opened": true,
+                "project_id": 1_i64,
+            }],
+        };
+        for (path, legacy) in paths.iter().zip([projects, groups]) {
+            std::fs::write(path, bson::to_vec(&legacy).unwrap()).expect("Failed to write file");
+        }
+        let repos = repositories_at(paths);
+
+        let content = repos
+            .content
+            .get(project.id)
+            .await
+            .expect("Failed to get content")
+            .expect("Project not found");
+
+        assert_eq!(content.project, project);
+        assert_eq!(content.groups.len(), 1);
+        assert_eq!(content.groups[0].position, 1.0);
+    }
+}
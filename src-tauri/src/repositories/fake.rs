@@ -1,2 +1,20 @@
+mod activity;
+mod backup;
+mod content;
+mod group;
+mod history;
 mod project;
+mod recovery;
+mod settings;
+mod todo;
+mod trash;
+pub use activity::FakeActivityRepository;
+pub use backup::FakeBackupRepository;
+pub use content::FakeProjectContentRepository;
+pub use group::FakeGroupRepository;
+pub use history::FakeHistoryRepository;
 pub use project::FakeProjectRepository;
+pub use recovery::FakeRecoveryRepository;
+pub use settings::FakeSettingsRepository;
+pub use todo::FakeTodoRepository;
+pub use trash::FakeTrashRepository;
@@ -0,0 +1,98 @@
+use crate::models;
+use crate::ports;
+use crate::result::Result;
+use crate::utils::{IsSend, IsSync};
+use async_trait::async_trait;
+use tauri::async_runtime::RwLock;
+use time::OffsetDateTime;
+
+struct FakeActivityStorage {
+    /// Oldest first.
+    activities: Vec<models::Activity>,
+    last_id: u64,
+}
+
+pub struct FakeActivityRepository {
+    storage: RwLock<FakeActivityStorage>,
+}
+
+impl IsSync for FakeActivityRepository {}
+impl IsSend for FakeActivityRepository {}
+
+impl FakeActivityRepository {
+    pub const fn new() -> Self {
+        FakeActivityRepository {
+            storage: RwLock::const_new(FakeActivityStorage {
+                activities: Vec::new(),
+                last_id: 0,
+            }),
+        }
+    }
+}
+
+impl Default for FakeActivityRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl ports::ActivityRepository for FakeActivityRepository {
+    async fn append(&self, activities: Vec<ports::CreateActivityData>) -> Result<()> {
+        let mut storage = self.storage.write().await;
+
+        for data in activities {
+            storage.last_id += 1;
+            let id = storage.last_id;
+
+            storage.activities.push(models::Activity {
+                id,
+                at: data.at,
+                project_id: data.project_id,
+                entity: data.entity,
+                entity_id: data.entity_id,
+                action: data.action,
+                undone: data.undone,
+                before: data.before,
+                after: data.after,
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn list(
+        &self,
+        project_id: u64,
+        before_id: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<models::Activity>> {
+        let storage = self.storage.read().await;
+
+        Ok(storage
+            .activities
+            .iter()
+            .rev()
+            .filter(|a| a.project_id == project_id && before_id.is_none_or(|id| a.id < id))
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn prune(&self, before: OffsetDateTime) -> Result<usize> {
+        let mut storage = self.storage.write().await;
+
+        let len = storage.activities.len();
+        storage.activities.retain(|a| a.at >= before);
+
+        Ok(len - storage.activities.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activity_repository_test;
+
+    activity_repository_test! {FakeActivityRepository::new()}
+}
@@ -0,0 +1,99 @@
+use crate::models;
+use crate::ports;
+use crate::result::Result;
+use crate::utils::{IsSend, IsSync};
+use async_trait::async_trait;
+use tauri::async_runtime::RwLock;
+use time::OffsetDateTime;
+
+struct FakeBackupStorage {
+    backups: Vec<models::Backup>,
+    restored: Vec<u64>,
+    writes: u64,
+}
+
+/// Keeps only the list of backups; restoring one records its id but
+/// leaves the other fakes as they are.
+pub struct FakeBackupRepository {
+    storage: RwLock<FakeBackupStorage>,
+}
+
+impl IsSync for FakeBackupRepository {}
+impl IsSend for FakeBackupRepository {}
+
+impl FakeBackupRepository {
+    pub const fn new() -> Self {
+        FakeBackupRepository {
+            storage: RwLock::const_new(FakeBackupStorage {
+                backups: Vec::new(),
+                restored: Vec::new(),
+                writes: 0,
+            }),
+        }
+    }
+
+    /// Pretends that `count` more writes hit the storage.
+    pub async fn add_writes(&self, count: u64) {
+        self.storage.write().await.writes += count;
+    }
+
+    /// Ids of the restored backups, in restore order.
+    pub async fn restored(&self) -> Vec<u64> {
+        self.storage.read().await.restored.clone()
+    }
+}
+
+impl Default for FakeBackupRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl ports::BackupRepository for FakeBackupRepository {
+    async fn create(&self, at: OffsetDateTime) -> Result<models::Backup> {
+        let mut storage = self.storage.write().await;
+
+        let mut id = (at.unix_timestamp_nanos() / 1_000_000) as u64;
+        while storage.backups.iter().any(|b| b.id == id) {
+            id += 1;
+        }
+
+        let backup = models::Backup {
+            id,
+            created_at: at,
+            size: 1,
+        };
+        storage.backups.push(backup.clone());
+        storage.backups.sort_by_key(|b| std::cmp::Reverse(b.id));
+
+        Ok(backup)
+    }
+
+    async fn list(&self) -> Result<Vec<models::Backup>> {
+        Ok(self.storage.read().await.backups.clone())
+    }
+
+    async fn restore(&self, id: u64) -> Result<Option<models::Backup>> {
+        let mut storage = self.storage.write().await;
+
+        let backup = storage.backups.iter().find(|b| b.id == id).cloned();
+        if backup.is_some() {
+            storage.restored.push(id);
+        }
+
+        Ok(backup)
+    }
+
+    async fn delete(&self, id: u64) -> Result<Option<models::Backup>> {
+        let mut storage = self.storage.write().await;
+
+        let index = storage.backups.iter().position(|b| b.id == id);
+
+        Ok(index.map(|index| storage.backups.remove(index)))
+    }
+
+    async fn write_count(&self) -> Result<u64> {
+        Ok(self.storage.read().await.writes)
+    }
+}
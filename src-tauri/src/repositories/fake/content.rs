@@ -0,0 +1,193 @@
+use std::sync::Arc;
+
+use super::{FakeGroupRepository, FakeProjectRepository, FakeTodoRepository, FakeTrashRepository};
+use crate::models;
+use crate::ports;
+use crate::repositories::content::Tables;
+use crate::result::Result;
+use crate::utils::{IsSend, IsSync};
+use anyhow::Context;
+use async_trait::async_trait;
+use time::OffsetDateTime;
+
+/// Applies project changes to the storages of the other fakes, holding
+/// all four of them while it does.
+pub struct FakeProjectContentRepository {
+    project: Arc<FakeProjectRepository>,
+    group: Arc<FakeGroupRepository>,
+    todo: Arc<FakeTodoRepository>,
+    trash: Arc<FakeTrashRepository>,
+}
+
+impl IsSync for FakeProjectContentRepository {}
+impl IsSend for FakeProjectContentRepository {}
+
+impl FakeProjectContentRepository {
+    pub fn new(
+        project: Arc<FakeProjectRepository>,
+        group: Arc<FakeGroupRepository>,
+        todo: Arc<FakeTodoRepository>,
+        trash: Arc<FakeTrashRepository>,
+    ) -> Self {
+        FakeProjectContentRepository {
+            project,
+            group,
+            todo,
+            trash,
+        }
+    }
+}
+
+impl FakeProjectContentRepository {
+    /// Runs `f` on the tables of the four fakes and stores them back
+    /// unless it returns `None`.
+    async fn write<T, F>(&self, f: F) -> Result<Option<T>>
+    where
+        F: FnOnce(&mut Tables) -> Result<Option<T>>,
+    {
+        let mut projects = self.project.storage.write().await;
+        let mut groups = self.group.storage.write().await;
+        let mut todos = self.todo.storage.write().await;
+        let mut trash = self.trash.storage.write().await;
+
+        let mut tables = Tables {
+            projects: projects.projects.iter().cloned().map(Into::into).collect(),
+            groups: groups.groups.iter().cloned().map(Into::into).collect(),
+            todos: todos.todos.iter().cloned().map(Into::into).collect(),
+            trash: trash.items.clone(),
+            last_project_id: projects.last_id,
+            last_group_id: groups.last_id,
+            last_todo_id: todos.last_id,
+            last_trash_id: trash.last_id,
+        };
+
+        let Some(result) = f(&mut tables)? else {
+            return Ok(None);
+        };
+
+        projects.projects = tables.projects.into_iter().map(Into::into).collect();
+        projects.last_id = tables.last_project_id;
+        groups.groups = tables.groups.into_iter().map(Into::into).collect();
+        groups.last_id = tables.last_group_id;
+        todos.todos = tables.todos.into_iter().map(Into::into).collect();
+        todos.last_id = tables.last_todo_id;
+        trash.items = tables.trash;
+        trash.last_id = tables.last_trash_id;
+
+        Ok(Some(result))
+    }
+}
+
+#[async_trait]
+impl ports::ProjectContentRepository for FakeProjectContentRepository {
+    async fn create(
+        &self,
+        project: ports::CreateProjectData<'_>,
+        group_names: &[String],
+    ) -> Result<models::ProjectContent> {
+        let now = OffsetDateTime::now_utc();
+        let name = project.name.to_string();
+        let group_names = group_names.to_vec();
+
+        let content = self
+            .write(|tables| Ok(Some(tables.create(name, group_names, now))))
+            .await?;
+
+        Ok(content.context("Failed to create project")?)
+    }
+
+    async fn get(&self, project_id: u64) -> Result<Option<models::ProjectContent>> {
+        let projects = self.project.storage.read().await;
+        let groups = self.group.storage.read().await;
+        let todos = self.todo.storage.read().await;
+
+        // The trash is not read, the content does not need it.
+        let tables = Tables {
+            projects: projects.projects.iter().cloned().map(Into::into).collect(),
+            groups: groups.groups.iter().cloned().map(Into::into).collect(),
+            todos: todos.todos.iter().cloned().map(Into::into).collect(),
+            trash: Vec::new(),
+            last_project_id: projects.last_id,
+            last_group_id: groups.last_id,
+            last_todo_id: todos.last_id,
+            last_trash_id: 0,
+        };
+
+        Ok(tables.content(project_id))
+    }
+
+    async fn apply(
+        &self,
+        project_id: u64,
+        expected: Option<models::ProjectContent>,
+        changes: ports::ProjectChanges,
+    ) -> Result<Option<models::ProjectContent>> {
+        let now = OffsetDateTime::now_utc();
+
+        self.write(|tables| tables.apply(project_id, expected, changes, now))
+            .await
+    }
+
+    async fn trash(
+        &self,
+        entity: models::EntityKind,
+        id: u64,
+        at: OffsetDateTime,
+    ) -> Result<Option<models::TrashItem>> {
+        self.write(|tables| Ok(tables.trash(entity, id, at))).await
+    }
+
+    async fn put(
+        &self,
+        entities: models::Snapshot,
+        deleted: ports::EntityIds,
+        at: OffsetDateTime,
+    ) -> Result<()> {
+        self.write(|tables| {
+            tables.put(entities, &deleted, at);
+
+            Ok(Some(()))
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    async fn restore(
+        &self,
+        item: models::TrashItem,
+        at: OffsetDateTime,
+    ) -> Result<Option<models::TrashItem>> {
+        self.write(|tables| tables.restore(item, at)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports::repository_tests::ContentRepositories;
+    use crate::project_content_repository_test;
+
+    fn repositories() -> ContentRepositories {
+        let project = Arc::new(FakeProjectRepository::new());
+        let group = Arc::new(FakeGroupRepository::new());
+        let todo = Arc::new(FakeTodoRepository::new());
+        let trash = Arc::new(FakeTrashRepository::new());
+
+        ContentRepositories {
+            content: Arc::new(FakeProjectContentRepository::new(
+                project.clone(),
+                group.clone(),
+                todo.clone(),
+                trash.clone(),
+            )),
+            project,
+            group,
+            todo,
+            trash,
+            cleanup: None,
+        }
+    }
+
+    project_content_repository_test! {repositories()}
+}
@@ -0,0 +1,144 @@
+use crate::models;
+use crate::ports;
+use crate::position;
+use crate::result::Result;
+use crate::utils::{IsSend, IsSync};
+use async_trait::async_trait;
+use tauri::async_runtime::RwLock;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Group {
+    id: u64,
+    name: String,
+    position: f64,
+    is_opened: bool,
+    project_id: u64,
+}
+
+impl From<Group> for models::Group {
+    fn from(group: Group) -> Self {
+        models::Group {
+            id: group.id,
+            name: group.name,
+            position: group.position,
+            is_opened: group.is_opened,
+            project_id: group.project_id,
+        }
+    }
+}
+
+impl From<models::Group> for Group {
+    fn from(group: models::Group) -> Self {
+        Group {
+            id: group.id,
+            name: group.name,
+            position: group.position,
+            is_opened: group.is_opened,
+            project_id: group.project_id,
+        }
+    }
+}
+
+pub(super) struct FakeGroupStorage {
+    pub(super) groups: Vec<Group>,
+    pub(super) last_id: u64,
+}
+
+pub struct FakeGroupRepository {
+    pub(super) storage: RwLock<FakeGroupStorage>,
+}
+
+impl IsSync for FakeGroupRepository {}
+impl IsSend for FakeGroupRepository {}
+
+impl FakeGroupRepository {
+    pub const fn new() -> Self {
+        FakeGroupRepository {
+            storage: RwLock::const_new(FakeGroupStorage {
+                groups: Vec::new(),
+                last_id: 0,
+            }),
+        }
+    }
+}
+
+impl Default for FakeGroupRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl ports::GroupRepository for FakeGroupRepository {
+    async fn create(&self, data: ports::CreateGroupData<'_>) -> Result<models::Group> {
+        let mut storage = self.storage.write().await;
+        storage.last_id += 1;
+
+        let group = Group {
+            id: storage.last_id,
+            name: data.name.to_string(),
+            position: position::between(
+                storage
+                    .groups
+                    .iter()
+                    .filter(|g| g.project_id == data.project_id)
+                    .map(|g| g.position)
+                    .reduce(f64::max),
+                None,
+            )
+            .unwrap_or_default(),
+            is_opened: true,
+            project_id: data.project_id,
+        };
+
+        storage.groups.push(group.clone());
+
+        Ok(group.into())
+    }
+
+    async fn get(&self, id: u64) -> Result<Option<models::Group>> {
+        let storage = self.storage.read().await;
+        let item = storage.groups.iter().find(|g| g.id == id);
+
+        Ok(item.cloned().map(Into::into))
+    }
+
+    async fn find_by_project(&self, project_id: u64) -> Result<Vec<models::Group>> {
+        let storage = self.storage.read().await;
+
+        let mut groups = storage
+            .groups
+            .iter()
+            .filter(|g| g.project_id == project_id)
+            .cloned()
+            .map(Into::into)
+            .collect::<Vec<models::Group>>();
+
+        groups.sort_by(|a, b| a.position.total_cmp(&b.position));
+
+        Ok(groups)
+    }
+
+    async fn list(&self) -> Result<Vec<models::Group>> {
+        let storage = self.storage.read().await;
+
+        let mut groups = storage
+            .groups
+            .iter()
+            .cloned()
+            .map(Into::into)
+            .collect::<Vec<models::Group>>();
+
+        groups.sort_by_key(|g| g.id);
+
+        Ok(groups)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group_repository_test;
+
+    group_repository_test! {FakeGroupRepository::new()}
+}
@@ -0,0 +1,51 @@
+use crate::history::History;
+use crate::ports;
+use crate::result::Result;
+use crate::utils::{IsSend, IsSync};
+use async_trait::async_trait;
+use tauri::async_runtime::RwLock;
+
+pub struct FakeHistoryRepository {
+    storage: RwLock<History>,
+}
+
+impl IsSync for FakeHistoryRepository {}
+impl IsSend for FakeHistoryRepository {}
+
+impl FakeHistoryRepository {
+    pub const fn new() -> Self {
+        FakeHistoryRepository {
+            storage: RwLock::const_new(History {
+                undo: Vec::new(),
+                redo: Vec::new(),
+            }),
+        }
+    }
+}
+
+impl Default for FakeHistoryRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl ports::HistoryRepository for FakeHistoryRepository {
+    async fn get(&self) -> Result<History> {
+        Ok(self.storage.read().await.clone())
+    }
+
+    async fn save(&self, history: History) -> Result<()> {
+        *self.storage.write().await = history;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history_repository_test;
+
+    history_repository_test! {FakeHistoryRepository::new()}
+}
@@ -7,7 +7,7 @@ use tauri::async_runtime::RwLock;
 use time::OffsetDateTime;
 
 #[derive(Debug, Clone, PartialEq)]
-struct Project {
+pub(super) struct Project {
     id: u64,
     name: String,
     created_at: OffsetDateTime,
@@ -16,21 +16,39 @@ struct Project {
     archived_at: Option<OffsetDateTime>,
 }
 
-impl Into<models::Project> for Project {
-    fn into(self) -> models::Project {
+impl From<Project> for models::Project {
+    fn from(project: Project) -> Self {
         models::Project {
-            id: self.id,
-            name: self.name,
-            created_at: self.created_at,
-            updated_at: self.updated_at,
-            is_active: self.is_active,
-            archived_at: self.archived_at,
+            id: project.id,
+            name: project.name,
+            created_at: project.created_at,
+            updated_at: project.updated_at,
+            is_active: project.is_active,
+            archived_at: project.archived_at,
         }
     }
 }
 
+impl From<models::Project> for Project {
+    fn from(project: models::Project) -> Self {
+        Project {
+            id: project.id,
+            name: project.name,
+            created_at: project.created_at,
+            updated_at: project.updated_at,
+            is_active: project.is_active,
+            archived_at: project.archived_at,
+        }
+    }
+}
+
+pub(super) struct FakeProjectStorage {
+    pub(super) projects: Vec<Project>,
+    pub(super) last_id: u64,
+}
+
 pub struct FakeProjectRepository {
-    projects: RwLock<Vec<Project>>,
+    pub(super) storage: RwLock<FakeProjectStorage>,
 }
 
 impl IsSync for FakeProjectRepository {}
@@ -39,11 +57,20 @@ impl IsSend for FakeProjectRepository {}
 impl FakeProjectRepository {
     pub const fn new() -> Self {
         FakeProjectRepository {
-            projects: RwLock::const_new(Vec::new()),
+            storage: RwLock::const_new(FakeProjectStorage {
+                projects: Vec::new(),
+                last_id: 0,
+            }),
         }
     }
 }
 
+impl Default for FakeProjectRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[async_trait]
 impl ports::ProjectRepository for FakeProjectRepository {
     async fn create(&self, data: ports::CreateProjectData<'_>) -> Result<models::Project> {
@@ -57,28 +84,106 @@ impl ports::ProjectRepository for FakeProjectRepository {
             archived_at: None,
         };
 
-        let mut projects = self.projects.write().await;
-        project.id = (projects.len() as u64) + 1;
+        let mut storage = self.storage.write().await;
+        storage.last_id += 1;
+        project.id = storage.last_id;
 
-        projects.push(project.clone());
+        storage.projects.push(project.clone());
 
         Ok(project.into())
     }
 
     async fn get(&self, id: u64) -> Result<Option<models::Project>> {
-        let projects = self.projects.read().await;
-        if id == 0 {
+        let storage = self.storage.read().await;
+        let item = storage.projects.iter().find(|p| p.id == id);
+
+        Ok(item.cloned().map(Into::into))
+    }
+
+    async fn list(
+        &self,
+        filter: ports::ProjectFilter,
+        sort: ports::ProjectSort,
+    ) -> Result<Vec<models::Project>> {
+        let storage = self.storage.read().await;
+
+        let mut projects = storage
+            .projects
+            .iter()
+            .cloned()
+            .map(Into::into)
+            .filter(|p| filter.matches(p))
+            .collect::<Vec<models::Project>>();
+
+        sort.sort(&mut projects);
+
+        Ok(projects)
+    }
+
+    async fn update(
+        &self,
+        id: u64,
+        data: ports::UpdateProjectData<'_>,
+    ) -> Result<Option<models::Project>> {
+        let mut storage = self.storage.write().await;
+
+        let Some(project) = storage.projects.iter_mut().find(|p| p.id == id) else {
             return Ok(None);
+        };
+
+        if let Some(name) = data.name {
+            project.name = name.to_string();
+        }
+        let now = OffsetDateTime::now_utc();
+        if let Some(is_active) = data.is_active {
+            project.is_active = is_active;
+            project.archived_at = if is_active {
+                None
+            } else {
+                project.archived_at.or(Some(now))
+            };
         }
-        let item = projects.get((id - 1) as usize);
+        project.updated_at = now;
 
-        Ok(item.cloned().map(Into::into))
+        Ok(Some(project.clone().into()))
+    }
+
+    async fn archive(&self, id: u64) -> Result<Option<models::Project>> {
+        let mut storage = self.storage.write().await;
+
+        let Some(project) = storage.projects.iter_mut().find(|p| p.id == id) else {
+            return Ok(None);
+        };
+
+        project.is_active = false;
+        project.archived_at.get_or_insert(OffsetDateTime::now_utc());
+
+        Ok(Some(project.clone().into()))
+    }
+
+    async fn unarchive(&self, id: u64) -> Result<Option<models::Project>> {
+        let mut storage = self.storage.write().await;
+
+        let Some(project) = storage.projects.iter_mut().find(|p| p.id == id) else {
+            return Ok(None);
+        };
+
+        project.is_active = true;
+        project.archived_at = None;
+
+        Ok(Some(project.clone().into()))
     }
 
-    async fn list(&self) -> Result<Vec<models::Project>> {
-        let projects = self.projects.read().await;
+    async fn set_updated_at(&self, updates: &[(u64, OffsetDateTime)]) -> Result<()> {
+        let mut storage = self.storage.write().await;
+
+        for (id, updated_at) in updates {
+            if let Some(project) = storage.projects.iter_mut().find(|p| p.id == *id) {
+                project.updated_at = *updated_at;
+            }
+        }
 
-        Ok(projects.iter().cloned().map(Into::into).collect())
+        Ok(())
     }
 }
 
@@ -0,0 +1,40 @@
+use crate::models;
+use crate::ports;
+use crate::result::Result;
+use crate::utils::{IsSend, IsSync};
+use async_trait::async_trait;
+use tauri::async_runtime::RwLock;
+use time::OffsetDateTime;
+
+/// Reports the files added with `damage` once, as if they had been repaired.
+pub struct FakeRecoveryRepository {
+    damaged: RwLock<Vec<models::RecoveredFile>>,
+}
+
+impl IsSync for FakeRecoveryRepository {}
+impl IsSend for FakeRecoveryRepository {}
+
+impl FakeRecoveryRepository {
+    pub const fn new() -> Self {
+        FakeRecoveryRepository {
+            damaged: RwLock::const_new(Vec::new()),
+        }
+    }
+
+    pub async fn damage(&self, file: models::RecoveredFile) {
+        self.damaged.write().await.push(file);
+    }
+}
+
+impl Default for FakeRecoveryRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl ports::RecoveryRepository for FakeRecoveryRepository {
+    async fn recover(&self, _at: OffsetDateTime) -> Result<Vec<models::RecoveredFile>> {
+        Ok(std::mem::take(&mut *self.damaged.write().await))
+    }
+}
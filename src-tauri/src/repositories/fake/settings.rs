@@ -0,0 +1,62 @@
+use crate::models;
+use crate::ports;
+use crate::result::Result;
+use crate::utils::{IsSend, IsSync};
+use async_trait::async_trait;
+use tauri::async_runtime::RwLock;
+
+pub struct FakeSettingsRepository {
+    storage: RwLock<models::Settings>,
+}
+
+impl IsSync for FakeSettingsRepository {}
+impl IsSend for FakeSettingsRepository {}
+
+impl FakeSettingsRepository {
+    pub const fn new() -> Self {
+        FakeSettingsRepository {
+            storage: RwLock::const_new(models::Settings {
+                default_groups: Vec::new(),
+                activity_retention_days: models::Settings::DEFAULT_ACTIVITY_RETENTION_DAYS,
+                trash_retention_days: 0,
+            }),
+        }
+    }
+}
+
+impl Default for FakeSettingsRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl ports::SettingsRepository for FakeSettingsRepository {
+    async fn get(&self) -> Result<models::Settings> {
+        Ok(self.storage.read().await.clone())
+    }
+
+    async fn update(&self, data: ports::UpdateSettingsData) -> Result<models::Settings> {
+        let mut storage = self.storage.write().await;
+
+        if let Some(default_groups) = data.default_groups {
+            storage.default_groups = default_groups;
+        }
+        if let Some(days) = data.activity_retention_days {
+            storage.activity_retention_days = days;
+        }
+        if let Some(days) = data.trash_retention_days {
+            storage.trash_retention_days = days;
+        }
+
+        Ok(storage.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings_repository_test;
+
+    settings_repository_test! {FakeSettingsRepository::new()}
+}
@@ -0,0 +1,158 @@
+use crate::models;
+use crate::ports;
+use crate::position;
+use crate::result::Result;
+use crate::utils::{IsSend, IsSync};
+use async_trait::async_trait;
+use tauri::async_runtime::RwLock;
+use time::OffsetDateTime;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Todo {
+    id: u64,
+    text: String,
+    position: f64,
+    created_at: OffsetDateTime,
+    updated_at: OffsetDateTime,
+    is_done: bool,
+    done_at: Option<OffsetDateTime>,
+    group_id: u64,
+}
+
+impl From<Todo> for models::Todo {
+    fn from(todo: Todo) -> Self {
+        models::Todo {
+            id: todo.id,
+            text: todo.text,
+            position: todo.position,
+            created_at: todo.created_at,
+            updated_at: todo.updated_at,
+            is_done: todo.is_done,
+            done_at: todo.done_at,
+            group_id: todo.group_id,
+        }
+    }
+}
+
+impl From<models::Todo> for Todo {
+    fn from(todo: models::Todo) -> Self {
+        Todo {
+            id: todo.id,
+            text: todo.text,
+            position: todo.position,
+            created_at: todo.created_at,
+            updated_at: todo.updated_at,
+            is_done: todo.is_done,
+            done_at: todo.done_at,
+            group_id: todo.group_id,
+        }
+    }
+}
+
+pub(super) struct FakeTodoStorage {
+    pub(super) todos: Vec<Todo>,
+    pub(super) last_id: u64,
+}
+
+pub struct FakeTodoRepository {
+    pub(super) storage: RwLock<FakeTodoStorage>,
+}
+
+impl IsSync for FakeTodoRepository {}
+impl IsSend for FakeTodoRepository {}
+
+impl FakeTodoRepository {
+    pub const fn new() -> Self {
+        FakeTodoRepository {
+            storage: RwLock::const_new(FakeTodoStorage {
+                todos: Vec::new(),
+                last_id: 0,
+            }),
+        }
+    }
+}
+
+impl Default for FakeTodoRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl ports::TodoRepository for FakeTodoRepository {
+    async fn create(&self, data: ports::CreateTodoData<'_>) -> Result<models::Todo> {
+        let now = OffsetDateTime::now_utc();
+        let mut storage = self.storage.write().await;
+        storage.last_id += 1;
+
+        let todo = Todo {
+            id: storage.last_id,
+            text: data.text.to_string(),
+            position: position::between(
+                storage
+                    .todos
+                    .iter()
+                    .filter(|t| t.group_id == data.group_id)
+                    .map(|t| t.position)
+                    .reduce(f64::max),
+                None,
+            )
+            .unwrap_or_default(),
+            created_at: now,
+            updated_at: now,
+            is_done: false,
+            done_at: None,
+            group_id: data.group_id,
+        };
+
+        storage.todos.push(todo.clone());
+
+        Ok(todo.into())
+    }
+
+    async fn get(&self, id: u64) -> Result<Option<models::Todo>> {
+        let storage = self.storage.read().await;
+        let item = storage.todos.iter().find(|t| t.id == id);
+
+        Ok(item.cloned().map(Into::into))
+    }
+
+    async fn find_by_group(&self, group_id: u64) -> Result<Vec<models::Todo>> {
+        let storage = self.storage.read().await;
+
+        let mut todos = storage
+            .todos
+            .iter()
+            .filter(|t| t.group_id == group_id)
+            .cloned()
+            .map(Into::into)
+            .collect::<Vec<models::Todo>>();
+
+        todos.sort_by(|a, b| a.position.total_cmp(&b.position));
+
+        Ok(todos)
+    }
+
+    async fn list(&self) -> Result<Vec<models::Todo>> {
+        let storage = self.storage.read().await;
+
+        let mut todos = storage
+            .todos
+            .iter()
+            .cloned()
+            .map(Into::into)
+            .collect::<Vec<models::Todo>>();
+
+        todos.sort_by_key(|t| t.id);
+
+        Ok(todos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todo_repository_test;
+
+    todo_repository_test! {FakeTodoRepository::new()}
+}
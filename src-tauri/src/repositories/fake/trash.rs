@@ -0,0 +1,73 @@
+use crate::models;
+use crate::ports;
+use crate::result::Result;
+use crate::utils::{IsSend, IsSync};
+use async_trait::async_trait;
+use tauri::async_runtime::RwLock;
+use time::OffsetDateTime;
+
+pub(super) struct FakeTrashStorage {
+    /// Oldest first.
+    pub(super) items: Vec<models::TrashItem>,
+    pub(super) last_id: u64,
+}
+
+pub struct FakeTrashRepository {
+    pub(super) storage: RwLock<FakeTrashStorage>,
+}
+
+impl IsSync for FakeTrashRepository {}
+impl IsSend for FakeTrashRepository {}
+
+impl FakeTrashRepository {
+    pub const fn new() -> Self {
+        FakeTrashRepository {
+            storage: RwLock::const_new(FakeTrashStorage {
+                items: Vec::new(),
+                last_id: 0,
+            }),
+        }
+    }
+}
+
+impl Default for FakeTrashRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl ports::TrashRepository for FakeTrashRepository {
+    async fn get(&self, id: u64) -> Result<Option<models::TrashItem>> {
+        let storage = self.storage.read().await;
+
+        Ok(storage.items.iter().find(|i| i.id == id).cloned())
+    }
+
+    async fn list(&self) -> Result<Vec<models::TrashItem>> {
+        let storage = self.storage.read().await;
+
+        Ok(storage.items.iter().rev().cloned().collect())
+    }
+
+    async fn remove(&self, id: u64) -> Result<Option<models::TrashItem>> {
+        let mut storage = self.storage.write().await;
+
+        let Some(index) = storage.items.iter().position(|i| i.id == id) else {
+            return Ok(None);
+        };
+
+        Ok(Some(storage.items.remove(index)))
+    }
+
+    async fn purge(&self, before: OffsetDateTime) -> Result<Vec<models::TrashItem>> {
+        let mut storage = self.storage.write().await;
+
+        let (purged, kept) = std::mem::take(&mut storage.items)
+            .into_iter()
+            .partition(|i| i.deleted_at < before);
+        storage.items = kept;
+
+        Ok(purged)
+    }
+}
@@ -0,0 +1,1362 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
+
+use super::salvage;
+use crate::models::RecoveredFile;
+use crate::result::{
+    ErrorContext, ErrorKind, Result, StorageCorrupt, StorageLocked, StorageTooNew,
+};
+use anyhow::{anyhow, Context};
+use fs4::FileExt;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use time::OffsetDateTime;
+
+const VERSION_KEY: &str = "version";
+
+/// Upgrades a raw document from one storage version to the next.
+pub type Migration = fn(&mut bson::Document) -> anyhow::Result<()>;
+
+/// Document kept in a `FileStorage`.
+///
+/// `MIGRATIONS[i]` upgrades a version `i` document to version `i + 1`,
+/// so the current version is the number of registered migrations.
+/// Files written before versioning was introduced are version 0.
+pub trait StorageData: Serialize + DeserializeOwned + Default {
+    const MIGRATIONS: &'static [Migration];
+
+    fn version() -> u32 {
+        Self::MIGRATIONS.len() as u32
+    }
+
+    /// Rebuilds the data from what `salvage::document` recovered of
+    /// a damaged document.
+    fn salvage(document: &bson::Document) -> Salvaged<Self>;
+}
+
+/// Data rebuilt by `StorageData::salvage`, with the number of records it
+/// kept and dropped.
+pub struct Salvaged<D> {
+    pub data: D,
+    pub kept: usize,
+    pub dropped: usize,
+}
+
+/// Migration step introducing the `last_id` counter next to the `key` array,
+/// seeded with the largest id stored so far.
+pub fn add_last_id(document: &mut bson::Document, key: &str) -> anyhow::Result<()> {
+    let mut last_id = 0;
+
+    if let Ok(items) = document.get_array(key) {
+        for item in items {
+            let id = item
+                .as_document()
+                .and_then(|item| item.get("id"))
+                .and_then(|id| id.as_i64().or_else(|| id.as_i32().map(i64::from)))
+                .ok_or_else(|| anyhow!("Item in {} has no id", key))?;
+
+            last_id = last_id.max(id);
+        }
+    }
+
+    document.insert("last_id", last_id);
+
+    Ok(())
+}
+
+/// Migration step storing the integer `position` of every item in the `key`
+/// array as a double, the type fractional positions are kept in.
+pub fn positions_to_double(document: &mut bson::Document, key: &str) -> anyhow::Result<()> {
+    let Ok(items) = document.get_array_mut(key) else {
+        return Ok(());
+    };
+
+    for item in items {
+        let position = item
+            .as_document_mut()
+            .and_then(|item| item.get_mut("position"))
+            .ok_or_else(|| anyhow!("Item in {} has no position", key))?;
+
+        match *position {
+            bson::Bson::Int32(value) => *position = bson::Bson::Double(value.into()),
+            bson::Bson::Int64(value) => *position = bson::Bson::Double(value as f64),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Single BSON document on disk guarded by `fs4` file locks, see `FileLock`.
+///
+/// `open_exclusive` keeps the lock until the storage is dropped, so
+/// a read-modify-`save` cycle is never interleaved with another writer.
+pub struct FileStorage<D> {
+    pub data: D,
+    path: PathBuf,
+    lock: FileLock,
+}
+
+impl<D: StorageData> FileStorage<D> {
+    /// Opens the storage for writing, upgrading an outdated document first.
+    ///
+    /// Before an outdated document is migrated, the original file is copied
+    /// to `<file>.v<version>.bak`.
+    pub fn open_exclusive(path: &Path) -> Result<Self> {
+        Self::open_locked(path, FileLock::exclusive(path)?)
+    }
+
+    /// Reads the document while `lock` is held exclusively.
+    fn open_locked(path: &Path, lock: FileLock) -> Result<Self> {
+        let (data, version) = read_document::<D>(path, lock.document.as_ref())?;
+
+        let mut storage = Self {
+            data,
+            path: path.to_path_buf(),
+            lock,
+        };
+
+        if let Some(version) = version.filter(|version| *version < D::version()) {
+            storage.backup(version)?;
+            storage.save().context("Failed to save migrated storage")?;
+        }
+
+        Ok(storage)
+    }
+
+    /// Reads the document under a shared lock.
+    ///
+    /// An outdated document is upgraded on disk through `open_exclusive`.
+    pub fn read_data(path: &Path) -> Result<D> {
+        let lock = FileLock::shared(path)?;
+
+        let (data, version) = read_document::<D>(path, lock.document.as_ref())?;
+        drop(lock);
+
+        if version.is_some_and(|version| version < D::version()) {
+            let mut storage = Self::open_exclusive(path)?;
+
+            return Ok(std::mem::take(&mut storage.data));
+        }
+
+        Ok(data)
+    }
+
+    /// Reads the document under a shared lock that is kept until the
+    /// returned `StorageLock` is dropped, so several storages can be read as
+    /// of the same commit. An outdated document is only upgraded in memory;
+    /// it is written back migrated by the next `open_exclusive`.
+    pub fn read_locked(path: &Path) -> Result<(D, StorageLock)> {
+        let lock = FileLock::shared(path)?;
+        let (data, _) = read_document::<D>(path, lock.document.as_ref())?;
+
+        Ok((data, StorageLock { _lock: lock }))
+    }
+
+    /// Checks that the document can be read. A damaged document is moved to
+    /// `<file>.corrupt-<unix time>` and replaced by what could be salvaged.
+    pub fn recover(path: &Path, at: OffsetDateTime) -> Result<Option<RecoveredFile>> {
+        let mut storage = Self {
+            data: D::default(),
+            path: path.to_path_buf(),
+            lock: FileLock::exclusive(path)?,
+        };
+
+        let document = storage.lock.document.as_ref();
+        let problem = match read_document::<D>(path, document) {
+            Ok(_) => return Ok(None),
+            Err(error) if error.kind() == ErrorKind::StorageCorrupt => format!("{:#}", error),
+            Err(error) => return Err(error),
+        };
+
+        let mut bytes = Vec::new();
+        if let Some(mut f) = document {
+            f.seek(SeekFrom::Start(0))
+                .and_then(|_| f.read_to_end(&mut bytes))
+                .context(format!("Failed to read {}", path.display()))?;
+        }
+        let mut document = salvage::document(&bytes);
+
+        // The version is written last, so a cut-off file most likely lost it
+        // while being written by this version of the app.
+        let version = document
+            .remove(VERSION_KEY)
+            .and_then(|version| version.as_i32())
+            .and_then(|version| u32::try_from(version).ok())
+            .unwrap_or(D::version());
+        for migration in D::MIGRATIONS.iter().skip(version as usize) {
+            if migration(&mut document).is_err() {
+                break;
+            }
+        }
+
+        let salvaged = D::salvage(&document);
+
+        let quarantine_path = sibling_path(path, &format!("corrupt-{}", at.unix_timestamp()));
+        std::fs::rename(path, &quarantine_path).context(format!(
+            "Failed to move {} to {}",
+            path.display(),
+            quarantine_path.display()
+        ))?;
+
+        storage.data = salvaged.data;
+        storage
+            .save()
+            .context(format!("Failed to save salvaged {}", path.display()))?;
+
+        Ok(Some(RecoveredFile {
+            path: path.to_path_buf(),
+            quarantine_path: Some(quarantine_path),
+            problem,
+            salvaged: salvaged.kept,
+            dropped: salvaged.dropped,
+        }))
+    }
+
+    /// Replaces the document atomically: the new content is written and
+    /// synced to `<file>.tmp` first and then renamed over the original,
+    /// so a crash leaves either the old or the new document in place.
+    pub fn save(&mut self) -> Result<()> {
+        self.stage()?.commit()
+    }
+
+    /// Writes and syncs `<file>.tmp` without replacing the document yet, so
+    /// several storages can all be written before any of them is committed.
+    pub fn stage(&self) -> Result<Staged> {
+        let tmp_path = sibling_path(&self.path, "tmp");
+        let bytes = encode(&self.data)?;
+
+        let mut f = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp_path)
+            .context(format!("Failed to open {}", tmp_path.display()))?;
+
+        f.write_all(&bytes).context("Failed to write storage")?;
+        f.sync_all().context("Failed to sync storage")?;
+
+        Ok(Staged {
+            tmp_path,
+            path: self.path.clone(),
+        })
+    }
+
+    fn backup(&self, version: u32) -> Result<()> {
+        let backup_path = sibling_path(&self.path, &format!("v{}.bak", version));
+
+        if backup_path.exists() {
+            return Ok(());
+        }
+
+        std::fs::copy(&self.path, &backup_path).context(format!(
+            "Failed to backup {} to {}",
+            self.path.display(),
+            backup_path.display()
+        ))?;
+
+        Ok(())
+    }
+}
+
+/// Documents saved by this process so far, by path.
+static SAVES: Mutex<BTreeMap<PathBuf, u64>> = Mutex::new(BTreeMap::new());
+
+/// How often this process saved the document at `path`.
+pub fn save_count(path: &Path) -> u64 {
+    let saves = SAVES.lock().unwrap_or_else(PoisonError::into_inner);
+
+    saves.get(path).copied().unwrap_or(0)
+}
+
+/// Identity of the file currently at a path: its size and modification
+/// time, and how often this process saved a document there, which tells
+/// apart own writes that kept both. On unix the inode also reveals such
+/// writes of other processes, as `save` writes a new file every time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStamp {
+    len: u64,
+    modified: Option<SystemTime>,
+    saves: u64,
+    #[cfg(unix)]
+    inode: u64,
+}
+
+impl FileStamp {
+    /// Stamp of the file at `path`, or `None` when there is no file yet.
+    pub fn of(path: &Path) -> Result<Option<Self>> {
+        Self::read(path, save_count(path))
+    }
+
+    /// Stamp of the file at `path` with `saves` as the save count.
+    fn read(path: &Path, saves: u64) -> Result<Option<Self>> {
+        let metadata = match std::fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(anyhow::Error::new(e)
+                    .context(format!("Failed to get metadata of {}", path.display()))
+                    .into())
+            }
+        };
+
+        Ok(Some(FileStamp {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            saves,
+            #[cfg(unix)]
+            inode: std::os::unix::fs::MetadataExt::ino(&metadata),
+        }))
+    }
+}
+
+/// Document of a `FileStorage` kept in memory between calls. It is decoded
+/// again only when the `FileStamp` of the file changed, i.e. when someone
+/// else wrote it; writes through the cache keep it up to date.
+pub struct StorageCache<D> {
+    entry: Mutex<Option<(FileStamp, Arc<D>)>>,
+}
+
+impl<D: StorageData + Clone> StorageCache<D> {
+    pub const fn new() -> Self {
+        StorageCache {
+            entry: Mutex::new(None),
+        }
+    }
+
+    pub fn read(&self, path: &Path) -> Result<Arc<D>> {
+        // Stamped before reading: should the file change in between, the
+        // stamp is already stale and the next read decodes the file again.
+        let stamp = FileStamp::of(path)?;
+
+        // The document of an interrupted commit may still change when it
+        // is settled.
+        if let Some((cached, data)) = self.entry().as_ref().filter(|_| !is_pending(path)) {
+            if Some(*cached) == stamp {
+                return Ok(data.clone());
+            }
+        }
+
+        let data = Arc::new(FileStorage::<D>::read_data(path)?);
+        if let Some(stamp) = stamp {
+            *self.entry() = Some((stamp, data.clone()));
+        }
+
+        Ok(data)
+    }
+
+    /// Opens the storage for writing, starting from the cached document when
+    /// the file has not changed since. The document leaves the cache until
+    /// it is saved through `save`.
+    pub fn open_exclusive(&self, path: &Path) -> Result<FileStorage<D>> {
+        let lock = FileLock::exclusive(path)?;
+
+        let stamp = FileStamp::of(path)?;
+        let cached = self
+            .entry()
+            .take()
+            .filter(|(cached, _)| Some(*cached) == stamp);
+
+        let Some((_, data)) = cached else {
+            return FileStorage::open_locked(path, lock);
+        };
+
+        Ok(FileStorage {
+            data: Arc::try_unwrap(data).unwrap_or_else(|data| (*data).clone()),
+            path: path.to_path_buf(),
+            lock,
+        })
+    }
+
+    /// Saves the storage and caches the document it wrote.
+    pub fn save(&self, mut storage: FileStorage<D>) -> Result<()> {
+        storage.save()?;
+
+        // Still locked, so the stamp belongs to the document just written.
+        if let Some(stamp) = FileStamp::of(&storage.path)? {
+            *self.entry() = Some((stamp, Arc::new(std::mem::take(&mut storage.data))));
+        }
+
+        Ok(())
+    }
+
+    fn entry(&self) -> MutexGuard<'_, Option<(FileStamp, Arc<D>)>> {
+        self.entry.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Document written by `FileStorage::stage` and not yet in place.
+#[must_use]
+pub struct Staged {
+    tmp_path: PathBuf,
+    path: PathBuf,
+}
+
+impl Staged {
+    /// Copies `source` to `<path>.tmp`, to be committed over `path` later.
+    pub fn copy(source: &Path, path: &Path) -> Result<Self> {
+        let tmp_path = sibling_path(path, "tmp");
+
+        std::fs::copy(source, &tmp_path)
+            .and_then(|_| std::fs::File::open(&tmp_path)?.sync_all())
+            .context(format!(
+                "Failed to copy {} to {}",
+                source.display(),
+                tmp_path.display()
+            ))?;
+
+        Ok(Staged {
+            tmp_path,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Writes an empty `<path>.tmp`, which reads as the default data once
+    /// committed over `path`.
+    pub fn empty(path: &Path) -> Result<Self> {
+        let tmp_path = sibling_path(path, "tmp");
+        write_synced(&tmp_path, &[])?;
+
+        Ok(Staged {
+            tmp_path,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Renames the temporary file over the document.
+    pub fn commit(self) -> Result<()> {
+        self.replace()?;
+
+        sync_parent_dir(&self.path)
+    }
+
+    /// Commits the documents all at once, also across a crash.
+    ///
+    /// Each document first gets a `<file>.txn` pointer to a journal, and the
+    /// journal is written after all of them as the single point where the
+    /// documents are committed; only then are the temporary files renamed.
+    /// Locking a document settles what an interrupted commit left behind:
+    /// with the journal in place its temporary file still goes over it,
+    /// without the journal the temporary file is dropped.
+    ///
+    /// Every document has to be locked exclusively.
+    pub fn commit_all(staged: Vec<Staged>) -> Result<()> {
+        let Some(first) = staged.first() else {
+            return Ok(());
+        };
+
+        let journal_path = new_journal_path(&first.path);
+        let pointer = format!("{}\n", journal_path.to_string_lossy());
+
+        let pointers = staged
+            .iter()
+            .map(|staged| sibling_path(&staged.path, "txn"))
+            .collect::<Vec<_>>();
+        let pointers = scopeguard::guard(pointers, |pointers| {
+            for pointer_path in pointers {
+                let _ = std::fs::remove_file(pointer_path);
+            }
+        });
+        for pointer_path in pointers.iter() {
+            write_synced(pointer_path, pointer.as_bytes())?;
+        }
+        sync_parent_dirs(&staged)?;
+
+        let members = staged
+            .iter()
+            .map(|staged| format!("{}\n", staged.path.to_string_lossy()))
+            .collect::<String>();
+        let journal_tmp_path = sibling_path(&journal_path, "tmp");
+        write_synced(&journal_tmp_path, members.as_bytes())?;
+        std::fs::rename(&journal_tmp_path, &journal_path).context(format!(
+            "Failed to rename {} to {}",
+            journal_tmp_path.display(),
+            journal_path.display()
+        ))?;
+        sync_parent_dir(&journal_path)?;
+
+        // Committed: from here on the pointers are needed to finish the
+        // commit should it be interrupted.
+        let pointers = scopeguard::ScopeGuard::into_inner(pointers);
+
+        for staged in &staged {
+            staged.replace()?;
+        }
+        sync_parent_dirs(&staged)?;
+
+        for pointer_path in &pointers {
+            std::fs::remove_file(pointer_path)
+                .context(format!("Failed to remove {}", pointer_path.display()))?;
+        }
+        sync_parent_dirs(&staged)?;
+
+        std::fs::remove_file(&journal_path)
+            .context(format!("Failed to remove {}", journal_path.display()))?;
+
+        Ok(())
+    }
+
+    fn replace(&self) -> Result<()> {
+        // Counted and recorded before the rename, so whoever stamps the
+        // document as soon as it shows up, such as the storage watcher,
+        // finds it to be this process's. The rename keeps the metadata, so
+        // the stamp of the temporary file is the one the document gets.
+        let saves = {
+            let mut saves = SAVES.lock().unwrap_or_else(PoisonError::into_inner);
+            let count = saves.entry(self.path.clone()).or_default();
+            *count += 1;
+
+            *count
+        };
+        if let Some(stamp) = FileStamp::read(&self.tmp_path, saves)? {
+            own_writes().insert(self.path.clone(), stamp);
+        }
+
+        std::fs::rename(&self.tmp_path, &self.path).context(format!(
+            "Failed to rename {} to {}",
+            self.tmp_path.display(),
+            self.path.display()
+        ))?;
+
+        Ok(())
+    }
+}
+
+/// Journal path for a commit that starts with the document at `path`,
+/// unique so a commit never picks up the journal of an earlier one.
+fn new_journal_path(path: &Path) -> PathBuf {
+    let mut nanos = OffsetDateTime::now_utc().unix_timestamp_nanos();
+
+    loop {
+        let journal_path = sibling_path(path, &format!("journal-{}", nanos));
+        if !journal_path.exists() {
+            return journal_path;
+        }
+
+        nanos += 1;
+    }
+}
+
+/// Whether an interrupted `Staged::commit_all` left the document at `path`
+/// to be settled.
+fn is_pending(path: &Path) -> bool {
+    sibling_path(path, "txn").exists()
+}
+
+/// Finishes or drops what an interrupted `Staged::commit_all` left of the
+/// document at `path`, whose lock has to be held exclusively.
+fn settle(path: &Path) -> Result<()> {
+    let pointer_path = sibling_path(path, "txn");
+    let pointer = match std::fs::read_to_string(&pointer_path) {
+        Ok(pointer) => pointer,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => {
+            return Err(anyhow::Error::new(e)
+                .context(format!("Failed to read {}", pointer_path.display()))
+                .into())
+        }
+    };
+    // A pointer cut off while it was written belongs to a commit that never
+    // got to its journal.
+    let journal_path = pointer.strip_suffix('\n').map(PathBuf::from);
+
+    let tmp_path = sibling_path(path, "tmp");
+    let committed = journal_path.as_ref().is_some_and(|path| path.exists());
+    let result = if committed {
+        std::fs::rename(&tmp_path, path)
+    } else {
+        std::fs::remove_file(&tmp_path)
+    };
+    match result {
+        Ok(()) => {}
+        // Already renamed or never written.
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => {
+            return Err(anyhow::Error::new(e)
+                .context(format!("Failed to settle {}", path.display()))
+                .into())
+        }
+    }
+    sync_parent_dir(path)?;
+
+    std::fs::remove_file(&pointer_path)
+        .context(format!("Failed to remove {}", pointer_path.display()))?;
+    sync_parent_dir(path)?;
+
+    let Some(journal_path) = journal_path else {
+        return Ok(());
+    };
+    if !committed {
+        let _ = std::fs::remove_file(sibling_path(&journal_path, "tmp"));
+
+        return Ok(());
+    }
+
+    // The journal goes once no document points to it any more.
+    let members = std::fs::read_to_string(&journal_path).unwrap_or_default();
+    let is_referenced = members.lines().any(|member| {
+        std::fs::read_to_string(sibling_path(Path::new(member), "txn"))
+            .is_ok_and(|other| other == pointer)
+    });
+    if !is_referenced {
+        let _ = std::fs::remove_file(&journal_path);
+    }
+
+    Ok(())
+}
+
+fn write_synced(path: &Path, bytes: &[u8]) -> Result<()> {
+    let mut f = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .context(format!("Failed to open {}", path.display()))?;
+
+    f.write_all(bytes)
+        .and_then(|_| f.sync_all())
+        .context(format!("Failed to write {}", path.display()))?;
+
+    Ok(())
+}
+
+/// Stamp of the last document this process committed at each path.
+static OWN_WRITES: Mutex<BTreeMap<PathBuf, FileStamp>> = Mutex::new(BTreeMap::new());
+
+fn own_writes() -> MutexGuard<'static, BTreeMap<PathBuf, FileStamp>> {
+    OWN_WRITES.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Whether the document at `path` is the one this process last committed
+/// there, as opposed to one written by another process.
+pub fn is_own_write(path: &Path) -> Result<bool> {
+    let stamp = FileStamp::of(path)?;
+
+    Ok(stamp.is_some() && own_writes().get(path) == stamp.as_ref())
+}
+
+/// Lock of a storage taken without reading it, for work on the file as a
+/// whole such as backups; released on drop.
+pub struct StorageLock {
+    _lock: FileLock,
+}
+
+impl StorageLock {
+    pub fn shared(path: &Path) -> Result<Self> {
+        Ok(StorageLock {
+            _lock: FileLock::shared(path)?,
+        })
+    }
+
+    pub fn exclusive(path: &Path) -> Result<Self> {
+        Ok(StorageLock {
+            _lock: FileLock::exclusive(path)?,
+        })
+    }
+}
+
+/// Fails with `StorageCorrupt` unless `path` holds a readable document.
+pub fn check_document(path: &Path) -> Result<()> {
+    let mut f = std::fs::File::open(path).context(format!("Failed to open {}", path.display()))?;
+
+    if f.metadata().context("Failed to get metadata")?.len() == 0 {
+        return Ok(());
+    }
+
+    bson::Document::from_reader(&mut f).context(StorageCorrupt {
+        path: path.to_path_buf(),
+    })?;
+
+    Ok(())
+}
+
+/// Removes the document together with its lock, temporary, backup and
+/// quarantined siblings.
+#[cfg(test)]
+pub fn remove_files(path: &Path) {
+    let _ = std::fs::remove_file(path);
+
+    // Every sibling is named `<file name>.<extension>`.
+    let siblings = sibling_path(path, "");
+    if let Some(entries) = path.parent().and_then(|dir| std::fs::read_dir(dir).ok()) {
+        for entry in entries.flatten() {
+            let entry_path = entry.path();
+
+            if entry_path
+                .as_os_str()
+                .to_string_lossy()
+                .starts_with(&*siblings.to_string_lossy())
+            {
+                let _ = std::fs::remove_file(entry_path);
+            }
+        }
+    }
+}
+
+fn sibling_path(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(".");
+    name.push(extension);
+
+    path.with_file_name(name)
+}
+
+/// Locks of a storage, released on drop.
+///
+/// The lock is taken on the `<file>.lock` sibling first: `save` replaces the
+/// document with a new file, so a lock on the document alone would be left
+/// on the replaced file and a waiting writer would go on with stale data.
+/// The document itself is then locked as well, which keeps out processes
+/// that lock the data file directly, as versions before the sibling did.
+/// It is read through the same handle, because Windows does not let other
+/// handles read a locked file.
+struct FileLock {
+    sibling: std::fs::File,
+    /// `None` while there is no document yet.
+    document: Option<std::fs::File>,
+}
+
+impl FileLock {
+    fn shared(path: &Path) -> Result<Self> {
+        Self::acquire(path, false, LOCK_TIMEOUT)
+    }
+
+    fn exclusive(path: &Path) -> Result<Self> {
+        Self::acquire(path, true, LOCK_TIMEOUT)
+    }
+
+    fn acquire(path: &Path, mut exclusive: bool, timeout: Duration) -> Result<Self> {
+        let started = Instant::now();
+
+        let sibling = open_lock(path)?;
+        lock_file(&sibling, path, exclusive, timeout)?;
+        let mut lock = FileLock {
+            sibling,
+            document: None,
+        };
+
+        if is_pending(path) {
+            if !exclusive {
+                let _ = lock.sibling.unlock();
+                exclusive = true;
+                lock_file(
+                    &lock.sibling,
+                    path,
+                    exclusive,
+                    timeout.saturating_sub(started.elapsed()),
+                )?;
+            }
+
+            settle(path)?;
+        }
+
+        let document = match std::fs::File::open(path) {
+            Ok(document) => document,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(lock),
+            Err(e) => {
+                return Err(anyhow::Error::new(e)
+                    .context(format!("Failed to open {}", path.display()))
+                    .into())
+            }
+        };
+        lock_file(
+            &document,
+            path,
+            exclusive,
+            timeout.saturating_sub(started.elapsed()),
+        )?;
+        lock.document = Some(document);
+
+        Ok(lock)
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        if let Some(document) = &self.document {
+            let _ = document.unlock();
+        }
+        let _ = self.sibling.unlock();
+    }
+}
+
+fn open_lock(path: &Path) -> Result<std::fs::File> {
+    let lock_path = sibling_path(path, "lock");
+
+    Ok(std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&lock_path)
+        .context(format!("Failed to open {}", lock_path.display()))?)
+}
+
+/// How long a storage waits for another process to release its lock, the
+/// same as the busy timeout of the SQLite backend.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Takes the lock, failing with `StorageLocked` once `timeout` is over.
+fn lock_file(lock: &std::fs::File, path: &Path, exclusive: bool, timeout: Duration) -> Result<()> {
+    let started = Instant::now();
+
+    loop {
+        let result = if exclusive {
+            FileExt::try_lock_exclusive(lock)
+        } else {
+            FileExt::try_lock_shared(lock)
+        };
+
+        match result {
+            Ok(()) => return Ok(()),
+            Err(e) if e.raw_os_error() == fs4::lock_contended_error().raw_os_error() => {
+                if started.elapsed() >= timeout {
+                    return Err(StorageLocked {
+                        path: path.to_path_buf(),
+                    }
+                    .into());
+                }
+
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            Err(e) => {
+                return Err(anyhow::Error::new(e)
+                    .context("Failed to lock storage")
+                    .into())
+            }
+        }
+    }
+}
+
+fn encode<D: StorageData>(data: &D) -> Result<Vec<u8>> {
+    let mut document = bson::to_document(data).context("Failed serialize storage")?;
+    document.insert(VERSION_KEY, D::version());
+
+    Ok(bson::to_vec(&document).context("Failed serialize storage")?)
+}
+
+/// Reads and decodes `document`, the file at `path`, running the migrations
+/// it needs.
+///
+/// Returns the version the file was stored with, or `None` when there is
+/// no file yet.
+fn read_document<D: StorageData>(
+    path: &Path,
+    document: Option<&std::fs::File>,
+) -> Result<(D, Option<u32>)> {
+    let Some(mut f) = document else {
+        return Ok((D::default(), None));
+    };
+
+    if f.metadata().context("Failed to get metadata")?.len() == 0 {
+        return Ok((D::default(), None));
+    }
+
+    let corrupt = || StorageCorrupt {
+        path: path.to_path_buf(),
+    };
+
+    let mut document = bson::Document::from_reader(&mut f).context(corrupt())?;
+
+    let version = match document.remove(VERSION_KEY) {
+        None => 0,
+        Some(version) => version
+            .as_i32()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| anyhow!("Invalid storage version {}", version))
+            .context(corrupt())?,
+    };
+
+    if version > D::version() {
+        return Err(StorageTooNew {
+            path: path.to_path_buf(),
+            version: version as usize,
+            supported: D::version() as usize,
+        }
+        .into());
+    }
+
+    for (i, migration) in D::MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migration(&mut document).context(format!(
+            "Failed to migrate {} to version {}",
+            path.display(),
+            i + 1
+        ))?;
+    }
+
+    let data = bson::from_document(document).context(corrupt())?;
+
+    Ok((data, Some(version)))
+}
+
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> Result<()> {
+    let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) else {
+        return Ok(());
+    };
+
+    std::fs::File::open(dir)
+        .and_then(|dir| dir.sync_all())
+        .context(format!("Failed to sync directory {}", dir.display()))?;
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Syncs the directory of every staged document, each one once.
+fn sync_parent_dirs(staged: &[Staged]) -> Result<()> {
+    let mut dirs = BTreeMap::new();
+    for staged in staged {
+        dirs.entry(staged.path.parent()).or_insert(&staged.path);
+    }
+
+    for path in dirs.into_values() {
+        sync_parent_dir(path)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+    struct TestData {
+        items: Vec<String>,
+    }
+
+    impl StorageData for TestData {
+        const MIGRATIONS: &'static [Migration] = &[|document| {
+            let names = document
+                .remove("names")
+                .unwrap_or(bson::Bson::Array(vec![]));
+            document.insert("items", names);
+
+            Ok(())
+        }];
+
+        fn salvage(document: &bson::Document) -> Salvaged<Self> {
+            let (items, dropped) = salvage::items(document, "items");
+
+            Salvaged {
+                kept: items.len(),
+                data: TestData { items },
+                dropped,
+            }
+        }
+    }
+
+    struct TestPath(PathBuf);
+
+    impl TestPath {
+        fn new() -> Self {
+            let name = format!("test_Storage_{}.bson", rand::random::<u32>());
+            Self(Path::new(env!("CARGO_MANIFEST_DIR")).join("tmp").join(name))
+        }
+    }
+
+    impl Drop for TestPath {
+        fn drop(&mut self) {
+            remove_files(&self.0);
+        }
+    }
+
+    fn items(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("item {}", i)).collect()
+    }
+
+    #[test]
+    fn positions_to_double_converts_integers() {
+        let mut document = bson::doc! {
+            "groups": [
+                { "id": 1_i64, "position": 1_i32 },
+                { "id": 2_i64, "position": 2_i64 },
+                { "id": 3_i64, "position": 2.5 },
+            ],
+        };
+
+        positions_to_double(&mut document, "groups").expect("migrate");
+
+        let positions: Vec<_> = document
+            .get_array("groups")
+            .expect("groups")
+            .iter()
+            .map(|item| item.as_document().expect("item").get("position").cloned())
+            .collect();
+        assert_eq!(
+            positions,
+            [1.0, 2.0, 2.5].map(|position| Some(bson::Bson::Double(position)))
+        );
+    }
+
+    #[test]
+    fn save_and_read() {
+        let path = TestPath::new();
+
+        let mut storage = FileStorage::<TestData>::open_exclusive(&path.0).expect("open");
+        storage.data.items = items(3);
+        storage.save().expect("save");
+        drop(storage);
+
+        let data = FileStorage::<TestData>::read_data(&path.0).expect("read");
+        assert_eq!(data.items, items(3));
+    }
+
+    #[test]
+    fn save_shorter_document() {
+        let path = TestPath::new();
+
+        let mut storage = FileStorage::<TestData>::open_exclusive(&path.0).expect("open");
+        storage.data.items = items(100);
+        storage.save().expect("save");
+        drop(storage);
+
+        let mut storage = FileStorage::<TestData>::open_exclusive(&path.0).expect("open");
+        storage.data.items.truncate(1);
+        storage.save().expect("save");
+        drop(storage);
+
+        let data = FileStorage::<TestData>::read_data(&path.0).expect("read");
+        assert_eq!(data.items, items(1));
+        assert_eq!(
+            std::fs::metadata(&path.0).expect("metadata").len(),
+            encode(&data).expect("serialize").len() as u64
+        );
+    }
+
+    #[test]
+    fn interrupted_save_keeps_previous_state() {
+        let path = TestPath::new();
+
+        let mut storage = FileStorage::<TestData>::open_exclusive(&path.0).expect("open");
+        storage.data.items = items(3);
+        storage.save().expect("save");
+
+        // Crash in the middle of the next write: the temporary file is
+        // only partially written and never renamed over the document.
+        storage.data.items = items(50);
+        let tmp_path = storage.stage().expect("stage").tmp_path;
+        let tmp_len = std::fs::metadata(&tmp_path).expect("metadata").len();
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(&tmp_path)
+            .and_then(|f| f.set_len(tmp_len / 2))
+            .expect("truncate temp");
+        drop(storage);
+
+        let data = FileStorage::<TestData>::read_data(&path.0).expect("read");
+        assert_eq!(data.items, items(3));
+
+        let mut storage = FileStorage::<TestData>::open_exclusive(&path.0).expect("open");
+        assert_eq!(storage.data.items, items(3));
+        storage.data.items.push("after crash".into());
+        storage.save().expect("save after crash");
+        drop(storage);
+
+        let data = FileStorage::<TestData>::read_data(&path.0).expect("read");
+        assert_eq!(data.items.len(), 4);
+    }
+
+    fn write_raw(path: &Path, document: bson::Document) {
+        std::fs::write(path, bson::to_vec(&document).expect("serialize")).expect("write");
+    }
+
+    fn read_raw(path: &Path) -> bson::Document {
+        bson::from_slice(&std::fs::read(path).expect("read")).expect("deserialize")
+    }
+
+    #[test]
+    fn save_writes_version() {
+        let path = TestPath::new();
+
+        let mut storage = FileStorage::<TestData>::open_exclusive(&path.0).expect("open");
+        storage.save().expect("save");
+        drop(storage);
+
+        assert_eq!(read_raw(&path.0).get_i32(VERSION_KEY), Ok(1));
+    }
+
+    #[test]
+    fn open_migrates_unversioned_document() {
+        let path = TestPath::new();
+        let legacy = bson::doc! { "names": ["a", "b"] };
+        write_raw(&path.0, legacy.clone());
+
+        let data = FileStorage::<TestData>::read_data(&path.0).expect("read");
+        assert_eq!(data.items, vec!["a", "b"]);
+
+        assert_eq!(
+            read_raw(&path.0),
+            bson::doc! { "items": ["a", "b"], "version": 1 }
+        );
+        assert_eq!(read_raw(&sibling_path(&path.0, "v0.bak")), legacy);
+    }
+
+    #[test]
+    fn open_rejects_newer_version() {
+        let path = TestPath::new();
+        write_raw(&path.0, bson::doc! { "items": ["a"], "version": 2 });
+
+        let error = FileStorage::<TestData>::open_exclusive(&path.0)
+            .err()
+            .expect("open should fail");
+
+        assert!(
+            error.to_string().contains("newer version"),
+            "unexpected error {}",
+            error
+        );
+        assert_eq!(error.kind(), ErrorKind::StorageTooNew);
+        assert!(FileStorage::<TestData>::read_data(&path.0).is_err());
+    }
+
+    #[test]
+    fn read_reports_corrupt_document() {
+        let path = TestPath::new();
+        std::fs::write(&path.0, b"not a bson document").expect("write");
+
+        let error = FileStorage::<TestData>::read_data(&path.0).expect_err("read should fail");
+
+        assert_eq!(error.kind(), crate::result::ErrorKind::StorageCorrupt);
+        assert_eq!(error.path(), Some(path.0.as_path()));
+    }
+
+    #[test]
+    fn recover_drops_undecodable_records() {
+        let path = TestPath::new();
+        write_raw(&path.0, bson::doc! { "items": ["a", 2, "c"], "version": 1 });
+        let at = time::macros::datetime!(2024-03-01 12:00 UTC);
+
+        let recovered = FileStorage::<TestData>::recover(&path.0, at)
+            .expect("recover")
+            .expect("document should be damaged");
+
+        assert_eq!((recovered.salvaged, recovered.dropped), (2, 1));
+        assert_eq!(
+            recovered.quarantine_path,
+            Some(sibling_path(
+                &path.0,
+                &format!("corrupt-{}", at.unix_timestamp())
+            ))
+        );
+        let data = FileStorage::<TestData>::read_data(&path.0).expect("read");
+        assert_eq!(data.items, vec!["a", "c"]);
+        assert_eq!(
+            FileStorage::<TestData>::recover(&path.0, at).expect("recover"),
+            None
+        );
+    }
+
+    #[test]
+    fn cache_reuses_document_until_file_changes() {
+        let path = TestPath::new();
+        let cache = StorageCache::<TestData>::new();
+
+        let mut storage = cache.open_exclusive(&path.0).expect("open");
+        storage.data.items = items(2);
+        cache.save(storage).expect("save");
+
+        let first = cache.read(&path.0).expect("read");
+        let second = cache.read(&path.0).expect("read");
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(first.items, items(2));
+
+        let mut storage = FileStorage::<TestData>::open_exclusive(&path.0).expect("open");
+        storage.data.items = items(2).into_iter().rev().collect();
+        storage.save().expect("save");
+        drop(storage);
+
+        let changed = cache.read(&path.0).expect("read");
+        assert_eq!(changed.items, ["item 1", "item 0"]);
+
+        let mut storage = cache.open_exclusive(&path.0).expect("open");
+        assert_eq!(storage.data.items, ["item 1", "item 0"]);
+        storage.data.items.push("item 2".into());
+        cache.save(storage).expect("save");
+
+        assert_eq!(
+            FileStorage::<TestData>::read_data(&path.0)
+                .expect("read")
+                .items
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn stamp_changes_with_every_save() {
+        let path = TestPath::new();
+        let mut storage = FileStorage::<TestData>::open_exclusive(&path.0).expect("open");
+        storage.data.items = items(2);
+        storage.save().expect("save");
+        let first = FileStamp::of(&path.0).expect("stamp");
+        let modified = std::fs::metadata(&path.0)
+            .and_then(|metadata| metadata.modified())
+            .expect("modified");
+
+        storage.data.items.reverse();
+        storage.save().expect("save");
+        // Same size, and as if saved within the same modification time tick.
+        std::fs::File::options()
+            .write(true)
+            .open(&path.0)
+            .and_then(|f| f.set_modified(modified))
+            .expect("set modified");
+
+        assert!(first.is_some());
+        assert_ne!(FileStamp::of(&path.0).expect("stamp"), first);
+    }
+
+    #[test]
+    fn lock_times_out_while_held() {
+        let path = TestPath::new();
+        let storage = FileStorage::<TestData>::open_exclusive(&path.0).expect("open");
+
+        let lock = open_lock(&path.0).expect("open lock");
+        let error = lock_file(&lock, &path.0, false, Duration::from_millis(50))
+            .expect_err("lock should fail");
+        assert_eq!(error.kind(), crate::result::ErrorKind::StorageLocked);
+
+        drop(storage);
+        lock_file(&lock, &path.0, false, Duration::from_millis(50)).expect("lock");
+    }
+
+    #[test]
+    fn lock_waits_for_lock_on_data_file() {
+        let path = TestPath::new();
+        let mut storage = FileStorage::<TestData>::open_exclusive(&path.0).expect("open");
+        storage.data.items.push("a".to_string());
+        storage.save().expect("save");
+        drop(storage);
+
+        // Someone locking the data file itself rather than the sibling.
+        let other = std::fs::File::open(&path.0).expect("open data file");
+        FileExt::lock_exclusive(&other).expect("lock data file");
+
+        let error = FileLock::acquire(&path.0, true, Duration::from_millis(50))
+            .err()
+            .expect("lock should fail");
+        assert_eq!(error.kind(), crate::result::ErrorKind::StorageLocked);
+
+        FileExt::unlock(&other).expect("unlock data file");
+        FileLock::acquire(&path.0, true, Duration::from_millis(50)).expect("lock");
+    }
+
+    /// Storages at `paths` holding `items(n)`, opened for writing.
+    fn open_all(paths: &[&TestPath], n: usize) -> Vec<FileStorage<TestData>> {
+        paths
+            .iter()
+            .map(|path| {
+                let mut storage = FileStorage::<TestData>::open_exclusive(&path.0).expect("open");
+                storage.data.items = items(n);
+                storage.save().expect("save");
+                storage
+            })
+            .collect()
+    }
+
+    /// Writes the pointers of `staged` to `journal_path`, as an interrupted
+    /// `Staged::commit_all` leaves them.
+    fn write_pointers(staged: &[Staged], journal_path: &Path) {
+        for staged in staged {
+            std::fs::write(
+                sibling_path(&staged.path, "txn"),
+                format!("{}\n", journal_path.to_string_lossy()),
+            )
+            .expect("write pointer");
+        }
+    }
+
+    fn read_items(path: &TestPath) -> Vec<String> {
+        FileStorage::<TestData>::read_data(&path.0)
+            .expect("read")
+            .items
+    }
+
+    #[test]
+    fn commit_all_commits_every_document() {
+        let (a, b) = (TestPath::new(), TestPath::new());
+        let mut storages = open_all(&[&a, &b], 1);
+
+        for storage in &mut storages {
+            storage.data.items = items(2);
+        }
+        let staged = storages
+            .iter()
+            .map(|storage| storage.stage().expect("stage"))
+            .collect();
+        Staged::commit_all(staged).expect("commit");
+        drop(storages);
+
+        assert_eq!(read_items(&a), items(2));
+        assert_eq!(read_items(&b), items(2));
+        for path in [&a, &b] {
+            assert!(!sibling_path(&path.0, "txn").exists());
+            assert!(!sibling_path(&path.0, "tmp").exists());
+        }
+    }
+
+    #[test]
+    fn commit_all_interrupted_before_journal_keeps_previous_state() {
+        let (a, b) = (TestPath::new(), TestPath::new());
+        let mut storages = open_all(&[&a, &b], 1);
+
+        for storage in &mut storages {
+            storage.data.items = items(2);
+        }
+        let staged = storages
+            .iter()
+            .map(|storage| storage.stage().expect("stage"))
+            .collect::<Vec<_>>();
+        write_pointers(&staged, &sibling_path(&a.0, "journal-1"));
+        drop(storages);
+
+        assert_eq!(read_items(&a), items(1));
+        assert_eq!(read_items(&b), items(1));
+        for path in [&a, &b] {
+            assert!(!sibling_path(&path.0, "txn").exists());
+            assert!(!sibling_path(&path.0, "tmp").exists());
+        }
+    }
+
+    #[test]
+    fn commit_all_interrupted_after_journal_finishes_commit() {
+        let (a, b) = (TestPath::new(), TestPath::new());
+        let mut storages = open_all(&[&a, &b], 1);
+
+        for storage in &mut storages {
+            storage.data.items = items(2);
+        }
+        let staged = storages
+            .iter()
+            .map(|storage| storage.stage().expect("stage"))
+            .collect::<Vec<_>>();
+        let journal_path = sibling_path(&a.0, "journal-1");
+        write_pointers(&staged, &journal_path);
+        std::fs::write(
+            &journal_path,
+            format!("{}\n{}\n", a.0.to_string_lossy(), b.0.to_string_lossy()),
+        )
+        .expect("write journal");
+        // Crash after the first rename.
+        staged[0].replace().expect("replace");
+        drop(storages);
+
+        assert_eq!(read_items(&b), items(2));
+        assert!(journal_path.exists());
+
+        let cache = StorageCache::<TestData>::new();
+        assert_eq!(cache.read(&a.0).expect("read").items, items(2));
+        assert!(!journal_path.exists());
+    }
+}
@@ -1,37 +1,176 @@
+use std::path::Path;
+
+use super::file_storage::{self, FileStorage, Migration, Salvaged, StorageData};
+use super::salvage;
 use crate::models;
 use crate::ports;
-use crate::result::Result;
+use crate::position;
+use crate::result::{ErrorContext, Result};
 use crate::utils::{IsSend, IsSync};
 use async_trait::async_trait;
+use blocking::unblock;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) struct Group {
+    id: u64,
+    name: String,
+    position: f64,
+    is_opened: bool,
+    project_id: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub(super) struct GroupFileStorageData {
+    pub(super) groups: Vec<Group>,
+    /// Largest id ever handed out, so ids of deleted groups are never reused.
+    pub(super) last_id: u64,
+}
+
+impl StorageData for GroupFileStorageData {
+    const MIGRATIONS: &'static [Migration] = &[
+        |document| file_storage::add_last_id(document, "groups"),
+        |document| file_storage::positions_to_double(document, "groups"),
+    ];
+
+    fn salvage(document: &bson::Document) -> Salvaged<Self> {
+        let (groups, dropped) = salvage::items::<Group>(document, "groups");
+        let last_id = salvage::last_id(document, groups.iter().map(|item| item.id));
+
+        Salvaged {
+            kept: groups.len(),
+            data: GroupFileStorageData { groups, last_id },
+            dropped,
+        }
+    }
+}
+
+pub(super) type GroupFileStorage = FileStorage<GroupFileStorageData>;
+
+impl From<Group> for models::Group {
+    fn from(group: Group) -> Self {
+        models::Group {
+            id: group.id,
+            name: group.name,
+            position: group.position,
+            is_opened: group.is_opened,
+            project_id: group.project_id,
+        }
+    }
+}
+
+impl From<models::Group> for Group {
+    fn from(group: models::Group) -> Self {
+        Group {
+            id: group.id,
+            name: group.name,
+            position: group.position,
+            is_opened: group.is_opened,
+            project_id: group.project_id,
+        }
+    }
+}
 
 pub struct GroupRepository {
-    // file_path: std::path::PathBuf,
+    file_path: std::path::PathBuf,
 }
 
 impl IsSync for GroupRepository {}
 impl IsSend for GroupRepository {}
 
 impl GroupRepository {
-    pub fn new() -> Self {
-        GroupRepository {}
+    pub fn new(file_path: &Path) -> Self {
+        GroupRepository {
+            file_path: std::path::PathBuf::from(file_path),
+        }
+    }
+
+    async fn read_data(&self) -> Result<GroupFileStorageData> {
+        let file_path = self.file_path.clone();
+
+        let data = unblock(move || {
+            GroupFileStorage::read_data(&file_path).context("Failed to read storage")
+        })
+        .await?;
+
+        Ok(data)
     }
-    // pub fn new(file_path: &Path) -> Self {
-    //     GroupRepository {
-    //         file_path: std::path::PathBuf::from(file_path),
-    //     }
-    // }
 }
 
 #[async_trait]
 impl ports::GroupRepository for GroupRepository {
     async fn create(&self, data: ports::CreateGroupData<'_>) -> Result<models::Group> {
-        Ok(models::Group {
-            id: 1,
+        let mut group = Group {
+            id: 0,
             name: data.name.to_string(),
-            position: 1,
+            position: 0.0,
             is_opened: true,
             project_id: data.project_id,
+        };
+
+        let file_path = self.file_path.clone();
+
+        unblock(move || {
+            let mut storage = GroupFileStorage::open_exclusive(&file_path)
+                .context("Failed to open_exclusive storage")?;
+
+            storage.data.last_id += 1;
+            group.id = storage.data.last_id;
+            group.position = position::between(
+                storage
+                    .data
+                    .groups
+                    .iter()
+                    .filter(|g| g.project_id == group.project_id)
+                    .map(|g| g.position)
+                    .reduce(f64::max),
+                None,
+            )
+            .unwrap_or_default();
+
+            storage.data.groups.push(group.clone());
+            storage.save().context("Failed to save storage")?;
+
+            Ok(group.into())
         })
+        .await
+    }
+
+    async fn get(&self, id: u64) -> Result<Option<models::Group>> {
+        let data = self.read_data().await?;
+
+        let item = data.groups.into_iter().find(|g| g.id == id);
+
+        Ok(item.map(Into::into))
+    }
+
+    async fn find_by_project(&self, project_id: u64) -> Result<Vec<models::Group>> {
+        let data = self.read_data().await?;
+
+        let mut groups = data
+            .groups
+            .into_iter()
+            .filter(|g| g.project_id == project_id)
+            .map(Into::into)
+            .collect::<Vec<models::Group>>();
+
+        groups.sort_by(|a, b| a.position.total_cmp(&b.position));
+
+        Ok(groups)
+    }
+
+    async fn list(&self) -> Result<Vec<models::Group>> {
+        let data = self.read_data().await?;
+
+        let mut groups = data
+            .groups
+            .into_iter()
+            .map(Into::into)
+            .collect::<Vec<models::Group>>();
+
+        groups.sort_by_key(|g| g.id);
+
+        Ok(groups)
     }
 }
 
@@ -39,32 +178,44 @@ impl ports::GroupRepository for GroupRepository {
 mod tests {
     use super::*;
     use crate::group_repository_test;
+    use crate::repositories::file_storage;
 
     struct GroupRepositoryTest {
         repo: GroupRepository,
-        // path: std::path::PathBuf,
+        path: std::path::PathBuf,
     }
 
-    // impl Drop for GroupRepositoryTest {
-    //     fn drop(&mut self) {
-    //         let _ = std::fs::remove_file(&self.path);
-    //     }
-    // }
+    impl Drop for GroupRepositoryTest {
+        fn drop(&mut self) {
+            file_storage::remove_files(&self.path);
+        }
+    }
 
     #[async_trait]
     impl ports::GroupRepository for GroupRepositoryTest {
         async fn create(&self, data: ports::CreateGroupData<'_>) -> Result<models::Group> {
             self.repo.create(data).await
         }
+
+        async fn get(&self, id: u64) -> Result<Option<models::Group>> {
+            self.repo.get(id).await
+        }
+
+        async fn find_by_project(&self, project_id: u64) -> Result<Vec<models::Group>> {
+            self.repo.find_by_project(project_id).await
+        }
+
+        async fn list(&self) -> Result<Vec<models::Group>> {
+            self.repo.list().await
+        }
     }
 
     group_repository_test! {{
-        // let name = format!("test_Projects_{}.bson", rand::random::<u32>());
-        // let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tmp").join(name);
+        let name = format!("test_Groups_{}.bson", rand::random::<u32>());
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tmp").join(name);
         GroupRepositoryTest {
-            // repo: GroupRepository::new(&path),
-            repo: GroupRepository::new(),
-            // path,
+            repo: GroupRepository::new(&path),
+            path,
         }
     }}
 }
@@ -0,0 +1,126 @@
+use std::path::Path;
+
+use super::file_storage::{FileStorage, Migration, Salvaged, StorageData};
+use super::salvage;
+use crate::history::{History, HistoryEntry};
+use crate::ports;
+use crate::result::{ErrorContext, Result};
+use crate::utils::{IsSend, IsSync};
+use async_trait::async_trait;
+use blocking::unblock;
+use serde::{Deserialize, Serialize};
+
+/// Entries are stored the way `HistoryEntry` serializes, without a storage
+/// type of their own: an unreadable history is started over by
+/// `HistoryInteractor` rather than migrated.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub(super) struct HistoryFileStorageData {
+    #[serde(default)]
+    undo: Vec<HistoryEntry>,
+    #[serde(default)]
+    redo: Vec<HistoryEntry>,
+}
+
+impl StorageData for HistoryFileStorageData {
+    const MIGRATIONS: &'static [Migration] = &[];
+
+    fn salvage(document: &bson::Document) -> Salvaged<Self> {
+        let (undo, dropped_undo) = salvage::items(document, "undo");
+        let (redo, dropped_redo) = salvage::items(document, "redo");
+
+        Salvaged {
+            kept: undo.len() + redo.len(),
+            data: HistoryFileStorageData { undo, redo },
+            dropped: dropped_undo + dropped_redo,
+        }
+    }
+}
+
+pub(super) type HistoryFileStorage = FileStorage<HistoryFileStorageData>;
+
+pub struct HistoryRepository {
+    file_path: std::path::PathBuf,
+}
+
+impl IsSync for HistoryRepository {}
+impl IsSend for HistoryRepository {}
+
+impl HistoryRepository {
+    pub fn new(file_path: &Path) -> Self {
+        HistoryRepository {
+            file_path: std::path::PathBuf::from(file_path),
+        }
+    }
+}
+
+#[async_trait]
+impl ports::HistoryRepository for HistoryRepository {
+    async fn get(&self) -> Result<History> {
+        let file_path = self.file_path.clone();
+
+        let data = unblock(move || {
+            HistoryFileStorage::read_data(&file_path).context("Failed to read storage")
+        })
+        .await?;
+
+        Ok(History {
+            undo: data.undo,
+            redo: data.redo,
+        })
+    }
+
+    async fn save(&self, history: History) -> Result<()> {
+        let file_path = self.file_path.clone();
+
+        unblock(move || {
+            let mut storage = HistoryFileStorage::open_exclusive(&file_path)
+                .context("Failed to open_exclusive storage")?;
+
+            storage.data.undo = history.undo;
+            storage.data.redo = history.redo;
+
+            storage.save().context("Failed to save storage")?;
+
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history_repository_test;
+    use crate::repositories::file_storage;
+
+    struct HistoryRepositoryTest {
+        repo: HistoryRepository,
+        path: std::path::PathBuf,
+    }
+
+    impl Drop for HistoryRepositoryTest {
+        fn drop(&mut self) {
+            file_storage::remove_files(&self.path);
+        }
+    }
+
+    #[async_trait]
+    impl ports::HistoryRepository for HistoryRepositoryTest {
+        async fn get(&self) -> Result<History> {
+            self.repo.get().await
+        }
+
+        async fn save(&self, history: History) -> Result<()> {
+            self.repo.save(history).await
+        }
+    }
+
+    history_repository_test! {{
+        let name = format!("test_History_{}.bson", rand::random::<u32>());
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tmp").join(name);
+        HistoryRepositoryTest {
+            repo: HistoryRepository::new(&path),
+            path,
+        }
+    }}
+}
@@ -1,21 +1,20 @@
-use std::io::{Seek, SeekFrom, Write};
-use std::ops::DerefMut;
 use std::path::Path;
 
+use super::file_storage::{self, FileStorage, Migration, Salvaged, StorageCache, StorageData};
+use super::salvage;
 use crate::models;
 use crate::ports;
-use crate::result::Result;
+use crate::result::{ErrorContext, Result};
 use crate::utils::{IsSend, IsSync};
-use anyhow::Context;
 use async_trait::async_trait;
 use blocking::unblock;
-use fs4::FileExt;
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
+use std::sync::Arc;
 use time::OffsetDateTime;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-struct Project {
+pub(super) struct Project {
     id: u64,
     name: String,
     #[serde(with = "time::serde::iso8601")]
@@ -27,105 +26,73 @@ struct Project {
     archived_at: Option<OffsetDateTime>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-struct ProjectFileStorageData {
-    projects: Vec<Project>,
-}
-
-struct ProjectFileStorage {
-    data: ProjectFileStorageData,
-    f: std::fs::File,
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub(super) struct ProjectFileStorageData {
+    pub(super) projects: Vec<Project>,
+    /// Largest id ever handed out, so ids of deleted projects are never reused.
+    pub(super) last_id: u64,
 }
 
-impl ProjectFileStorage {
-    fn open_exclusive(path: &Path) -> Result<Self> {
-        let f = std::fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(path)
-            .context(format!("Failed to open {}", path.display()))?;
-
-        f.lock_exclusive().context("Failed to lock exclusive")?;
-        let mut f = scopeguard::guard(f, |f| {
-            let _ = f.unlock();
-        });
-
-        let data = if f.metadata().context("Failed to get metadata")?.len() == 0 {
-            ProjectFileStorageData {
-                projects: Vec::with_capacity(1),
-            }
-        } else {
-            bson::from_reader(f.deref_mut()).context("Failed to read document")?
-        };
+impl StorageData for ProjectFileStorageData {
+    const MIGRATIONS: &'static [Migration] =
+        &[|document| file_storage::add_last_id(document, "projects")];
 
-        let f = scopeguard::ScopeGuard::into_inner(f);
+    fn salvage(document: &bson::Document) -> Salvaged<Self> {
+        let (projects, dropped) = salvage::items::<Project>(document, "projects");
+        let last_id = salvage::last_id(document, projects.iter().map(|item| item.id));
 
-        Ok(Self { data, f })
-    }
-
-    fn read_data(path: &Path) -> Result<ProjectFileStorageData> {
-        let f = std::fs::OpenOptions::new().read(true).open(path);
-        if f.as_ref()
-            .is_err_and(|x| x.kind() == std::io::ErrorKind::NotFound)
-        {
-            return Ok(ProjectFileStorageData {
-                projects: Vec::new(),
-            });
+        Salvaged {
+            kept: projects.len(),
+            data: ProjectFileStorageData { projects, last_id },
+            dropped,
         }
+    }
+}
 
-        let f = f.context(format!("Failed to open {}", path.display()))?;
-
-        f.lock_shared().context("Failed to lock shared")?;
-        let mut f = scopeguard::guard(f, |f| {
-            let _ = f.unlock();
-        });
+pub(super) type ProjectFileStorage = FileStorage<ProjectFileStorageData>;
 
-        let data = if f.metadata().context("Failed to get metadata")?.len() == 0 {
-            ProjectFileStorageData {
-                projects: Vec::new(),
-            }
+impl Project {
+    fn set_active(&mut self, is_active: bool, now: OffsetDateTime) {
+        self.is_active = is_active;
+        self.archived_at = if is_active {
+            None
         } else {
-            bson::from_reader(f.deref_mut()).context("Failed to read document")?
+            self.archived_at.or(Some(now))
         };
-
-        Ok(data)
-    }
-
-    fn save(&mut self) -> Result<()> {
-        self.f
-            .seek(SeekFrom::Start(0))
-            .context("Failed to move cursor to start of file")?;
-
-        self.f
-            .write(&bson::to_vec(&self.data).context("Failed serialize storage")?)
-            .context("Failed to write storage")?;
-
-        Ok(())
     }
 }
 
-impl Drop for ProjectFileStorage {
-    fn drop(&mut self) {
-        let _ = self.f.unlock();
+impl From<Project> for models::Project {
+    fn from(project: Project) -> Self {
+        models::Project {
+            id: project.id,
+            name: project.name,
+            created_at: project.created_at,
+            updated_at: project.updated_at,
+            is_active: project.is_active,
+            archived_at: project.archived_at,
+        }
     }
 }
 
-impl Into<models::Project> for Project {
-    fn into(self) -> models::Project {
-        models::Project {
-            id: self.id,
-            name: self.name,
-            created_at: self.created_at,
-            updated_at: self.updated_at,
-            is_active: self.is_active,
-            archived_at: self.archived_at,
+impl From<models::Project> for Project {
+    fn from(project: models::Project) -> Self {
+        Project {
+            id: project.id,
+            name: project.name,
+            created_at: project.created_at,
+            updated_at: project.updated_at,
+            is_active: project.is_active,
+            archived_at: project.archived_at,
         }
     }
 }
 
+/// Project storage in a single BSON file. Reads are served from a
+/// `StorageCache` that is refreshed only when the file changed on disk.
 pub struct ProjectRepository {
     file_path: std::path::PathBuf,
+    cache: Arc<StorageCache<ProjectFileStorageData>>,
 }
 
 impl IsSync for ProjectRepository {}
@@ -135,8 +102,36 @@ impl ProjectRepository {
     pub fn new(file_path: &Path) -> Self {
         ProjectRepository {
             file_path: std::path::PathBuf::from(file_path),
+            cache: Arc::new(StorageCache::new()),
         }
     }
+
+    /// Applies `f` to the stored project with `id` and saves the storage.
+    async fn modify<F>(&self, id: u64, f: F) -> Result<Option<models::Project>>
+    where
+        F: FnOnce(&mut Project) + Send + 'static,
+    {
+        let file_path = self.file_path.clone();
+        let cache = self.cache.clone();
+
+        unblock(move || {
+            let mut storage = cache
+                .open_exclusive(&file_path)
+                .context("Failed to open_exclusive storage")?;
+
+            let Some(project) = storage.data.projects.iter_mut().find(|p| p.id == id) else {
+                return Ok(None);
+            };
+
+            f(project);
+
+            let project = project.clone();
+            cache.save(storage).context("Failed to save storage")?;
+
+            Ok(Some(project.into()))
+        })
+        .await
+    }
 }
 
 #[async_trait]
@@ -153,15 +148,18 @@ impl ports::ProjectRepository for ProjectRepository {
         };
 
         let file_path = self.file_path.clone();
+        let cache = self.cache.clone();
 
         unblock(move || {
-            let mut storage = ProjectFileStorage::open_exclusive(&file_path)
+            let mut storage = cache
+                .open_exclusive(&file_path)
                 .context("Failed to open_exclusive storage")?;
 
-            project.id = (storage.data.projects.len() as u64) + 1;
+            storage.data.last_id += 1;
+            project.id = storage.data.last_id;
 
             storage.data.projects.push(project.clone());
-            storage.save().context("Failed to save storage")?;
+            cache.save(storage).context("Failed to save storage")?;
 
             Ok(project.into())
         })
@@ -169,36 +167,97 @@ impl ports::ProjectRepository for ProjectRepository {
     }
 
     async fn get(&self, id: u64) -> Result<Option<models::Project>> {
-        if id == 0 {
-            return Ok(None);
-        }
         let file_path = self.file_path.clone();
+        let cache = self.cache.clone();
 
-        let data: ProjectFileStorageData = unblock(move || {
-            ProjectFileStorage::read_data(&file_path).context("Failed to open_shared storage")
-        })
-        .await?;
+        let data =
+            unblock(move || cache.read(&file_path).context("Failed to read storage")).await?;
 
-        let item = data.projects.get((id - 1) as usize);
+        let item = data.projects.iter().find(|p| p.id == id).cloned();
 
-        Ok(item.cloned().map(Into::into))
+        Ok(item.map(Into::into))
     }
 
-    async fn list(&self) -> Result<Vec<models::Project>> {
+    async fn list(
+        &self,
+        filter: ports::ProjectFilter,
+        sort: ports::ProjectSort,
+    ) -> Result<Vec<models::Project>> {
         let file_path = self.file_path.clone();
+        let cache = self.cache.clone();
 
-        let data: ProjectFileStorageData = unblock(move || {
-            ProjectFileStorage::read_data(&file_path).context("Failed to open_shared storage")
-        })
-        .await?;
+        let data =
+            unblock(move || cache.read(&file_path).context("Failed to read storage")).await?;
 
-        Ok(data
+        let mut projects = data
             .projects
             .iter()
-            .rev()
             .cloned()
             .map(Into::into)
-            .collect())
+            .filter(|p| filter.matches(p))
+            .collect::<Vec<models::Project>>();
+
+        sort.sort(&mut projects);
+
+        Ok(projects)
+    }
+
+    async fn update(
+        &self,
+        id: u64,
+        data: ports::UpdateProjectData<'_>,
+    ) -> Result<Option<models::Project>> {
+        let name = data.name.map(ToString::to_string);
+        let is_active = data.is_active;
+        let now = OffsetDateTime::now_utc();
+
+        self.modify(id, move |project| {
+            if let Some(name) = name {
+                project.name = name;
+            }
+            if let Some(is_active) = is_active {
+                project.set_active(is_active, now);
+            }
+            project.updated_at = now;
+        })
+        .await
+    }
+
+    async fn archive(&self, id: u64) -> Result<Option<models::Project>> {
+        let now = OffsetDateTime::now_utc();
+
+        self.modify(id, move |project| project.set_active(false, now))
+            .await
+    }
+
+    async fn unarchive(&self, id: u64) -> Result<Option<models::Project>> {
+        let now = OffsetDateTime::now_utc();
+
+        self.modify(id, move |project| project.set_active(true, now))
+            .await
+    }
+
+    async fn set_updated_at(&self, updates: &[(u64, OffsetDateTime)]) -> Result<()> {
+        let updates = updates.to_vec();
+        let file_path = self.file_path.clone();
+        let cache = self.cache.clone();
+
+        unblock(move || {
+            let mut storage = cache
+                .open_exclusive(&file_path)
+                .context("Failed to open_exclusive storage")?;
+
+            for (id, updated_at) in updates {
+                if let Some(project) = storage.data.projects.iter_mut().find(|p| p.id == id) {
+                    project.updated_at = updated_at;
+                }
+            }
+
+            cache.save(storage).context("Failed to save storage")?;
+
+            Ok(())
+        })
+        .await
     }
 }
 
@@ -206,6 +265,7 @@ impl ports::ProjectRepository for ProjectRepository {
 mod tests {
     use super::*;
     use crate::project_repository_test;
+    use crate::repositories::file_storage;
 
     struct ProjectRepositoryTest {
         repo: ProjectRepository,
@@ -214,7 +274,7 @@ mod tests {
 
     impl Drop for ProjectRepositoryTest {
         fn drop(&mut self) {
-            let _ = std::fs::remove_file(&self.path);
+            file_storage::remove_files(&self.path);
         }
     }
 
@@ -228,8 +288,32 @@ mod tests {
             self.repo.get(id).await
         }
 
-        async fn list(&self) -> Result<Vec<models::Project>> {
-            self.repo.list().await
+        async fn list(
+            &self,
+            filter: ports::ProjectFilter,
+            sort: ports::ProjectSort,
+        ) -> Result<Vec<models::Project>> {
+            self.repo.list(filter, sort).await
+        }
+
+        async fn update(
+            &self,
+            id: u64,
+            data: ports::UpdateProjectData<'_>,
+        ) -> Result<Option<models::Project>> {
+            self.repo.update(id, data).await
+        }
+
+        async fn archive(&self, id: u64) -> Result<Option<models::Project>> {
+            self.repo.archive(id).await
+        }
+
+        async fn unarchive(&self, id: u64) -> Result<Option<models::Project>> {
+            self.repo.unarchive(id).await
+        }
+
+        async fn set_updated_at(&self, updates: &[(u64, OffsetDateTime)]) -> Result<()> {
+            self.repo.set_updated_at(updates).await
         }
     }
 
@@ -241,4 +325,91 @@ mod tests {
             path,
         }
     }}
+
+    #[tokio::test]
+    async fn create_after_legacy_file_skips_stored_ids() {
+        let name = format!("test_Projects_{}.bson", rand::random::<u32>());
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tmp")
+            .join(name);
+        let repo = ProjectRepositoryTest {
+            repo: ProjectRepository::new(&path),
+            path: path.clone(),
+        };
+
+        let now = OffsetDateTime::now_utc();
+        let projects = [1, 3]
+            .into_iter()
+            .map(|id| Project {
+                id,
+                name: format!("Project {}", id),
+                created_at: now,
+                updated_at: now,
+                is_active: true,
+                archived_at: None,
+            })
+            .collect::<Vec<_>>();
+        let legacy = bson::doc! { "projects": bson::to_bson(&projects).unwrap() };
+        std::fs::write(&path, bson::to_vec(&legacy).unwrap()).expect("Failed to write file");
+
+        let project = ports::ProjectRepository::create(
+            &repo,
+            ports::CreateProjectData {
+                name: "New project",
+            },
+        )
+        .await
+        .expect("Failed to create project");
+
+        assert_eq!(project.id, 4);
+        assert_eq!(
+            ports::ProjectRepository::get(&repo, 3)
+                .await
+                .expect("Failed to get project")
+                .map(|p| p.name),
+            Some("Project 3".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn cache_sees_writes_of_other_repositories() {
+        let name = format!("test_Projects_{}.bson", rand::random::<u32>());
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tmp")
+            .join(name);
+        let repo = ProjectRepositoryTest {
+            repo: ProjectRepository::new(&path),
+            path: path.clone(),
+        };
+        let other = ProjectRepository::new(&path);
+
+        let project =
+            ports::ProjectRepository::create(&repo, ports::CreateProjectData { name: "Project" })
+                .await
+                .expect("Failed to create project");
+        assert_eq!(
+            ports::ProjectRepository::get(&other, project.id)
+                .await
+                .expect("Failed to get project"),
+            Some(project.clone())
+        );
+
+        let renamed = ports::ProjectRepository::update(
+            &other,
+            project.id,
+            ports::UpdateProjectData {
+                name: Some("Renamed"),
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("Failed to update project");
+
+        assert_eq!(
+            ports::ProjectRepository::get(&repo, project.id)
+                .await
+                .expect("Failed to get project"),
+            renamed
+        );
+    }
 }
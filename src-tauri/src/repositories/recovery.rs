@@ -0,0 +1,198 @@
+use std::path::{Path, PathBuf};
+
+use super::activity::ActivityFileStorage;
+use super::group::GroupFileStorage;
+use super::history::HistoryFileStorage;
+use super::project::ProjectFileStorage;
+use super::settings::SettingsFileStorage;
+use super::todo::TodoFileStorage;
+use super::trash::TrashFileStorage;
+use crate::models;
+use crate::ports;
+use crate::result::{ErrorContext, Result};
+use crate::utils::{IsSend, IsSync};
+use async_trait::async_trait;
+use blocking::unblock;
+use time::OffsetDateTime;
+
+/// Startup check of every storage file: projects, groups, todos,
+/// settings, history, activity and trash.
+pub struct RecoveryRepository {
+    projects_path: PathBuf,
+    groups_path: PathBuf,
+    todos_path: PathBuf,
+    settings_path: PathBuf,
+    history_path: PathBuf,
+    activity_path: PathBuf,
+    trash_path: PathBuf,
+}
+
+impl IsSync for RecoveryRepository {}
+impl IsSend for RecoveryRepository {}
+
+impl RecoveryRepository {
+    pub fn new(
+        projects_path: &Path,
+        groups_path: &Path,
+        todos_path: &Path,
+        settings_path: &Path,
+        history_path: &Path,
+        activity_path: &Path,
+        trash_path: &Path,
+    ) -> Self {
+        RecoveryRepository {
+            projects_path: projects_path.to_path_buf(),
+            groups_path: groups_path.to_path_buf(),
+            todos_path: todos_path.to_path_buf(),
+            settings_path: settings_path.to_path_buf(),
+            history_path: history_path.to_path_buf(),
+            activity_path: activity_path.to_path_buf(),
+            trash_path: trash_path.to_path_buf(),
+        }
+    }
+}
+
+#[async_trait]
+impl ports::RecoveryRepository for RecoveryRepository {
+    async fn recover(&self, at: OffsetDateTime) -> Result<Vec<models::RecoveredFile>> {
+        let projects_path = self.projects_path.clone();
+        let groups_path = self.groups_path.clone();
+        let todos_path = self.todos_path.clone();
+        let settings_path = self.settings_path.clone();
+        let history_path = self.history_path.clone();
+        let activity_path = self.activity_path.clone();
+        let trash_path = self.trash_path.clone();
+
+        unblock(move || {
+            let recovered = [
+                ProjectFileStorage::recover(&projects_path, at)
+                    .context("Failed to check project storage")?,
+                GroupFileStorage::recover(&groups_path, at)
+                    .context("Failed to check group storage")?,
+                TodoFileStorage::recover(&todos_path, at)
+                    .context("Failed to check todo storage")?,
+                SettingsFileStorage::recover(&settings_path, at)
+                    .context("Failed to check settings storage")?,
+                HistoryFileStorage::recover(&history_path, at)
+                    .context("Failed to check history storage")?,
+                ActivityFileStorage::recover(&activity_path, at)
+                    .context("Failed to check activity storage")?,
+                TrashFileStorage::recover(&trash_path, at)
+                    .context("Failed to check trash storage")?,
+            ];
+
+            Ok(recovered.into_iter().flatten().collect())
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports::{
+        ProjectFilter, ProjectRepository as _, ProjectSort, RecoveryRepository as _,
+    };
+    use crate::repositories::{file_storage, ProjectRepository};
+
+    struct TestFiles {
+        paths: [PathBuf; 7],
+    }
+
+    impl TestFiles {
+        fn new() -> Self {
+            let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tmp");
+            let suffix = rand::random::<u32>();
+
+            TestFiles {
+                paths: [
+                    "Projects", "Groups", "Todos", "Settings", "History", "Activity", "Trash",
+                ]
+                .map(|name| dir.join(format!("test_Recovery_{}_{}.bson", name, suffix))),
+            }
+        }
+
+        fn repository(&self) -> RecoveryRepository {
+            RecoveryRepository::new(
+                &self.paths[0],
+                &self.paths[1],
+                &self.paths[2],
+                &self.paths[3],
+                &self.paths[4],
+                &self.paths[5],
+                &self.paths[6],
+            )
+        }
+    }
+
+    impl Drop for TestFiles {
+        fn drop(&mut self) {
+            for path in &self.paths {
+                file_storage::remove_files(path);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn recover_leaves_readable_files() {
+        let files = TestFiles::new();
+        let projects = ProjectRepository::new(&files.paths[0]);
+        projects
+            .create(ports::CreateProjectData { name: "Project" })
+            .await
+            .expect("Failed to create project");
+
+        let recovered = files
+            .repository()
+            .recover(OffsetDateTime::now_utc())
+            .await
+            .expect("Failed to recover");
+
+        assert_eq!(recovered, vec![]);
+    }
+
+    #[tokio::test]
+    async fn recover_salvages_cut_off_file() {
+        let files = TestFiles::new();
+        let projects = ProjectRepository::new(&files.paths[0]);
+        for i in 0..20 {
+            projects
+                .create(ports::CreateProjectData {
+                    name: &format!("Project {}", i),
+                })
+                .await
+                .expect("Failed to create project");
+        }
+        let bytes = std::fs::read(&files.paths[0]).expect("Failed to read projects");
+        std::fs::write(&files.paths[0], &bytes[..bytes.len() / 2]).expect("Failed to cut projects");
+
+        let at = time::macros::datetime!(2024-03-01 12:00 UTC);
+        let recovered = files
+            .repository()
+            .recover(at)
+            .await
+            .expect("Failed to recover");
+
+        assert_eq!(recovered.len(), 1);
+        let file = &recovered[0];
+        assert_eq!(file.path, files.paths[0]);
+        let quarantine_path = file.quarantine_path.as_ref().expect("Not quarantined");
+        assert_eq!(
+            std::fs::read(quarantine_path).expect("Failed to read quarantine"),
+            &bytes[..bytes.len() / 2]
+        );
+
+        let salvaged = projects
+            .list(ProjectFilter::All, ProjectSort::default())
+            .await
+            .expect("Failed to list projects");
+        assert_eq!(salvaged.len(), file.salvaged);
+        assert!(file.salvaged > 0 && file.salvaged < 20);
+
+        let created = projects
+            .create(ports::CreateProjectData { name: "After" })
+            .await
+            .expect("Failed to create project");
+        assert!(salvaged.iter().all(|p| p.id < created.id));
+    }
+}
@@ -0,0 +1,223 @@
+//! Reading what is left of a damaged BSON document.
+//!
+//! `document` walks the elements one by one and keeps each one that still
+//! decodes, so a file cut off in the middle of a write yields everything
+//! before the cut. An embedded document or array that does not decode as
+//! a whole is salvaged the same way, element by element.
+
+use serde::de::DeserializeOwned;
+
+const DOCUMENT: u8 = 0x03;
+const ARRAY: u8 = 0x04;
+
+/// Elements of the document at the start of `bytes` that can be decoded.
+pub fn document(bytes: &[u8]) -> bson::Document {
+    let mut document = bson::Document::new();
+    let end = read_len(bytes).map_or(bytes.len(), |len| len.min(bytes.len()));
+    let mut offset = 4;
+
+    while offset < end && bytes[offset] != 0 {
+        let kind = bytes[offset];
+        let Some(key_end) = bytes[offset + 1..end]
+            .iter()
+            .position(|b| *b == 0)
+            .map(|i| offset + 1 + i)
+        else {
+            break;
+        };
+        let Ok(key) = std::str::from_utf8(&bytes[offset + 1..key_end]) else {
+            break;
+        };
+        let start = key_end + 1;
+
+        let Some(value_end) = value_len(kind, &bytes[start..end])
+            .map(|len| start + len)
+            .filter(|value_end| *value_end <= end)
+        else {
+            // Cut off: only the beginning of an embedded document is left.
+            if kind == DOCUMENT || kind == ARRAY {
+                document.insert(key, nested(kind, &bytes[start..end]));
+            }
+            break;
+        };
+
+        if let Some(value) = decode(&bytes[offset..value_end]) {
+            document.insert(key, value);
+        } else if kind == DOCUMENT || kind == ARRAY {
+            document.insert(key, nested(kind, &bytes[start..value_end]));
+        }
+
+        offset = value_end;
+    }
+
+    document
+}
+
+/// Items of the `key` array that still decode as `T`, with the number of
+/// items that did not.
+pub fn items<T: DeserializeOwned>(document: &bson::Document, key: &str) -> (Vec<T>, usize) {
+    let Ok(values) = document.get_array(key) else {
+        return (Vec::new(), 0);
+    };
+
+    let mut items = Vec::with_capacity(values.len());
+    let mut dropped = 0;
+    for value in values {
+        match bson::from_bson(value.clone()) {
+            Ok(item) => items.push(item),
+            Err(_) => dropped += 1,
+        }
+    }
+
+    (items, dropped)
+}
+
+/// `last_id` of the document, raised to the largest of `ids` in case the
+/// counter itself was lost.
+pub fn last_id(document: &bson::Document, ids: impl Iterator<Item = u64>) -> u64 {
+    let stored = document
+        .get("last_id")
+        .and_then(|id| id.as_i64().or_else(|| id.as_i32().map(i64::from)))
+        .and_then(|id| u64::try_from(id).ok())
+        .unwrap_or(0);
+
+    ids.fold(stored, u64::max)
+}
+
+fn read_len(bytes: &[u8]) -> Option<usize> {
+    let len = i32::from_le_bytes(bytes.get(..4)?.try_into().ok()?);
+
+    usize::try_from(len).ok()
+}
+
+/// Size of a value of type `kind` at the start of `bytes`, or `None` when
+/// the type is unknown or its size is cut off.
+fn value_len(kind: u8, bytes: &[u8]) -> Option<usize> {
+    match kind {
+        // Double, datetime, timestamp, int64.
+        0x01 | 0x09 | 0x11 | 0x12 => Some(8),
+        // String, JavaScript code, symbol.
+        0x02 | 0x0D | 0x0E => Some(4 + read_len(bytes)?),
+        // Document, array, code with scope.
+        DOCUMENT | ARRAY | 0x0F => read_len(bytes),
+        // Binary with its subtype.
+        0x05 => Some(5 + read_len(bytes)?),
+        // Undefined, null, max key, min key.
+        0x06 | 0x0A | 0x7F | 0xFF => Some(0),
+        // ObjectId.
+        0x07 => Some(12),
+        // Boolean.
+        0x08 => Some(1),
+        // Regular expression: pattern and options.
+        0x0B => {
+            let pattern = bytes.iter().position(|b| *b == 0)? + 1;
+            let options = bytes[pattern..].iter().position(|b| *b == 0)? + 1;
+            Some(pattern + options)
+        }
+        // DB pointer: namespace and ObjectId.
+        0x0C => Some(4 + read_len(bytes)? + 12),
+        // Int32.
+        0x10 => Some(4),
+        // Decimal128.
+        0x13 => Some(16),
+        _ => None,
+    }
+}
+
+/// Decodes a single element by wrapping it into a document of its own.
+fn decode(element: &[u8]) -> Option<bson::Bson> {
+    let len = i32::try_from(element.len() + 5).ok()?;
+
+    let mut bytes = Vec::with_capacity(element.len() + 5);
+    bytes.extend_from_slice(&len.to_le_bytes());
+    bytes.extend_from_slice(element);
+    bytes.push(0);
+
+    let document = bson::Document::from_reader(bytes.as_slice()).ok()?;
+
+    document.into_iter().next().map(|(_, value)| value)
+}
+
+fn nested(kind: u8, bytes: &[u8]) -> bson::Bson {
+    let document = document(bytes);
+
+    if kind == ARRAY {
+        bson::Bson::Array(document.into_iter().map(|(_, value)| value).collect())
+    } else {
+        bson::Bson::Document(document)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Item {
+        id: u64,
+        name: String,
+    }
+
+    fn records(n: u64) -> bson::Document {
+        let items = (1..=n)
+            .map(|id| bson::bson!({ "id": id as i64, "name": format!("item {}", id) }))
+            .collect::<Vec<_>>();
+
+        bson::doc! { "items": items, "last_id": n as i64, "version": 1 }
+    }
+
+    #[test]
+    fn document_reads_intact_document() {
+        let bytes = bson::to_vec(&records(3)).expect("serialize");
+
+        assert_eq!(document(&bytes), records(3));
+    }
+
+    #[test]
+    fn document_keeps_records_before_cut() {
+        let bytes = bson::to_vec(&records(10)).expect("serialize");
+
+        let salvaged = document(&bytes[..bytes.len() / 2]);
+        let (items, dropped) = items::<Item>(&salvaged, "items");
+
+        assert!(items.len() >= 3, "salvaged only {:?}", items);
+        assert!(items.len() < 10);
+        assert!(dropped <= 1);
+        assert_eq!(
+            items[0],
+            Item {
+                id: 1,
+                name: "item 1".into()
+            }
+        );
+        assert_eq!(
+            last_id(&salvaged, items.iter().map(|i| i.id)),
+            items.len() as u64
+        );
+    }
+
+    #[test]
+    fn items_drops_invalid_records() {
+        let damaged = bson::doc! {
+            "items": [
+                { "id": 1_i64, "name": "first" },
+                { "id": "two", "name": "second" },
+                { "id": 3_i64, "name": "third" },
+            ],
+            "last_id": 4_i64,
+        };
+
+        let (items, dropped) = items::<Item>(&damaged, "items");
+
+        assert_eq!(items.iter().map(|i| i.id).collect::<Vec<_>>(), [1, 3]);
+        assert_eq!(dropped, 1);
+        assert_eq!(last_id(&damaged, items.iter().map(|i| i.id)), 4);
+    }
+
+    #[test]
+    fn document_of_garbage_is_empty() {
+        assert_eq!(document(b"not a bson document"), bson::Document::new());
+        assert_eq!(document(b""), bson::Document::new());
+    }
+}
@@ -0,0 +1,162 @@
+use std::path::Path;
+
+use super::file_storage::{FileStorage, Migration, Salvaged, StorageData};
+use super::salvage;
+use crate::models;
+use crate::ports;
+use crate::result::{ErrorContext, Result};
+use crate::utils::{IsSend, IsSync};
+use async_trait::async_trait;
+use blocking::unblock;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(super) struct SettingsFileStorageData {
+    default_groups: Vec<String>,
+    activity_retention_days: u32,
+    trash_retention_days: u32,
+}
+
+impl Default for SettingsFileStorageData {
+    fn default() -> Self {
+        let settings = models::Settings::default();
+
+        SettingsFileStorageData {
+            default_groups: settings.default_groups,
+            activity_retention_days: settings.activity_retention_days,
+            trash_retention_days: settings.trash_retention_days,
+        }
+    }
+}
+
+impl StorageData for SettingsFileStorageData {
+    const MIGRATIONS: &'static [Migration] = &[];
+
+    fn salvage(document: &bson::Document) -> Salvaged<Self> {
+        let (default_groups, dropped) = salvage::items(document, "default_groups");
+        let defaults = Self::default();
+        let days = |key, default| {
+            document
+                .get(key)
+                .and_then(|days| bson::from_bson(days.clone()).ok())
+                .unwrap_or(default)
+        };
+
+        Salvaged {
+            kept: default_groups.len(),
+            data: SettingsFileStorageData {
+                default_groups,
+                activity_retention_days: days(
+                    "activity_retention_days",
+                    defaults.activity_retention_days,
+                ),
+                trash_retention_days: days("trash_retention_days", defaults.trash_retention_days),
+            },
+            dropped,
+        }
+    }
+}
+
+pub(super) type SettingsFileStorage = FileStorage<SettingsFileStorageData>;
+
+impl From<SettingsFileStorageData> for models::Settings {
+    fn from(data: SettingsFileStorageData) -> Self {
+        models::Settings {
+            default_groups: data.default_groups,
+            activity_retention_days: data.activity_retention_days,
+            trash_retention_days: data.trash_retention_days,
+        }
+    }
+}
+
+pub struct SettingsRepository {
+    file_path: std::path::PathBuf,
+}
+
+impl IsSync for SettingsRepository {}
+impl IsSend for SettingsRepository {}
+
+impl SettingsRepository {
+    pub fn new(file_path: &Path) -> Self {
+        SettingsRepository {
+            file_path: std::path::PathBuf::from(file_path),
+        }
+    }
+}
+
+#[async_trait]
+impl ports::SettingsRepository for SettingsRepository {
+    async fn get(&self) -> Result<models::Settings> {
+        let file_path = self.file_path.clone();
+
+        let data: SettingsFileStorageData = unblock(move || {
+            SettingsFileStorage::read_data(&file_path).context("Failed to read storage")
+        })
+        .await?;
+
+        Ok(data.into())
+    }
+
+    async fn update(&self, data: ports::UpdateSettingsData) -> Result<models::Settings> {
+        let file_path = self.file_path.clone();
+
+        unblock(move || {
+            let mut storage = SettingsFileStorage::open_exclusive(&file_path)
+                .context("Failed to open_exclusive storage")?;
+
+            if let Some(default_groups) = data.default_groups {
+                storage.data.default_groups = default_groups;
+            }
+            if let Some(days) = data.activity_retention_days {
+                storage.data.activity_retention_days = days;
+            }
+            if let Some(days) = data.trash_retention_days {
+                storage.data.trash_retention_days = days;
+            }
+
+            storage.save().context("Failed to save storage")?;
+
+            Ok(storage.data.clone().into())
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::file_storage;
+    use crate::settings_repository_test;
+
+    struct SettingsRepositoryTest {
+        repo: SettingsRepository,
+        path: std::path::PathBuf,
+    }
+
+    impl Drop for SettingsRepositoryTest {
+        fn drop(&mut self) {
+            file_storage::remove_files(&self.path);
+        }
+    }
+
+    #[async_trait]
+    impl ports::SettingsRepository for SettingsRepositoryTest {
+        async fn get(&self) -> Result<models::Settings> {
+            self.repo.get().await
+        }
+
+        async fn update(&self, data: ports::UpdateSettingsData) -> Result<models::Settings> {
+            self.repo.update(data).await
+        }
+    }
+
+    settings_repository_test! {{
+        let name = format!("test_Settings_{}.bson", rand::random::<u32>());
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tmp").join(name);
+        SettingsRepositoryTest {
+            repo: SettingsRepository::new(&path),
+            path,
+        }
+    }}
+}
@@ -0,0 +1,365 @@
+mod activity;
+mod backup;
+mod content;
+mod group;
+mod history;
+mod project;
+mod recovery;
+mod settings;
+mod todo;
+mod trash;
+mod watcher;
+pub use activity::SqliteActivityRepository;
+pub use backup::SqliteBackupRepository;
+pub use content::SqliteProjectContentRepository;
+pub use group::SqliteGroupRepository;
+pub use history::SqliteHistoryRepository;
+pub use project::SqliteProjectRepository;
+pub use recovery::SqliteRecoveryRepository;
+pub use settings::SqliteSettingsRepository;
+pub use todo::SqliteTodoRepository;
+pub use trash::SqliteTrashRepository;
+pub use watcher::SqliteStorageWatcher;
+
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::result::{ErrorContext, Result, StorageTooNew};
+use anyhow::{anyhow, Context};
+use blocking::unblock;
+use rusqlite::{Connection, Row};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use time::OffsetDateTime;
+
+/// Schema steps applied in order; `PRAGMA user_version` stores how many
+/// of them the database has already seen.
+///
+/// Steps run with foreign keys off, so a table can be rebuilt without
+/// cascading into the rows that reference it.
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE projects (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL,
+        is_active INTEGER NOT NULL,
+        archived_at TEXT
+    );
+
+    CREATE TABLE project_groups (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL,
+        position REAL NOT NULL,
+        is_opened INTEGER NOT NULL,
+        project_id INTEGER NOT NULL REFERENCES projects (id) ON DELETE CASCADE
+    );
+
+    CREATE INDEX project_groups_project_id ON project_groups (project_id, position);
+
+    CREATE TABLE todos (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        text TEXT NOT NULL,
+        position REAL NOT NULL,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL,
+        is_done INTEGER NOT NULL,
+        done_at TEXT,
+        group_id INTEGER NOT NULL REFERENCES project_groups (id) ON DELETE CASCADE
+    );
+
+    CREATE INDEX todos_group_id ON todos (group_id, position);
+",
+    "
+    CREATE TABLE settings (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+",
+    "
+    CREATE TABLE history_entries (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        stack TEXT NOT NULL CHECK (stack IN ('undo', 'redo')),
+        entry TEXT NOT NULL
+    );
+",
+    "
+    CREATE TABLE activities (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        at INTEGER NOT NULL,
+        project_id INTEGER NOT NULL,
+        entity TEXT NOT NULL,
+        entity_id INTEGER NOT NULL,
+        action TEXT NOT NULL,
+        undone INTEGER NOT NULL,
+        before TEXT,
+        after TEXT
+    );
+
+    CREATE INDEX activities_project_id ON activities (project_id, id);
+    CREATE INDEX activities_at ON activities (at);
+",
+    "
+    CREATE TABLE trash_items (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        deleted_at INTEGER NOT NULL,
+        entity TEXT NOT NULL,
+        entity_id INTEGER NOT NULL,
+        entities TEXT NOT NULL
+    );
+
+    CREATE INDEX trash_items_deleted_at ON trash_items (deleted_at);
+",
+];
+
+/// Shared connection to the SQLite database used by all `Sqlite*Repository`.
+#[derive(Clone)]
+pub struct SqliteDatabase {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteDatabase {
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path).context(format!("Failed to open {}", path.display()))?;
+
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .context("Failed to enable WAL")?;
+
+        Self::init(conn, Some(path))
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self> {
+        let conn = Connection::open_in_memory().context("Failed to open in-memory database")?;
+
+        Self::init(conn, None)
+    }
+
+    fn init(mut conn: Connection, path: Option<&Path>) -> Result<Self> {
+        conn.busy_timeout(std::time::Duration::from_secs(5))
+            .context("Failed to set busy timeout")?;
+
+        migrate(&mut conn, path).context("Failed to migrate database")?;
+
+        conn.pragma_update(None, "foreign_keys", true)
+            .context("Failed to enable foreign keys")?;
+
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Locks the connection for the calling thread.
+    fn lock(&self) -> Result<MutexGuard<'_, Connection>> {
+        Ok(self
+            .conn
+            .lock()
+            .map_err(|_| anyhow!("Database connection is poisoned"))?)
+    }
+
+    /// Runs `f` with the connection on the blocking thread pool.
+    async fn call<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+
+        unblock(move || {
+            let mut conn = conn
+                .lock()
+                .map_err(|_| anyhow!("Database connection is poisoned"))?;
+
+            f(&mut conn)
+        })
+        .await
+    }
+}
+
+/// Name a unit variant such as `models::EntityKind::Todo` serializes to.
+fn to_name<T: Serialize>(value: &T) -> Result<String> {
+    match serde_json::to_value(value).context("Failed to encode name")? {
+        serde_json::Value::String(name) => Ok(name),
+        value => Err(anyhow!("Expected a name, got {}", value).into()),
+    }
+}
+
+fn from_name<T: DeserializeOwned>(row: &Row<'_>, column: &str) -> rusqlite::Result<T> {
+    let name: String = row.get(column)?;
+
+    serde_json::from_value(serde_json::Value::String(name)).map_err(|error| {
+        rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, error.into())
+    })
+}
+
+/// Milliseconds since the Unix epoch, how `activities.at` and
+/// `trash_items.deleted_at` are stored so they compare in SQL.
+fn to_unix_ms(at: OffsetDateTime) -> i64 {
+    at.unix_timestamp_nanos().div_euclid(1_000_000) as i64
+}
+
+fn from_unix_ms(row: &Row<'_>, column: &str) -> rusqlite::Result<OffsetDateTime> {
+    let ms: i64 = row.get(column)?;
+
+    OffsetDateTime::from_unix_timestamp_nanos(i128::from(ms) * 1_000_000).map_err(|error| {
+        rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Integer, error.into())
+    })
+}
+
+fn from_json<T: DeserializeOwned>(row: &Row<'_>, column: &str) -> rusqlite::Result<Option<T>> {
+    let Some(json) = row.get::<_, Option<String>>(column)? else {
+        return Ok(None);
+    };
+
+    serde_json::from_str(&json).map(Some).map_err(|error| {
+        rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, error.into())
+    })
+}
+
+/// Brings the schema to the latest version.
+///
+/// A database file with an older, non-empty schema is copied to
+/// `<file>.v<version>.bak` before any migration runs.
+fn migrate(conn: &mut Connection, path: Option<&Path>) -> Result<()> {
+    let version: usize = conn
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .context("Failed to read user_version")?;
+
+    if version > MIGRATIONS.len() {
+        return Err(StorageTooNew {
+            path: path.unwrap_or(Path::new(":memory:")).to_path_buf(),
+            version,
+            supported: MIGRATIONS.len(),
+        }
+        .into());
+    }
+
+    if version == MIGRATIONS.len() {
+        return Ok(());
+    }
+
+    if let Some(path) = path.filter(|_| version > 0) {
+        let mut backup_path = path.to_path_buf().into_os_string();
+        backup_path.push(format!(".v{}.bak", version));
+
+        if !Path::new(&backup_path).exists() {
+            conn.execute("VACUUM INTO ?1", [backup_path.to_string_lossy()])
+                .context("Failed to backup database")?;
+        }
+    }
+
+    // Some builds enable foreign keys by default, and the pragma is a no-op
+    // inside a transaction.
+    conn.pragma_update(None, "foreign_keys", false)
+        .context("Failed to disable foreign keys")?;
+
+    let tx = conn.transaction().context("Failed to begin transaction")?;
+
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        tx.execute_batch(migration)
+            .context(format!("Failed to apply migration {}", i + 1))?;
+    }
+
+    let violations = tx
+        .prepare("PRAGMA foreign_key_check")
+        .and_then(|mut stmt| stmt.exists([]))
+        .context("Failed to check foreign keys")?;
+    if violations {
+        return Err(anyhow!("Migrations left rows with dangling foreign keys").into());
+    }
+
+    tx.pragma_update(None, "user_version", MIGRATIONS.len())
+        .context("Failed to write user_version")?;
+    tx.commit().context("Failed to commit migrations")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_file_twice() {
+        let name = format!("test_Database_{}.sqlite3", rand::random::<u32>());
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tmp")
+            .join(name);
+
+        let _guard = scopeguard::guard(path.clone(), |path| {
+            for suffix in ["", "-wal", "-shm"] {
+                let mut name = path.clone().into_os_string();
+                name.push(suffix);
+                let _ = std::fs::remove_file(name);
+            }
+        });
+
+        drop(SqliteDatabase::open(&path).expect("Failed to create database"));
+        let db = SqliteDatabase::open(&path).expect("Failed to reopen database");
+
+        let version: usize = db
+            .conn
+            .lock()
+            .unwrap()
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .expect("Failed to read user_version");
+
+        assert_eq!(version, MIGRATIONS.len());
+    }
+
+    #[test]
+    fn open_rejects_newer_version() {
+        let conn = Connection::open_in_memory().expect("Failed to open database");
+        conn.pragma_update(None, "user_version", MIGRATIONS.len() + 1)
+            .expect("Failed to write user_version");
+
+        let error = SqliteDatabase::init(conn, None)
+            .err()
+            .expect("open should fail");
+
+        assert!(
+            format!("{:#}", error).contains("newer version"),
+            "unexpected error {:#}",
+            error
+        );
+    }
+
+    #[test]
+    fn positions_are_stored_as_real() {
+        let db = SqliteDatabase::open_in_memory().expect("Failed to open database");
+        let conn = db.conn.lock().unwrap();
+        conn.execute_batch(
+            "INSERT INTO projects (id, name, created_at, updated_at, is_active)
+                 VALUES (1, 'Project', '', '', 1);
+             INSERT INTO project_groups (id, name, position, is_opened, project_id)
+                 VALUES (1, 'Group', 1, 1, 1);
+             INSERT INTO todos (id, text, position, created_at, updated_at, is_done, group_id)
+                 VALUES (1, 'Todo', 1, '', '', 0, 1);",
+        )
+        .expect("Failed to insert rows");
+
+        let types: Vec<String> = ["project_groups", "todos"]
+            .iter()
+            .map(|table| {
+                let sql = format!("SELECT typeof(position) FROM {}", table);
+                conn.query_row(&sql, [], |row| row.get(0))
+                    .expect("position")
+            })
+            .collect();
+        assert_eq!(types, ["real", "real"]);
+    }
+
+    #[test]
+    fn foreign_keys_are_enforced() {
+        let db = SqliteDatabase::open_in_memory().expect("Failed to open database");
+
+        let result = db.conn.lock().unwrap().execute(
+            "INSERT INTO project_groups (name, position, is_opened, project_id)
+             VALUES ('Orphan', 1, 1, 42)",
+            [],
+        );
+
+        assert!(result.is_err(), "orphan group was inserted");
+    }
+}
@@ -0,0 +1,128 @@
+use super::{from_json, from_name, from_unix_ms, to_name, to_unix_ms, SqliteDatabase};
+use crate::models;
+use crate::ports;
+use crate::result::Result;
+use crate::utils::{IsSend, IsSync};
+use anyhow::Context;
+use async_trait::async_trait;
+use rusqlite::{params, Row};
+use time::OffsetDateTime;
+
+const COLUMNS: &str = "id, at, project_id, entity, entity_id, action, undone, before, after";
+
+fn from_row(row: &Row<'_>) -> rusqlite::Result<models::Activity> {
+    Ok(models::Activity {
+        id: row.get("id")?,
+        at: from_unix_ms(row, "at")?,
+        project_id: row.get("project_id")?,
+        entity: from_name(row, "entity")?,
+        entity_id: row.get("entity_id")?,
+        action: from_name(row, "action")?,
+        undone: row.get("undone")?,
+        before: from_json(row, "before")?,
+        after: from_json(row, "after")?,
+    })
+}
+
+/// Activities are kept in `activities`, with the entity states as JSON.
+pub struct SqliteActivityRepository {
+    db: SqliteDatabase,
+}
+
+impl IsSync for SqliteActivityRepository {}
+impl IsSend for SqliteActivityRepository {}
+
+impl SqliteActivityRepository {
+    pub fn new(db: SqliteDatabase) -> Self {
+        SqliteActivityRepository { db }
+    }
+}
+
+#[async_trait]
+impl ports::ActivityRepository for SqliteActivityRepository {
+    async fn append(&self, activities: Vec<ports::CreateActivityData>) -> Result<()> {
+        self.db
+            .call(move |conn| {
+                let tx = conn.transaction().context("Failed to begin transaction")?;
+
+                {
+                    let mut stmt = tx
+                        .prepare_cached(
+                            "INSERT INTO activities
+                             (at, project_id, entity, entity_id, action, undone, before, after)
+                             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                        )
+                        .context("Failed to prepare statement")?;
+
+                    for data in activities {
+                        stmt.execute(params![
+                            to_unix_ms(data.at),
+                            data.project_id,
+                            to_name(&data.entity)?,
+                            data.entity_id,
+                            to_name(&data.action)?,
+                            data.undone,
+                            data.before.map(|value| value.to_string()),
+                            data.after.map(|value| value.to_string()),
+                        ])
+                        .context("Failed to insert activity")?;
+                    }
+                }
+
+                tx.commit().context("Failed to commit transaction")?;
+
+                Ok(())
+            })
+            .await
+    }
+
+    async fn list(
+        &self,
+        project_id: u64,
+        before_id: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<models::Activity>> {
+        self.db
+            .call(move |conn| {
+                let activities = conn
+                    .prepare_cached(&format!(
+                        "SELECT {} FROM activities
+                         WHERE project_id = ?1 AND (?2 IS NULL OR id < ?2)
+                         ORDER BY id DESC
+                         LIMIT ?3",
+                        COLUMNS
+                    ))
+                    .and_then(|mut stmt| {
+                        stmt.query_map(params![project_id, before_id, limit], from_row)?
+                            .collect::<rusqlite::Result<Vec<_>>>()
+                    })
+                    .context("Failed to select activities")?;
+
+                Ok(activities)
+            })
+            .await
+    }
+
+    async fn prune(&self, before: OffsetDateTime) -> Result<usize> {
+        self.db
+            .call(move |conn| {
+                let pruned = conn
+                    .execute(
+                        "DELETE FROM activities WHERE at < ?1",
+                        params![to_unix_ms(before)],
+                    )
+                    .context("Failed to delete activities")?;
+
+                Ok(pruned)
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activity_repository_test;
+
+    activity_repository_test! {SqliteActivityRepository::new(SqliteDatabase::open_in_memory().expect("Failed to open database"))}
+}
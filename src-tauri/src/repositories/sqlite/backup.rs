@@ -0,0 +1,185 @@
+use std::path::{Path, PathBuf};
+
+use super::{migrate, SqliteDatabase};
+use crate::models;
+use crate::ports;
+use crate::repositories::backup::{
+    delete_backup, list_backups, new_backup_dir, publish, read_backup,
+};
+use crate::result::{ErrorContext, Result};
+use crate::utils::{IsSend, IsSync};
+use anyhow::Context;
+use async_trait::async_trait;
+use rusqlite::Connection;
+use time::OffsetDateTime;
+
+const FILE_NAME: &str = "Todo.sqlite3";
+
+/// Backups of the database: each one is a directory in `backups_dir` with
+/// a copy of the database written by `VACUUM INTO`.
+pub struct SqliteBackupRepository {
+    db: SqliteDatabase,
+    backups_dir: PathBuf,
+}
+
+impl IsSync for SqliteBackupRepository {}
+impl IsSend for SqliteBackupRepository {}
+
+impl SqliteBackupRepository {
+    pub fn new(db: SqliteDatabase, backups_dir: &Path) -> Self {
+        SqliteBackupRepository {
+            db,
+            backups_dir: backups_dir.to_path_buf(),
+        }
+    }
+}
+
+/// Tables of the schema, in an order that satisfies their foreign keys.
+fn tables(conn: &Connection) -> Result<Vec<String>> {
+    let tables = conn
+        .prepare("SELECT name FROM main.sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY rowid")
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()
+        })
+        .context("Failed to select tables")?;
+
+    Ok(tables)
+}
+
+#[async_trait]
+impl ports::BackupRepository for SqliteBackupRepository {
+    async fn create(&self, at: OffsetDateTime) -> Result<models::Backup> {
+        let backups_dir = self.backups_dir.clone();
+
+        self.db
+            .call(move |conn| {
+                let (id, dir) = new_backup_dir(&backups_dir, at)?;
+                publish(&dir, |tmp_dir| {
+                    let path = tmp_dir.join(FILE_NAME);
+                    conn.execute("VACUUM INTO ?1", [path.to_string_lossy()])
+                        .context("Failed to backup database")?;
+
+                    Ok(())
+                })?;
+
+                let backup = read_backup(&backups_dir, id)?
+                    .context("Backup disappeared after it was written")?;
+
+                Ok(backup)
+            })
+            .await
+    }
+
+    async fn list(&self) -> Result<Vec<models::Backup>> {
+        let backups_dir = self.backups_dir.clone();
+
+        blocking::unblock(move || list_backups(&backups_dir)).await
+    }
+
+    async fn restore(&self, id: u64) -> Result<Option<models::Backup>> {
+        let backups_dir = self.backups_dir.clone();
+
+        self.db
+            .call(move |conn| {
+                let Some(backup) = read_backup(&backups_dir, id)? else {
+                    return Ok(None);
+                };
+
+                // The backup may predate migrations, so a copy of it is
+                // migrated first and the backup itself stays untouched.
+                let source = backups_dir.join(id.to_string()).join(FILE_NAME);
+                let copy = backups_dir.join(format!("{}.restore", id));
+                let _guard = scopeguard::guard(copy.clone(), |copy| {
+                    let _ = std::fs::remove_file(copy);
+                });
+                std::fs::copy(&source, &copy)
+                    .context(format!("Failed to copy {}", source.display()))?;
+
+                let mut copy_conn = Connection::open(&copy)
+                    .context(format!("Failed to open {}", copy.display()))?;
+                migrate(&mut copy_conn, None).context("Failed to migrate backup")?;
+                drop(copy_conn);
+
+                conn.execute("ATTACH DATABASE ?1 AS backup", [copy.to_string_lossy()])
+                    .context("Failed to attach backup")?;
+                let result = (|| -> Result<()> {
+                    let tx = conn.transaction().context("Failed to begin transaction")?;
+                    tx.pragma_update(None, "defer_foreign_keys", true)
+                        .context("Failed to defer foreign keys")?;
+
+                    for table in tables(&tx)?.iter().rev() {
+                        tx.execute(&format!("DELETE FROM main.{}", table), [])
+                            .context(format!("Failed to clear {}", table))?;
+                    }
+                    for table in tables(&tx)? {
+                        tx.execute(
+                            &format!("INSERT INTO main.{0} SELECT * FROM backup.{0}", table),
+                            [],
+                        )
+                        .context(format!("Failed to restore {}", table))?;
+                    }
+                    tx.execute_batch(
+                        "DELETE FROM main.sqlite_sequence;
+                         INSERT INTO main.sqlite_sequence SELECT * FROM backup.sqlite_sequence;",
+                    )
+                    .context("Failed to restore sequences")?;
+
+                    tx.commit().context("Failed to commit transaction")?;
+
+                    Ok(())
+                })();
+                conn.execute("DETACH DATABASE backup", [])
+                    .context("Failed to detach backup")?;
+                result?;
+
+                Ok(Some(backup))
+            })
+            .await
+    }
+
+    async fn delete(&self, id: u64) -> Result<Option<models::Backup>> {
+        let backups_dir = self.backups_dir.clone();
+
+        blocking::unblock(move || delete_backup(&backups_dir, id)).await
+    }
+
+    async fn write_count(&self) -> Result<u64> {
+        self.db
+            .call(|conn| {
+                let count = conn
+                    .query_row("SELECT total_changes()", [], |row| row.get(0))
+                    .context("Failed to count changes")?;
+
+                Ok(count)
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::backup_repository_test;
+    use crate::ports::repository_tests::BackupRepositories;
+    use crate::repositories::sqlite::SqliteProjectRepository;
+
+    fn repositories() -> BackupRepositories {
+        let db = SqliteDatabase::open_in_memory().expect("Failed to open database");
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tmp")
+            .join(format!("test_SqliteBackup_{}", rand::random::<u32>()));
+
+        BackupRepositories {
+            project: Arc::new(SqliteProjectRepository::new(db.clone())),
+            backup: Arc::new(SqliteBackupRepository::new(db, &dir)),
+            cleanup: Some(Box::new(move || {
+                let _ = std::fs::remove_dir_all(dir);
+            })),
+        }
+    }
+
+    backup_repository_test! {repositories()}
+}
@@ -0,0 +1,639 @@
+use std::collections::HashSet;
+
+use super::{group, project, to_name, to_unix_ms, todo, trash, SqliteDatabase};
+use crate::models;
+use crate::ports;
+use crate::position;
+use crate::repositories::content::stale;
+use crate::result::Result;
+use crate::utils::{IsSend, IsSync};
+use anyhow::Context;
+use async_trait::async_trait;
+use rusqlite::{params, OptionalExtension};
+use time::OffsetDateTime;
+
+pub struct SqliteProjectContentRepository {
+    db: SqliteDatabase,
+}
+
+impl IsSync for SqliteProjectContentRepository {}
+impl IsSend for SqliteProjectContentRepository {}
+
+impl SqliteProjectContentRepository {
+    pub fn new(db: SqliteDatabase) -> Self {
+        SqliteProjectContentRepository { db }
+    }
+}
+
+#[async_trait]
+impl ports::ProjectContentRepository for SqliteProjectContentRepository {
+    async fn create(
+        &self,
+        project: ports::CreateProjectData<'_>,
+        group_names: &[String],
+    ) -> Result<models::ProjectContent> {
+        let now = OffsetDateTime::now_utc();
+        let name = project.name.to_string();
+        let group_names = group_names.to_vec();
+
+        self.db
+            .call(move |conn| {
+                let tx = conn.transaction().context("Failed to begin transaction")?;
+
+                let project_id: u64 = tx
+                    .query_row(
+                        "INSERT INTO projects (name, created_at, updated_at, is_active)
+                         VALUES (?1, ?2, ?2, 1)
+                         RETURNING id",
+                        params![name, now],
+                        |row| row.get(0),
+                    )
+                    .context("Failed to insert project")?;
+
+                for (name, position) in group_names.iter().zip(position::spread(group_names.len()))
+                {
+                    tx.execute(
+                        "INSERT INTO project_groups (name, position, is_opened, project_id)
+                         VALUES (?1, ?2, 1, ?3)",
+                        params![name, position, project_id],
+                    )
+                    .context("Failed to insert group")?;
+                }
+
+                let content = read_content(&tx, project_id)?;
+                tx.commit().context("Failed to commit transaction")?;
+
+                Ok(content.context("Failed to create project")?)
+            })
+            .await
+    }
+
+    async fn get(&self, project_id: u64) -> Result<Option<models::ProjectContent>> {
+        self.db
+            .call(move |conn| {
+                let tx = conn.transaction().context("Failed to begin transaction")?;
+
+                read_content(&tx, project_id)
+            })
+            .await
+    }
+
+    async fn apply(
+        &self,
+        project_id: u64,
+        expected: Option<models::ProjectContent>,
+        changes: ports::ProjectChanges,
+    ) -> Result<Option<models::ProjectContent>> {
+        let now = OffsetDateTime::now_utc();
+
+        self.db
+            .call(move |conn| {
+                let tx = conn.transaction().context("Failed to begin transaction")?;
+
+                let Some(content) = read_content(&tx, project_id)? else {
+                    return Ok(None);
+                };
+                if expected.is_some_and(|expected| expected != content) {
+                    return Err(stale(project_id).into());
+                }
+                let project = content.project;
+
+                let group_ids = tx
+                    .prepare("SELECT id FROM project_groups WHERE project_id = ?1")
+                    .and_then(|mut stmt| {
+                        stmt.query_map(params![project_id], |row| row.get(0))?
+                            .collect::<rusqlite::Result<HashSet<u64>>>()
+                    })
+                    .context("Failed to select group ids")?;
+                let todo_ids = tx
+                    .prepare(
+                        "SELECT id FROM todos WHERE group_id IN
+                         (SELECT id FROM project_groups WHERE project_id = ?1)",
+                    )
+                    .and_then(|mut stmt| {
+                        stmt.query_map(params![project_id], |row| row.get(0))?
+                            .collect::<rusqlite::Result<HashSet<u64>>>()
+                    })
+                    .context("Failed to select todo ids")?;
+                changes.check_ids(&group_ids, &todo_ids)?;
+
+                tx.execute(
+                    "UPDATE projects SET name = COALESCE(?2, name), updated_at = ?3 WHERE id = ?1",
+                    params![project_id, changes.name, project.updated_at.max(now)],
+                )
+                .context("Failed to update project")?;
+
+                let mut created_ids = Vec::with_capacity(changes.create_groups.len());
+                for data in &changes.create_groups {
+                    let id: u64 = tx
+                        .query_row(
+                            "INSERT INTO project_groups (name, position, is_opened, project_id)
+                             VALUES (?1, ?2, ?3, ?4)
+                             RETURNING id",
+                            params![data.name, data.position, data.is_opened, project_id],
+                            |row| row.get(0),
+                        )
+                        .context("Failed to insert group")?;
+                    created_ids.push(id);
+                }
+
+                for data in &changes.update_groups {
+                    tx.execute(
+                        "UPDATE project_groups SET name = ?2, position = ?3, is_opened = ?4
+                         WHERE id = ?1",
+                        params![data.id, data.name, data.position, data.is_opened],
+                    )
+                    .context("Failed to update group")?;
+                }
+
+                let group_id = |group| match group {
+                    ports::GroupRef::Stored(id) => id,
+                    ports::GroupRef::Created(index) => created_ids[index],
+                };
+
+                for data in &changes.create_todos {
+                    tx.execute(
+                        "INSERT INTO todos
+                             (text, position, created_at, updated_at, is_done, done_at, group_id)
+                         VALUES (?1, ?2, ?3, ?3, ?4, ?5, ?6)",
+                        params![
+                            data.text,
+                            data.position,
+                            now,
+                            data.is_done,
+                            data.is_done.then_some(now),
+                            group_id(data.group)
+                        ],
+                    )
+                    .context("Failed to insert todo")?;
+                }
+
+                for data in &changes.update_todos {
+                    tx.execute(
+                        "UPDATE todos
+                         SET text = ?2,
+                             position = ?3,
+                             is_done = ?4,
+                             done_at = CASE WHEN ?4 THEN COALESCE(done_at, ?5) ELSE NULL END,
+                             updated_at = ?5,
+                             group_id = ?6
+                         WHERE id = ?1",
+                        params![
+                            data.id,
+                            data.text,
+                            data.position,
+                            data.is_done,
+                            now,
+                            group_id(data.group)
+                        ],
+                    )
+                    .context("Failed to update todo")?;
+                }
+
+                for id in &changes.delete_todos {
+                    tx.execute("DELETE FROM todos WHERE id = ?1", params![id])
+                        .context("Failed to delete todo")?;
+                }
+
+                // Todos still in deleted groups go with them by `ON DELETE CASCADE`.
+                for id in &changes.delete_groups {
+                    tx.execute("DELETE FROM project_groups WHERE id = ?1", params![id])
+                        .context("Failed to delete group")?;
+                }
+
+                let content = read_content(&tx, project_id)?;
+                tx.commit().context("Failed to commit transaction")?;
+
+                Ok(content)
+            })
+            .await
+    }
+
+    async fn put(
+        &self,
+        entities: models::Snapshot,
+        deleted: ports::EntityIds,
+        at: OffsetDateTime,
+    ) -> Result<()> {
+        self.db
+            .call(move |conn| {
+                let tx = conn.transaction().context("Failed to begin transaction")?;
+                put(&tx, &entities, &deleted, at)?;
+                tx.commit().context("Failed to commit transaction")?;
+
+                Ok(())
+            })
+            .await
+    }
+
+    async fn restore(
+        &self,
+        item: models::TrashItem,
+        at: OffsetDateTime,
+    ) -> Result<Option<models::TrashItem>> {
+        self.db
+            .call(move |conn| {
+                let tx = conn.transaction().context("Failed to begin transaction")?;
+
+                let removed = tx
+                    .execute("DELETE FROM trash_items WHERE id = ?1", params![item.id])
+                    .context("Failed to delete trash item")?;
+                if removed == 0 {
+                    return Ok(None);
+                }
+                // Dropping the transaction on a conflict keeps the item.
+                ports::check_restorable(&item, |entity, id| {
+                    let table = table(entity);
+                    let exists = tx
+                        .query_row(
+                            &format!("SELECT EXISTS (SELECT 1 FROM {} WHERE id = ?1)", table),
+                            params![id],
+                            |row| row.get(0),
+                        )
+                        .context(format!("Failed to select from {}", table))?;
+
+                    Ok(exists)
+                })?;
+
+                put(&tx, &item.entities, &ports::EntityIds::default(), at)?;
+                tx.commit().context("Failed to commit transaction")?;
+
+                Ok(Some(item))
+            })
+            .await
+    }
+
+    async fn trash(
+        &self,
+        entity: models::EntityKind,
+        id: u64,
+        at: OffsetDateTime,
+    ) -> Result<Option<models::TrashItem>> {
+        self.db
+            .call(move |conn| {
+                let tx = conn.transaction().context("Failed to begin transaction")?;
+
+                let (entities, project_id) = match entity {
+                    models::EntityKind::Project => {
+                        let Some(content) = read_content(&tx, id)? else {
+                            return Ok(None);
+                        };
+                        let entities = models::Snapshot {
+                            projects: vec![content.project],
+                            groups: content.groups,
+                            todos: content.todos,
+                        };
+
+                        (entities, None)
+                    }
+                    models::EntityKind::Group => {
+                        let group = tx
+                            .query_row(
+                                &format!(
+                                    "SELECT {} FROM project_groups WHERE id = ?1",
+                                    group::COLUMNS
+                                ),
+                                params![id],
+                                group::from_row,
+                            )
+                            .optional()
+                            .context("Failed to select group")?;
+                        let Some(group) = group else {
+                            return Ok(None);
+                        };
+                        let todos = tx
+                            .prepare(&format!(
+                                "SELECT {} FROM todos WHERE group_id = ?1 ORDER BY position, id",
+                                todo::COLUMNS
+                            ))
+                            .and_then(|mut stmt| {
+                                stmt.query_map(params![id], todo::from_row)?
+                                    .collect::<rusqlite::Result<Vec<_>>>()
+                            })
+                            .context("Failed to select todos")?;
+                        let project_id = group.project_id;
+                        let entities = models::Snapshot {
+                            groups: vec![group],
+                            todos,
+                            ..Default::default()
+                        };
+
+                        (entities, Some(project_id))
+                    }
+                    models::EntityKind::Todo => {
+                        let todo = tx
+                            .query_row(
+                                &format!("SELECT {} FROM todos WHERE id = ?1", todo::COLUMNS),
+                                params![id],
+                                todo::from_row,
+                            )
+                            .optional()
+                            .context("Failed to select todo")?;
+                        let Some(todo) = todo else {
+                            return Ok(None);
+                        };
+                        let project_id = tx
+                            .query_row(
+                                "SELECT project_id FROM project_groups WHERE id = ?1",
+                                params![todo.group_id],
+                                |row| row.get(0),
+                            )
+                            .optional()
+                            .context("Failed to select group")?;
+                        let entities = models::Snapshot {
+                            todos: vec![todo],
+                            ..Default::default()
+                        };
+
+                        (entities, project_id)
+                    }
+                };
+
+                let item = tx
+                    .query_row(
+                        &format!(
+                            "INSERT INTO trash_items (deleted_at, entity, entity_id, entities)
+                             VALUES (?1, ?2, ?3, ?4)
+                             RETURNING {}",
+                            trash::COLUMNS
+                        ),
+                        params![
+                            to_unix_ms(at),
+                            to_name(&entity)?,
+                            id,
+                            serde_json::to_string(&entities)
+                                .context("Failed to encode entities")?
+                        ],
+                        trash::from_row,
+                    )
+                    .context("Failed to insert trash item")?;
+
+                // Children go with the row by `ON DELETE CASCADE`.
+                let table = table(entity);
+                tx.execute(&format!("DELETE FROM {} WHERE id = ?1", table), params![id])
+                    .context(format!("Failed to delete from {}", table))?;
+
+                if let Some(project_id) = project_id {
+                    touch_project(&tx, project_id, at)?;
+                }
+
+                tx.commit().context("Failed to commit transaction")?;
+
+                Ok(Some(item))
+            })
+            .await
+    }
+}
+
+/// Table holding the entities of kind `entity`.
+fn table(entity: models::EntityKind) -> &'static str {
+    match entity {
+        models::EntityKind::Project => "projects",
+        models::EntityKind::Group => "project_groups",
+        models::EntityKind::Todo => "todos",
+    }
+}
+
+/// Stores `entities`, deletes the `deleted` ones with their children and
+/// raises `updated_at` of the projects that changed, as
+/// `ports::ProjectContentRepository::put` does.
+fn put(
+    tx: &rusqlite::Transaction,
+    entities: &models::Snapshot,
+    deleted: &ports::EntityIds,
+    at: OffsetDateTime,
+) -> Result<()> {
+    let mut project_ids = entities
+        .groups
+        .iter()
+        .map(|g| g.project_id)
+        .collect::<HashSet<u64>>();
+
+    // Upserts rather than `INSERT OR REPLACE`, which would delete the
+    // children of a replaced row by `ON DELETE CASCADE`.
+    for project in &entities.projects {
+        tx.execute(
+            "INSERT INTO projects
+                 (id, name, created_at, updated_at, is_active, archived_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT (id) DO UPDATE SET
+                 name = excluded.name,
+                 created_at = excluded.created_at,
+                 updated_at = excluded.updated_at,
+                 is_active = excluded.is_active,
+                 archived_at = excluded.archived_at",
+            params![
+                project.id,
+                project.name,
+                project.created_at,
+                project.updated_at,
+                project.is_active,
+                project.archived_at
+            ],
+        )
+        .context("Failed to put project")?;
+    }
+
+    for group in &entities.groups {
+        tx.execute(
+            "INSERT INTO project_groups (id, name, position, is_opened, project_id)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT (id) DO UPDATE SET
+                 name = excluded.name,
+                 position = excluded.position,
+                 is_opened = excluded.is_opened,
+                 project_id = excluded.project_id",
+            params![
+                group.id,
+                group.name,
+                group.position,
+                group.is_opened,
+                group.project_id
+            ],
+        )
+        .context("Failed to put group")?;
+    }
+
+    for todo in &entities.todos {
+        tx.execute(
+            "INSERT INTO todos
+                 (id, text, position, created_at, updated_at, is_done, done_at, group_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+             ON CONFLICT (id) DO UPDATE SET
+                 text = excluded.text,
+                 position = excluded.position,
+                 created_at = excluded.created_at,
+                 updated_at = excluded.updated_at,
+                 is_done = excluded.is_done,
+                 done_at = excluded.done_at,
+                 group_id = excluded.group_id",
+            params![
+                todo.id,
+                todo.text,
+                todo.position,
+                todo.created_at,
+                todo.updated_at,
+                todo.is_done,
+                todo.done_at,
+                todo.group_id
+            ],
+        )
+        .context("Failed to put todo")?;
+    }
+
+    // Looked up before the deletes, which take the groups along.
+    let todo_ids = entities
+        .todos
+        .iter()
+        .map(|t| t.id)
+        .chain(deleted.todos.iter().copied());
+    for id in todo_ids {
+        let project_id: Option<u64> = tx
+            .query_row(
+                "SELECT project_id FROM project_groups
+                 WHERE id = (SELECT group_id FROM todos WHERE id = ?1)",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("Failed to select group")?;
+        project_ids.extend(project_id);
+    }
+    for id in &deleted.groups {
+        let project_id: Option<u64> = tx
+            .query_row(
+                "SELECT project_id FROM project_groups WHERE id = ?1",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("Failed to select group")?;
+        project_ids.extend(project_id);
+    }
+
+    // Children of deleted rows go with them by `ON DELETE CASCADE`.
+    for id in &deleted.todos {
+        tx.execute("DELETE FROM todos WHERE id = ?1", params![id])
+            .context("Failed to delete todo")?;
+    }
+    for id in &deleted.groups {
+        tx.execute("DELETE FROM project_groups WHERE id = ?1", params![id])
+            .context("Failed to delete group")?;
+    }
+    for id in &deleted.projects {
+        tx.execute("DELETE FROM projects WHERE id = ?1", params![id])
+            .context("Failed to delete project")?;
+    }
+
+    for id in project_ids {
+        if !entities.projects.iter().any(|p| p.id == id) {
+            touch_project(tx, id, at)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Raises `updated_at` of the project to `at`, if it still exists.
+fn touch_project(tx: &rusqlite::Transaction, id: u64, at: OffsetDateTime) -> Result<()> {
+    let updated_at: Option<OffsetDateTime> = tx
+        .query_row(
+            "SELECT updated_at FROM projects WHERE id = ?1",
+            params![id],
+            |row| row.get(0),
+        )
+        .optional()
+        .context("Failed to select project")?;
+
+    if updated_at.is_some_and(|updated_at| at > updated_at) {
+        tx.execute(
+            "UPDATE projects SET updated_at = ?2 WHERE id = ?1",
+            params![id, at],
+        )
+        .context("Failed to touch project")?;
+    }
+
+    Ok(())
+}
+
+fn read_content(
+    conn: &rusqlite::Connection,
+    project_id: u64,
+) -> Result<Option<models::ProjectContent>> {
+    let project = conn
+        .query_row(
+            &format!("SELECT {} FROM projects WHERE id = ?1", project::COLUMNS),
+            params![project_id],
+            project::from_row,
+        )
+        .optional()
+        .context("Failed to select project")?;
+
+    let Some(project) = project else {
+        return Ok(None);
+    };
+
+    let groups = conn
+        .prepare(&format!(
+            "SELECT {} FROM project_groups WHERE project_id = ?1 ORDER BY position, id",
+            group::COLUMNS
+        ))
+        .and_then(|mut stmt| {
+            stmt.query_map(params![project_id], group::from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()
+        })
+        .context("Failed to select groups")?;
+
+    let mut todos = conn
+        .prepare(&format!(
+            "SELECT {} FROM todos WHERE group_id IN
+             (SELECT id FROM project_groups WHERE project_id = ?1)",
+            todo::COLUMNS
+        ))
+        .and_then(|mut stmt| {
+            stmt.query_map(params![project_id], todo::from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()
+        })
+        .context("Failed to select todos")?;
+
+    let rank = |group_id| groups.iter().position(|g: &models::Group| g.id == group_id);
+    todos.sort_by(|a, b| {
+        rank(a.group_id)
+            .cmp(&rank(b.group_id))
+            .then(a.position.total_cmp(&b.position))
+            .then(a.id.cmp(&b.id))
+    });
+
+    Ok(Some(models::ProjectContent {
+        project,
+        groups,
+        todos,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::ports::repository_tests::ContentRepositories;
+    use crate::project_content_repository_test;
+    use crate::repositories::sqlite::{
+        SqliteGroupRepository, SqliteProjectRepository, SqliteTodoRepository, SqliteTrashRepository,
+    };
+
+    fn repositories() -> ContentRepositories {
+        let db = SqliteDatabase::open_in_memory().expect("Failed to open database");
+
+        ContentRepositories {
+            project: Arc::new(SqliteProjectRepository::new(db.clone())),
+            group: Arc::new(SqliteGroupRepository::new(db.clone())),
+            todo: Arc::new(SqliteTodoRepository::new(db.clone())),
+            trash: Arc::new(SqliteTrashRepository::new(db.clone())),
+            content: Arc::new(SqliteProjectContentRepository::new(db)),
+            cleanup: None,
+        }
+    }
+
+    project_content_repository_test! {repositories()}
+}
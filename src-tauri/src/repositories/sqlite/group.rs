@@ -0,0 +1,150 @@
+use super::SqliteDatabase;
+use crate::models;
+use crate::ports;
+use crate::result::Result;
+use crate::utils::{IsSend, IsSync};
+use anyhow::Context;
+use async_trait::async_trait;
+use rusqlite::{params, OptionalExtension, Row};
+
+pub(super) const COLUMNS: &str = "id, name, position, is_opened, project_id";
+
+pub(super) fn from_row(row: &Row<'_>) -> rusqlite::Result<models::Group> {
+    Ok(models::Group {
+        id: row.get("id")?,
+        name: row.get("name")?,
+        position: row.get("position")?,
+        is_opened: row.get("is_opened")?,
+        project_id: row.get("project_id")?,
+    })
+}
+
+pub struct SqliteGroupRepository {
+    db: SqliteDatabase,
+}
+
+impl IsSync for SqliteGroupRepository {}
+impl IsSend for SqliteGroupRepository {}
+
+impl SqliteGroupRepository {
+    pub fn new(db: SqliteDatabase) -> Self {
+        SqliteGroupRepository { db }
+    }
+}
+
+#[async_trait]
+impl ports::GroupRepository for SqliteGroupRepository {
+    async fn create(&self, data: ports::CreateGroupData<'_>) -> Result<models::Group> {
+        let name = data.name.to_string();
+        let project_id = data.project_id;
+
+        self.db
+            .call(move |conn| {
+                let group = conn
+                    .query_row(
+                        &format!(
+                            "INSERT INTO project_groups (name, position, is_opened, project_id)
+                             VALUES (
+                                 ?1,
+                                 (SELECT COALESCE(CAST(MAX(position) AS INTEGER), 0) + 1
+                                  FROM project_groups WHERE project_id = ?2),
+                                 1,
+                                 ?2
+                             )
+                             RETURNING {}",
+                            COLUMNS
+                        ),
+                        params![name, project_id],
+                        from_row,
+                    )
+                    .context("Failed to insert group")?;
+
+                Ok(group)
+            })
+            .await
+    }
+
+    async fn get(&self, id: u64) -> Result<Option<models::Group>> {
+        self.db
+            .call(move |conn| {
+                let group = conn
+                    .query_row(
+                        &format!("SELECT {} FROM project_groups WHERE id = ?1", COLUMNS),
+                        params![id],
+                        from_row,
+                    )
+                    .optional()
+                    .context("Failed to select group")?;
+
+                Ok(group)
+            })
+            .await
+    }
+
+    async fn find_by_project(&self, project_id: u64) -> Result<Vec<models::Group>> {
+        self.db
+            .call(move |conn| {
+                let mut stmt = conn
+                    .prepare_cached(&format!(
+                        "SELECT {} FROM project_groups WHERE project_id = ?1 ORDER BY position",
+                        COLUMNS
+                    ))
+                    .context("Failed to prepare statement")?;
+
+                let groups = stmt
+                    .query_map(params![project_id], from_row)
+                    .and_then(Iterator::collect)
+                    .context("Failed to select groups")?;
+
+                Ok(groups)
+            })
+            .await
+    }
+
+    async fn list(&self) -> Result<Vec<models::Group>> {
+        self.db
+            .call(move |conn| {
+                let mut stmt = conn
+                    .prepare_cached(&format!(
+                        "SELECT {} FROM project_groups ORDER BY id",
+                        COLUMNS
+                    ))
+                    .context("Failed to prepare statement")?;
+
+                let groups = stmt
+                    .query_map([], from_row)
+                    .and_then(Iterator::collect)
+                    .context("Failed to select groups")?;
+
+                Ok(groups)
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use crate::group_repository_test;
+
+    /// Opens an in-memory database with projects `1..=count`, so
+    /// the shared suite can refer to project ids without creating them.
+    pub fn database_with_projects(count: u64) -> SqliteDatabase {
+        let db = SqliteDatabase::open_in_memory().expect("Failed to open database");
+
+        let conn = db.conn.lock().unwrap();
+        for id in 1..=count {
+            conn.execute(
+                "INSERT INTO projects (id, name, created_at, updated_at, is_active)
+                 VALUES (?1, 'Project', '2023-01-01 00:00:00Z', '2023-01-01 00:00:00Z', 1)",
+                params![id],
+            )
+            .expect("Failed to insert project");
+        }
+        drop(conn);
+
+        db
+    }
+
+    group_repository_test! {SqliteGroupRepository::new(database_with_projects(12))}
+}
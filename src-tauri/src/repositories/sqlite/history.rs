@@ -0,0 +1,94 @@
+use super::SqliteDatabase;
+use crate::history::{History, HistoryEntry};
+use crate::ports;
+use crate::result::{Result, StorageCorrupt};
+use crate::utils::{IsSend, IsSync};
+use anyhow::Context;
+use async_trait::async_trait;
+use rusqlite::params;
+
+/// Stack names in `history_entries.stack`.
+const UNDO: &str = "undo";
+const REDO: &str = "redo";
+
+/// Entries are stored one per row as JSON, in stack order by `id`.
+pub struct SqliteHistoryRepository {
+    db: SqliteDatabase,
+}
+
+impl IsSync for SqliteHistoryRepository {}
+impl IsSend for SqliteHistoryRepository {}
+
+impl SqliteHistoryRepository {
+    pub fn new(db: SqliteDatabase) -> Self {
+        SqliteHistoryRepository { db }
+    }
+}
+
+#[async_trait]
+impl ports::HistoryRepository for SqliteHistoryRepository {
+    async fn get(&self) -> Result<History> {
+        self.db
+            .call(|conn| {
+                let rows = conn
+                    .prepare("SELECT stack, entry FROM history_entries ORDER BY id")
+                    .and_then(|mut stmt| {
+                        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                            .collect::<rusqlite::Result<Vec<(String, String)>>>()
+                    })
+                    .context("Failed to select history entries")?;
+
+                let mut history = History::default();
+                for (stack, entry) in rows {
+                    let entry: HistoryEntry =
+                        serde_json::from_str(&entry).context(StorageCorrupt {
+                            path: "history_entries".into(),
+                        })?;
+
+                    if stack == UNDO {
+                        history.undo.push(entry);
+                    } else {
+                        history.redo.push(entry);
+                    }
+                }
+
+                Ok(history)
+            })
+            .await
+    }
+
+    async fn save(&self, history: History) -> Result<()> {
+        self.db
+            .call(move |conn| {
+                let tx = conn.transaction().context("Failed to begin transaction")?;
+
+                tx.execute("DELETE FROM history_entries", [])
+                    .context("Failed to delete history entries")?;
+
+                let undo = history.undo.iter().map(|entry| (UNDO, entry));
+                for (stack, entry) in undo.chain(history.redo.iter().map(|entry| (REDO, entry))) {
+                    let entry =
+                        serde_json::to_string(entry).context("Failed to encode history entry")?;
+
+                    tx.execute(
+                        "INSERT INTO history_entries (stack, entry) VALUES (?1, ?2)",
+                        params![stack, entry],
+                    )
+                    .context("Failed to insert history entry")?;
+                }
+
+                tx.commit().context("Failed to commit transaction")?;
+
+                Ok(())
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history_repository_test;
+
+    history_repository_test! {SqliteHistoryRepository::new(SqliteDatabase::open_in_memory().expect("Failed to open database"))}
+}
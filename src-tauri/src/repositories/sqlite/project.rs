@@ -0,0 +1,234 @@
+use super::SqliteDatabase;
+use crate::models;
+use crate::ports;
+use crate::result::Result;
+use crate::utils::{IsSend, IsSync};
+use anyhow::Context;
+use async_trait::async_trait;
+use rusqlite::{params, OptionalExtension, Row};
+use time::OffsetDateTime;
+
+pub(super) const COLUMNS: &str = "id, name, created_at, updated_at, is_active, archived_at";
+
+pub(super) fn from_row(row: &Row<'_>) -> rusqlite::Result<models::Project> {
+    Ok(models::Project {
+        id: row.get("id")?,
+        name: row.get("name")?,
+        created_at: row.get("created_at")?,
+        updated_at: row.get("updated_at")?,
+        is_active: row.get("is_active")?,
+        archived_at: row.get("archived_at")?,
+    })
+}
+
+pub struct SqliteProjectRepository {
+    db: SqliteDatabase,
+}
+
+impl IsSync for SqliteProjectRepository {}
+impl IsSend for SqliteProjectRepository {}
+
+impl SqliteProjectRepository {
+    pub fn new(db: SqliteDatabase) -> Self {
+        SqliteProjectRepository { db }
+    }
+}
+
+#[async_trait]
+impl ports::ProjectRepository for SqliteProjectRepository {
+    async fn create(&self, data: ports::CreateProjectData<'_>) -> Result<models::Project> {
+        let now = OffsetDateTime::now_utc();
+        let name = data.name.to_string();
+
+        self.db
+            .call(move |conn| {
+                let project = conn
+                    .query_row(
+                        &format!(
+                            "INSERT INTO projects (name, created_at, updated_at, is_active)
+                             VALUES (?1, ?2, ?2, 1)
+                             RETURNING {}",
+                            COLUMNS
+                        ),
+                        params![name, now],
+                        from_row,
+                    )
+                    .context("Failed to insert project")?;
+
+                Ok(project)
+            })
+            .await
+    }
+
+    async fn get(&self, id: u64) -> Result<Option<models::Project>> {
+        self.db
+            .call(move |conn| {
+                let project = conn
+                    .query_row(
+                        &format!("SELECT {} FROM projects WHERE id = ?1", COLUMNS),
+                        params![id],
+                        from_row,
+                    )
+                    .optional()
+                    .context("Failed to select project")?;
+
+                Ok(project)
+            })
+            .await
+    }
+
+    async fn list(
+        &self,
+        filter: ports::ProjectFilter,
+        sort: ports::ProjectSort,
+    ) -> Result<Vec<models::Project>> {
+        let condition = match filter {
+            ports::ProjectFilter::Active => "archived_at IS NULL",
+            ports::ProjectFilter::Archived => "archived_at IS NOT NULL",
+            ports::ProjectFilter::All => "1",
+        };
+
+        self.db
+            .call(move |conn| {
+                let mut stmt = conn
+                    .prepare_cached(&format!(
+                        "SELECT {} FROM projects WHERE {} ORDER BY id DESC",
+                        COLUMNS, condition
+                    ))
+                    .context("Failed to prepare statement")?;
+
+                let mut projects = stmt
+                    .query_map([], from_row)
+                    .and_then(Iterator::collect::<rusqlite::Result<Vec<_>>>)
+                    .context("Failed to select projects")?;
+
+                // Timestamps are stored as text with a variable number of
+                // fractional digits, so they are compared here, not in SQL.
+                sort.sort(&mut projects);
+
+                Ok(projects)
+            })
+            .await
+    }
+
+    async fn update(
+        &self,
+        id: u64,
+        data: ports::UpdateProjectData<'_>,
+    ) -> Result<Option<models::Project>> {
+        let now = OffsetDateTime::now_utc();
+        let name = data.name.map(ToString::to_string);
+        let is_active = data.is_active;
+
+        self.db
+            .call(move |conn| {
+                let project = conn
+                    .query_row(
+                        &format!(
+                            "UPDATE projects
+                             SET name = COALESCE(?2, name),
+                                 is_active = COALESCE(?3, is_active),
+                                 archived_at = CASE ?3
+                                     WHEN 1 THEN NULL
+                                     WHEN 0 THEN COALESCE(archived_at, ?4)
+                                     ELSE archived_at
+                                 END,
+                                 updated_at = ?4
+                             WHERE id = ?1
+                             RETURNING {}",
+                            COLUMNS
+                        ),
+                        params![id, name, is_active, now],
+                        from_row,
+                    )
+                    .optional()
+                    .context("Failed to update project")?;
+
+                Ok(project)
+            })
+            .await
+    }
+
+    async fn archive(&self, id: u64) -> Result<Option<models::Project>> {
+        let now = OffsetDateTime::now_utc();
+
+        self.db
+            .call(move |conn| {
+                let project = conn
+                    .query_row(
+                        &format!(
+                            "UPDATE projects
+                             SET is_active = 0, archived_at = COALESCE(archived_at, ?2)
+                             WHERE id = ?1
+                             RETURNING {}",
+                            COLUMNS
+                        ),
+                        params![id, now],
+                        from_row,
+                    )
+                    .optional()
+                    .context("Failed to archive project")?;
+
+                Ok(project)
+            })
+            .await
+    }
+
+    async fn unarchive(&self, id: u64) -> Result<Option<models::Project>> {
+        self.db
+            .call(move |conn| {
+                let project = conn
+                    .query_row(
+                        &format!(
+                            "UPDATE projects
+                             SET is_active = 1, archived_at = NULL
+                             WHERE id = ?1
+                             RETURNING {}",
+                            COLUMNS
+                        ),
+                        params![id],
+                        from_row,
+                    )
+                    .optional()
+                    .context("Failed to unarchive project")?;
+
+                Ok(project)
+            })
+            .await
+    }
+
+    async fn set_updated_at(&self, updates: &[(u64, OffsetDateTime)]) -> Result<()> {
+        let updates = updates.to_vec();
+
+        self.db
+            .call(move |conn| {
+                let tx = conn.transaction().context("Failed to begin transaction")?;
+
+                {
+                    let mut stmt = tx
+                        .prepare_cached("UPDATE projects SET updated_at = ?2 WHERE id = ?1")
+                        .context("Failed to prepare statement")?;
+
+                    for (id, updated_at) in updates {
+                        stmt.execute(params![id, updated_at])
+                            .context("Failed to update project")?;
+                    }
+                }
+
+                tx.commit().context("Failed to commit transaction")?;
+
+                Ok(())
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project_repository_test;
+
+    project_repository_test! {
+        SqliteProjectRepository::new(SqliteDatabase::open_in_memory().expect("Failed to open database"))
+    }
+}
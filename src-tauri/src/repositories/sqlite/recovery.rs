@@ -0,0 +1,80 @@
+use std::path::{Path, PathBuf};
+
+use super::SqliteDatabase;
+use crate::models;
+use crate::ports;
+use crate::result::Result;
+use crate::utils::{IsSend, IsSync};
+use anyhow::Context;
+use async_trait::async_trait;
+use time::OffsetDateTime;
+
+/// Runs `PRAGMA quick_check` on the database. Problems are only reported:
+/// the open database can not be moved aside, so restoring a backup is the
+/// way to repair it.
+pub struct SqliteRecoveryRepository {
+    db: SqliteDatabase,
+    path: PathBuf,
+}
+
+impl IsSync for SqliteRecoveryRepository {}
+impl IsSend for SqliteRecoveryRepository {}
+
+impl SqliteRecoveryRepository {
+    pub fn new(db: SqliteDatabase, path: &Path) -> Self {
+        SqliteRecoveryRepository {
+            db,
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+#[async_trait]
+impl ports::RecoveryRepository for SqliteRecoveryRepository {
+    async fn recover(&self, _at: OffsetDateTime) -> Result<Vec<models::RecoveredFile>> {
+        let path = self.path.clone();
+
+        self.db
+            .call(move |conn| {
+                let problems = conn
+                    .prepare("PRAGMA quick_check")
+                    .and_then(|mut stmt| {
+                        stmt.query_map([], |row| row.get(0))?
+                            .collect::<rusqlite::Result<Vec<String>>>()
+                    })
+                    .context("Failed to check database")?;
+
+                if problems == ["ok"] {
+                    return Ok(Vec::new());
+                }
+
+                Ok(vec![models::RecoveredFile {
+                    path,
+                    quarantine_path: None,
+                    problem: problems.join("\n"),
+                    salvaged: 0,
+                    dropped: 0,
+                }])
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports::RecoveryRepository as _;
+
+    #[tokio::test]
+    async fn recover_passes_intact_database() {
+        let db = SqliteDatabase::open_in_memory().expect("Failed to open database");
+        let repository = SqliteRecoveryRepository::new(db, Path::new(":memory:"));
+
+        let recovered = repository
+            .recover(OffsetDateTime::now_utc())
+            .await
+            .expect("Failed to check database");
+
+        assert_eq!(recovered, vec![]);
+    }
+}
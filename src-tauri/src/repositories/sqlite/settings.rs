@@ -0,0 +1,109 @@
+use super::SqliteDatabase;
+use crate::models;
+use crate::ports;
+use crate::result::Result;
+use crate::utils::{IsSend, IsSync};
+use anyhow::Context;
+use async_trait::async_trait;
+use rusqlite::{params, Connection, OptionalExtension};
+
+/// Settings are stored one per row as JSON values, keyed by field name.
+const DEFAULT_GROUPS: &str = "default_groups";
+const ACTIVITY_RETENTION_DAYS: &str = "activity_retention_days";
+const TRASH_RETENTION_DAYS: &str = "trash_retention_days";
+
+fn read_value<T: serde::de::DeserializeOwned>(conn: &Connection, key: &str) -> Result<Option<T>> {
+    let value: Option<String> = conn
+        .query_row(
+            "SELECT value FROM settings WHERE key = ?1",
+            params![key],
+            |row| row.get(0),
+        )
+        .optional()
+        .context(format!("Failed to select setting {}", key))?;
+
+    let Some(value) = value else {
+        return Ok(None);
+    };
+
+    let value = serde_json::from_str(&value).context(format!("Failed to parse setting {}", key))?;
+
+    Ok(Some(value))
+}
+
+fn write_value<T: serde::Serialize>(conn: &Connection, key: &str, value: &T) -> Result<()> {
+    let value =
+        serde_json::to_string(value).context(format!("Failed to encode setting {}", key))?;
+
+    conn.execute(
+        "INSERT INTO settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+        params![key, value],
+    )
+    .context(format!("Failed to write setting {}", key))?;
+
+    Ok(())
+}
+
+fn read_settings(conn: &Connection) -> Result<models::Settings> {
+    let defaults = models::Settings::default();
+
+    Ok(models::Settings {
+        default_groups: read_value(conn, DEFAULT_GROUPS)?.unwrap_or(defaults.default_groups),
+        activity_retention_days: read_value(conn, ACTIVITY_RETENTION_DAYS)?
+            .unwrap_or(defaults.activity_retention_days),
+        trash_retention_days: read_value(conn, TRASH_RETENTION_DAYS)?
+            .unwrap_or(defaults.trash_retention_days),
+    })
+}
+
+pub struct SqliteSettingsRepository {
+    db: SqliteDatabase,
+}
+
+impl IsSync for SqliteSettingsRepository {}
+impl IsSend for SqliteSettingsRepository {}
+
+impl SqliteSettingsRepository {
+    pub fn new(db: SqliteDatabase) -> Self {
+        SqliteSettingsRepository { db }
+    }
+}
+
+#[async_trait]
+impl ports::SettingsRepository for SqliteSettingsRepository {
+    async fn get(&self) -> Result<models::Settings> {
+        self.db.call(|conn| read_settings(conn)).await
+    }
+
+    async fn update(&self, data: ports::UpdateSettingsData) -> Result<models::Settings> {
+        self.db
+            .call(move |conn| {
+                let tx = conn.transaction().context("Failed to begin transaction")?;
+
+                if let Some(default_groups) = data.default_groups {
+                    write_value(&tx, DEFAULT_GROUPS, &default_groups)?;
+                }
+                if let Some(days) = data.activity_retention_days {
+                    write_value(&tx, ACTIVITY_RETENTION_DAYS, &days)?;
+                }
+                if let Some(days) = data.trash_retention_days {
+                    write_value(&tx, TRASH_RETENTION_DAYS, &days)?;
+                }
+
+                let settings = read_settings(&tx)?;
+                tx.commit().context("Failed to commit transaction")?;
+
+                Ok(settings)
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings_repository_test;
+
+    settings_repository_test! {SqliteSettingsRepository::new(SqliteDatabase::open_in_memory().expect("Failed to open database"))}
+}
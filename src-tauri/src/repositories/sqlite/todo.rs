@@ -0,0 +1,155 @@
+use super::SqliteDatabase;
+use crate::models;
+use crate::ports;
+use crate::result::Result;
+use crate::utils::{IsSend, IsSync};
+use anyhow::Context;
+use async_trait::async_trait;
+use rusqlite::{params, OptionalExtension, Row};
+use time::OffsetDateTime;
+
+pub(super) const COLUMNS: &str =
+    "id, text, position, created_at, updated_at, is_done, done_at, group_id";
+
+pub(super) fn from_row(row: &Row<'_>) -> rusqlite::Result<models::Todo> {
+    Ok(models::Todo {
+        id: row.get("id")?,
+        text: row.get("text")?,
+        position: row.get("position")?,
+        created_at: row.get("created_at")?,
+        updated_at: row.get("updated_at")?,
+        is_done: row.get("is_done")?,
+        done_at: row.get("done_at")?,
+        group_id: row.get("group_id")?,
+    })
+}
+
+pub struct SqliteTodoRepository {
+    db: SqliteDatabase,
+}
+
+impl IsSync for SqliteTodoRepository {}
+impl IsSend for SqliteTodoRepository {}
+
+impl SqliteTodoRepository {
+    pub fn new(db: SqliteDatabase) -> Self {
+        SqliteTodoRepository { db }
+    }
+}
+
+#[async_trait]
+impl ports::TodoRepository for SqliteTodoRepository {
+    async fn create(&self, data: ports::CreateTodoData<'_>) -> Result<models::Todo> {
+        let now = OffsetDateTime::now_utc();
+        let text = data.text.to_string();
+        let group_id = data.group_id;
+
+        self.db
+            .call(move |conn| {
+                let todo = conn
+                    .query_row(
+                        &format!(
+                            "INSERT INTO todos (text, position, created_at, updated_at, is_done, group_id)
+                             VALUES (
+                                 ?1,
+                                 (SELECT COALESCE(CAST(MAX(position) AS INTEGER), 0) + 1
+                                  FROM todos WHERE group_id = ?3),
+                                 ?2,
+                                 ?2,
+                                 0,
+                                 ?3
+                             )
+                             RETURNING {}",
+                            COLUMNS
+                        ),
+                        params![text, now, group_id],
+                        from_row,
+                    )
+                    .context("Failed to insert todo")?;
+
+                Ok(todo)
+            })
+            .await
+    }
+
+    async fn get(&self, id: u64) -> Result<Option<models::Todo>> {
+        self.db
+            .call(move |conn| {
+                let todo = conn
+                    .query_row(
+                        &format!("SELECT {} FROM todos WHERE id = ?1", COLUMNS),
+                        params![id],
+                        from_row,
+                    )
+                    .optional()
+                    .context("Failed to select todo")?;
+
+                Ok(todo)
+            })
+            .await
+    }
+
+    async fn find_by_group(&self, group_id: u64) -> Result<Vec<models::Todo>> {
+        self.db
+            .call(move |conn| {
+                let mut stmt = conn
+                    .prepare_cached(&format!(
+                        "SELECT {} FROM todos WHERE group_id = ?1 ORDER BY position",
+                        COLUMNS
+                    ))
+                    .context("Failed to prepare statement")?;
+
+                let todos = stmt
+                    .query_map(params![group_id], from_row)
+                    .and_then(Iterator::collect)
+                    .context("Failed to select todos")?;
+
+                Ok(todos)
+            })
+            .await
+    }
+
+    async fn list(&self) -> Result<Vec<models::Todo>> {
+        self.db
+            .call(move |conn| {
+                let mut stmt = conn
+                    .prepare_cached(&format!("SELECT {} FROM todos ORDER BY id", COLUMNS))
+                    .context("Failed to prepare statement")?;
+
+                let todos = stmt
+                    .query_map([], from_row)
+                    .and_then(Iterator::collect)
+                    .context("Failed to select todos")?;
+
+                Ok(todos)
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::sqlite::group::tests::database_with_projects;
+    use crate::todo_repository_test;
+
+    /// Opens an in-memory database with groups `1..=count` in one project.
+    fn database_with_groups(count: u64) -> SqliteDatabase {
+        let db = database_with_projects(1);
+
+        let conn = db.conn.lock().unwrap();
+        for id in 1..=count {
+            conn.execute(
+                "INSERT INTO project_groups (id, name, position, is_opened, project_id)
+                 VALUES (?1, 'Group', ?1, 1, 1)",
+                params![id],
+            )
+            .expect("Failed to insert group");
+        }
+        drop(conn);
+
+        db
+    }
+
+    todo_repository_test! {SqliteTodoRepository::new(database_with_groups(12))}
+}
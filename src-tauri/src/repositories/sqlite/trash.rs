@@ -0,0 +1,115 @@
+use super::{from_json, from_name, from_unix_ms, to_unix_ms, SqliteDatabase};
+use crate::models;
+use crate::ports;
+use crate::result::Result;
+use crate::utils::{IsSend, IsSync};
+use anyhow::Context;
+use async_trait::async_trait;
+use rusqlite::{params, OptionalExtension, Row};
+use time::OffsetDateTime;
+
+pub(super) const COLUMNS: &str = "id, deleted_at, entity, entity_id, entities";
+
+pub(super) fn from_row(row: &Row<'_>) -> rusqlite::Result<models::TrashItem> {
+    Ok(models::TrashItem {
+        id: row.get("id")?,
+        deleted_at: from_unix_ms(row, "deleted_at")?,
+        entity: from_name(row, "entity")?,
+        entity_id: row.get("entity_id")?,
+        entities: from_json(row, "entities")?.unwrap_or_default(),
+    })
+}
+
+/// Trash items are kept in `trash_items`, with the deleted entities as JSON.
+pub struct SqliteTrashRepository {
+    db: SqliteDatabase,
+}
+
+impl IsSync for SqliteTrashRepository {}
+impl IsSend for SqliteTrashRepository {}
+
+impl SqliteTrashRepository {
+    pub fn new(db: SqliteDatabase) -> Self {
+        SqliteTrashRepository { db }
+    }
+}
+
+#[async_trait]
+impl ports::TrashRepository for SqliteTrashRepository {
+    async fn get(&self, id: u64) -> Result<Option<models::TrashItem>> {
+        self.db
+            .call(move |conn| {
+                let item = conn
+                    .query_row(
+                        &format!("SELECT {} FROM trash_items WHERE id = ?1", COLUMNS),
+                        params![id],
+                        from_row,
+                    )
+                    .optional()
+                    .context("Failed to select trash item")?;
+
+                Ok(item)
+            })
+            .await
+    }
+
+    async fn list(&self) -> Result<Vec<models::TrashItem>> {
+        self.db
+            .call(move |conn| {
+                let items = conn
+                    .prepare_cached(&format!(
+                        "SELECT {} FROM trash_items ORDER BY id DESC",
+                        COLUMNS
+                    ))
+                    .and_then(|mut stmt| {
+                        stmt.query_map([], from_row)?
+                            .collect::<rusqlite::Result<Vec<_>>>()
+                    })
+                    .context("Failed to select trash items")?;
+
+                Ok(items)
+            })
+            .await
+    }
+
+    async fn remove(&self, id: u64) -> Result<Option<models::TrashItem>> {
+        self.db
+            .call(move |conn| {
+                let item = conn
+                    .query_row(
+                        &format!(
+                            "DELETE FROM trash_items WHERE id = ?1 RETURNING {}",
+                            COLUMNS
+                        ),
+                        params![id],
+                        from_row,
+                    )
+                    .optional()
+                    .context("Failed to delete trash item")?;
+
+                Ok(item)
+            })
+            .await
+    }
+
+    async fn purge(&self, before: OffsetDateTime) -> Result<Vec<models::TrashItem>> {
+        self.db
+            .call(move |conn| {
+                let mut purged = conn
+                    .prepare_cached(&format!(
+                        "DELETE FROM trash_items WHERE deleted_at < ?1 RETURNING {}",
+                        COLUMNS
+                    ))
+                    .and_then(|mut stmt| {
+                        stmt.query_map(params![to_unix_ms(before)], from_row)?
+                            .collect::<rusqlite::Result<Vec<_>>>()
+                    })
+                    .context("Failed to delete trash items")?;
+                // RETURNING yields rows in no particular order.
+                purged.sort_by_key(|item| item.id);
+
+                Ok(purged)
+            })
+            .await
+    }
+}
@@ -0,0 +1,203 @@
+use std::sync::mpsc;
+use std::time::Duration;
+
+use super::{group, project, todo, SqliteDatabase};
+use crate::models::StorageChange;
+use crate::repositories::watcher::{self, Snapshots};
+use crate::result::Result;
+use anyhow::Context;
+use rusqlite::{Connection, Row};
+
+/// How often the database is asked whether another connection committed.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+fn select_all<T>(
+    conn: &Connection,
+    table: &str,
+    columns: &str,
+    from_row: fn(&Row<'_>) -> rusqlite::Result<T>,
+) -> Result<Vec<T>> {
+    let mut stmt = conn
+        .prepare(&format!("SELECT {} FROM {}", columns, table))
+        .context("Failed to prepare statement")?;
+    let rows = stmt
+        .query_map([], from_row)
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .context(format!("Failed to read {}", table))?;
+
+    Ok(rows)
+}
+
+/// `PRAGMA data_version`, which only moves when another connection
+/// commits, and the number of rows this connection changed so far.
+fn counters(conn: &Connection) -> Result<(i64, i64)> {
+    let data_version = conn
+        .pragma_query_value(None, "data_version", |row| row.get(0))
+        .context("Failed to read data_version")?;
+    let total_changes = conn
+        .query_row("SELECT total_changes()", [], |row| row.get(0))
+        .context("Failed to read total_changes")?;
+
+    Ok((data_version, total_changes))
+}
+
+/// Tables as last read, with the counters they were read at.
+struct PollState {
+    counters: (i64, i64),
+    snapshots: Snapshots,
+}
+
+impl PollState {
+    /// Reads the counters and tables; the connection is locked, so no
+    /// write can land in between.
+    fn read(conn: &Connection) -> Result<Self> {
+        Ok(PollState {
+            counters: counters(conn)?,
+            snapshots: Snapshots {
+                projects: watcher::snapshot_projects(select_all(
+                    conn,
+                    "projects",
+                    project::COLUMNS,
+                    project::from_row,
+                )?),
+                groups: watcher::snapshot_groups(select_all(
+                    conn,
+                    "project_groups",
+                    group::COLUMNS,
+                    group::from_row,
+                )?),
+                todos: watcher::snapshot_todos(select_all(
+                    conn,
+                    "todos",
+                    todo::COLUMNS,
+                    todo::from_row,
+                )?),
+            },
+        })
+    }
+
+    /// Reports what other connections changed since the last poll.
+    ///
+    /// The tables are read again after writes of this connection as well,
+    /// so those are never taken for foreign ones; a foreign commit that
+    /// lands in the same poll as one of ours is reported together with it.
+    fn poll(&mut self, conn: &Connection) -> Result<Vec<StorageChange>> {
+        if counters(conn)? == self.counters {
+            return Ok(Vec::new());
+        }
+
+        let after = PollState::read(conn)?;
+        let before = std::mem::replace(self, after);
+
+        if self.counters.0 == before.counters.0 {
+            return Ok(Vec::new());
+        }
+
+        Ok(before.snapshots.changes(&self.snapshots))
+    }
+}
+
+/// Polls the database for commits of other processes and reports the
+/// changes they made to projects, groups and todos.
+///
+/// Stops once dropped.
+pub struct SqliteStorageWatcher {
+    _stop: mpsc::Sender<()>,
+}
+
+impl SqliteStorageWatcher {
+    pub fn start<F>(db: SqliteDatabase, on_change: F) -> Result<Self>
+    where
+        F: Fn(StorageChange) + Send + 'static,
+    {
+        let mut state = PollState::read(&*db.lock()?)?;
+        let (stop, stopped) = mpsc::channel::<()>();
+
+        // Ends when the watcher is dropped together with its sender.
+        std::thread::spawn(move || {
+            while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(POLL_INTERVAL) {
+                let changes = db.lock().and_then(|conn| state.poll(&conn));
+
+                match changes {
+                    Ok(changes) => changes.into_iter().for_each(&on_change),
+                    Err(error) => log::error!("Failed to poll database: {:?}", error),
+                }
+            }
+        });
+
+        Ok(SqliteStorageWatcher { _stop: stop })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ChangedIds;
+    use crate::ports::{self, ProjectRepository as _};
+    use crate::repositories::sqlite::SqliteProjectRepository;
+
+    #[tokio::test]
+    async fn poll_reports_only_foreign_commits() {
+        let name = format!("test_Watcher_{}.sqlite3", rand::random::<u32>());
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tmp")
+            .join(name);
+        let _guard = scopeguard::guard(path.clone(), |path| {
+            for suffix in ["", "-wal", "-shm"] {
+                let mut name = path.clone().into_os_string();
+                name.push(suffix);
+                let _ = std::fs::remove_file(name);
+            }
+        });
+
+        let db = SqliteDatabase::open(&path).expect("Failed to open database");
+        let repo = SqliteProjectRepository::new(db.clone());
+        let project = repo
+            .create(ports::CreateProjectData { name: "Project" })
+            .await
+            .expect("Failed to create project");
+
+        let mut state = PollState::read(&db.lock().unwrap()).expect("Failed to read");
+
+        repo.update(
+            project.id,
+            ports::UpdateProjectData {
+                name: Some("Renamed here"),
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("Failed to update project");
+        assert_eq!(
+            state.poll(&db.lock().unwrap()).expect("Failed to poll"),
+            vec![]
+        );
+
+        // Another process renames the project.
+        let other = SqliteDatabase::open(&path).expect("Failed to open database");
+        SqliteProjectRepository::new(other)
+            .update(
+                project.id,
+                ports::UpdateProjectData {
+                    name: Some("Renamed elsewhere"),
+                    ..Default::default()
+                },
+            )
+            .await
+            .expect("Failed to update project");
+
+        assert_eq!(
+            state.poll(&db.lock().unwrap()).expect("Failed to poll"),
+            vec![StorageChange::Projects {
+                ids: ChangedIds {
+                    updated: vec![project.id],
+                    ..Default::default()
+                }
+            }]
+        );
+        assert_eq!(
+            state.poll(&db.lock().unwrap()).expect("Failed to poll"),
+            vec![]
+        );
+    }
+}
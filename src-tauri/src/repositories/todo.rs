@@ -0,0 +1,242 @@
+use std::path::Path;
+
+use super::file_storage::{self, FileStorage, Migration, Salvaged, StorageData};
+use super::salvage;
+use crate::models;
+use crate::ports;
+use crate::position;
+use crate::result::{ErrorContext, Result};
+use crate::utils::{IsSend, IsSync};
+use async_trait::async_trait;
+use blocking::unblock;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) struct Todo {
+    id: u64,
+    text: String,
+    position: f64,
+    #[serde(with = "time::serde::iso8601")]
+    created_at: OffsetDateTime,
+    #[serde(with = "time::serde::iso8601")]
+    updated_at: OffsetDateTime,
+    is_done: bool,
+    #[serde(with = "time::serde::iso8601::option")]
+    done_at: Option<OffsetDateTime>,
+    group_id: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub(super) struct TodoFileStorageData {
+    pub(super) todos: Vec<Todo>,
+    /// Largest id ever handed out, so ids of deleted todos are never reused.
+    pub(super) last_id: u64,
+}
+
+impl StorageData for TodoFileStorageData {
+    const MIGRATIONS: &'static [Migration] = &[
+        |document| file_storage::add_last_id(document, "todos"),
+        |document| file_storage::positions_to_double(document, "todos"),
+    ];
+
+    fn salvage(document: &bson::Document) -> Salvaged<Self> {
+        let (todos, dropped) = salvage::items::<Todo>(document, "todos");
+        let last_id = salvage::last_id(document, todos.iter().map(|item| item.id));
+
+        Salvaged {
+            kept: todos.len(),
+            data: TodoFileStorageData { todos, last_id },
+            dropped,
+        }
+    }
+}
+
+pub(super) type TodoFileStorage = FileStorage<TodoFileStorageData>;
+
+impl From<Todo> for models::Todo {
+    fn from(todo: Todo) -> Self {
+        models::Todo {
+            id: todo.id,
+            text: todo.text,
+            position: todo.position,
+            created_at: todo.created_at,
+            updated_at: todo.updated_at,
+            is_done: todo.is_done,
+            done_at: todo.done_at,
+            group_id: todo.group_id,
+        }
+    }
+}
+
+impl From<models::Todo> for Todo {
+    fn from(todo: models::Todo) -> Self {
+        Todo {
+            id: todo.id,
+            text: todo.text,
+            position: todo.position,
+            created_at: todo.created_at,
+            updated_at: todo.updated_at,
+            is_done: todo.is_done,
+            done_at: todo.done_at,
+            group_id: todo.group_id,
+        }
+    }
+}
+
+pub struct TodoRepository {
+    file_path: std::path::PathBuf,
+}
+
+impl IsSync for TodoRepository {}
+impl IsSend for TodoRepository {}
+
+impl TodoRepository {
+    pub fn new(file_path: &Path) -> Self {
+        TodoRepository {
+            file_path: std::path::PathBuf::from(file_path),
+        }
+    }
+}
+
+#[async_trait]
+impl ports::TodoRepository for TodoRepository {
+    async fn create(&self, data: ports::CreateTodoData<'_>) -> Result<models::Todo> {
+        let now = OffsetDateTime::now_utc();
+        let mut todo = Todo {
+            id: 0,
+            text: data.text.to_string(),
+            position: 0.0,
+            created_at: now,
+            updated_at: now,
+            is_done: false,
+            done_at: None,
+            group_id: data.group_id,
+        };
+
+        let file_path = self.file_path.clone();
+
+        unblock(move || {
+            let mut storage = TodoFileStorage::open_exclusive(&file_path)
+                .context("Failed to open_exclusive storage")?;
+
+            storage.data.last_id += 1;
+            todo.id = storage.data.last_id;
+            todo.position = position::between(
+                storage
+                    .data
+                    .todos
+                    .iter()
+                    .filter(|t| t.group_id == todo.group_id)
+                    .map(|t| t.position)
+                    .reduce(f64::max),
+                None,
+            )
+            .unwrap_or_default();
+
+            storage.data.todos.push(todo.clone());
+            storage.save().context("Failed to save storage")?;
+
+            Ok(todo.into())
+        })
+        .await
+    }
+
+    async fn get(&self, id: u64) -> Result<Option<models::Todo>> {
+        let file_path = self.file_path.clone();
+
+        let data: TodoFileStorageData = unblock(move || {
+            TodoFileStorage::read_data(&file_path).context("Failed to read storage")
+        })
+        .await?;
+
+        let item = data.todos.into_iter().find(|t| t.id == id);
+
+        Ok(item.map(Into::into))
+    }
+
+    async fn find_by_group(&self, group_id: u64) -> Result<Vec<models::Todo>> {
+        let file_path = self.file_path.clone();
+
+        let data: TodoFileStorageData = unblock(move || {
+            TodoFileStorage::read_data(&file_path).context("Failed to read storage")
+        })
+        .await?;
+
+        let mut todos = data
+            .todos
+            .into_iter()
+            .filter(|t| t.group_id == group_id)
+            .map(Into::into)
+            .collect::<Vec<models::Todo>>();
+
+        todos.sort_by(|a, b| a.position.total_cmp(&b.position));
+
+        Ok(todos)
+    }
+
+    async fn list(&self) -> Result<Vec<models::Todo>> {
+        let file_path = self.file_path.clone();
+
+        let data: TodoFileStorageData = unblock(move || {
+            TodoFileStorage::read_data(&file_path).context("Failed to read storage")
+        })
+        .await?;
+
+        let mut todos = data
+            .todos
+            .into_iter()
+            .map(Into::into)
+            .collect::<Vec<models::Todo>>();
+
+        todos.sort_by_key(|t| t.id);
+
+        Ok(todos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::file_storage;
+    use crate::todo_repository_test;
+
+    struct TodoRepositoryTest {
+        repo: TodoRepository,
+        path: std::path::PathBuf,
+    }
+
+    impl Drop for TodoRepositoryTest {
+        fn drop(&mut self) {
+            file_storage::remove_files(&self.path);
+        }
+    }
+
+    #[async_trait]
+    impl ports::TodoRepository for TodoRepositoryTest {
+        async fn create(&self, data: ports::CreateTodoData<'_>) -> Result<models::Todo> {
+            self.repo.create(data).await
+        }
+
+        async fn get(&self, id: u64) -> Result<Option<models::Todo>> {
+            self.repo.get(id).await
+        }
+
+        async fn find_by_group(&self, group_id: u64) -> Result<Vec<models::Todo>> {
+            self.repo.find_by_group(group_id).await
+        }
+
+        async fn list(&self) -> Result<Vec<models::Todo>> {
+            self.repo.list().await
+        }
+    }
+
+    todo_repository_test! {{
+        let name = format!("test_Todos_{}.bson", rand::random::<u32>());
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tmp").join(name);
+        TodoRepositoryTest {
+            repo: TodoRepository::new(&path),
+            path,
+        }
+    }}
+}
@@ -0,0 +1,151 @@
+use std::path::Path;
+
+use super::file_storage::{FileStorage, Migration, Salvaged, StorageData};
+use super::salvage;
+use crate::models;
+use crate::ports;
+use crate::result::{ErrorContext, Result};
+use crate::utils::{IsSend, IsSync};
+use async_trait::async_trait;
+use blocking::unblock;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) struct TrashItem {
+    id: u64,
+    #[serde(with = "time::serde::iso8601")]
+    deleted_at: OffsetDateTime,
+    entity: models::EntityKind,
+    entity_id: u64,
+    entities: models::Snapshot,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub(super) struct TrashFileStorageData {
+    /// Oldest first.
+    pub(super) items: Vec<TrashItem>,
+    /// Largest id ever handed out, so ids of removed items are never reused.
+    pub(super) last_id: u64,
+}
+
+impl StorageData for TrashFileStorageData {
+    const MIGRATIONS: &'static [Migration] = &[];
+
+    fn salvage(document: &bson::Document) -> Salvaged<Self> {
+        let (items, dropped) = salvage::items::<TrashItem>(document, "items");
+        let last_id = salvage::last_id(document, items.iter().map(|item| item.id));
+
+        Salvaged {
+            kept: items.len(),
+            data: TrashFileStorageData { items, last_id },
+            dropped,
+        }
+    }
+}
+
+pub(super) type TrashFileStorage = FileStorage<TrashFileStorageData>;
+
+impl From<TrashItem> for models::TrashItem {
+    fn from(item: TrashItem) -> Self {
+        models::TrashItem {
+            id: item.id,
+            deleted_at: item.deleted_at,
+            entity: item.entity,
+            entity_id: item.entity_id,
+            entities: item.entities,
+        }
+    }
+}
+
+impl From<models::TrashItem> for TrashItem {
+    fn from(item: models::TrashItem) -> Self {
+        TrashItem {
+            id: item.id,
+            deleted_at: item.deleted_at,
+            entity: item.entity,
+            entity_id: item.entity_id,
+            entities: item.entities,
+        }
+    }
+}
+
+pub struct TrashRepository {
+    file_path: std::path::PathBuf,
+}
+
+impl IsSync for TrashRepository {}
+impl IsSend for TrashRepository {}
+
+impl TrashRepository {
+    pub fn new(file_path: &Path) -> Self {
+        TrashRepository {
+            file_path: std::path::PathBuf::from(file_path),
+        }
+    }
+}
+
+#[async_trait]
+impl ports::TrashRepository for TrashRepository {
+    async fn get(&self, id: u64) -> Result<Option<models::TrashItem>> {
+        let file_path = self.file_path.clone();
+
+        let data = unblock(move || {
+            TrashFileStorage::read_data(&file_path).context("Failed to read storage")
+        })
+        .await?;
+
+        Ok(data.items.into_iter().find(|i| i.id == id).map(Into::into))
+    }
+
+    async fn list(&self) -> Result<Vec<models::TrashItem>> {
+        let file_path = self.file_path.clone();
+
+        let data = unblock(move || {
+            TrashFileStorage::read_data(&file_path).context("Failed to read storage")
+        })
+        .await?;
+
+        Ok(data.items.into_iter().rev().map(Into::into).collect())
+    }
+
+    async fn remove(&self, id: u64) -> Result<Option<models::TrashItem>> {
+        let file_path = self.file_path.clone();
+
+        unblock(move || {
+            let mut storage = TrashFileStorage::open_exclusive(&file_path)
+                .context("Failed to open_exclusive storage")?;
+
+            let Some(index) = storage.data.items.iter().position(|i| i.id == id) else {
+                return Ok(None);
+            };
+
+            let item = storage.data.items.remove(index);
+            storage.save().context("Failed to save storage")?;
+
+            Ok(Some(item.into()))
+        })
+        .await
+    }
+
+    async fn purge(&self, before: OffsetDateTime) -> Result<Vec<models::TrashItem>> {
+        let file_path = self.file_path.clone();
+
+        unblock(move || {
+            let mut storage = TrashFileStorage::open_exclusive(&file_path)
+                .context("Failed to open_exclusive storage")?;
+
+            let (purged, kept): (Vec<_>, _) = std::mem::take(&mut storage.data.items)
+                .into_iter()
+                .partition(|i| i.deleted_at < before);
+            storage.data.items = kept;
+
+            if !purged.is_empty() {
+                storage.save().context("Failed to save storage")?;
+            }
+
+            Ok(purged.into_iter().map(Into::into).collect())
+        })
+        .await
+    }
+}
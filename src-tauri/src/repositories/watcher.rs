@@ -0,0 +1,492 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use super::file_storage::{self, FileStorage};
+use super::group::GroupFileStorageData;
+use super::project::ProjectFileStorageData;
+use super::todo::TodoFileStorageData;
+use crate::models::{self, ChangedIds, StorageChange};
+use crate::result::Result;
+use anyhow::Context;
+use notify::{RecursiveMode, Watcher};
+
+/// How long the directory has to be quiet before changed files are read.
+const SETTLE_TIME: Duration = Duration::from_millis(100);
+
+/// Storage files whose changes `StorageWatcher` reports.
+#[derive(Debug, Clone)]
+pub struct WatchedFiles {
+    pub projects: PathBuf,
+    pub groups: PathBuf,
+    pub todos: PathBuf,
+}
+
+/// Entities of a storage file as last seen by the watcher, by id, each with
+/// the id of its parent.
+pub(super) type Snapshot<T> = HashMap<u64, (T, u64)>;
+
+pub(super) fn snapshot_projects(
+    projects: impl IntoIterator<Item = models::Project>,
+) -> Snapshot<models::Project> {
+    projects
+        .into_iter()
+        .map(|project| (project.id, (project, 0)))
+        .collect()
+}
+
+pub(super) fn snapshot_groups(
+    groups: impl IntoIterator<Item = models::Group>,
+) -> Snapshot<models::Group> {
+    groups
+        .into_iter()
+        .map(|group| (group.id, (group.clone(), group.project_id)))
+        .collect()
+}
+
+pub(super) fn snapshot_todos(
+    todos: impl IntoIterator<Item = models::Todo>,
+) -> Snapshot<models::Todo> {
+    todos
+        .into_iter()
+        .map(|todo| (todo.id, (todo.clone(), todo.group_id)))
+        .collect()
+}
+
+fn read_projects(path: &Path) -> Result<Snapshot<models::Project>> {
+    let data = FileStorage::<ProjectFileStorageData>::read_data(path)?;
+
+    Ok(snapshot_projects(
+        data.projects.into_iter().map(models::Project::from),
+    ))
+}
+
+fn read_groups(path: &Path) -> Result<Snapshot<models::Group>> {
+    let data = FileStorage::<GroupFileStorageData>::read_data(path)?;
+
+    Ok(snapshot_groups(
+        data.groups.into_iter().map(models::Group::from),
+    ))
+}
+
+fn read_todos(path: &Path) -> Result<Snapshot<models::Todo>> {
+    let data = FileStorage::<TodoFileStorageData>::read_data(path)?;
+
+    Ok(snapshot_todos(
+        data.todos.into_iter().map(models::Todo::from),
+    ))
+}
+
+/// Ids that differ between two snapshots, with the parents they had before
+/// or have after the change.
+fn diff<T: PartialEq>(before: &Snapshot<T>, after: &Snapshot<T>) -> (ChangedIds, Vec<u64>) {
+    let mut ids = ChangedIds::default();
+    let mut parent_ids = BTreeSet::new();
+
+    for (id, (item, parent_id)) in after {
+        match before.get(id) {
+            None => {
+                ids.created.push(*id);
+                parent_ids.insert(*parent_id);
+            }
+            Some((old_item, old_parent_id)) if old_item != item => {
+                ids.updated.push(*id);
+                parent_ids.extend([*old_parent_id, *parent_id]);
+            }
+            Some(_) => {}
+        }
+    }
+    for (id, (_, parent_id)) in before {
+        if !after.contains_key(id) {
+            ids.deleted.push(*id);
+            parent_ids.insert(*parent_id);
+        }
+    }
+
+    ids.created.sort_unstable();
+    ids.updated.sort_unstable();
+    ids.deleted.sort_unstable();
+
+    (ids, parent_ids.into_iter().collect())
+}
+
+/// Projects, groups and todos as last seen by a watcher that reads all of
+/// them at once.
+#[derive(Default)]
+pub(super) struct Snapshots {
+    pub projects: Snapshot<models::Project>,
+    pub groups: Snapshot<models::Group>,
+    pub todos: Snapshot<models::Todo>,
+}
+
+impl Snapshots {
+    /// Changes from these snapshots to `after`.
+    pub fn changes(&self, after: &Snapshots) -> Vec<StorageChange> {
+        let mut changes = Vec::new();
+
+        let (ids, _) = diff(&self.projects, &after.projects);
+        if ids != ChangedIds::default() {
+            changes.push(StorageChange::Projects { ids });
+        }
+        let (ids, project_ids) = diff(&self.groups, &after.groups);
+        if ids != ChangedIds::default() {
+            changes.push(StorageChange::Groups { ids, project_ids });
+        }
+        let (ids, group_ids) = diff(&self.todos, &after.todos);
+        if ids != ChangedIds::default() {
+            changes.push(StorageChange::Todos { ids, group_ids });
+        }
+
+        changes
+    }
+}
+
+/// Reads the file at `path` into `snapshot` and returns what changed, unless
+/// only this process wrote it: no event found a `foreign` document there,
+/// and the one read is the one this process committed last.
+///
+/// Files that can not be read right now are skipped; the next event for
+/// the file compares against the last snapshot that could be read.
+fn refresh<T: PartialEq>(
+    path: &Path,
+    foreign: bool,
+    snapshot: &mut Snapshot<T>,
+    read: fn(&Path) -> Result<Snapshot<T>>,
+) -> Option<(ChangedIds, Vec<u64>)> {
+    let after = read(path).ok()?;
+    // Checked after reading as well: a foreign write since the events is
+    // already in the snapshot, so the next event would not report it.
+    let is_own_write = !foreign && file_storage::is_own_write(path).unwrap_or(false);
+
+    let before = std::mem::replace(snapshot, after);
+    if is_own_write {
+        return None;
+    }
+
+    let (ids, parent_ids) = diff(&before, snapshot);
+    if ids == ChangedIds::default() {
+        return None;
+    }
+
+    Some((ids, parent_ids))
+}
+
+/// Paths of an event, each with whether the document there is one this
+/// process did not commit.
+fn touched_by(paths: Vec<PathBuf>) -> Vec<(PathBuf, bool)> {
+    paths
+        .into_iter()
+        .map(|path| {
+            let foreign = !file_storage::is_own_write(&path).unwrap_or(false);
+
+            (path, foreign)
+        })
+        .collect()
+}
+
+struct WatchState {
+    files: WatchedFiles,
+    projects: Snapshot<models::Project>,
+    groups: Snapshot<models::Group>,
+    todos: Snapshot<models::Todo>,
+}
+
+impl WatchState {
+    fn new(files: WatchedFiles) -> Self {
+        WatchState {
+            projects: read_projects(&files.projects).unwrap_or_default(),
+            groups: read_groups(&files.groups).unwrap_or_default(),
+            todos: read_todos(&files.todos).unwrap_or_default(),
+            files,
+        }
+    }
+
+    /// Changes other processes made to the files among `touched`, each
+    /// with whether an event found a document there that this process did
+    /// not commit.
+    fn changes(&mut self, touched: &[(PathBuf, bool)]) -> Vec<StorageChange> {
+        let touch = |file: &Path| {
+            let mut events = touched
+                .iter()
+                .filter(|(path, _)| path.file_name() == file.file_name())
+                .peekable();
+            events.peek()?;
+
+            Some(events.any(|(_, foreign)| *foreign))
+        };
+        let mut changes = Vec::new();
+
+        if let Some(foreign) = touch(&self.files.projects) {
+            if let Some((ids, _)) = refresh(
+                &self.files.projects,
+                foreign,
+                &mut self.projects,
+                read_projects,
+            ) {
+                changes.push(StorageChange::Projects { ids });
+            }
+        }
+        if let Some(foreign) = touch(&self.files.groups) {
+            if let Some((ids, project_ids)) =
+                refresh(&self.files.groups, foreign, &mut self.groups, read_groups)
+            {
+                changes.push(StorageChange::Groups { ids, project_ids });
+            }
+        }
+        if let Some(foreign) = touch(&self.files.todos) {
+            if let Some((ids, group_ids)) =
+                refresh(&self.files.todos, foreign, &mut self.todos, read_todos)
+            {
+                changes.push(StorageChange::Todos { ids, group_ids });
+            }
+        }
+
+        changes
+    }
+}
+
+/// Watches the storage directory and reports the changes other processes
+/// make to the project, group and todo files. Documents are replaced on
+/// every save, so the directory is watched instead of the files.
+///
+/// Stops once dropped.
+pub struct StorageWatcher {
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl StorageWatcher {
+    pub fn start<F>(files: WatchedFiles, on_change: F) -> Result<Self>
+    where
+        F: Fn(StorageChange) + Send + 'static,
+    {
+        let dir = files
+            .projects
+            .parent()
+            .context("Storage file has no directory")?
+            .to_path_buf();
+
+        let mut state = WatchState::new(files);
+
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(sender).context("Failed to create file watcher")?;
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .context(format!("Failed to watch {}", dir.display()))?;
+
+        // Ends when the watcher is dropped together with its sender.
+        std::thread::spawn(move || {
+            while let Ok(event) = receiver.recv() {
+                let mut touched = Vec::new();
+                let mut next = event;
+                // Files other programs write in place are read once the
+                // writes settle rather than half written. Whose document
+                // each event found is told right away, though: a foreign
+                // write followed by a commit of this process within the
+                // settle time is no own write.
+                loop {
+                    if let Ok(event) = next {
+                        let event: notify::Event = event;
+                        touched.extend(touched_by(event.paths));
+                    }
+                    match receiver.recv_timeout(SETTLE_TIME) {
+                        Ok(event) => next = event,
+                        Err(mpsc::RecvTimeoutError::Timeout) => break,
+                        Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+
+                for change in state.changes(&touched) {
+                    on_change(change);
+                }
+            }
+        });
+
+        Ok(StorageWatcher { _watcher: watcher })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports::{self, ProjectRepository as _};
+    use crate::repositories::ProjectRepository;
+
+    fn snapshot(items: &[(u64, &str, u64)]) -> Snapshot<String> {
+        items
+            .iter()
+            .map(|(id, item, parent_id)| (*id, (item.to_string(), *parent_id)))
+            .collect()
+    }
+
+    #[test]
+    fn diff_reports_ids_and_parents() {
+        let before = snapshot(&[(1, "a", 10), (2, "b", 10), (3, "c", 20)]);
+        let after = snapshot(&[(1, "a", 10), (2, "b moved", 30), (4, "d", 40)]);
+
+        let (ids, parent_ids) = diff(&before, &after);
+
+        assert_eq!(
+            ids,
+            ChangedIds {
+                created: vec![4],
+                updated: vec![2],
+                deleted: vec![3],
+            }
+        );
+        assert_eq!(parent_ids, [10, 20, 30, 40]);
+    }
+
+    #[tokio::test]
+    async fn watch_state_reports_only_foreign_writes() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tmp")
+            .join(format!("test_Watcher_{}", rand::random::<u32>()));
+        std::fs::create_dir_all(&dir).expect("Failed to create directory");
+        let _guard = scopeguard::guard(dir.clone(), |dir| {
+            let _ = std::fs::remove_dir_all(dir);
+        });
+        let files = WatchedFiles {
+            projects: dir.join("Projects.bson"),
+            groups: dir.join("Groups.bson"),
+            todos: dir.join("Todos.bson"),
+        };
+
+        let repo = ProjectRepository::new(&files.projects);
+        let project = repo
+            .create(ports::CreateProjectData { name: "Project" })
+            .await
+            .expect("Failed to create project");
+
+        let mut state = WatchState::new(files.clone());
+
+        repo.update(
+            project.id,
+            ports::UpdateProjectData {
+                name: Some("Renamed here"),
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("Failed to update project");
+        assert_eq!(
+            state.changes(&touched_by(vec![dir.join("Projects.bson")])),
+            vec![]
+        );
+
+        // Another process renames the project.
+        let bytes = std::fs::read(&files.projects).expect("Failed to read projects");
+        let mut document = bson::Document::from_reader(bytes.as_slice()).expect("Failed to decode");
+        document.get_array_mut("projects").expect("No projects")[0]
+            .as_document_mut()
+            .expect("Not a project")
+            .insert("name", "Renamed elsewhere");
+        std::fs::write(
+            &files.projects,
+            bson::to_vec(&document).expect("Failed to encode"),
+        )
+        .expect("Failed to write projects");
+
+        assert_eq!(
+            state.changes(&touched_by(vec![dir.join("Groups.bson")])),
+            vec![]
+        );
+
+        let changes = state.changes(&touched_by(vec![dir.join("Projects.bson")]));
+
+        assert_eq!(
+            changes,
+            vec![StorageChange::Projects {
+                ids: ChangedIds {
+                    updated: vec![project.id],
+                    ..Default::default()
+                }
+            }]
+        );
+        assert_eq!(changes[0].event(), "projects-changed");
+        assert_eq!(
+            state.changes(&touched_by(vec![dir.join("Projects.bson")])),
+            vec![]
+        );
+    }
+
+    #[tokio::test]
+    async fn watch_state_reports_foreign_write_followed_by_own() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tmp")
+            .join(format!("test_Watcher_{}", rand::random::<u32>()));
+        std::fs::create_dir_all(&dir).expect("Failed to create directory");
+        let _guard = scopeguard::guard(dir.clone(), |dir| {
+            let _ = std::fs::remove_dir_all(dir);
+        });
+        let files = WatchedFiles {
+            projects: dir.join("Projects.bson"),
+            groups: dir.join("Groups.bson"),
+            todos: dir.join("Todos.bson"),
+        };
+
+        let repo = ProjectRepository::new(&files.projects);
+        repo.create(ports::CreateProjectData { name: "Project" })
+            .await
+            .expect("Failed to create project");
+        let mut state = WatchState::new(files.clone());
+
+        // Another process adds a project, its document put in place by a
+        // rename this process does not record, and this one creates another
+        // before the writes settle.
+        let foreign = ProjectRepository::new(&files.projects)
+            .create(ports::CreateProjectData { name: "Elsewhere" })
+            .await
+            .expect("Failed to create project");
+        std::fs::copy(&files.projects, dir.join("Copy.bson")).expect("Failed to copy");
+        std::fs::rename(dir.join("Copy.bson"), &files.projects).expect("Failed to rename");
+        let mut touched = touched_by(vec![files.projects.clone()]);
+        let own = repo
+            .create(ports::CreateProjectData { name: "Here" })
+            .await
+            .expect("Failed to create project");
+        touched.extend(touched_by(vec![files.projects.clone()]));
+
+        assert_eq!(
+            state.changes(&touched),
+            vec![StorageChange::Projects {
+                ids: ChangedIds {
+                    created: vec![foreign.id, own.id],
+                    ..Default::default()
+                }
+            }]
+        );
+    }
+
+    #[test]
+    fn snapshots_report_changes_per_table() {
+        let group = |id, project_id| models::Group {
+            id,
+            name: "Group".into(),
+            position: 0.0,
+            is_opened: true,
+            project_id,
+        };
+        let before = Snapshots {
+            groups: snapshot_groups([group(1, 10), group(2, 10)]),
+            ..Default::default()
+        };
+        let after = Snapshots {
+            groups: snapshot_groups([group(1, 10), group(2, 20)]),
+            ..Default::default()
+        };
+
+        assert_eq!(before.changes(&before), vec![]);
+        assert_eq!(
+            before.changes(&after),
+            vec![StorageChange::Groups {
+                ids: ChangedIds {
+                    updated: vec![2],
+                    ..Default::default()
+                },
+                project_ids: vec![10, 20],
+            }]
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use core::result;
 use std::fmt::{self, Debug, Display};
+use std::path::PathBuf;
 
 use serde::ser::SerializeMap;
 
@@ -7,6 +8,206 @@ pub struct Error {
     inner: anyhow::Error,
 }
 
+/// Stable, machine-readable category of an `Error`, sent as its `code`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    Validation,
+    NotFound,
+    /// The request contradicts the stored state, e.g. it was prepared
+    /// against data that changed in the meantime.
+    Conflict,
+    /// Another process kept the storage locked for too long.
+    StorageLocked,
+    /// Stored data exists but can not be decoded.
+    StorageCorrupt,
+    /// Stored data was written by a newer version of the app.
+    StorageTooNew,
+    Io,
+    Unknown,
+}
+
+/// Requested entity does not exist.
+#[derive(Debug, serde::Serialize)]
+pub struct NotFound {
+    pub entity: &'static str,
+    pub id: u64,
+}
+
+impl Display for NotFound {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{} {} not found", self.entity, self.id)
+    }
+}
+
+impl std::error::Error for NotFound {}
+
+/// Entity exists, but not in the state the request expects.
+#[derive(Debug)]
+pub struct Conflict {
+    pub entity: &'static str,
+    pub id: u64,
+    pub reason: &'static str,
+}
+
+impl Display for Conflict {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{} {} {}", self.entity, self.id, self.reason)
+    }
+}
+
+impl std::error::Error for Conflict {}
+
+/// Storage file or database is held by another process.
+#[derive(Debug)]
+pub struct StorageLocked {
+    pub path: PathBuf,
+}
+
+impl Display for StorageLocked {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{} is locked by another process",
+            self.path.display()
+        )
+    }
+}
+
+impl std::error::Error for StorageLocked {}
+
+/// Context for errors decoding the storage at `path`.
+#[derive(Debug)]
+pub struct StorageCorrupt {
+    pub path: PathBuf,
+}
+
+impl Display for StorageCorrupt {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{} is corrupt", self.path.display())
+    }
+}
+
+/// Storage at `path` has a newer format than this version understands.
+#[derive(Debug)]
+pub struct StorageTooNew {
+    pub path: PathBuf,
+    pub version: usize,
+    pub supported: usize,
+}
+
+impl Display for StorageTooNew {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{} was written by a newer version of the app (storage version {}, supported {}). Please update the app",
+            self.path.display(),
+            self.version,
+            self.supported
+        )
+    }
+}
+
+impl std::error::Error for StorageTooNew {}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        if self.inner.is::<validator::ValidationErrors>() {
+            ErrorKind::Validation
+        } else if self.inner.is::<NotFound>() {
+            ErrorKind::NotFound
+        } else if self.inner.is::<Conflict>() {
+            ErrorKind::Conflict
+        } else if self.inner.is::<StorageLocked>() {
+            ErrorKind::StorageLocked
+        } else if self.inner.is::<StorageCorrupt>() {
+            ErrorKind::StorageCorrupt
+        } else if self.inner.is::<StorageTooNew>() {
+            ErrorKind::StorageTooNew
+        } else if let Some(error) = self.inner.downcast_ref::<rusqlite::Error>() {
+            sqlite_kind(error)
+        } else if self.inner.is::<std::io::Error>() {
+            ErrorKind::Io
+        } else {
+            ErrorKind::Unknown
+        }
+    }
+
+    /// Entity the error is about, as `(entity, id)`.
+    pub fn entity(&self) -> Option<(&'static str, u64)> {
+        if let Some(error) = self.inner.downcast_ref::<NotFound>() {
+            Some((error.entity, error.id))
+        } else {
+            self.inner
+                .downcast_ref::<Conflict>()
+                .map(|error| (error.entity, error.id))
+        }
+    }
+
+    /// Storage file the error is about.
+    pub fn path(&self) -> Option<&std::path::Path> {
+        if let Some(error) = self.inner.downcast_ref::<StorageLocked>() {
+            Some(&error.path)
+        } else if let Some(error) = self.inner.downcast_ref::<StorageCorrupt>() {
+            Some(&error.path)
+        } else {
+            self.inner
+                .downcast_ref::<StorageTooNew>()
+                .map(|error| error.path.as_path())
+        }
+    }
+}
+
+fn sqlite_kind(error: &rusqlite::Error) -> ErrorKind {
+    use rusqlite::ErrorCode;
+
+    match error.sqlite_error_code() {
+        Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked) => ErrorKind::StorageLocked,
+        Some(ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase) => ErrorKind::StorageCorrupt,
+        Some(ErrorCode::ConstraintViolation) => ErrorKind::Conflict,
+        Some(ErrorCode::SystemIoFailure | ErrorCode::CannotOpen | ErrorCode::DiskFull) => {
+            ErrorKind::Io
+        }
+        _ => ErrorKind::Unknown,
+    }
+}
+
+impl From<NotFound> for Error {
+    #[cold]
+    fn from(error: NotFound) -> Self {
+        Self {
+            inner: error.into(),
+        }
+    }
+}
+
+impl From<Conflict> for Error {
+    #[cold]
+    fn from(error: Conflict) -> Self {
+        Self {
+            inner: error.into(),
+        }
+    }
+}
+
+impl From<StorageLocked> for Error {
+    #[cold]
+    fn from(error: StorageLocked) -> Self {
+        Self {
+            inner: error.into(),
+        }
+    }
+}
+
+impl From<StorageTooNew> for Error {
+    #[cold]
+    fn from(error: StorageTooNew) -> Self {
+        Self {
+            inner: error.into(),
+        }
+    }
+}
+
 impl From<anyhow::Error> for Error {
     #[cold]
     fn from(error: anyhow::Error) -> Self {
@@ -14,6 +215,14 @@ impl From<anyhow::Error> for Error {
     }
 }
 
+/// For callers outside the crate's `Result`, e.g. the app setup.
+impl From<Error> for anyhow::Error {
+    #[cold]
+    fn from(error: Error) -> Self {
+        error.inner
+    }
+}
+
 impl From<validator::ValidationErrors> for Error {
     #[cold]
     fn from(error: validator::ValidationErrors) -> Self {
@@ -35,29 +244,181 @@ impl Debug for Error {
     }
 }
 
-impl std::error::Error for Error {}
-
+/// Serializes as `{"code", "message", "context"}` where `context` lists
+/// the whole chain from the outermost message down to the root cause,
+/// plus `entity` and `id`, `path` or `validation` when they apply.
 impl serde::Serialize for Error {
     fn serialize<S>(&self, serializer: S) -> result::Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        let mut map = serializer.serialize_map(Some(1))?;
+        let mut map = serializer.serialize_map(None)?;
 
-        if self.inner.is::<validator::ValidationErrors>() {
-            let errors = self
+        map.serialize_entry("code", &self.kind())?;
+        map.serialize_entry("message", &self.inner.to_string())?;
+        map.serialize_entry(
+            "context",
+            &self
                 .inner
-                .downcast_ref::<validator::ValidationErrors>()
-                .expect("downcast error");
+                .chain()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+        )?;
 
+        if let Some((entity, id)) = self.entity() {
+            map.serialize_entry("entity", entity)?;
+            map.serialize_entry("id", &id)?;
+        }
+        if let Some(path) = self.path() {
+            map.serialize_entry("path", path)?;
+        }
+        if let Some(errors) = self.inner.downcast_ref::<validator::ValidationErrors>() {
             map.serialize_entry("validation", errors)?;
-        } else {
-            let error = serde_error::Error::new(&*self.inner);
-
-            map.serialize_entry("unknown", &error)?;
         }
+
         map.end()
     }
 }
 
 pub type Result<T> = result::Result<T, Error>;
+
+/// Adds context to a crate `Result` the way `anyhow::Context` does for
+/// other errors.
+///
+/// `Error` is deliberately no `std::error::Error`: `anyhow::Context` would
+/// wrap it as an opaque cause, and `kind` could no longer see the storage,
+/// conflict and not found errors underneath.
+pub trait ErrorContext<T> {
+    fn context<C>(self, context: C) -> Result<T>
+    where
+        C: Display + Send + Sync + 'static;
+
+    fn with_context<C, F>(self, context: F) -> Result<T>
+    where
+        C: Display + Send + Sync + 'static,
+        F: FnOnce() -> C;
+}
+
+impl<T> ErrorContext<T> for Result<T> {
+    fn context<C>(self, context: C) -> Result<T>
+    where
+        C: Display + Send + Sync + 'static,
+    {
+        self.map_err(|error| Error {
+            inner: error.inner.context(context),
+        })
+    }
+
+    fn with_context<C, F>(self, context: F) -> Result<T>
+    where
+        C: Display + Send + Sync + 'static,
+        F: FnOnce() -> C,
+    {
+        self.map_err(|error| Error {
+            inner: error.inner.context(context()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn serialize_not_found() {
+        let error: Error = NotFound {
+            entity: "project",
+            id: 12,
+        }
+        .into();
+
+        let j = serde_json::to_string(&error).expect("Error serialization");
+        assert_eq!(j, "{\"code\":\"not_found\",\"message\":\"project 12 not found\",\"context\":[\"project 12 not found\"],\"entity\":\"project\",\"id\":12}");
+    }
+
+    #[test]
+    fn serialize_validation() {
+        let mut errors = validator::ValidationErrors::new();
+        errors.add("name", validator::ValidationError::new("length"));
+        let error: Error = errors.into();
+
+        let j = serde_json::to_value(&error).expect("Error serialization");
+        assert_eq!(j["code"], "validation");
+        assert_eq!(j["validation"]["name"][0]["code"], "length");
+    }
+
+    #[test]
+    fn serialize_context_chain() {
+        let error: Error = Err::<(), _>(Conflict {
+            entity: "group",
+            id: 7,
+            reason: "is not part of the project",
+        })
+        .context("Failed to apply changes")
+        .unwrap_err()
+        .into();
+
+        let j = serde_json::to_value(&error).expect("Error serialization");
+        assert_eq!(
+            j,
+            serde_json::json!({
+                "code": "conflict",
+                "message": "Failed to apply changes",
+                "context": ["Failed to apply changes", "group 7 is not part of the project"],
+                "entity": "group",
+                "id": 7,
+            })
+        );
+    }
+
+    #[test]
+    fn classify_storage_errors() {
+        let io = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied");
+        let error: Error = anyhow::Error::new(io).context("Failed to open").into();
+        assert_eq!(error.kind(), ErrorKind::Io);
+
+        let error: Error = anyhow::anyhow!("unexpected end of file")
+            .context(StorageCorrupt {
+                path: "Todos.bson".into(),
+            })
+            .into();
+        assert_eq!(error.kind(), ErrorKind::StorageCorrupt);
+        assert_eq!(error.path(), Some(std::path::Path::new("Todos.bson")));
+
+        let busy = rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+            None,
+        );
+        let error: Error = anyhow::Error::new(busy).context("Failed to insert").into();
+        assert_eq!(error.kind(), ErrorKind::StorageLocked);
+
+        let error: Error = StorageTooNew {
+            path: "Todos.bson".into(),
+            version: 3,
+            supported: 2,
+        }
+        .into();
+        assert_eq!(error.kind(), ErrorKind::StorageTooNew);
+        assert_eq!(error.path(), Some(std::path::Path::new("Todos.bson")));
+
+        let error: Error = anyhow::anyhow!("Something else").into();
+        assert_eq!(error.kind(), ErrorKind::Unknown);
+    }
+
+    #[test]
+    fn context_keeps_kind() {
+        let error = Err::<(), Error>(
+            StorageLocked {
+                path: "Todos.bson".into(),
+            }
+            .into(),
+        )
+        .context("Failed to open_exclusive storage")
+        .unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::StorageLocked);
+        assert_eq!(error.path(), Some(std::path::Path::new("Todos.bson")));
+        assert_eq!(error.to_string(), "Failed to open_exclusive storage");
+    }
+}
@@ -71,7 +71,9 @@ export function ProjectCreateModal(props: Props) {
         </button>
 
         <div className="field__error">
-          {error?.unknown && (error.unknown?.description || "Unknown error")}
+          {error &&
+            error.code !== "validation" &&
+            (error.message || "Unknown error")}
         </div>
       </form>
     </Modal>